@@ -1,22 +1,26 @@
-use std::env;
+use std::{env, sync::Arc};
 
 use askama::Template;
 use axum::{
+    extract::State,
     http::StatusCode,
     response::{Html, IntoResponse},
     routing::get,
     Json, Router,
 };
 use axum_extra::extract::CookieJar;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use tower_http::services::ServeDir;
 
 #[tokio::main]
 async fn main() {
+    let auth_service = Arc::new(AuthServiceClient::from_env());
+
     let app = Router::new()
         .nest_service("/assets", ServeDir::new("assets"))
         .route("/", get(root))
-        .route("/protected", get(protected));
+        .route("/protected", get(protected))
+        .with_state(auth_service);
 
     let listener = tokio::net::TcpListener::bind("0.0.0.0:8000").await.unwrap();
 
@@ -24,6 +28,37 @@ async fn main() {
     axum::serve(listener, app).await.unwrap();
 }
 
+// The app is registered with the auth service as a machine client, which lets it introspect
+// the token to learn who the user is
+struct AuthServiceClient {
+    http_client: reqwest::Client,
+    introspect_url: String,
+    client_id: String,
+    client_secret: String,
+}
+
+impl AuthServiceClient {
+    // Without the secret every protected request would fail, so refuse to start instead
+    fn from_env() -> Self {
+        let client_secret = env::var("AUTH_SERVICE_CLIENT_SECRET")
+            .ok()
+            .filter(|secret| !secret.is_empty())
+            .expect(
+                "AUTH_SERVICE_CLIENT_SECRET must be set to the secret of the app service client",
+            );
+        let client_id = env::var("AUTH_SERVICE_CLIENT_ID").unwrap_or("app-service".to_owned());
+
+        let auth_hostname = env::var("AUTH_SERVICE_HOST_NAME").unwrap_or("0.0.0.0".to_owned());
+
+        Self {
+            http_client: reqwest::Client::builder().build().unwrap(),
+            introspect_url: format!("http://{}:3000/introspect", auth_hostname),
+            client_id,
+            client_secret,
+        }
+    }
+}
+
 #[derive(Template)]
 #[template(path = "index.html")]
 struct IndexTemplate {
@@ -46,7 +81,10 @@ async fn root() -> impl IntoResponse {
     Html(template.render().unwrap())
 }
 
-async fn protected(jar: CookieJar) -> impl IntoResponse {
+async fn protected(
+    State(auth_service): State<Arc<AuthServiceClient>>,
+    jar: CookieJar,
+) -> impl IntoResponse {
     let jwt_cookie = match jar.get("jwt") {
         Some(cookie) => cookie,
         None => {
@@ -54,16 +92,14 @@ async fn protected(jar: CookieJar) -> impl IntoResponse {
         }
     };
 
-    let api_client = reqwest::Client::builder().build().unwrap();
-
-    let verify_token_body = serde_json::json!({
-        "token": &jwt_cookie.value(),
-    });
-
-    let auth_hostname = env::var("AUTH_SERVICE_HOST_NAME").unwrap_or("0.0.0.0".to_owned());
-    let url = format!("http://{}:3000/verify-token", auth_hostname);
-
-    let response = match api_client.post(&url).json(&verify_token_body).send().await {
+    let response = match auth_service
+        .http_client
+        .post(&auth_service.introspect_url)
+        .basic_auth(&auth_service.client_id, Some(&auth_service.client_secret))
+        .form(&[("token", jwt_cookie.value())])
+        .send()
+        .await
+    {
         Ok(response) => response,
         Err(_) => {
             return StatusCode::INTERNAL_SERVER_ERROR.into_response();
@@ -71,18 +107,42 @@ async fn protected(jar: CookieJar) -> impl IntoResponse {
     };
 
     match response.status() {
-        reqwest::StatusCode::UNAUTHORIZED | reqwest::StatusCode::BAD_REQUEST => {
-            StatusCode::UNAUTHORIZED.into_response()
+        reqwest::StatusCode::OK => {}
+        reqwest::StatusCode::UNAUTHORIZED => return StatusCode::UNAUTHORIZED.into_response(),
+        _ => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+
+    let introspection = match response.json::<IntrospectionResponse>().await {
+        Ok(introspection) => introspection,
+        Err(_) => {
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
         }
-        reqwest::StatusCode::OK => Json(ProtectedRouteResponse {
+    };
+
+    // Tokens issued to clients are not login tokens of a user
+    match introspection {
+        IntrospectionResponse {
+            active: true,
+            sub: Some(email),
+            client_id: None,
+        } => Json(ProtectedRouteResponse {
             img_url: "https://i.ibb.co/YP90j68/Light-Live-Bootcamp-Certificate.png".to_owned(),
+            email,
         })
         .into_response(),
-        _ => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+        _ => StatusCode::UNAUTHORIZED.into_response(),
     }
 }
 
+#[derive(Deserialize)]
+struct IntrospectionResponse {
+    active: bool,
+    sub: Option<String>,
+    client_id: Option<String>,
+}
+
 #[derive(Serialize)]
 pub struct ProtectedRouteResponse {
     pub img_url: String,
+    pub email: String,
 }
@@ -1,10 +1,12 @@
 const loginSection = document.getElementById("login-section");
 const twoFASection = document.getElementById("2fa-section");
 const signupSection = document.getElementById("signup-section");
+const totpSection = document.getElementById("totp-section");
 
 const signupLink = document.getElementById("signup-link");
 const twoFALoginLink = document.getElementById("2fa-login-link");
 const signupLoginLink = document.getElementById("signup-login-link");
+const totpLoginLink = document.getElementById("totp-login-link");
 
 signupLink.addEventListener("click", (e) => {
     e.preventDefault();
@@ -30,6 +32,13 @@ signupLoginLink.addEventListener("click", (e) => {
     signupSection.style.display = "none";
 });
 
+totpLoginLink.addEventListener("click", (e) => {
+    e.preventDefault();
+
+    loginSection.style.display = "block";
+    totpSection.style.display = "none";
+});
+
 // -----------------------------------------------------
 
 const loginForm = document.getElementById("login-form");
@@ -53,6 +62,9 @@ loginButton.addEventListener("click", (e) => {
             TwoFAForm.email.value = email;
             response.json().then(data => {
                 TwoFAForm.login_attempt_id.value = data.loginAttemptId;
+                TwoFAHint.innerText = data.twoFAMethod === "totp"
+                    ? "Enter the code shown in your authenticator app"
+                    : "Enter the code we sent to your email";
             });
 
             loginForm.email.value = "";
@@ -67,9 +79,10 @@ loginButton.addEventListener("click", (e) => {
             loginForm.password.value = "";
             loginErrAlter.style.display = "none";
             alert("You have successfully logged in.");
+            showLoggedInSection();
         } else {
             response.json().then(data => {
-                let error_msg = data.error;
+                let error_msg = problemMessage(data);
                 if (error_msg !== undefined && error_msg !== null && error_msg !== "") {
                     loginErrAlter.innerHTML = `<span><strong>Error: </strong>${error_msg}</span>`;
                     loginErrAlter.style.display = "block";
@@ -104,13 +117,13 @@ signupButton.addEventListener("click", (e) => {
             signupForm.password.value = "";
             signupForm.twoFA.checked = false;
             signupErrAlter.style.display = "none";
-            alert("You have successfully created a user.");
+            alert("You have successfully created a user. Check your inbox for a link to verify your email address.");
             loginSection.style.display = "block";
             twoFASection.style.display = "none";
             signupSection.style.display = "none";
         } else {
             response.json().then(data => {
-                let error_msg = data.error;
+                let error_msg = problemMessage(data);
                 if (error_msg !== undefined && error_msg !== null && error_msg !== "") {
                     signupErrAlter.innerHTML = `<span><strong>Error: </strong>${error_msg}</span>`;
                     signupErrAlter.style.display = "block";
@@ -125,20 +138,28 @@ signupButton.addEventListener("click", (e) => {
 const TwoFAForm = document.getElementById("2fa-form");
 const TwoFAButton = document.getElementById("2fa-form-submit");
 const TwoFAErrAlter = document.getElementById("2fa-err-alert");
+const TwoFAHint = document.getElementById("2fa-hint");
 
 TwoFAButton.addEventListener("click", (e) => {
     e.preventDefault();
 
     const email = TwoFAForm.email.value;
     const loginAttemptId = TwoFAForm.login_attempt_id.value;
-    const TwoFACode = TwoFAForm.email_code.value;
+    const TwoFACode = TwoFAForm.email_code.value.trim();
 
-    fetch('/verify-2fa', {
+    // Recovery codes are longer than the 6 digit codes and look like `xxxxx-xxxxx`
+    const isRecoveryCode = TwoFACode.length > 6;
+    const url = isRecoveryCode ? '/verify-2fa/recovery' : '/verify-2fa';
+    const body = isRecoveryCode
+        ? { email, loginAttemptId, recoveryCode: TwoFACode }
+        : { email, loginAttemptId, "2FACode": TwoFACode };
+
+    fetch(url, {
         method: 'POST',
         headers: {
             'Content-Type': 'application/json',
         },
-        body: JSON.stringify({ email, loginAttemptId, "2FACode": TwoFACode }),
+        body: JSON.stringify(body),
     }).then(response => {
         if (response.ok) {
             TwoFAForm.email.value = "";
@@ -146,12 +167,12 @@ TwoFAButton.addEventListener("click", (e) => {
             TwoFAForm.login_attempt_id.value = "";
             TwoFAErrAlter.style.display = "none";
             alert("You have successfully logged in.");
-            loginSection.style.display = "block";
             twoFASection.style.display = "none";
             signupSection.style.display = "none";
+            showLoggedInSection();
         } else {
             response.json().then(data => {
-                let error_msg = data.error;
+                let error_msg = problemMessage(data);
                 if (error_msg !== undefined && error_msg !== null && error_msg !== "") {
                     TwoFAErrAlter.innerHTML = `<span><strong>Error: </strong>${error_msg}</span>`;
                     TwoFAErrAlter.style.display = "block";
@@ -161,4 +182,687 @@ TwoFAButton.addEventListener("click", (e) => {
             });
         }
     });
-});
\ No newline at end of file
+});
+
+// -----------------------------------------------------
+
+const totpForm = document.getElementById("totp-form");
+const totpEnrollButton = document.getElementById("totp-enroll-button");
+const totpDisableButton = document.getElementById("totp-disable-button");
+const totpConfirmButton = document.getElementById("totp-form-submit");
+const totpQrCode = document.getElementById("totp-qr-code");
+const totpErrAlter = document.getElementById("totp-err-alert");
+// Setting up or turning off the authenticator app asks for the password again
+const totpPassword = document.getElementById("totp-password");
+const recoveryCodesButton = document.getElementById("recovery-codes-button");
+const recoveryCodes = document.getElementById("recovery-codes");
+const recoveryCodesList = document.getElementById("recovery-codes-list");
+
+// Text for an error response of the API (RFC 7807 problem details), naming every invalid field.
+// Field details can echo the submitted values, so the text is escaped before it goes into HTML.
+function problemMessage(problem) {
+    const fieldMessages = (problem.errors ?? []).map(error => `${error.field}: ${error.detail}`);
+    const message = fieldMessages.length > 0 ? fieldMessages.join(", ") : (problem.detail ?? problem.title);
+
+    const text = document.createElement("span");
+    text.innerText = message ?? "";
+    return text.innerHTML;
+}
+
+function showTotpSection() {
+    loginSection.style.display = "none";
+    totpForm.style.display = "none";
+    totpErrAlter.style.display = "none";
+    totpPassword.value = "";
+    recoveryCodes.style.display = "none";
+    recoveryCodesList.innerText = "";
+    sessionsList.style.display = "none";
+    sessionsList.innerHTML = "";
+    totpSection.style.display = "block";
+}
+
+function showTotpError(response) {
+    response.json().then(data => {
+        let error_msg = problemMessage(data);
+        if (error_msg !== undefined && error_msg !== null && error_msg !== "") {
+            totpErrAlter.innerHTML = `<span><strong>Error: </strong>${error_msg}</span>`;
+            totpErrAlter.style.display = "block";
+        } else {
+            totpErrAlter.style.display = "none";
+        }
+    });
+}
+
+totpEnrollButton.addEventListener("click", (e) => {
+    e.preventDefault();
+
+    fetch('/totp/enroll', {
+        method: 'POST',
+        headers: {
+            'Content-Type': 'application/json',
+        },
+        body: JSON.stringify({ password: totpPassword.value }),
+    }).then(response => {
+        if (response.ok) {
+            response.json().then(data => {
+                totpQrCode.src = data.qrCode;
+                totpForm.style.display = "block";
+                totpErrAlter.style.display = "none";
+            });
+        } else {
+            showTotpError(response);
+        }
+    });
+});
+
+totpConfirmButton.addEventListener("click", (e) => {
+    e.preventDefault();
+
+    const TwoFACode = totpForm.totp_code.value;
+
+    fetch('/totp/confirm', {
+        method: 'POST',
+        headers: {
+            'Content-Type': 'application/json',
+        },
+        body: JSON.stringify({ password: totpPassword.value, "2FACode": TwoFACode }),
+    }).then(response => {
+        if (response.ok) {
+            totpForm.totp_code.value = "";
+            totpPassword.value = "";
+            totpForm.style.display = "none";
+            totpErrAlter.style.display = "none";
+            alert("Your authenticator app is now used for 2-factor authentication.");
+        } else {
+            showTotpError(response);
+        }
+    });
+});
+
+totpDisableButton.addEventListener("click", (e) => {
+    e.preventDefault();
+
+    fetch('/totp/disable', {
+        method: 'POST',
+        headers: {
+            'Content-Type': 'application/json',
+        },
+        body: JSON.stringify({ password: totpPassword.value }),
+    }).then(response => {
+        if (response.ok) {
+            totpPassword.value = "";
+            totpForm.style.display = "none";
+            totpErrAlter.style.display = "none";
+            alert("2-factor codes will be sent to your email again.");
+        } else {
+            showTotpError(response);
+        }
+    });
+});
+
+recoveryCodesButton.addEventListener("click", (e) => {
+    e.preventDefault();
+
+    fetch('/recovery-codes', {
+        method: 'POST',
+        headers: {
+            'Content-Type': 'application/json',
+        },
+        body: JSON.stringify({ password: totpPassword.value }),
+    }).then(response => {
+        if (response.ok) {
+            totpPassword.value = "";
+            response.json().then(data => {
+                recoveryCodesList.innerText = data.recoveryCodes.join("\n");
+                recoveryCodes.style.display = "block";
+                totpErrAlter.style.display = "none";
+            });
+        } else {
+            showTotpError(response);
+        }
+    });
+});
+
+const sessionsButton = document.getElementById("sessions-button");
+const sessionsList = document.getElementById("sessions-list");
+
+function loadSessions() {
+    fetch('/sessions').then(response => {
+        if (response.ok) {
+            response.json().then(sessions => {
+                sessionsList.innerHTML = "";
+                sessions.forEach(session => sessionsList.appendChild(sessionItem(session)));
+                sessionsList.style.display = "block";
+                totpErrAlter.style.display = "none";
+            });
+        } else {
+            showTotpError(response);
+        }
+    });
+}
+
+function sessionItem(session) {
+    const item = document.createElement("li");
+    item.className = "list-group-item d-flex justify-content-between align-items-center";
+
+    const description = document.createElement("span");
+    const seenAt = new Date(session.lastSeenAt).toLocaleString();
+    description.innerText = `${session.device} (${session.ipAddress ?? "unknown address"}), last seen ${seenAt}`;
+    item.appendChild(description);
+
+    if (session.current) {
+        const badge = document.createElement("span");
+        badge.className = "badge bg-secondary";
+        badge.innerText = "This device";
+        item.appendChild(badge);
+        return item;
+    }
+
+    const revokeButton = document.createElement("button");
+    revokeButton.className = "btn btn-sm btn-outline-danger";
+    revokeButton.type = "button";
+    revokeButton.innerText = "Log out";
+    revokeButton.addEventListener("click", () => {
+        fetch(`/sessions/${session.id}`, { method: 'DELETE' }).then(response => {
+            if (response.ok) {
+                loadSessions();
+            } else {
+                showTotpError(response);
+            }
+        });
+    });
+    item.appendChild(revokeButton);
+
+    return item;
+}
+
+sessionsButton.addEventListener("click", (e) => {
+    e.preventDefault();
+    loadSessions();
+});
+
+const logoutAllButton = document.getElementById("logout-all-button");
+
+logoutAllButton.addEventListener("click", (e) => {
+    e.preventDefault();
+
+    fetch('/logout-all', { method: 'POST' }).then(response => {
+        if (response.ok) {
+            totpSection.style.display = "none";
+            loginSection.style.display = "block";
+        } else {
+            showTotpError(response);
+        }
+    });
+});
+
+// -----------------------------------------------------
+
+const passkeyLoginButton = document.getElementById("passkey-login-button");
+const passkeyRegisterButton = document.getElementById("passkey-register-button");
+
+function base64urlToBuffer(value) {
+    const base64 = value.replace(/-/g, "+").replace(/_/g, "/");
+    const padded = base64 + "=".repeat((4 - base64.length % 4) % 4);
+    return Uint8Array.from(atob(padded), c => c.charCodeAt(0)).buffer;
+}
+
+function bufferToBase64url(buffer) {
+    const bytes = String.fromCharCode(...new Uint8Array(buffer));
+    return btoa(bytes).replace(/\+/g, "-").replace(/\//g, "_").replace(/=+$/, "");
+}
+
+passkeyRegisterButton.addEventListener("click", (e) => {
+    e.preventDefault();
+
+    fetch('/passkeys/register/start', { method: 'POST' }).then(response => {
+        if (!response.ok) {
+            showTotpError(response);
+            return;
+        }
+
+        response.json().then(async data => {
+            const publicKey = data.publicKey;
+            publicKey.challenge = base64urlToBuffer(publicKey.challenge);
+            publicKey.user.id = base64urlToBuffer(publicKey.user.id);
+            publicKey.excludeCredentials = publicKey.excludeCredentials.map(c => ({ ...c, id: base64urlToBuffer(c.id) }));
+
+            const credential = await navigator.credentials.create({ publicKey });
+
+            const finish = await fetch('/passkeys/register/finish', {
+                method: 'POST',
+                headers: {
+                    'Content-Type': 'application/json',
+                },
+                body: JSON.stringify({
+                    ceremonyId: data.ceremonyId,
+                    credential: {
+                        id: bufferToBase64url(credential.rawId),
+                        response: {
+                            clientDataJSON: bufferToBase64url(credential.response.clientDataJSON),
+                            attestationObject: bufferToBase64url(credential.response.attestationObject),
+                        },
+                    },
+                }),
+            });
+
+            if (finish.ok) {
+                totpErrAlter.style.display = "none";
+                alert("Your passkey has been added.");
+            } else {
+                showTotpError(finish);
+            }
+        });
+    });
+});
+
+passkeyLoginButton.addEventListener("click", (e) => {
+    e.preventDefault();
+
+    const email = loginForm.email.value || null;
+
+    fetch('/passkeys/login/start', {
+        method: 'POST',
+        headers: {
+            'Content-Type': 'application/json',
+        },
+        body: JSON.stringify({ email }),
+    }).then(response => response.json()).then(async data => {
+        const publicKey = data.publicKey;
+        publicKey.challenge = base64urlToBuffer(publicKey.challenge);
+        publicKey.allowCredentials = publicKey.allowCredentials.map(c => ({ ...c, id: base64urlToBuffer(c.id) }));
+
+        const credential = await navigator.credentials.get({ publicKey });
+
+        const finish = await fetch('/passkeys/login/finish', {
+            method: 'POST',
+            headers: {
+                'Content-Type': 'application/json',
+            },
+            body: JSON.stringify({
+                ceremonyId: data.ceremonyId,
+                credential: {
+                    id: bufferToBase64url(credential.rawId),
+                    response: {
+                        clientDataJSON: bufferToBase64url(credential.response.clientDataJSON),
+                        authenticatorData: bufferToBase64url(credential.response.authenticatorData),
+                        signature: bufferToBase64url(credential.response.signature),
+                        userHandle: credential.response.userHandle
+                            ? bufferToBase64url(credential.response.userHandle)
+                            : null,
+                    },
+                },
+            }),
+        });
+
+        if (finish.ok) {
+            loginForm.email.value = "";
+            loginForm.password.value = "";
+            loginErrAlter.style.display = "none";
+            alert("You have successfully logged in.");
+            showLoggedInSection();
+        } else {
+            finish.json().then(data => {
+                loginErrAlter.innerHTML = `<span><strong>Error: </strong>${problemMessage(data)}</span>`;
+                loginErrAlter.style.display = "block";
+            });
+        }
+    });
+});
+
+// -----------------------------------------------------
+
+const passwordResetSection = document.getElementById("password-reset-section");
+const passwordResetLink = document.getElementById("password-reset-link");
+const passwordResetLoginLink = document.getElementById("password-reset-login-link");
+const passwordResetRequestForm = document.getElementById("password-reset-request-form");
+const passwordResetRequestButton = document.getElementById("password-reset-request-submit");
+const passwordResetConfirmForm = document.getElementById("password-reset-confirm-form");
+const passwordResetConfirmButton = document.getElementById("password-reset-confirm-submit");
+const passwordResetErrAlter = document.getElementById("password-reset-err-alert");
+
+function showPasswordResetError(response) {
+    response.json().then(data => {
+        let error_msg = problemMessage(data);
+        if (error_msg !== undefined && error_msg !== null && error_msg !== "") {
+            passwordResetErrAlter.innerHTML = `<span><strong>Error: </strong>${error_msg}</span>`;
+            passwordResetErrAlter.style.display = "block";
+        } else {
+            passwordResetErrAlter.style.display = "none";
+        }
+    });
+}
+
+passwordResetLink.addEventListener("click", (e) => {
+    e.preventDefault();
+
+    loginSection.style.display = "none";
+    passwordResetRequestForm.style.display = "block";
+    passwordResetConfirmForm.style.display = "none";
+    passwordResetSection.style.display = "block";
+});
+
+passwordResetLoginLink.addEventListener("click", (e) => {
+    e.preventDefault();
+
+    loginSection.style.display = "block";
+    passwordResetSection.style.display = "none";
+});
+
+// The emailed link points back to this page with the token in the query string
+const passwordResetToken = new URLSearchParams(window.location.search).get("token");
+if (passwordResetToken) {
+    passwordResetConfirmForm.token.value = passwordResetToken;
+    loginSection.style.display = "none";
+    passwordResetRequestForm.style.display = "none";
+    passwordResetConfirmForm.style.display = "block";
+    passwordResetSection.style.display = "block";
+}
+
+passwordResetRequestButton.addEventListener("click", (e) => {
+    e.preventDefault();
+
+    const email = passwordResetRequestForm.email.value;
+
+    fetch('/password-reset/request', {
+        method: 'POST',
+        headers: {
+            'Content-Type': 'application/json',
+        },
+        body: JSON.stringify({ email }),
+    }).then(response => {
+        if (response.ok) {
+            response.json().then(data => {
+                passwordResetRequestForm.email.value = "";
+                passwordResetErrAlter.style.display = "none";
+                alert(data.message);
+            });
+        } else {
+            showPasswordResetError(response);
+        }
+    });
+});
+
+passwordResetConfirmButton.addEventListener("click", (e) => {
+    e.preventDefault();
+
+    const token = passwordResetConfirmForm.token.value;
+    const newPassword = passwordResetConfirmForm.password.value;
+
+    fetch('/password-reset/confirm', {
+        method: 'POST',
+        headers: {
+            'Content-Type': 'application/json',
+        },
+        body: JSON.stringify({ token, newPassword }),
+    }).then(response => {
+        if (response.ok) {
+            passwordResetConfirmForm.password.value = "";
+            passwordResetErrAlter.style.display = "none";
+            window.history.replaceState(null, "", window.location.pathname);
+            alert("Your password has been changed, you can now log in with it.");
+            passwordResetSection.style.display = "none";
+            loginSection.style.display = "block";
+        } else {
+            showPasswordResetError(response);
+        }
+    });
+});
+
+// -----------------------------------------------------
+
+const emailConfirmationSection = document.getElementById("email-confirmation-section");
+const emailConfirmationForm = document.getElementById("email-confirmation-form");
+const emailConfirmationText = document.getElementById("email-confirmation-text");
+const emailConfirmationButton = document.getElementById("email-confirmation-submit");
+const emailConfirmationLoginLink = document.getElementById("email-confirmation-login-link");
+const emailConfirmationErrAlter = document.getElementById("email-confirmation-err-alert");
+
+// Emailed links only open this page and the token is posted once the user confirms, since mail
+// scanners follow links too and would use it up before the user gets to it. Keyed by the query
+// parameter the link carries the token in.
+const emailConfirmations = {
+    verify_email_token: {
+        url: '/verify-email',
+        text: "Confirm that this email address belongs to you",
+        done: "Your email address has been verified.",
+    },
+    email_change_token: {
+        url: '/account/email/confirm',
+        text: "Confirm this address as the new email of your account, you will be logged out everywhere",
+        done: "Your email address has been changed, log in with the new one.",
+    },
+};
+
+function showEmailConfirmationError(response) {
+    response.json().then(data => {
+        let error_msg = problemMessage(data);
+        if (error_msg !== undefined && error_msg !== null && error_msg !== "") {
+            emailConfirmationErrAlter.innerHTML = `<span><strong>Error: </strong>${error_msg}</span>`;
+            emailConfirmationErrAlter.style.display = "block";
+        } else {
+            emailConfirmationErrAlter.style.display = "none";
+        }
+    });
+}
+
+const emailConfirmationParams = new URLSearchParams(window.location.search);
+const emailConfirmationParam = Object.keys(emailConfirmations)
+    .find(param => emailConfirmationParams.has(param));
+if (emailConfirmationParam) {
+    emailConfirmationForm.token.value = emailConfirmationParams.get(emailConfirmationParam);
+    emailConfirmationText.innerText = emailConfirmations[emailConfirmationParam].text;
+    loginSection.style.display = "none";
+    emailConfirmationSection.style.display = "block";
+}
+
+emailConfirmationLoginLink.addEventListener("click", (e) => {
+    e.preventDefault();
+
+    window.history.replaceState(null, "", window.location.pathname);
+    loginSection.style.display = "block";
+    emailConfirmationSection.style.display = "none";
+});
+
+emailConfirmationButton.addEventListener("click", (e) => {
+    e.preventDefault();
+
+    const confirmation = emailConfirmations[emailConfirmationParam];
+    const token = emailConfirmationForm.token.value;
+
+    fetch(confirmation.url, {
+        method: 'POST',
+        headers: {
+            'Content-Type': 'application/json',
+        },
+        body: JSON.stringify({ token }),
+    }).then(response => {
+        if (response.ok) {
+            emailConfirmationErrAlter.style.display = "none";
+            window.history.replaceState(null, "", window.location.pathname);
+            alert(confirmation.done);
+            emailConfirmationSection.style.display = "none";
+            loginSection.style.display = "block";
+        } else {
+            showEmailConfirmationError(response);
+        }
+    });
+});
+
+// -----------------------------------------------------
+
+const changePasswordForm = document.getElementById("change-password-form");
+const changePasswordButton = document.getElementById("change-password-submit");
+const changeEmailForm = document.getElementById("change-email-form");
+const changeEmailButton = document.getElementById("change-email-submit");
+
+changePasswordButton.addEventListener("click", (e) => {
+    e.preventDefault();
+
+    const currentPassword = changePasswordForm.current_password.value;
+    const newPassword = changePasswordForm.new_password.value;
+
+    fetch('/account/password', {
+        method: 'POST',
+        headers: {
+            'Content-Type': 'application/json',
+        },
+        body: JSON.stringify({ currentPassword, newPassword }),
+    }).then(response => {
+        if (response.ok) {
+            changePasswordForm.current_password.value = "";
+            changePasswordForm.new_password.value = "";
+            totpErrAlter.style.display = "none";
+            alert("Your password has been changed and your other sessions have been logged out.");
+        } else {
+            showTotpError(response);
+        }
+    });
+});
+
+changeEmailButton.addEventListener("click", (e) => {
+    e.preventDefault();
+
+    const currentPassword = changeEmailForm.current_password.value;
+    const newEmail = changeEmailForm.new_email.value;
+
+    fetch('/account/email', {
+        method: 'POST',
+        headers: {
+            'Content-Type': 'application/json',
+        },
+        body: JSON.stringify({ currentPassword, newEmail }),
+    }).then(response => {
+        if (response.ok) {
+            changeEmailForm.current_password.value = "";
+            changeEmailForm.new_email.value = "";
+            totpErrAlter.style.display = "none";
+            response.json().then(data => alert(data.message));
+        } else {
+            showTotpError(response);
+        }
+    });
+});
+
+const deleteAccountForm = document.getElementById("delete-account-form");
+const deleteAccountButton = document.getElementById("delete-account-submit");
+
+deleteAccountButton.addEventListener("click", (e) => {
+    e.preventDefault();
+
+    if (!confirm("This permanently deletes your account. Continue?")) {
+        return;
+    }
+
+    const password = deleteAccountForm.password.value;
+
+    fetch('/account', {
+        method: 'DELETE',
+        headers: {
+            'Content-Type': 'application/json',
+        },
+        body: JSON.stringify({ password }),
+    }).then(response => {
+        deleteAccountForm.password.value = "";
+        if (response.ok) {
+            totpSection.style.display = "none";
+            loginSection.style.display = "block";
+        } else {
+            showTotpError(response);
+        }
+    });
+});
+
+// -----------------------------------------------------
+
+const consentSection = document.getElementById("consent-section");
+const consentClientName = document.getElementById("consent-client-name");
+const consentScopes = document.getElementById("consent-scopes");
+const consentEmail = document.getElementById("consent-email");
+const consentAllowButton = document.getElementById("consent-allow-button");
+const consentDenyButton = document.getElementById("consent-deny-button");
+const consentErrAlter = document.getElementById("consent-err-alert");
+
+const scopeDescriptions = {
+    openid: "Know who you are",
+    email: "See your email address",
+};
+
+// `/authorize` sends the browser here with the request of an OpenID Connect client to allow
+const authorizationRequest = new URLSearchParams(window.location.search).get("authorizationRequest");
+
+function showLoggedInSection() {
+    if (authorizationRequest) {
+        showConsentSection();
+    } else {
+        showTotpSection();
+    }
+}
+
+function showConsentError(response) {
+    response.json().then(data => {
+        consentErrAlter.innerHTML = `<span><strong>Error: </strong>${problemMessage(data)}</span>`;
+        consentErrAlter.style.display = "block";
+    });
+}
+
+// Asks the user to allow the pending request, or to log in first if they are not yet
+function showConsentSection() {
+    fetch(`/authorize/consent?request=${encodeURIComponent(authorizationRequest)}`).then(async response => {
+        const data = await response.json();
+        if (data.code === "missing_token" || data.code === "invalid_token") {
+            consentSection.style.display = "none";
+            loginSection.style.display = "block";
+            return;
+        }
+
+        loginSection.style.display = "none";
+        consentSection.style.display = "block";
+        consentErrAlter.style.display = "none";
+
+        if (response.ok) {
+            consentClientName.innerText = data.clientName;
+            consentEmail.innerText = data.email;
+            consentScopes.innerHTML = "";
+            data.scopes.forEach(scope => {
+                const item = document.createElement("li");
+                item.className = "list-group-item";
+                item.innerText = scopeDescriptions[scope] ?? scope;
+                consentScopes.appendChild(item);
+            });
+        } else {
+            consentErrAlter.innerHTML = `<span><strong>Error: </strong>${problemMessage(data)}</span>`;
+            consentErrAlter.style.display = "block";
+        }
+    });
+}
+
+function submitConsent(approved) {
+    fetch('/authorize/consent', {
+        method: 'POST',
+        headers: {
+            'Content-Type': 'application/json',
+        },
+        body: JSON.stringify({ request: authorizationRequest, approved }),
+    }).then(response => {
+        if (response.ok) {
+            response.json().then(data => window.location.assign(data.redirectUri));
+        } else {
+            showConsentError(response);
+        }
+    });
+}
+
+consentAllowButton.addEventListener("click", (e) => {
+    e.preventDefault();
+    submitConsent(true);
+});
+
+consentDenyButton.addEventListener("click", (e) => {
+    e.preventDefault();
+    submitConsent(false);
+});
+
+if (authorizationRequest) {
+    showConsentSection();
+}
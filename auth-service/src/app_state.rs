@@ -1,34 +1,138 @@
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 
 use tokio::sync::RwLock;
 
-use crate::domain::{BannedTokenStore, UserStore};
+use crate::{
+    domain::{
+        AuthorizationCodeStore, BannedTokenStore, EmailTokenStore, LoginAttemptStore,
+        MachineClientStore, OAuthClientStore, PasskeyCredentialStore, PasswordPolicy,
+        PasswordResetTokenStore, RateLimitRule, RateLimitStore, RecoveryCodeStore,
+        RefreshTokenStore, SessionStore, UserStore, WebAuthnChallengeStore,
+    },
+    utils::jwt_keyring::JwtKeyring,
+};
 
-pub type UserStoreType = Arc<RwLock<dyn UserStore>>;
-pub type BannedTokenStoreType = Arc<RwLock<dyn BannedTokenStore>>;
-pub type TwoFACodeStoreType = Arc<RwLock<dyn crate::domain::TwoFACodeStore>>;
+pub type UserStoreType = Arc<dyn UserStore>;
+pub type BannedTokenStoreType = Arc<dyn BannedTokenStore>;
+pub type TwoFACodeStoreType = Arc<dyn crate::domain::TwoFACodeStore>;
+pub type RefreshTokenStoreType = Arc<dyn RefreshTokenStore>;
+pub type SessionStoreType = Arc<dyn SessionStore>;
+pub type RecoveryCodeStoreType = Arc<dyn RecoveryCodeStore>;
+pub type PasskeyCredentialStoreType = Arc<dyn PasskeyCredentialStore>;
+pub type WebAuthnChallengeStoreType = Arc<dyn WebAuthnChallengeStore>;
+pub type PasswordResetTokenStoreType = Arc<dyn PasswordResetTokenStore>;
+pub type EmailTokenStoreType = Arc<dyn EmailTokenStore>;
+pub type LoginAttemptStoreType = Arc<dyn LoginAttemptStore>;
+pub type RateLimitStoreType = Arc<dyn RateLimitStore>;
+pub type OAuthClientStoreType = Arc<dyn OAuthClientStore>;
+pub type AuthorizationCodeStoreType = Arc<dyn AuthorizationCodeStore>;
+pub type MachineClientStoreType = Arc<dyn MachineClientStore>;
 pub type EmailClientType = Arc<dyn crate::domain::EmailClient>;
+pub type JwtKeyringType = Arc<RwLock<JwtKeyring>>;
+
+// Switches for behaviour that deployments may want to tighten
+#[derive(Debug, Clone, Default)]
+pub struct AuthPolicy {
+    // Refuse to log in users who have not clicked the link in their verification email yet
+    pub require_verified_email: bool,
+    pub login_throttle: LoginThrottlePolicy,
+    // Limits on requests to any route, applied before the route handles them
+    pub rate_limits: Vec<RateLimitRule>,
+    // Rules for passwords chosen at signup, on a change and on a reset
+    pub password: PasswordPolicy,
+}
+
+// Limits on failed logins. Once a limit is reached the account or the client address is locked
+// out, and every further failure doubles the lockout.
+#[derive(Debug, Clone)]
+pub struct LoginThrottlePolicy {
+    pub max_failures_per_email: u32,
+    // Higher than the per account limit, since one address may be shared by many users
+    pub max_failures_per_ip: u32,
+    // Failures are forgotten once there was none for this long
+    pub failure_window: Duration,
+    pub base_lockout: Duration,
+    pub max_lockout: Duration,
+    // Wrong codes after which a pending 2FA login attempt is dropped and the password is asked again
+    pub max_two_fa_attempts: u32,
+}
+
+impl Default for LoginThrottlePolicy {
+    fn default() -> Self {
+        Self {
+            max_failures_per_email: 5,
+            max_failures_per_ip: 50,
+            failure_window: Duration::from_secs(15 * 60),
+            base_lockout: Duration::from_secs(30),
+            max_lockout: Duration::from_secs(15 * 60),
+            max_two_fa_attempts: 5,
+        }
+    }
+}
 
 #[derive(Clone)]
 pub struct AppState {
     pub user_store: UserStoreType,
     pub banned_token_store: BannedTokenStoreType,
     pub two_fa_code_store: TwoFACodeStoreType,
+    pub refresh_token_store: RefreshTokenStoreType,
+    pub session_store: SessionStoreType,
+    pub recovery_code_store: RecoveryCodeStoreType,
+    pub passkey_credential_store: PasskeyCredentialStoreType,
+    pub webauthn_challenge_store: WebAuthnChallengeStoreType,
+    pub password_reset_token_store: PasswordResetTokenStoreType,
+    pub email_token_store: EmailTokenStoreType,
+    pub login_attempt_store: LoginAttemptStoreType,
+    pub rate_limit_store: RateLimitStoreType,
+    pub oauth_client_store: OAuthClientStoreType,
+    pub authorization_code_store: AuthorizationCodeStoreType,
+    pub machine_client_store: MachineClientStoreType,
     pub email_client: EmailClientType,
+    pub jwt_keyring: JwtKeyringType,
+    pub policy: AuthPolicy,
 }
 
 impl AppState {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         user_store: UserStoreType,
         banned_token_store: BannedTokenStoreType,
         two_fa_code_store: TwoFACodeStoreType,
+        refresh_token_store: RefreshTokenStoreType,
+        session_store: SessionStoreType,
+        recovery_code_store: RecoveryCodeStoreType,
+        passkey_credential_store: PasskeyCredentialStoreType,
+        webauthn_challenge_store: WebAuthnChallengeStoreType,
+        password_reset_token_store: PasswordResetTokenStoreType,
+        email_token_store: EmailTokenStoreType,
+        login_attempt_store: LoginAttemptStoreType,
+        rate_limit_store: RateLimitStoreType,
+        oauth_client_store: OAuthClientStoreType,
+        authorization_code_store: AuthorizationCodeStoreType,
+        machine_client_store: MachineClientStoreType,
         email_client: EmailClientType,
+        jwt_keyring: JwtKeyringType,
+        policy: AuthPolicy,
     ) -> Self {
         Self {
             user_store,
             banned_token_store,
             two_fa_code_store,
+            refresh_token_store,
+            session_store,
+            recovery_code_store,
+            passkey_credential_store,
+            webauthn_challenge_store,
+            password_reset_token_store,
+            email_token_store,
+            login_attempt_store,
+            rate_limit_store,
+            oauth_client_store,
+            authorization_code_store,
+            machine_client_store,
             email_client,
+            jwt_keyring,
+            policy,
         }
     }
 }
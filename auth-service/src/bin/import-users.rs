@@ -0,0 +1,77 @@
+use std::{fs::File, path::PathBuf, process::exit};
+
+use auth_service::{
+    domain::{UserStore, UserStoreError},
+    get_postgres_pool,
+    services::PostgresUserStore,
+    utils::{
+        constants::{DATABASE_URL, PASSWORD_HASH_PARAMS, TOTP_ENCRYPTION_KEY},
+        secret_cipher::SecretCipher,
+        user_import::{read_users, ImportFormat},
+    },
+};
+
+// Loads users exported from another system into the `users` table, with the hashes they had
+// there. Usage: `import-users <users.csv|users.jsonl>`
+#[tokio::main]
+async fn main() {
+    let Some(path) = std::env::args().nth(1).map(PathBuf::from) else {
+        eprintln!("Usage: import-users <users.csv|users.jsonl>");
+        exit(2);
+    };
+
+    let format = ImportFormat::from_path(&path).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        exit(2);
+    });
+    let file = File::open(&path).unwrap_or_else(|e| {
+        eprintln!("Failed to open {}: {}", path.display(), e);
+        exit(2);
+    });
+
+    let pg_pool = get_postgres_pool(&DATABASE_URL)
+        .await
+        .expect("Failed to create Postgres connection pool");
+
+    sqlx::migrate!("../migrations")
+        .run(&pg_pool)
+        .await
+        .expect("Failed to run database migrations");
+
+    let secret_cipher = SecretCipher::from_base64(&TOTP_ENCRYPTION_KEY)
+        .expect("TOTP_ENCRYPTION_KEY must be 32 bytes encoded in base64");
+    let user_store = PostgresUserStore::new(pg_pool, secret_cipher, *PASSWORD_HASH_PARAMS);
+
+    let (mut imported, mut skipped, mut failed) = (0, 0, 0);
+
+    for record in read_users(file, format) {
+        let (user, password_hash) = match record {
+            Ok(record) => record,
+            Err(e) => {
+                eprintln!("Skipping invalid record: {}", e);
+                failed += 1;
+                continue;
+            }
+        };
+
+        let email = user.email.clone();
+        match user_store.import_user(user, password_hash).await {
+            Ok(()) => imported += 1,
+            // Running the import again only adds the users that are still missing
+            Err(UserStoreError::UserAlreadyExists) => skipped += 1,
+            Err(e) => {
+                eprintln!("Failed to import {}: {:?}", email.as_ref(), e);
+                failed += 1;
+            }
+        }
+    }
+
+    println!(
+        "Imported {} users, skipped {} existing ones, {} failed",
+        imported, skipped, failed
+    );
+
+    if failed > 0 {
+        exit(1);
+    }
+}
@@ -0,0 +1,59 @@
+use std::process::exit;
+
+use auth_service::{
+    domain::{OAuthClient, OAuthClientStore},
+    get_postgres_pool,
+    services::PostgresOAuthClientStore,
+    utils::constants::DATABASE_URL,
+};
+use url::Url;
+
+// Registers an OpenID Connect client, or replaces the name and redirect URIs of an existing one.
+// Usage: `register-client <client-id> <name> <redirect-uri>...`
+#[tokio::main]
+async fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let [client_id, name, redirect_uris @ ..] = args.as_slice() else {
+        eprintln!("Usage: register-client <client-id> <name> <redirect-uri>...");
+        exit(2);
+    };
+    if redirect_uris.is_empty() {
+        eprintln!("Usage: register-client <client-id> <name> <redirect-uri>...");
+        exit(2);
+    }
+
+    // Redirect URIs are compared as they are, so they must be exactly what the client will send
+    for uri in redirect_uris {
+        if let Err(e) = Url::parse(uri) {
+            eprintln!("Invalid redirect URI {}: {}", uri, e);
+            exit(2);
+        }
+    }
+
+    let pg_pool = get_postgres_pool(&DATABASE_URL)
+        .await
+        .expect("Failed to create Postgres connection pool");
+
+    sqlx::migrate!("../migrations")
+        .run(&pg_pool)
+        .await
+        .expect("Failed to run database migrations");
+
+    let client_store = PostgresOAuthClientStore::new(pg_pool);
+    let client = OAuthClient {
+        client_id: client_id.clone(),
+        name: name.clone(),
+        redirect_uris: redirect_uris.to_vec(),
+    };
+
+    if let Err(e) = client_store.save_client(client).await {
+        eprintln!("Failed to register {}: {:?}", client_id, e);
+        exit(1);
+    }
+
+    println!(
+        "Registered client {} with {} redirect URIs",
+        client_id,
+        redirect_uris.len()
+    );
+}
@@ -0,0 +1,62 @@
+use std::process::exit;
+
+use auth_service::{
+    domain::{ClientSecret, MachineClient, MachineClientStore, Scope},
+    get_postgres_pool,
+    services::PostgresMachineClientStore,
+    utils::constants::DATABASE_URL,
+};
+
+// Registers a backend service for the client credentials grant and prints its new secret, which
+// is only stored as a hash. Registering an existing client again replaces its scopes and secret.
+// A client without scopes can still introspect and revoke tokens, but not get any of its own.
+// Usage: `register-machine-client <client-id> <name> [<scope>...]`
+#[tokio::main]
+async fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let [client_id, name, scopes @ ..] = args.as_slice() else {
+        eprintln!("Usage: register-machine-client <client-id> <name> [<scope>...]");
+        exit(2);
+    };
+
+    let scope = match scopes {
+        [] => None,
+        scopes => Some(Scope::parse(&scopes.join(" ")).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            eprintln!("Usage: register-machine-client <client-id> <name> [<scope>...]");
+            exit(2);
+        })),
+    };
+
+    let pg_pool = get_postgres_pool(&DATABASE_URL)
+        .await
+        .expect("Failed to create Postgres connection pool");
+
+    sqlx::migrate!("../migrations")
+        .run(&pg_pool)
+        .await
+        .expect("Failed to run database migrations");
+
+    let client_store = PostgresMachineClientStore::new(pg_pool);
+    let client = MachineClient {
+        client_id: client_id.clone(),
+        name: name.clone(),
+        allowed_scopes: scope
+            .iter()
+            .flat_map(Scope::iter)
+            .map(str::to_owned)
+            .collect(),
+    };
+    let secret = ClientSecret::default();
+
+    if let Err(e) = client_store.save_client(client, &secret).await {
+        eprintln!("Failed to register {}: {:?}", client_id, e);
+        exit(1);
+    }
+
+    match scope {
+        Some(scope) => println!("Registered client {} with scopes {}", client_id, scope),
+        None => println!("Registered client {} without scopes", client_id),
+    }
+    println!("Client secret, it is not shown again: {}", secret.as_ref());
+}
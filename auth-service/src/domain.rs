@@ -1,12 +1,28 @@
+mod breached_passwords;
 mod data_stores;
 mod email;
+mod email_client;
 pub mod error;
+mod oauth;
+mod passkey;
 mod password;
+mod password_hash;
+mod password_policy;
+mod rate_limit;
+mod session;
+mod totp_secret;
 mod user;
-mod email_client;
 
+pub use breached_passwords::*;
 pub use data_stores::*;
 pub use email::*;
+pub use email_client::*;
+pub use oauth::*;
+pub use passkey::*;
 pub use password::*;
+pub use password_hash::*;
+pub use password_policy::*;
+pub use rate_limit::*;
+pub use session::*;
+pub use totp_secret::*;
 pub use user::*;
-pub use email_client::*;
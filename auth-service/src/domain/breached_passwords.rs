@@ -0,0 +1,106 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    io::{BufRead, BufReader, Read},
+};
+
+use sha1::{Digest, Sha1};
+
+// Hashes are looked up by their first five hex digits, like the Pwned Passwords range API does
+const PREFIX_LENGTH: usize = 5;
+const SHA1_HEX_LENGTH: usize = 40;
+
+// Passwords known from data breaches, kept as upper case hex SHA-1 hashes grouped by prefix.
+// A lookup only asks for the range of a prefix and compares the rest of the hash itself
+// (k-anonymity), so the corpus never needs to see more than five digits of a hash.
+#[derive(Default)]
+pub struct BreachedPasswords {
+    ranges: HashMap<String, HashSet<String>>,
+}
+
+impl BreachedPasswords {
+    // One `<sha1>` or `<sha1>:<count>` per line, the layout of the Pwned Passwords downloads
+    pub fn from_reader(reader: impl Read) -> Result<Self, String> {
+        let mut ranges: HashMap<String, HashSet<String>> = HashMap::new();
+
+        for (index, line) in BufReader::new(reader).lines().enumerate() {
+            let line = line.map_err(|e| e.to_string())?;
+            let hash = line.split(':').next().unwrap_or_default().trim();
+            if hash.is_empty() {
+                continue;
+            }
+            if hash.len() != SHA1_HEX_LENGTH || !hash.chars().all(|c| c.is_ascii_hexdigit()) {
+                return Err(format!("line {}: {} is not a SHA-1 hash", index + 1, hash));
+            }
+
+            let hash = hash.to_ascii_uppercase();
+            let (prefix, suffix) = hash.split_at(PREFIX_LENGTH);
+            ranges
+                .entry(prefix.to_owned())
+                .or_default()
+                .insert(suffix.to_owned());
+        }
+
+        Ok(Self { ranges })
+    }
+
+    // Remaining digits of every breached hash that starts with the prefix
+    pub fn range(&self, prefix: &str) -> Option<&HashSet<String>> {
+        self.ranges.get(prefix)
+    }
+
+    pub fn contains(&self, password: &str) -> bool {
+        let hash: String = Sha1::digest(password.as_bytes())
+            .iter()
+            .map(|byte| format!("{:02X}", byte))
+            .collect();
+        let (prefix, suffix) = hash.split_at(PREFIX_LENGTH);
+
+        self.range(prefix)
+            .is_some_and(|suffixes| suffixes.contains(suffix))
+    }
+}
+
+// The corpus can hold millions of hashes, only their number is worth printing
+impl fmt::Debug for BreachedPasswords {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BreachedPasswords")
+            .field("ranges", &self.ranges.len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // SHA-1 of "password123" and "letmein"
+    const CORPUS: &str = "CBFDAC6008F9CAB4083784CBD1874F76618D2A97:2254650\n\
+                          b7a875fc1ea228b9061041b7cec4bd3c52ab3ce3\n\
+                          \n";
+
+    #[test]
+    fn should_find_only_breached_passwords() {
+        let corpus = BreachedPasswords::from_reader(CORPUS.as_bytes()).unwrap();
+
+        assert!(corpus.contains("password123"));
+        assert!(corpus.contains("letmein"));
+        assert!(!corpus.contains("correct horse battery staple"));
+    }
+
+    #[test]
+    fn should_group_hashes_by_prefix() {
+        let corpus = BreachedPasswords::from_reader(CORPUS.as_bytes()).unwrap();
+
+        let range = corpus.range("CBFDA").unwrap();
+        assert_eq!(range.len(), 1);
+        assert!(range.contains("C6008F9CAB4083784CBD1874F76618D2A97"));
+        assert!(corpus.range("00000").is_none());
+    }
+
+    #[test]
+    fn should_reject_lines_that_are_not_hashes() {
+        let result = BreachedPasswords::from_reader("password123\n".as_bytes());
+        assert!(result.is_err());
+    }
+}
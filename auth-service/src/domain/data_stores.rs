@@ -1,27 +1,77 @@
+use std::{net::IpAddr, time::Duration};
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::{DateTime, Utc};
+use rand::RngCore;
 use uuid::Uuid;
 
-use crate::domain::{Email, Password, User};
+use crate::domain::{
+    AuthorizationCode, AuthorizationGrant, ClientSecret, CredentialId, Email, MachineClient,
+    OAuthClient, PasskeyCredential, Password, PasswordHash, Session, TokenBucket, TotpSecret,
+    TwoFAMethod, User, WebAuthnChallenge,
+};
 
 #[async_trait::async_trait]
 pub trait UserStore: Send + Sync {
-    async fn add_user(&mut self, user: User) -> Result<(), UserStoreError>;
+    // Hashes the password, it is never handed out again and only checked by `validate_user`
+    async fn add_user(&self, user: User, password: Password) -> Result<(), UserStoreError>;
+    // Takes over an account from another system along with the hash it had there, so its password
+    // never has to be known. Hashes of other algorithms than Argon2id are upgraded at the next login.
+    async fn import_user(
+        &self,
+        user: User,
+        password_hash: PasswordHash,
+    ) -> Result<(), UserStoreError>;
     async fn get_user(&self, email: &Email) -> Result<User, UserStoreError>;
     async fn validate_user(
         &self,
         email: &Email,
         password: &Password,
     ) -> Result<User, UserStoreError>;
+    // Keeps the secret of an enrollment until it is confirmed, replacing an earlier unconfirmed one.
+    // A confirmed secret and the 2FA method in use are left alone.
+    async fn set_pending_totp_secret(
+        &self,
+        email: &Email,
+        secret: TotpSecret,
+    ) -> Result<(), UserStoreError>;
+    async fn get_pending_totp_secret(&self, email: &Email) -> Result<TotpSecret, UserStoreError>;
+    // Makes the secret the one codes are checked against and switches the user to TOTP
+    async fn confirm_totp_secret(
+        &self,
+        email: &Email,
+        secret: TotpSecret,
+    ) -> Result<(), UserStoreError>;
+    async fn get_totp_secret(&self, email: &Email) -> Result<TotpSecret, UserStoreError>;
+    async fn set_two_fa_method(
+        &self,
+        email: &Email,
+        method: TwoFAMethod,
+    ) -> Result<(), UserStoreError>;
+    async fn update_password(
+        &self,
+        email: &Email,
+        password: Password,
+    ) -> Result<(), UserStoreError>;
+    async fn set_email_verified(&self, email: &Email) -> Result<(), UserStoreError>;
+    // Moves the account to a new address, which counts as verified since the change is confirmed from it
+    async fn update_email(&self, email: &Email, new_email: &Email) -> Result<(), UserStoreError>;
+    async fn delete_user(&self, email: &Email) -> Result<(), UserStoreError>;
+    // Auth tokens issued with an older version than the current one are no longer accepted
+    async fn get_token_version(&self, email: &Email) -> Result<u32, UserStoreError>;
+    // Invalidates every auth token issued to the user so far, returns the new version
+    async fn bump_token_version(&self, email: &Email) -> Result<u32, UserStoreError>;
 }
 
 #[async_trait::async_trait]
 pub trait TwoFACodeStore: Send + Sync {
     async fn add_code(
-        &mut self,
+        &self,
         email: Email,
         login_attempt_id: LoginAttemptId,
         code: TwoFACode,
     ) -> Result<(), TwoFACodeStoreError>;
-    async fn remove_code(&mut self, email: &Email) -> Result<(), TwoFACodeStoreError>;
+    async fn remove_code(&self, email: &Email) -> Result<(), TwoFACodeStoreError>;
     async fn get_code(
         &self,
         email: &Email,
@@ -75,7 +125,7 @@ pub struct TwoFACode(String);
 
 impl TwoFACode {
     pub fn parse(code: &str) -> Result<Self, String> {
-        if code.len() == 6 && code.chars().all(|c| c.is_digit(10)) {
+        if code.len() == 6 && code.chars().all(|c| c.is_ascii_digit()) {
             Ok(TwoFACode(code.to_owned()))
         } else {
             Err("Invalid 2FA code format".to_string())
@@ -85,8 +135,13 @@ impl TwoFACode {
 
 #[async_trait::async_trait]
 pub trait BannedTokenStore: Send + Sync {
-    async fn ban_token(&mut self, token: &str) -> Result<(), BannedTokenStoreError>;
-    async fn is_token_banned(&self, token: &str) -> Result<bool, BannedTokenStoreError>;
+    // Tokens are identified by their `jti`, the ban only has to last until the token expires
+    async fn ban_token(
+        &self,
+        jti: &Uuid,
+        expires_at: DateTime<Utc>,
+    ) -> Result<(), BannedTokenStoreError>;
+    async fn is_token_banned(&self, jti: &Uuid) -> Result<bool, BannedTokenStoreError>;
 }
 
 #[derive(Debug, Clone)]
@@ -94,6 +149,7 @@ pub enum UserStoreError {
     UserAlreadyExists,
     UserNotFound,
     InvalidCredentials,
+    TotpNotEnrolled,
     UnexpectedError,
 }
 
@@ -101,3 +157,487 @@ pub enum UserStoreError {
 pub enum BannedTokenStoreError {
     UnexpectedError,
 }
+
+#[async_trait::async_trait]
+pub trait RefreshTokenStore: Send + Sync {
+    async fn add_token(
+        &self,
+        token: RefreshToken,
+        record: RefreshTokenRecord,
+    ) -> Result<(), RefreshTokenStoreError>;
+    async fn get_token(
+        &self,
+        token: &RefreshToken,
+    ) -> Result<RefreshTokenRecord, RefreshTokenStoreError>;
+    // Uses up the token and stores `new_token` as the next member of its family in one step,
+    // returning the record of the new token. The new token expires along with the one it replaces,
+    // so rotating never extends a login. Expired tokens are not found. A token can only be used
+    // once, any further attempt fails with `TokenReused` so the caller can revoke the family.
+    async fn replace_token(
+        &self,
+        token: &RefreshToken,
+        new_token: RefreshToken,
+    ) -> Result<RefreshTokenRecord, RefreshTokenStoreError>;
+    async fn revoke_family(&self, family_id: &Uuid) -> Result<(), RefreshTokenStoreError>;
+    async fn revoke_all(&self, email: &Email) -> Result<(), RefreshTokenStoreError>;
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RefreshTokenStoreError {
+    TokenNotFound,
+    TokenReused(Uuid),
+    UnexpectedError,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RefreshToken(String);
+
+impl RefreshToken {
+    pub fn parse(token: String) -> Result<Self, String> {
+        if !token.is_empty() && URL_SAFE_NO_PAD.decode(&token).is_ok() {
+            Ok(RefreshToken(token))
+        } else {
+            Err("Invalid refresh token format".to_string())
+        }
+    }
+}
+
+impl Default for RefreshToken {
+    fn default() -> Self {
+        let mut bytes = [0u8; 32];
+        rand::thread_rng().fill_bytes(&mut bytes);
+        RefreshToken(URL_SAFE_NO_PAD.encode(bytes))
+    }
+}
+
+impl AsRef<str> for RefreshToken {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+// Every refresh token belongs to a family which starts at login and is carried over on each rotation
+#[derive(Debug, Clone, PartialEq)]
+pub struct RefreshTokenRecord {
+    pub family_id: Uuid,
+    pub email: Email,
+    pub expires_at: DateTime<Utc>,
+}
+
+#[async_trait::async_trait]
+pub trait RecoveryCodeStore: Send + Sync {
+    // Replaces the whole batch, so codes handed out earlier stop working
+    async fn replace_codes(
+        &self,
+        email: &Email,
+        codes: Vec<RecoveryCode>,
+    ) -> Result<(), RecoveryCodeStoreError>;
+    // Consumes the code, each one can only be used once
+    async fn use_code(
+        &self,
+        email: &Email,
+        code: &RecoveryCode,
+    ) -> Result<(), RecoveryCodeStoreError>;
+    async fn count_codes(&self, email: &Email) -> Result<usize, RecoveryCodeStoreError>;
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RecoveryCodeStoreError {
+    InvalidCode,
+    UnexpectedError,
+}
+
+// Lowercase letters and digits without the easily confused 0, 1, l and o
+const RECOVERY_CODE_ALPHABET: &[u8] = b"23456789abcdefghijkmnpqrstuvwxyz";
+const RECOVERY_CODE_GROUP_LENGTH: usize = 5;
+
+// Single use code in the form `xxxxx-xxxxx`, for users who lost access to their second factor
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RecoveryCode(String);
+
+impl RecoveryCode {
+    // Accepts codes typed in any case and with or without the dash
+    pub fn parse(code: &str) -> Result<Self, String> {
+        let code: String = code
+            .trim()
+            .chars()
+            .filter(|c| *c != '-')
+            .map(|c| c.to_ascii_lowercase())
+            .collect();
+
+        if code.len() == 2 * RECOVERY_CODE_GROUP_LENGTH
+            && code.bytes().all(|c| RECOVERY_CODE_ALPHABET.contains(&c))
+        {
+            let (first, second) = code.split_at(RECOVERY_CODE_GROUP_LENGTH);
+            Ok(RecoveryCode(format!("{}-{}", first, second)))
+        } else {
+            Err("Invalid recovery code format".to_string())
+        }
+    }
+}
+
+impl Default for RecoveryCode {
+    fn default() -> Self {
+        let mut rng = rand::thread_rng();
+        let mut group = || -> String {
+            (0..RECOVERY_CODE_GROUP_LENGTH)
+                .map(|_| {
+                    let index = (rng.next_u32() as usize) % RECOVERY_CODE_ALPHABET.len();
+                    RECOVERY_CODE_ALPHABET[index] as char
+                })
+                .collect()
+        };
+        let first = group();
+        let second = group();
+        RecoveryCode(format!("{}-{}", first, second))
+    }
+}
+
+impl AsRef<str> for RecoveryCode {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[async_trait::async_trait]
+pub trait PasskeyCredentialStore: Send + Sync {
+    async fn add_credential(
+        &self,
+        credential: PasskeyCredential,
+    ) -> Result<(), PasskeyCredentialStoreError>;
+    async fn get_credential(
+        &self,
+        credential_id: &CredentialId,
+    ) -> Result<PasskeyCredential, PasskeyCredentialStoreError>;
+    async fn get_credentials(
+        &self,
+        email: &Email,
+    ) -> Result<Vec<PasskeyCredential>, PasskeyCredentialStoreError>;
+    // Only moves the count on while it is still `previous_sign_count`. Of two logins with the same
+    // count, from a replayed assertion or a cloned authenticator, only one gets through.
+    async fn update_sign_count(
+        &self,
+        credential_id: &CredentialId,
+        previous_sign_count: u32,
+        sign_count: u32,
+    ) -> Result<(), PasskeyCredentialStoreError>;
+    async fn remove_credentials(&self, email: &Email) -> Result<(), PasskeyCredentialStoreError>;
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PasskeyCredentialStoreError {
+    CredentialAlreadyExists,
+    CredentialNotFound,
+    SignCountChanged,
+    UnexpectedError,
+}
+
+#[async_trait::async_trait]
+pub trait WebAuthnChallengeStore: Send + Sync {
+    async fn add_challenge(
+        &self,
+        ceremony_id: Uuid,
+        challenge: WebAuthnChallenge,
+    ) -> Result<(), WebAuthnChallengeStoreError>;
+    // Removes the challenge while returning it, so every ceremony can only be finished once
+    async fn take_challenge(
+        &self,
+        ceremony_id: &Uuid,
+    ) -> Result<WebAuthnChallenge, WebAuthnChallengeStoreError>;
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum WebAuthnChallengeStoreError {
+    ChallengeNotFound,
+    UnexpectedError,
+}
+
+#[async_trait::async_trait]
+pub trait PasswordResetTokenStore: Send + Sync {
+    async fn add_token(
+        &self,
+        token: PasswordResetToken,
+        email: Email,
+    ) -> Result<(), PasswordResetTokenStoreError>;
+    // Returns the owner while leaving the token usable, for checks that must pass before it is taken
+    async fn get_token(
+        &self,
+        token: &PasswordResetToken,
+    ) -> Result<Email, PasswordResetTokenStoreError>;
+    // Removes the token while returning its owner, so every link can only be used once
+    async fn take_token(
+        &self,
+        token: &PasswordResetToken,
+    ) -> Result<Email, PasswordResetTokenStoreError>;
+    // Removes every token of the owner, so none outlives the account
+    async fn remove_tokens(&self, email: &Email) -> Result<(), PasswordResetTokenStoreError>;
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PasswordResetTokenStoreError {
+    TokenNotFound,
+    UnexpectedError,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PasswordResetToken(String);
+
+impl PasswordResetToken {
+    pub fn parse(token: String) -> Result<Self, String> {
+        if !token.is_empty() && URL_SAFE_NO_PAD.decode(&token).is_ok() {
+            Ok(PasswordResetToken(token))
+        } else {
+            Err("Invalid password reset token format".to_string())
+        }
+    }
+}
+
+impl Default for PasswordResetToken {
+    fn default() -> Self {
+        let mut bytes = [0u8; 32];
+        rand::thread_rng().fill_bytes(&mut bytes);
+        PasswordResetToken(URL_SAFE_NO_PAD.encode(bytes))
+    }
+}
+
+impl AsRef<str> for PasswordResetToken {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+// Single use tokens behind the links that confirm an email address, one per confirmation
+#[async_trait::async_trait]
+pub trait EmailTokenStore: Send + Sync {
+    async fn add_token(
+        &self,
+        token: EmailToken,
+        confirmation: EmailConfirmation,
+    ) -> Result<(), EmailTokenStoreError>;
+    // Removes the token while returning what it confirms, so every link can only be used once
+    async fn take_token(
+        &self,
+        token: &EmailToken,
+    ) -> Result<EmailConfirmation, EmailTokenStoreError>;
+    // Removes every token sent for the account, so no link outlives it
+    async fn remove_tokens(&self, email: &Email) -> Result<(), EmailTokenStoreError>;
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum EmailTokenStoreError {
+    TokenNotFound,
+    UnexpectedError,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum EmailConfirmation {
+    // The address of a new account belongs to its owner
+    Verification {
+        email: Email,
+    },
+    // The account moves from `email` to `new_email`, unless its token version changed since
+    Change {
+        email: Email,
+        new_email: Email,
+        token_version: u32,
+    },
+}
+
+impl EmailConfirmation {
+    // Address of the account the link was sent for
+    pub fn email(&self) -> &Email {
+        match self {
+            EmailConfirmation::Verification { email } | EmailConfirmation::Change { email, .. } => {
+                email
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct EmailToken(String);
+
+impl EmailToken {
+    pub fn parse(token: String) -> Result<Self, String> {
+        if !token.is_empty() && URL_SAFE_NO_PAD.decode(&token).is_ok() {
+            Ok(EmailToken(token))
+        } else {
+            Err("Invalid email token format".to_string())
+        }
+    }
+}
+
+impl Default for EmailToken {
+    fn default() -> Self {
+        let mut bytes = [0u8; 32];
+        rand::thread_rng().fill_bytes(&mut bytes);
+        EmailToken(URL_SAFE_NO_PAD.encode(bytes))
+    }
+}
+
+impl AsRef<str> for EmailToken {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+// Counts failed login attempts per key and keeps keys with too many of them locked out for a while
+#[async_trait::async_trait]
+pub trait LoginAttemptStore: Send + Sync {
+    // Returns the number of failures so far. They are forgotten once none was added for `window`.
+    async fn add_failure(
+        &self,
+        key: &LoginAttemptKey,
+        window: Duration,
+    ) -> Result<u32, LoginAttemptStoreError>;
+    async fn lock(
+        &self,
+        key: &LoginAttemptKey,
+        duration: Duration,
+    ) -> Result<(), LoginAttemptStoreError>;
+    // Remaining time of the lockout, if any
+    async fn locked_for(
+        &self,
+        key: &LoginAttemptKey,
+    ) -> Result<Option<Duration>, LoginAttemptStoreError>;
+    // Forgets the failures and lifts the lockout of the key
+    async fn reset(&self, key: &LoginAttemptKey) -> Result<(), LoginAttemptStoreError>;
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LoginAttemptStoreError {
+    UnexpectedError,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum LoginAttemptKey {
+    Email(Email),
+    Ip(IpAddr),
+    // Wrong codes entered for the pending 2FA login attempt of the account
+    TwoFactor(Email),
+}
+
+#[async_trait::async_trait]
+pub trait RateLimitStore: Send + Sync {
+    // Takes a token from the bucket of the key, refilled for the time since the last request.
+    // Returns how long to wait for the next token when the bucket is empty.
+    async fn take_token(
+        &self,
+        key: &str,
+        bucket: &TokenBucket,
+    ) -> Result<Option<Duration>, RateLimitStoreError>;
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RateLimitStoreError {
+    UnexpectedError,
+}
+
+#[async_trait::async_trait]
+pub trait SessionStore: Send + Sync {
+    async fn add_session(&self, session: Session) -> Result<(), SessionStoreError>;
+    async fn get_session(&self, id: &Uuid) -> Result<Session, SessionStoreError>;
+    // Most recently seen first
+    async fn get_sessions(&self, email: &Email) -> Result<Vec<Session>, SessionStoreError>;
+    // Records activity of the session, from the given address if known
+    async fn touch_session(
+        &self,
+        id: &Uuid,
+        ip_address: Option<IpAddr>,
+    ) -> Result<(), SessionStoreError>;
+    // Only removes the session if it belongs to the user
+    async fn remove_session(&self, email: &Email, id: &Uuid) -> Result<(), SessionStoreError>;
+    async fn remove_sessions(&self, email: &Email) -> Result<(), SessionStoreError>;
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SessionStoreError {
+    SessionNotFound,
+    UnexpectedError,
+}
+
+// Applications registered to sign their users in through this service
+#[async_trait::async_trait]
+pub trait OAuthClientStore: Send + Sync {
+    // Registers the client, or replaces the name and redirect URIs of an existing one
+    async fn save_client(&self, client: OAuthClient) -> Result<(), OAuthClientStoreError>;
+    async fn get_client(&self, client_id: &str) -> Result<OAuthClient, OAuthClientStoreError>;
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum OAuthClientStoreError {
+    ClientNotFound,
+    UnexpectedError,
+}
+
+#[async_trait::async_trait]
+pub trait AuthorizationCodeStore: Send + Sync {
+    async fn add_code(
+        &self,
+        code: AuthorizationCode,
+        grant: AuthorizationGrant,
+    ) -> Result<(), AuthorizationCodeStoreError>;
+    // Removes the code while returning its grant, so every code can only be redeemed once
+    async fn take_code(
+        &self,
+        code: &AuthorizationCode,
+    ) -> Result<AuthorizationGrant, AuthorizationCodeStoreError>;
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AuthorizationCodeStoreError {
+    CodeNotFound,
+    UnexpectedError,
+}
+
+// Backend services that get tokens for themselves, authenticated by their client secret
+#[async_trait::async_trait]
+pub trait MachineClientStore: Send + Sync {
+    // Registers the client, or replaces the name, scopes and secret of an existing one
+    async fn save_client(
+        &self,
+        client: MachineClient,
+        secret: &ClientSecret,
+    ) -> Result<(), MachineClientStoreError>;
+    async fn authenticate_client(
+        &self,
+        client_id: &str,
+        secret: &ClientSecret,
+    ) -> Result<MachineClient, MachineClientStoreError>;
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MachineClientStoreError {
+    // Unknown client or wrong secret, callers can not tell which
+    InvalidCredentials,
+    UnexpectedError,
+}
+
+// Signing keys of rotating JWT keys, shared by every instance so tokens signed by one of them
+// validate on all others and outlive restarts
+#[async_trait::async_trait]
+pub trait JwtKeyStore: Send + Sync {
+    // Adds the key unless another one was added less than `interval` ago, so when every instance
+    // tries to rotate at once only one of them does. Returns whether the key was added.
+    async fn add_key(
+        &self,
+        kid: &str,
+        private_key_pem: &str,
+        interval: Duration,
+    ) -> Result<bool, JwtKeyStoreError>;
+    // Newest first
+    async fn get_keys(&self) -> Result<Vec<StoredJwtKey>, JwtKeyStoreError>;
+    async fn remove_keys(&self, added_before: DateTime<Utc>) -> Result<(), JwtKeyStoreError>;
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum JwtKeyStoreError {
+    UnexpectedError,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct StoredJwtKey {
+    pub kid: String,
+    pub private_key_pem: String,
+    pub created_at: DateTime<Utc>,
+}
@@ -1,10 +1,154 @@
+use axum::http::StatusCode;
+
+use crate::domain::{Password, PasswordPolicyViolation};
+
 #[derive(Debug)]
 pub enum AuthAPIError {
     UserAlreadyExists,
     InvalidCredentials,
+    // Fields of the request body that failed validation, each with its own reason
+    InvalidInput(Vec<FieldError>),
+    // The body could not be read as the expected JSON, with the status and message of the rejection
+    MalformedRequest(StatusCode, String),
     UnexpectedError,
     IncorrectCredentials,
     MissingToken,
     InvalidToken,
     Invalid2FACodeRequest,
+    TotpNotEnrolled,
+    InvalidPasskey,
+    InvalidPasswordResetToken,
+    EmailNotVerified,
+    SessionNotFound,
+    // The authorization request of an OAuth client is unknown, tampered with or expired
+    InvalidAuthorizationRequest,
+    // Seconds until the next attempt is allowed
+    TooManyAttempts(u64),
+    // Seconds until the rate limit lets the next request through
+    RateLimited(u64),
+}
+
+impl AuthAPIError {
+    // Stable identifier of the error for clients, unlike the title it never changes
+    pub fn code(&self) -> &'static str {
+        match self {
+            AuthAPIError::UserAlreadyExists => "user_already_exists",
+            AuthAPIError::InvalidCredentials => "invalid_credentials",
+            AuthAPIError::InvalidInput(_) => "invalid_input",
+            AuthAPIError::MalformedRequest(..) => "malformed_request",
+            AuthAPIError::UnexpectedError => "internal_error",
+            AuthAPIError::IncorrectCredentials => "incorrect_credentials",
+            AuthAPIError::MissingToken => "missing_token",
+            AuthAPIError::InvalidToken => "invalid_token",
+            AuthAPIError::Invalid2FACodeRequest => "invalid_2fa_code",
+            AuthAPIError::TotpNotEnrolled => "totp_not_enrolled",
+            AuthAPIError::InvalidPasskey => "invalid_passkey",
+            AuthAPIError::InvalidPasswordResetToken => "invalid_password_reset_token",
+            AuthAPIError::EmailNotVerified => "email_not_verified",
+            AuthAPIError::SessionNotFound => "session_not_found",
+            AuthAPIError::InvalidAuthorizationRequest => "invalid_authorization_request",
+            AuthAPIError::TooManyAttempts(_) => "too_many_attempts",
+            AuthAPIError::RateLimited(_) => "rate_limited",
+        }
+    }
+}
+
+// Errors of the OAuth endpoints, reported as described in RFC 6749 rather than as problem details
+// since that is what OAuth client libraries expect. The authorization endpoint passes them to the
+// client in the redirect, the token endpoint answers with them.
+#[derive(Debug)]
+pub enum OAuthError {
+    // A parameter is missing or malformed, with a description for the developer of the client
+    InvalidRequest(String),
+    InvalidClient,
+    // The code is unknown, used up, or was issued to another client or redirect URI
+    InvalidGrant,
+    UnsupportedGrantType,
+    UnsupportedResponseType,
+    InvalidScope(String),
+    // The user did not allow the request
+    AccessDenied,
+    // The token to revoke was issued to another client
+    UnauthorizedClient,
+    ServerError,
+}
+
+impl OAuthError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            OAuthError::InvalidRequest(_) => "invalid_request",
+            OAuthError::InvalidClient => "invalid_client",
+            OAuthError::InvalidGrant => "invalid_grant",
+            OAuthError::UnsupportedGrantType => "unsupported_grant_type",
+            OAuthError::UnsupportedResponseType => "unsupported_response_type",
+            OAuthError::InvalidScope(_) => "invalid_scope",
+            OAuthError::AccessDenied => "access_denied",
+            OAuthError::UnauthorizedClient => "unauthorized_client",
+            OAuthError::ServerError => "server_error",
+        }
+    }
+
+    pub fn description(&self) -> Option<&str> {
+        match self {
+            OAuthError::InvalidRequest(description) | OAuthError::InvalidScope(description) => {
+                Some(description)
+            }
+            _ => None,
+        }
+    }
+}
+
+// Why a single field of the request body was rejected. `field` is named as in the JSON body.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct FieldError {
+    pub field: String,
+    pub code: String,
+    pub detail: String,
+    // Only set for a password that breaks the password policy, one entry per broken rule
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub reasons: Vec<PasswordPolicyViolation>,
+}
+
+// Collects the validation failures of a request, so all of them are reported at once
+#[derive(Debug, Default)]
+pub struct FieldErrors(Vec<FieldError>);
+
+impl FieldErrors {
+    // Returns the parsed value, or records the error under the field and code
+    pub fn check<T>(&mut self, field: &str, code: &str, result: Result<T, String>) -> Option<T> {
+        result
+            .map_err(|detail| {
+                self.0.push(FieldError {
+                    field: field.to_owned(),
+                    code: code.to_owned(),
+                    detail,
+                    reasons: Vec::new(),
+                })
+            })
+            .ok()
+    }
+
+    // Like `check`, for a new password parsed by the password policy
+    pub fn check_password(
+        &mut self,
+        field: &str,
+        result: Result<Password, Vec<PasswordPolicyViolation>>,
+    ) -> Option<Password> {
+        result
+            .map_err(|reasons| {
+                self.0.push(FieldError {
+                    field: field.to_owned(),
+                    code: "weak_password".to_owned(),
+                    detail: "Password does not meet the requirements".to_owned(),
+                    reasons,
+                })
+            })
+            .ok()
+    }
+}
+
+impl From<FieldErrors> for AuthAPIError {
+    fn from(errors: FieldErrors) -> Self {
+        AuthAPIError::InvalidInput(errors.0)
+    }
 }
@@ -0,0 +1,281 @@
+use std::fmt;
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use crate::domain::Email;
+
+// Every OpenID Connect request has to ask for it, the email scope adds the address to the tokens
+pub const OPENID_SCOPE: &str = "openid";
+pub const EMAIL_SCOPE: &str = "email";
+pub const SUPPORTED_SCOPES: [&str; 2] = [OPENID_SCOPE, EMAIL_SCOPE];
+
+// Verifiers are 43 to 128 characters long (RFC 7636)
+const MIN_CODE_VERIFIER_LENGTH: usize = 43;
+const MAX_CODE_VERIFIER_LENGTH: usize = 128;
+
+// An application that signs its users in through this service
+#[derive(Debug, Clone, PartialEq)]
+pub struct OAuthClient {
+    pub client_id: String,
+    // Shown to the user when they are asked to allow the sign in
+    pub name: String,
+    // Codes are only ever sent to one of these, a requested URI has to match one exactly
+    pub redirect_uris: Vec<String>,
+}
+
+impl OAuthClient {
+    pub fn allows_redirect_uri(&self, redirect_uri: &str) -> bool {
+        self.redirect_uris.iter().any(|uri| uri == redirect_uri)
+    }
+}
+
+// A backend service that gets tokens for itself with the client credentials grant, no user is
+// involved. Its secret is only known to the stores, as a hash.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MachineClient {
+    pub client_id: String,
+    pub name: String,
+    // Tokens of the client can carry any of these, and nothing else
+    pub allowed_scopes: Vec<String>,
+}
+
+impl MachineClient {
+    pub fn allows_scope(&self, scope: &str) -> bool {
+        self.allowed_scopes.iter().any(|allowed| allowed == scope)
+    }
+}
+
+// Secret a machine client authenticates with, generated when the client is registered
+#[derive(Clone, PartialEq)]
+pub struct ClientSecret(String);
+
+impl ClientSecret {
+    pub fn parse(secret: String) -> Result<Self, String> {
+        if secret.is_empty() {
+            Err("Client secret must not be empty".to_string())
+        } else {
+            Ok(ClientSecret(secret))
+        }
+    }
+}
+
+impl Default for ClientSecret {
+    fn default() -> Self {
+        let mut bytes = [0u8; 32];
+        rand::thread_rng().fill_bytes(&mut bytes);
+        ClientSecret(URL_SAFE_NO_PAD.encode(bytes))
+    }
+}
+
+impl AsRef<str> for ClientSecret {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+// Space separated list of scopes, in the order they were asked for and without duplicates
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Scope(Vec<String>);
+
+impl Scope {
+    pub fn parse(scope: &str) -> Result<Self, String> {
+        let mut scopes: Vec<String> = Vec::new();
+
+        for token in scope.split(' ').filter(|token| !token.is_empty()) {
+            // Printable ASCII except space, `"` and `\` (RFC 6749)
+            if !token
+                .bytes()
+                .all(|b| (0x21..=0x7e).contains(&b) && b != b'"' && b != b'\\')
+            {
+                return Err(format!("{} is not a valid scope", token));
+            }
+            if !scopes.iter().any(|scope| scope == token) {
+                scopes.push(token.to_owned());
+            }
+        }
+
+        if scopes.is_empty() {
+            return Err("Scope must not be empty".to_string());
+        }
+
+        Ok(Scope(scopes))
+    }
+
+    pub fn contains(&self, scope: &str) -> bool {
+        self.0.iter().any(|s| s == scope)
+    }
+
+    // Only the scopes that are allowed, or nothing if none of them is
+    pub fn restrict(&self, is_allowed: impl Fn(&str) -> bool) -> Option<Self> {
+        let scopes: Vec<String> = self.0.iter().filter(|s| is_allowed(s)).cloned().collect();
+        (!scopes.is_empty()).then_some(Scope(scopes))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &str> {
+        self.0.iter().map(String::as_str)
+    }
+}
+
+impl fmt::Display for Scope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0.join(" "))
+    }
+}
+
+impl TryFrom<String> for Scope {
+    type Error = String;
+
+    fn try_from(scope: String) -> Result<Self, Self::Error> {
+        Scope::parse(&scope)
+    }
+}
+
+impl From<Scope> for String {
+    fn from(scope: Scope) -> Self {
+        scope.to_string()
+    }
+}
+
+// PKCE challenge (RFC 7636) of an authorization request. Only the S256 method is supported, the
+// challenge is the base64url encoded SHA-256 of the verifier the client redeems the code with.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct CodeChallenge(String);
+
+impl CodeChallenge {
+    pub const METHOD: &'static str = "S256";
+
+    pub fn parse(challenge: String) -> Result<Self, String> {
+        match URL_SAFE_NO_PAD.decode(&challenge) {
+            Ok(digest) if digest.len() == Sha256::output_size() => Ok(CodeChallenge(challenge)),
+            _ => Err("Invalid code challenge".to_string()),
+        }
+    }
+
+    pub fn verify(&self, verifier: &str) -> bool {
+        let is_valid_verifier = (MIN_CODE_VERIFIER_LENGTH..=MAX_CODE_VERIFIER_LENGTH)
+            .contains(&verifier.len())
+            && verifier
+                .bytes()
+                .all(|b| b.is_ascii_alphanumeric() || b"-._~".contains(&b));
+
+        is_valid_verifier && URL_SAFE_NO_PAD.encode(Sha256::digest(verifier.as_bytes())) == self.0
+    }
+}
+
+impl TryFrom<String> for CodeChallenge {
+    type Error = String;
+
+    fn try_from(challenge: String) -> Result<Self, Self::Error> {
+        CodeChallenge::parse(challenge)
+    }
+}
+
+impl From<CodeChallenge> for String {
+    fn from(challenge: CodeChallenge) -> Self {
+        challenge.0
+    }
+}
+
+impl AsRef<str> for CodeChallenge {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+// A validated authorization request, waiting for the user to log in and allow it
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct AuthorizationRequest {
+    pub client_id: String,
+    pub redirect_uri: String,
+    pub scope: Scope,
+    // Handed back to the client unchanged along with the code
+    pub state: Option<String>,
+    // Copied into the ID token, so the client can tell it was issued for this request
+    pub nonce: Option<String>,
+    pub code_challenge: CodeChallenge,
+}
+
+// Single use code the client exchanges for tokens
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct AuthorizationCode(String);
+
+impl AuthorizationCode {
+    pub fn parse(code: String) -> Result<Self, String> {
+        if !code.is_empty() && URL_SAFE_NO_PAD.decode(&code).is_ok() {
+            Ok(AuthorizationCode(code))
+        } else {
+            Err("Invalid authorization code format".to_string())
+        }
+    }
+}
+
+impl Default for AuthorizationCode {
+    fn default() -> Self {
+        let mut bytes = [0u8; 32];
+        rand::thread_rng().fill_bytes(&mut bytes);
+        AuthorizationCode(URL_SAFE_NO_PAD.encode(bytes))
+    }
+}
+
+impl AsRef<str> for AuthorizationCode {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+// What the user allowed a client, kept with the authorization code until the client redeems it
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuthorizationGrant {
+    pub client_id: String,
+    pub redirect_uri: String,
+    pub email: Email,
+    // Session the user allowed the request from, the tokens of the client are bound to it
+    pub session_id: Uuid,
+    pub scope: Scope,
+    pub nonce: Option<String>,
+    pub code_challenge: CodeChallenge,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_parse_scopes_without_duplicates() {
+        let scope = Scope::parse("openid  email openid").unwrap();
+
+        assert_eq!(scope.to_string(), "openid email");
+        assert!(scope.contains(OPENID_SCOPE));
+        assert!(!scope.contains("profile"));
+        assert_eq!(
+            Scope::parse("profile email")
+                .unwrap()
+                .restrict(|s| s == EMAIL_SCOPE),
+            Some(Scope::parse("email").unwrap())
+        );
+        assert!(Scope::parse(" ").is_err());
+        assert!(Scope::parse("openid \"email\"").is_err());
+    }
+
+    #[test]
+    fn should_verify_code_verifier() {
+        // Example of RFC 7636, appendix B
+        let challenge =
+            CodeChallenge::parse("E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM".to_owned()).unwrap();
+
+        assert!(challenge.verify("dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk"));
+        assert!(!challenge.verify("dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXl"));
+        assert!(!challenge.verify("short"));
+    }
+
+    #[test]
+    fn should_reject_challenges_that_are_no_sha256_digest() {
+        assert!(CodeChallenge::parse("E9Melhoa2OwvFrEMTJguCHaoeK1t8URW".to_owned()).is_err());
+        assert!(CodeChallenge::parse("not base64!".to_owned()).is_err());
+    }
+}
@@ -0,0 +1,76 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use rand::RngCore;
+
+use crate::domain::Email;
+
+// WebAuthn requires at least 16 random bytes per ceremony
+const CHALLENGE_LENGTH: usize = 32;
+
+// Id the authenticator assigned to a passkey, opaque bytes sent base64url encoded
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CredentialId(Vec<u8>);
+
+impl CredentialId {
+    pub fn parse(id: &str) -> Result<Self, String> {
+        match URL_SAFE_NO_PAD.decode(id) {
+            Ok(bytes) if !bytes.is_empty() => Ok(CredentialId(bytes)),
+            _ => Err("Invalid credential id".to_string()),
+        }
+    }
+
+    pub fn to_base64(&self) -> String {
+        URL_SAFE_NO_PAD.encode(&self.0)
+    }
+}
+
+impl From<Vec<u8>> for CredentialId {
+    fn from(bytes: Vec<u8>) -> Self {
+        CredentialId(bytes)
+    }
+}
+
+impl AsRef<[u8]> for CredentialId {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+// A registered passkey. Only the public key is known to us, the private key never leaves the authenticator.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PasskeyCredential {
+    pub credential_id: CredentialId,
+    pub email: Email,
+    // Uncompressed SEC1 encoded P-256 point
+    pub public_key: Vec<u8>,
+    // Signature counter reported by the authenticator, a counter going backwards points to a cloned key
+    pub sign_count: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WebAuthnCeremony {
+    Registration,
+    Authentication,
+}
+
+// Server side state of a registration or login ceremony that is waiting for the authenticator
+#[derive(Debug, Clone, PartialEq)]
+pub struct WebAuthnChallenge {
+    pub ceremony: WebAuthnCeremony,
+    pub challenge: Vec<u8>,
+    // Registration always belongs to a user, a login may start without knowing who is logging in
+    pub email: Option<Email>,
+}
+
+impl WebAuthnChallenge {
+    pub fn new(ceremony: WebAuthnCeremony, email: Option<Email>) -> Self {
+        let mut challenge = vec![0u8; CHALLENGE_LENGTH];
+        rand::thread_rng().fill_bytes(&mut challenge);
+
+        Self {
+            ceremony,
+            challenge,
+            email,
+        }
+    }
+}
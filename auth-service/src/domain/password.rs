@@ -1,9 +1,12 @@
+// Floor for every password, new ones also have to follow the configured `PasswordPolicy`
+pub const MIN_PASSWORD_LENGTH: usize = 8;
+
 #[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
 pub struct Password(String);
 
 impl Password {
     pub fn parse(s: String) -> Result<Password, String> {
-        if s.len() >= 8 {
+        if s.len() >= MIN_PASSWORD_LENGTH {
             Ok(Password(s))
         } else {
             Err("Password must be at least 8 characters long".to_string())
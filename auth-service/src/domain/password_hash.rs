@@ -0,0 +1,331 @@
+use argon2::{
+    password_hash::SaltString, Algorithm, Argon2, Params, PasswordHasher, PasswordVerifier, Version,
+};
+
+// Prefixes of bcrypt hashes in the modular crypt format, one per revision of the algorithm
+const BCRYPT_PREFIXES: [&str; 4] = ["$2a$", "$2b$", "$2x$", "$2y$"];
+
+// PHC string of an Argon2 hash, or of a bcrypt, PBKDF2 or scrypt hash taken over from accounts
+// imported from another system. Only the stores handle it, the rest of the service deals with
+// `User`, which carries no credentials.
+#[derive(Clone, PartialEq)]
+pub struct PasswordHash(String);
+
+impl PasswordHash {
+    pub fn parse(s: String) -> Result<Self, String> {
+        Scheme::of(&s)?;
+        Ok(PasswordHash(s))
+    }
+
+    // Hashing is slow on purpose, so it runs on the blocking thread pool
+    #[tracing::instrument(name = "Computing password hash", skip_all)]
+    pub async fn compute(
+        secret: &str,
+        params: &PasswordHashParams,
+    ) -> Result<Self, PasswordHashError> {
+        let secret = secret.to_owned();
+        let params = *params;
+        let current_span = tracing::Span::current();
+
+        tokio::task::spawn_blocking(move || {
+            current_span.in_scope(|| {
+                let salt = SaltString::generate(&mut rand::thread_rng());
+
+                let hash = params
+                    .hasher()?
+                    .hash_password(secret.as_bytes(), &salt)
+                    .map_err(|_| PasswordHashError::UnexpectedError)?;
+
+                Ok(PasswordHash(hash.to_string()))
+            })
+        })
+        .await
+        .map_err(|_| PasswordHashError::UnexpectedError)?
+    }
+
+    #[tracing::instrument(name = "Verifying password hash", skip_all)]
+    pub async fn verify(&self, candidate: &str) -> Result<(), PasswordHashError> {
+        let hash = self.0.clone();
+        let candidate = candidate.to_owned();
+        let current_span = tracing::Span::current();
+
+        tokio::task::spawn_blocking(move || {
+            current_span.in_scope(|| {
+                let scheme = Scheme::of(&hash).map_err(|_| PasswordHashError::UnexpectedError)?;
+
+                let argon2 = Argon2::default();
+                let verifier: &dyn PasswordVerifier = match scheme {
+                    Scheme::Argon2 => &argon2,
+                    Scheme::Pbkdf2 => &pbkdf2::Pbkdf2,
+                    Scheme::Scrypt => &scrypt::Scrypt,
+                    Scheme::Bcrypt => {
+                        return match bcrypt::verify(candidate.as_bytes(), &hash) {
+                            Ok(true) => Ok(()),
+                            Ok(false) => Err(PasswordHashError::Mismatch),
+                            Err(_) => Err(PasswordHashError::UnexpectedError),
+                        };
+                    }
+                };
+
+                let hash = argon2::PasswordHash::new(&hash)
+                    .map_err(|_| PasswordHashError::UnexpectedError)?;
+
+                verifier
+                    .verify_password(candidate.as_bytes(), &hash)
+                    .map_err(|e| match e {
+                        argon2::password_hash::Error::Password => PasswordHashError::Mismatch,
+                        _ => PasswordHashError::UnexpectedError,
+                    })
+            })
+        })
+        .await
+        .map_err(|_| PasswordHashError::UnexpectedError)?
+    }
+
+    // Whether the hash was computed with other parameters than the given ones, or with another
+    // algorithm than Argon2id. The password it belongs to should then be hashed again while it is
+    // at hand.
+    pub fn needs_rehash(&self, params: &PasswordHashParams) -> bool {
+        let Ok(hash) = argon2::PasswordHash::new(&self.0) else {
+            return true;
+        };
+
+        let same_params = Params::try_from(&hash).is_ok_and(|current| {
+            current.m_cost() == params.memory_kib
+                && current.t_cost() == params.iterations
+                && current.p_cost() == params.parallelism
+        });
+
+        !same_params
+            || hash.algorithm != Algorithm::Argon2id.ident()
+            || hash.version != Some(Version::V0x13 as u32)
+    }
+}
+
+impl AsRef<str> for PasswordHash {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+// Algorithm a hash was computed with, told apart by the identifier at the start of the string
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Scheme {
+    Argon2,
+    Pbkdf2,
+    Scrypt,
+    Bcrypt,
+}
+
+impl Scheme {
+    fn of(hash: &str) -> Result<Self, String> {
+        // bcrypt predates PHC strings, its hashes have their own layout
+        if BCRYPT_PREFIXES
+            .iter()
+            .any(|prefix| hash.starts_with(prefix))
+        {
+            return hash
+                .parse::<bcrypt::HashParts>()
+                .map(|_| Scheme::Bcrypt)
+                .map_err(|e| format!("Invalid password hash: {}", e));
+        }
+
+        let hash =
+            argon2::PasswordHash::new(hash).map_err(|e| format!("Invalid password hash: {}", e))?;
+
+        match hash.algorithm.as_str() {
+            "argon2id" | "argon2i" | "argon2d" => Ok(Scheme::Argon2),
+            "pbkdf2" | "pbkdf2-sha256" | "pbkdf2-sha512" => Ok(Scheme::Pbkdf2),
+            "scrypt" => Ok(Scheme::Scrypt),
+            algorithm => Err(format!(
+                "Unsupported password hash algorithm: {}",
+                algorithm
+            )),
+        }
+    }
+}
+
+// Cost of new Argon2id hashes. Raising it leaves existing hashes valid, they are upgraded the
+// next time their password is checked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PasswordHashParams {
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+}
+
+impl PasswordHashParams {
+    pub fn new(memory_kib: u32, iterations: u32, parallelism: u32) -> Result<Self, String> {
+        let params = Self {
+            memory_kib,
+            iterations,
+            parallelism,
+        };
+        params
+            .hasher()
+            .map_err(|_| "Invalid Argon2 parameters".to_owned())?;
+
+        Ok(params)
+    }
+
+    fn hasher(&self) -> Result<Argon2<'static>, PasswordHashError> {
+        let params = Params::new(self.memory_kib, self.iterations, self.parallelism, None)
+            .map_err(|_| PasswordHashError::UnexpectedError)?;
+
+        Ok(Argon2::new(Algorithm::Argon2id, Version::V0x13, params))
+    }
+}
+
+impl Default for PasswordHashParams {
+    fn default() -> Self {
+        Self {
+            memory_kib: 15000,
+            iterations: 2,
+            parallelism: 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PasswordHashError {
+    Mismatch,
+    UnexpectedError,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn should_verify_only_the_hashed_secret() {
+        let hash = PasswordHash::compute("password123", &PasswordHashParams::default())
+            .await
+            .unwrap();
+
+        assert!(hash.verify("password123").await.is_ok());
+        assert_eq!(
+            hash.verify("wrongpassword").await,
+            Err(PasswordHashError::Mismatch)
+        );
+    }
+
+    #[tokio::test]
+    async fn should_salt_every_hash() {
+        let first = PasswordHash::compute("password123", &PasswordHashParams::default())
+            .await
+            .unwrap();
+        let second = PasswordHash::compute("password123", &PasswordHashParams::default())
+            .await
+            .unwrap();
+
+        assert!(first != second);
+    }
+
+    #[tokio::test]
+    async fn should_parse_only_hashes() {
+        let hash = PasswordHash::compute("password123", &PasswordHashParams::default())
+            .await
+            .unwrap();
+
+        assert!(PasswordHash::parse(hash.as_ref().to_owned()).is_ok());
+        assert!(PasswordHash::parse("password123".to_owned()).is_err());
+    }
+
+    #[tokio::test]
+    async fn should_need_rehash_only_when_params_differ() {
+        let params = PasswordHashParams::default();
+        let hash = PasswordHash::compute("password123", &params).await.unwrap();
+
+        assert!(!hash.needs_rehash(&params));
+        assert!(hash.needs_rehash(&PasswordHashParams {
+            memory_kib: params.memory_kib * 2,
+            ..params
+        }));
+        assert!(hash.needs_rehash(&PasswordHashParams {
+            iterations: params.iterations + 1,
+            ..params
+        }));
+    }
+
+    // Computed with low costs, imported hashes are verified with whatever parameters they carry
+    fn legacy_hashes(password: &str) -> Vec<PasswordHash> {
+        let salt = SaltString::generate(&mut rand::thread_rng());
+        let bcrypt = bcrypt::hash_with_result(password, 4).unwrap();
+        let pbkdf2_params = pbkdf2::Params {
+            rounds: 1000,
+            output_length: 32,
+        };
+        let pbkdf2 = |algorithm: pbkdf2::Algorithm| {
+            pbkdf2::Pbkdf2
+                .hash_password_customized(
+                    password.as_bytes(),
+                    Some(algorithm.ident()),
+                    None,
+                    pbkdf2_params,
+                    &salt,
+                )
+                .unwrap()
+                .to_string()
+        };
+        let scrypt = scrypt::Scrypt
+            .hash_password_customized(
+                password.as_bytes(),
+                None,
+                None,
+                scrypt::Params::new(4, 8, 1, 32).unwrap(),
+                &salt,
+            )
+            .unwrap()
+            .to_string();
+
+        [
+            bcrypt.format_for_version(bcrypt::Version::TwoA),
+            bcrypt.format_for_version(bcrypt::Version::TwoB),
+            bcrypt.format_for_version(bcrypt::Version::TwoY),
+            pbkdf2(pbkdf2::Algorithm::Pbkdf2Sha256),
+            pbkdf2(pbkdf2::Algorithm::Pbkdf2Sha512),
+            scrypt,
+        ]
+        .into_iter()
+        .map(|hash| PasswordHash::parse(hash).unwrap())
+        .collect()
+    }
+
+    #[tokio::test]
+    async fn should_verify_legacy_hashes() {
+        for hash in legacy_hashes("password123") {
+            assert!(
+                hash.verify("password123").await.is_ok(),
+                "{}",
+                hash.as_ref()
+            );
+            assert_eq!(
+                hash.verify("wrongpassword").await,
+                Err(PasswordHashError::Mismatch),
+                "{}",
+                hash.as_ref()
+            );
+        }
+    }
+
+    #[test]
+    fn should_always_rehash_legacy_hashes() {
+        for hash in legacy_hashes("password123") {
+            assert!(hash.needs_rehash(&PasswordHashParams::default()));
+        }
+    }
+
+    #[test]
+    fn should_reject_unsupported_hashes() {
+        assert!(PasswordHash::parse("$2b$04$tooshort".to_owned()).is_err());
+        assert!(PasswordHash::parse("$md5$rounds=1000$salt$hash".to_owned()).is_err());
+        assert!(PasswordHash::parse("$1$saltsalt$qjXMvbEw8oaL.CzflDugX/".to_owned()).is_err());
+    }
+
+    #[test]
+    fn should_reject_invalid_params() {
+        assert!(PasswordHashParams::new(19456, 2, 1).is_ok());
+        assert!(PasswordHashParams::new(0, 2, 1).is_err());
+        assert!(PasswordHashParams::new(19456, 0, 1).is_err());
+    }
+}
@@ -0,0 +1,316 @@
+use std::{str::FromStr, sync::Arc};
+
+use crate::domain::{BreachedPasswords, Email, Password, MIN_PASSWORD_LENGTH};
+
+// Local parts shorter than this would match by accident, like an `a@example.com` account
+const MIN_EMAIL_LOCAL_PART_MATCH: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CharacterClass {
+    Lowercase,
+    Uppercase,
+    Digit,
+    // Anything else, including whitespace and non-latin letters without a case
+    Symbol,
+}
+
+impl CharacterClass {
+    fn of(c: char) -> Self {
+        if c.is_lowercase() {
+            CharacterClass::Lowercase
+        } else if c.is_uppercase() {
+            CharacterClass::Uppercase
+        } else if c.is_ascii_digit() {
+            CharacterClass::Digit
+        } else {
+            CharacterClass::Symbol
+        }
+    }
+
+    // Number of characters an attacker has to try per position for this class
+    fn pool_size(&self) -> u32 {
+        match self {
+            CharacterClass::Lowercase | CharacterClass::Uppercase => 26,
+            CharacterClass::Digit => 10,
+            CharacterClass::Symbol => 33,
+        }
+    }
+
+    // Parses a comma separated list like `lowercase,digit`
+    pub fn parse_list(classes: &str) -> Result<Vec<Self>, String> {
+        classes
+            .split(',')
+            .map(str::trim)
+            .filter(|class| !class.is_empty())
+            .map(str::parse)
+            .collect()
+    }
+}
+
+impl FromStr for CharacterClass {
+    type Err = String;
+
+    fn from_str(class: &str) -> Result<Self, Self::Err> {
+        match class {
+            "lowercase" => Ok(CharacterClass::Lowercase),
+            "uppercase" => Ok(CharacterClass::Uppercase),
+            "digit" => Ok(CharacterClass::Digit),
+            "symbol" => Ok(CharacterClass::Symbol),
+            _ => Err(format!("Unknown character class: {}", class)),
+        }
+    }
+}
+
+// A rule a new password broke, reported to the client so it can tell the user what to change
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "rule", rename_all = "snake_case")]
+pub enum PasswordPolicyViolation {
+    TooShort { min_length: usize },
+    TooLong { max_length: usize },
+    MissingCharacterClass { class: CharacterClass },
+    ContainsEmail,
+    TooWeak { strength: u8, min_strength: u8 },
+    Breached,
+}
+
+// Rules for passwords users choose at signup, on a change or on a reset. Passwords that are
+// only checked against an existing hash do not have to follow them, so tightening the policy
+// never locks anyone out.
+#[derive(Debug, Clone)]
+pub struct PasswordPolicy {
+    // Counted in characters, and never below `MIN_PASSWORD_LENGTH`
+    pub min_length: usize,
+    pub max_length: usize,
+    pub required_classes: Vec<CharacterClass>,
+    // Reject passwords that contain the part of the user's email before the `@`
+    pub reject_email: bool,
+    // From 0 to 4, see `estimate_strength`
+    pub min_strength: u8,
+    pub breached_passwords: Option<Arc<BreachedPasswords>>,
+}
+
+impl PasswordPolicy {
+    // Checks every rule rather than stopping at the first one, so all of them are reported at once
+    pub fn check(&self, password: &str, email: &Email) -> Result<(), Vec<PasswordPolicyViolation>> {
+        let mut violations = Vec::new();
+
+        let length = password.chars().count();
+        let min_length = self.min_length.max(MIN_PASSWORD_LENGTH);
+        if length < min_length {
+            violations.push(PasswordPolicyViolation::TooShort { min_length });
+        }
+        if length > self.max_length {
+            violations.push(PasswordPolicyViolation::TooLong {
+                max_length: self.max_length,
+            });
+        }
+
+        for class in &self.required_classes {
+            if !password.chars().any(|c| CharacterClass::of(c) == *class) {
+                violations.push(PasswordPolicyViolation::MissingCharacterClass { class: *class });
+            }
+        }
+
+        if self.reject_email && contains_email_local_part(password, email) {
+            violations.push(PasswordPolicyViolation::ContainsEmail);
+        }
+
+        let strength = estimate_strength(password);
+        if strength < self.min_strength {
+            violations.push(PasswordPolicyViolation::TooWeak {
+                strength,
+                min_strength: self.min_strength,
+            });
+        }
+
+        if let Some(breached_passwords) = &self.breached_passwords {
+            if breached_passwords.contains(password) {
+                violations.push(PasswordPolicyViolation::Breached);
+            }
+        }
+
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(violations)
+        }
+    }
+
+    pub fn parse(
+        &self,
+        password: String,
+        email: &Email,
+    ) -> Result<Password, Vec<PasswordPolicyViolation>> {
+        self.check(&password, email)?;
+
+        // Can not fail once the length was checked, but the floor is enforced there as well
+        Password::parse(password).map_err(|_| {
+            vec![PasswordPolicyViolation::TooShort {
+                min_length: MIN_PASSWORD_LENGTH,
+            }]
+        })
+    }
+}
+
+impl Default for PasswordPolicy {
+    fn default() -> Self {
+        Self {
+            min_length: MIN_PASSWORD_LENGTH,
+            // Long enough for any passphrase, short enough to keep hashing it cheap
+            max_length: 128,
+            required_classes: Vec::new(),
+            reject_email: true,
+            min_strength: 0,
+            breached_passwords: None,
+        }
+    }
+}
+
+fn contains_email_local_part(password: &str, email: &Email) -> bool {
+    let local_part = email
+        .as_ref()
+        .split('@')
+        .next()
+        .unwrap_or_default()
+        .to_lowercase();
+
+    local_part.chars().count() >= MIN_EMAIL_LOCAL_PART_MATCH
+        && password.to_lowercase().contains(&local_part)
+}
+
+// Rough guess of how hard the password is to brute force, from 0 (trivial) to 4 (very strong).
+// Repeated characters and runs like `abc` or `123` barely add to the guesses needed, so only the
+// characters that break such a pattern count towards the entropy.
+pub fn estimate_strength(password: &str) -> u8 {
+    let chars: Vec<char> = password.chars().collect();
+    if chars.is_empty() {
+        return 0;
+    }
+
+    let mut classes: Vec<CharacterClass> = chars.iter().map(|c| CharacterClass::of(*c)).collect();
+    classes.sort_by_key(|class| *class as u8);
+    classes.dedup();
+    let pool_size: u32 = classes.iter().map(CharacterClass::pool_size).sum();
+
+    let effective_length = 1 + chars
+        .windows(2)
+        .filter(|pair| (pair[1] as i64 - pair[0] as i64).abs() > 1)
+        .count();
+    let entropy_bits = effective_length as f64 * f64::from(pool_size).log2();
+
+    match entropy_bits {
+        bits if bits < 28.0 => 0,
+        bits if bits < 36.0 => 1,
+        bits if bits < 60.0 => 2,
+        bits if bits < 128.0 => 3,
+        _ => 4,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn email() -> Email {
+        Email::parse("jane.doe@example.com".to_owned()).unwrap()
+    }
+
+    #[test]
+    fn should_accept_passwords_following_the_default_policy() {
+        let policy = PasswordPolicy::default();
+
+        assert!(policy.check("password123", &email()).is_ok());
+        assert!(policy.parse("password123".to_owned(), &email()).is_ok());
+    }
+
+    #[test]
+    fn should_report_every_violation() {
+        let policy = PasswordPolicy {
+            required_classes: vec![CharacterClass::Uppercase, CharacterClass::Symbol],
+            min_strength: 3,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            policy.check("jane.doe", &email()),
+            Err(vec![
+                PasswordPolicyViolation::MissingCharacterClass {
+                    class: CharacterClass::Uppercase
+                },
+                PasswordPolicyViolation::ContainsEmail,
+                PasswordPolicyViolation::TooWeak {
+                    strength: 2,
+                    min_strength: 3
+                },
+            ])
+        );
+    }
+
+    #[test]
+    fn should_enforce_length_in_characters() {
+        let policy = PasswordPolicy {
+            min_length: 4,
+            max_length: 10,
+            ..Default::default()
+        };
+
+        // The floor of `Password::parse` holds even if the policy is configured lower
+        assert_eq!(
+            policy.check("short", &email()),
+            Err(vec![PasswordPolicyViolation::TooShort {
+                min_length: MIN_PASSWORD_LENGTH
+            }])
+        );
+        assert!(policy.check("pässwörd", &email()).is_ok());
+        assert_eq!(
+            policy.check("passwords123", &email()),
+            Err(vec![PasswordPolicyViolation::TooLong { max_length: 10 }])
+        );
+    }
+
+    #[test]
+    fn should_ignore_short_email_local_parts() {
+        let policy = PasswordPolicy::default();
+        let email = Email::parse("jo@example.com".to_owned()).unwrap();
+
+        assert!(policy.check("majorette42", &email).is_ok());
+    }
+
+    #[test]
+    fn should_reject_breached_passwords() {
+        // SHA-1 of "password123"
+        let corpus =
+            BreachedPasswords::from_reader("CBFDAC6008F9CAB4083784CBD1874F76618D2A97".as_bytes())
+                .unwrap();
+        let policy = PasswordPolicy {
+            breached_passwords: Some(Arc::new(corpus)),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            policy.check("password123", &email()),
+            Err(vec![PasswordPolicyViolation::Breached])
+        );
+        assert!(policy.check("password124", &email()).is_ok());
+    }
+
+    #[test]
+    fn should_rate_patterns_weaker_than_random_passwords() {
+        assert_eq!(estimate_strength(""), 0);
+        assert_eq!(estimate_strength("aaaaaaaaaaaa"), 0);
+        assert_eq!(estimate_strength("123456789"), 0);
+        assert_eq!(estimate_strength("password123"), 2);
+        assert_eq!(estimate_strength("Tr0ub4dor&3"), 3);
+        assert_eq!(estimate_strength("correct horse battery staple"), 4);
+    }
+
+    #[test]
+    fn should_parse_character_class_lists() {
+        assert_eq!(
+            CharacterClass::parse_list("lowercase, digit,"),
+            Ok(vec![CharacterClass::Lowercase, CharacterClass::Digit])
+        );
+        assert!(CharacterClass::parse_list("emoji").is_err());
+    }
+}
@@ -0,0 +1,159 @@
+use std::{str::FromStr, time::Duration};
+
+// What requests are told apart by when counting them against a limit
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RateLimitKey {
+    Ip,
+    // The `email` field of a JSON request body
+    Email,
+    // The user the auth token of the request was issued to
+    Subject,
+}
+
+impl RateLimitKey {
+    pub fn name(&self) -> &'static str {
+        match self {
+            RateLimitKey::Ip => "ip",
+            RateLimitKey::Email => "email",
+            RateLimitKey::Subject => "subject",
+        }
+    }
+}
+
+impl FromStr for RateLimitKey {
+    type Err = String;
+
+    fn from_str(key: &str) -> Result<Self, Self::Err> {
+        match key {
+            "ip" => Ok(RateLimitKey::Ip),
+            "email" => Ok(RateLimitKey::Email),
+            "subject" => Ok(RateLimitKey::Subject),
+            _ => Err(format!("Unknown rate limit key: {}", key)),
+        }
+    }
+}
+
+// Holds up to `capacity` requests and refills at `capacity` requests per `period`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TokenBucket {
+    pub capacity: u32,
+    pub period: Duration,
+}
+
+impl TokenBucket {
+    // Time it takes to refill a single token
+    pub fn refill_interval(&self) -> Duration {
+        self.period / self.capacity
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RateLimitRule {
+    // Exact request path, or `*` for every path
+    pub route: String,
+    pub key: RateLimitKey,
+    pub bucket: TokenBucket,
+}
+
+impl RateLimitRule {
+    pub fn matches(&self, path: &str) -> bool {
+        self.route == "*" || self.route == path
+    }
+
+    // Parses a comma separated list of rules like `/login ip 10/60`, allowing 10 requests to
+    // `/login` per client address every 60 seconds
+    pub fn parse_list(rules: &str) -> Result<Vec<Self>, String> {
+        rules
+            .split(',')
+            .map(str::trim)
+            .filter(|rule| !rule.is_empty())
+            .map(str::parse)
+            .collect()
+    }
+}
+
+impl FromStr for RateLimitRule {
+    type Err = String;
+
+    fn from_str(rule: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid rate limit rule: {}", rule);
+
+        let [route, key, limit] = rule.split_whitespace().collect::<Vec<_>>()[..] else {
+            return Err(invalid());
+        };
+        let (capacity, seconds) = limit.split_once('/').ok_or_else(invalid)?;
+
+        let capacity: u32 = capacity.parse().map_err(|_| invalid())?;
+        let seconds: u64 = seconds.parse().map_err(|_| invalid())?;
+        if capacity == 0 || seconds == 0 || !(route == "*" || route.starts_with('/')) {
+            return Err(invalid());
+        }
+
+        Ok(RateLimitRule {
+            route: route.to_owned(),
+            key: key.parse()?,
+            bucket: TokenBucket {
+                capacity,
+                period: Duration::from_secs(seconds),
+            },
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::constants::DEFAULT_RATE_LIMITS;
+
+    #[test]
+    fn test_parse_rule_list() {
+        let rules = RateLimitRule::parse_list("/login ip 10/60, * subject 100/1,").unwrap();
+
+        assert_eq!(
+            rules,
+            vec![
+                RateLimitRule {
+                    route: "/login".to_owned(),
+                    key: RateLimitKey::Ip,
+                    bucket: TokenBucket {
+                        capacity: 10,
+                        period: Duration::from_secs(60),
+                    },
+                },
+                RateLimitRule {
+                    route: "*".to_owned(),
+                    key: RateLimitKey::Subject,
+                    bucket: TokenBucket {
+                        capacity: 100,
+                        period: Duration::from_secs(1),
+                    },
+                },
+            ]
+        );
+        assert!(rules[1].matches("/signup"));
+        assert!(!rules[0].matches("/login/other"));
+    }
+
+    #[test]
+    fn test_default_rules_are_valid() {
+        let rules = RateLimitRule::parse_list(DEFAULT_RATE_LIMITS).unwrap();
+        assert!(!rules.is_empty());
+    }
+
+    #[test]
+    fn test_parse_rule_fails_for_invalid_rules() {
+        let rules = [
+            "/login ip",
+            "/login ip 10",
+            "/login ip 0/60",
+            "/login ip 10/0",
+            "/login host 10/60",
+            "login ip 10/60",
+            "/login ip 10/60 extra",
+        ];
+
+        for rule in rules {
+            assert!(rule.parse::<RateLimitRule>().is_err(), "Parsed: {}", rule);
+        }
+    }
+}
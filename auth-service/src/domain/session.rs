@@ -0,0 +1,103 @@
+use std::net::IpAddr;
+
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+use crate::domain::Email;
+
+// A login on one device. It lives as long as the family of refresh tokens issued with it, and
+// every auth token carries its id.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Session {
+    pub id: Uuid,
+    pub email: Email,
+    // Short description of the browser and OS, derived from the user agent
+    pub device: String,
+    pub ip_address: Option<IpAddr>,
+    pub user_agent: Option<String>,
+    pub created_at: DateTime<Utc>,
+    // Last time the session logged in or refreshed its auth token
+    pub last_seen_at: DateTime<Utc>,
+}
+
+impl Session {
+    pub fn new(email: Email, ip_address: Option<IpAddr>, user_agent: Option<String>) -> Self {
+        let now = Utc::now();
+
+        Self {
+            id: Uuid::new_v4(),
+            email,
+            device: describe_device(user_agent.as_deref()),
+            ip_address,
+            user_agent,
+            created_at: now,
+            last_seen_at: now,
+        }
+    }
+}
+
+// Good enough to tell sessions apart in a list, not meant to identify exact versions
+fn describe_device(user_agent: Option<&str>) -> String {
+    let Some(user_agent) = user_agent else {
+        return "Unknown device".to_owned();
+    };
+
+    // Order matters, most user agents name several browsers and systems for compatibility
+    let browser = [
+        ("Edg/", "Edge"),
+        ("OPR/", "Opera"),
+        ("Firefox/", "Firefox"),
+        ("Chrome/", "Chrome"),
+        ("Safari/", "Safari"),
+    ]
+    .iter()
+    .find(|(marker, _)| user_agent.contains(marker))
+    .map(|(_, name)| *name);
+
+    let os = [
+        ("Android", "Android"),
+        ("iPhone", "iOS"),
+        ("iPad", "iPadOS"),
+        ("Windows", "Windows"),
+        ("Mac OS X", "macOS"),
+        ("Linux", "Linux"),
+    ]
+    .iter()
+    .find(|(marker, _)| user_agent.contains(marker))
+    .map(|(_, name)| *name);
+
+    match (browser, os) {
+        (Some(browser), Some(os)) => format!("{} on {}", browser, os),
+        (Some(name), None) | (None, Some(name)) => name.to_owned(),
+        (None, None) => "Unknown device".to_owned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_describe_device() {
+        let test_cases = [
+            (
+                Some("Mozilla/5.0 (X11; Linux x86_64; rv:128.0) Gecko/20100101 Firefox/128.0"),
+                "Firefox on Linux",
+            ),
+            (
+                Some("Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/126.0.0.0 Safari/537.36 Edg/126.0.0.0"),
+                "Edge on Windows",
+            ),
+            (
+                Some("Mozilla/5.0 (iPhone; CPU iPhone OS 17_5 like Mac OS X) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/17.5 Mobile/15E148 Safari/604.1"),
+                "Safari on iOS",
+            ),
+            (Some("curl/8.5.0"), "Unknown device"),
+            (None, "Unknown device"),
+        ];
+
+        for (user_agent, expected) in test_cases {
+            assert_eq!(describe_device(user_agent), expected);
+        }
+    }
+}
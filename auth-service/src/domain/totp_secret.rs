@@ -0,0 +1,31 @@
+use rand::RngCore;
+
+// RFC 4226 recommends a shared secret of 160 bits
+const TOTP_SECRET_LENGTH: usize = 20;
+
+#[derive(Clone, PartialEq)]
+pub struct TotpSecret(Vec<u8>);
+
+impl TotpSecret {
+    pub fn parse(bytes: Vec<u8>) -> Result<Self, String> {
+        if bytes.len() == TOTP_SECRET_LENGTH {
+            Ok(TotpSecret(bytes))
+        } else {
+            Err("Invalid TOTP secret length".to_string())
+        }
+    }
+}
+
+impl Default for TotpSecret {
+    fn default() -> Self {
+        let mut bytes = vec![0u8; TOTP_SECRET_LENGTH];
+        rand::thread_rng().fill_bytes(&mut bytes);
+        TotpSecret(bytes)
+    }
+}
+
+impl AsRef<[u8]> for TotpSecret {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
@@ -1,18 +1,56 @@
-use crate::domain::{Email, Password};
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
 
+use crate::domain::Email;
+
+// An account without its credentials, the password hash stays inside the user store
 #[derive(Clone)]
 pub struct User {
+    pub id: Uuid,
     pub email: Email,
-    pub password: Password,
     pub requires_2fa: bool,
+    pub two_fa_method: TwoFAMethod,
+    pub email_verified: bool,
+    pub created_at: DateTime<Utc>,
 }
 
 impl User {
-    pub fn new(email: Email, password: Password, requires_2fa: bool) -> Self {
+    pub fn new(email: Email, requires_2fa: bool) -> Self {
         Self {
+            id: Uuid::new_v4(),
             email,
-            password,
             requires_2fa,
+            two_fa_method: TwoFAMethod::default(),
+            email_verified: false,
+            created_at: Utc::now(),
+        }
+    }
+}
+
+// Second factor a user has picked, TOTP only becomes available after an enrollment is confirmed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TwoFAMethod {
+    #[default]
+    Email,
+    Totp,
+}
+
+impl TwoFAMethod {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        match s {
+            "email" => Ok(TwoFAMethod::Email),
+            "totp" => Ok(TwoFAMethod::Totp),
+            _ => Err(format!("{} is not a valid 2FA method", s)),
+        }
+    }
+}
+
+impl AsRef<str> for TwoFAMethod {
+    fn as_ref(&self) -> &str {
+        match self {
+            TwoFAMethod::Email => "email",
+            TwoFAMethod::Totp => "totp",
         }
     }
 }
@@ -4,23 +4,44 @@ pub mod routes;
 pub mod services;
 pub mod utils;
 
-use crate::{app_state::AppState, domain::error::AuthAPIError, utils::tracing::*};
+use crate::{
+    app_state::AppState,
+    domain::error::{AuthAPIError, FieldError, OAuthError},
+    utils::{
+        rate_limit::rate_limit,
+        request_id::{request_id, RequestId, REQUEST_ID_HEADER},
+        tracing::*,
+    },
+};
 use axum::{
-    http::{Method, StatusCode},
+    extract::connect_info::{ConnectInfo, IntoMakeServiceWithConnectInfo},
+    http::{header, HeaderValue, Method, StatusCode},
+    middleware::{self, AddExtension},
     response::IntoResponse,
-    routing::post,
+    routing::{delete, get, post},
     serve::Serve,
     Json, Router,
 };
-use redis::RedisResult;
-use sqlx::{PgPool, postgres::PgPoolOptions};
-use std::error::Error;
+use redis::{aio::ConnectionManager, RedisResult};
+use sqlx::{postgres::PgPoolOptions, PgPool};
+use std::{error::Error, net::SocketAddr, time::Duration};
 use tower_http::{cors::CorsLayer, services::ServeDir, trace::TraceLayer};
+use uuid::Uuid;
 
 const PG_POOL_MAX_CONNECTIONS: u32 = 5;
 
+// Reconnect attempts wait 200ms, 400ms, ... up to 6.4s before a request gives up
+const REDIS_RECONNECT_BACKOFF_BASE: u64 = 2;
+const REDIS_RECONNECT_BACKOFF_FACTOR_MS: u64 = 100;
+const REDIS_RECONNECT_RETRIES: usize = 6;
+const REDIS_RESPONSE_TIMEOUT: Duration = Duration::from_secs(2);
+const REDIS_CONNECTION_TIMEOUT: Duration = Duration::from_secs(2);
+
 pub struct Application {
-    server: Serve<Router, Router>,
+    server: Serve<
+        IntoMakeServiceWithConnectInfo<Router, SocketAddr>,
+        AddExtension<Router, ConnectInfo<SocketAddr>>,
+    >,
     pub address: String,
 }
 
@@ -29,27 +50,102 @@ impl Application {
         let allowed_origins = ["http://localhost:8000".parse()?];
 
         let cors = CorsLayer::new()
-            .allow_methods([Method::GET, Method::POST])
+            .allow_methods([Method::GET, Method::POST, Method::DELETE])
             .allow_credentials(true)
-            .allow_origin(allowed_origins);
+            .allow_origin(allowed_origins)
+            .expose_headers([REQUEST_ID_HEADER]);
 
         let router = Router::new()
             .nest_service("/", ServeDir::new("assets"))
             .route("/signup", post(routes::signup))
             .route("/login", post(routes::login))
             .route("/verify-2fa", post(routes::verify_2fa))
+            .route("/verify-2fa/recovery", post(routes::verify_2fa_recovery))
+            .route("/recovery-codes", post(routes::regenerate_recovery_codes))
+            .route(
+                "/passkeys/register/start",
+                post(routes::start_passkey_registration),
+            )
+            .route(
+                "/passkeys/register/finish",
+                post(routes::finish_passkey_registration),
+            )
+            .route("/passkeys/login/start", post(routes::start_passkey_login))
+            .route("/passkeys/login/finish", post(routes::finish_passkey_login))
+            .route("/verify-email", post(routes::verify_email))
+            .route(
+                "/verify-email/resend",
+                post(routes::resend_verification_email),
+            )
+            .route(
+                "/password-reset/request",
+                post(routes::request_password_reset),
+            )
+            .route(
+                "/password-reset/confirm",
+                post(routes::confirm_password_reset),
+            )
+            .route("/account", delete(routes::delete_account))
+            .route("/account/password", post(routes::change_password))
+            .route("/account/email", post(routes::request_email_change))
+            .route("/account/email/confirm", post(routes::confirm_email_change))
+            .route("/totp/enroll", post(routes::enroll_totp))
+            .route("/totp/confirm", post(routes::confirm_totp))
+            .route("/totp/disable", post(routes::disable_totp))
             .route("/logout", post(routes::logout))
+            .route("/logout-all", post(routes::logout_all))
+            .route("/refresh", post(routes::refresh))
+            .route("/sessions", get(routes::list_sessions))
+            .route("/sessions/:id", delete(routes::revoke_session))
             .route("/verify-token", post(routes::verify_token))
+            .route("/.well-known/jwks.json", get(routes::jwks))
+            .route("/token", post(routes::token))
+            .route("/introspect", post(routes::introspect))
+            .route("/revoke", post(routes::revoke));
+
+        // Clients validate ID tokens with a published key, which a shared secret does not have
+        let router = match app_state.jwt_keyring.read().await.active_key().jwk() {
+            Some(_) => router
+                .route("/authorize", get(routes::authorize))
+                .route(
+                    "/authorize/consent",
+                    get(routes::get_consent).post(routes::submit_consent),
+                )
+                .route("/userinfo", get(routes::userinfo).post(routes::userinfo))
+                .route(
+                    "/.well-known/openid-configuration",
+                    get(routes::openid_configuration),
+                ),
+            None => {
+                tracing::warn!(
+                    "Tokens are signed with a shared secret, OpenID Connect is disabled"
+                );
+                router
+            }
+        };
+
+        let router = router
+            .layer(middleware::from_fn_with_state(
+                app_state.clone(),
+                rate_limit,
+            ))
             .with_state(app_state)
             .layer(cors)
-            .layer(TraceLayer::new_for_http()
-              .make_span_with(make_span_with_request_id)
-              .on_request(on_request)
-              .on_response(on_response));
+            .layer(
+                TraceLayer::new_for_http()
+                    .make_span_with(make_span_with_request_id)
+                    .on_request(on_request)
+                    .on_response(on_response),
+            )
+            .layer(middleware::from_fn(request_id));
 
         let listener = tokio::net::TcpListener::bind(address).await.unwrap();
         let address = listener.local_addr()?.to_string();
-        let server = axum::serve(listener, router);
+        // Handlers see the client address, failed logins are also counted per address
+        let server = axum::serve(
+            listener,
+            router.into_make_service_with_connect_info::<SocketAddr>(),
+        );
 
         Ok(Self { server, address })
     }
@@ -60,16 +156,42 @@ impl Application {
     }
 }
 
-#[derive(serde::Serialize, serde::Deserialize)]
+pub const PROBLEM_JSON_CONTENT_TYPE: &str = "application/problem+json";
+// Problem types name the kind of error, they do not point to any documentation
+const PROBLEM_TYPE_PREFIX: &str = "urn:auth-service:problem:";
+
+// Problem details (RFC 7807) of a failed request. `code` is the stable identifier clients should
+// match on, the title is only meant for humans.
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
 pub struct ErrorResponse {
-    pub error: String,
+    #[serde(rename = "type")]
+    pub problem_type: String,
+    pub title: String,
+    pub status: u16,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    pub code: String,
+    // Same as the `x-request-id` header, to find the request in the logs
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<Uuid>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<FieldError>,
 }
 
 impl IntoResponse for AuthAPIError {
     fn into_response(self) -> axum::response::Response {
-        let (status, error_message) = match self {
+        let retry_after = match self {
+            AuthAPIError::TooManyAttempts(seconds) | AuthAPIError::RateLimited(seconds) => {
+                Some(seconds)
+            }
+            _ => None,
+        };
+
+        let (status, title) = match &self {
             AuthAPIError::UserAlreadyExists => (StatusCode::CONFLICT, "User already exists"),
             AuthAPIError::InvalidCredentials => (StatusCode::BAD_REQUEST, "Invalid credentials"),
+            AuthAPIError::InvalidInput(_) => (StatusCode::BAD_REQUEST, "Invalid input"),
+            AuthAPIError::MalformedRequest(status, _) => (*status, "Malformed request"),
             AuthAPIError::IncorrectCredentials => {
                 (StatusCode::UNAUTHORIZED, "Incorrect credentials")
             }
@@ -79,20 +201,131 @@ impl IntoResponse for AuthAPIError {
             AuthAPIError::InvalidToken => (StatusCode::UNAUTHORIZED, "Invalid token"),
             AuthAPIError::MissingToken => (StatusCode::BAD_REQUEST, "Missing token"),
             AuthAPIError::Invalid2FACodeRequest => (StatusCode::BAD_REQUEST, "Invalid 2FA code"),
+            AuthAPIError::InvalidPasskey => (StatusCode::BAD_REQUEST, "Invalid passkey"),
+            AuthAPIError::EmailNotVerified => {
+                (StatusCode::FORBIDDEN, "Email address has not been verified")
+            }
+            AuthAPIError::SessionNotFound => (StatusCode::NOT_FOUND, "Session not found"),
+            AuthAPIError::InvalidAuthorizationRequest => (
+                StatusCode::BAD_REQUEST,
+                "Invalid or expired authorization request",
+            ),
+            AuthAPIError::InvalidPasswordResetToken => (
+                StatusCode::BAD_REQUEST,
+                "Invalid or expired password reset token",
+            ),
+            AuthAPIError::TotpNotEnrolled => (
+                StatusCode::BAD_REQUEST,
+                "No authenticator app enrollment in progress",
+            ),
+            AuthAPIError::TooManyAttempts(_) => (
+                StatusCode::TOO_MANY_REQUESTS,
+                "Too many failed attempts, try again later",
+            ),
+            AuthAPIError::RateLimited(_) => (
+                StatusCode::TOO_MANY_REQUESTS,
+                "Too many requests, try again later",
+            ),
+        };
+
+        let detail = match &self {
+            AuthAPIError::MalformedRequest(_, message) => Some(message.clone()),
+            _ => retry_after.map(|seconds| format!("Try again in {} seconds", seconds)),
+        };
+        let code = self.code();
+
+        let errors = match self {
+            AuthAPIError::InvalidInput(errors) => errors,
+            _ => Vec::new(),
         };
 
         let body = Json(ErrorResponse {
-            error: error_message.to_string(),
+            problem_type: format!("{}{}", PROBLEM_TYPE_PREFIX, code),
+            title: title.to_owned(),
+            status: status.as_u16(),
+            detail,
+            code: code.to_owned(),
+            request_id: RequestId::current().map(|request_id| request_id.0),
+            errors,
         });
-        (status, body).into_response()
+        let mut response = (
+            status,
+            [(header::CONTENT_TYPE, PROBLEM_JSON_CONTENT_TYPE)],
+            body,
+        )
+            .into_response();
+
+        if let Some(seconds) = retry_after {
+            response
+                .headers_mut()
+                .insert(header::RETRY_AFTER, HeaderValue::from(seconds));
+        }
+
+        response
+    }
+}
+
+// Error response of the token endpoint (RFC 6749, section 5.2)
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct OAuthErrorResponse {
+    pub error: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error_description: Option<String>,
+}
+
+impl IntoResponse for OAuthError {
+    fn into_response(self) -> axum::response::Response {
+        let status = match self {
+            OAuthError::InvalidClient => StatusCode::UNAUTHORIZED,
+            OAuthError::ServerError => StatusCode::INTERNAL_SERVER_ERROR,
+            _ => StatusCode::BAD_REQUEST,
+        };
+
+        let mut response = (
+            status,
+            [(header::CACHE_CONTROL, "no-store")],
+            Json(OAuthErrorResponse {
+                error: self.code().to_owned(),
+                error_description: self.description().map(str::to_owned),
+            }),
+        )
+            .into_response();
+
+        // Tells the client which authentication scheme to retry with (RFC 6749, section 5.2)
+        if let OAuthError::InvalidClient = self {
+            response
+                .headers_mut()
+                .insert(header::WWW_AUTHENTICATE, HeaderValue::from_static("Basic"));
+        }
+
+        response
     }
 }
 
 pub async fn get_postgres_pool(url: &str) -> Result<PgPool, sqlx::Error> {
-    PgPoolOptions::new().max_connections(PG_POOL_MAX_CONNECTIONS).connect(url).await
+    PgPoolOptions::new()
+        .max_connections(PG_POOL_MAX_CONNECTIONS)
+        .connect(url)
+        .await
 }
 
 pub fn get_redis_client(redis_hostname: String) -> RedisResult<redis::Client> {
-  let redis_url = format!("redis://{}/", redis_hostname);
-  redis::Client::open(redis_url)
+    let redis_url = format!("redis://{}/", redis_hostname);
+    redis::Client::open(redis_url)
+}
+
+// Requests are multiplexed over a single connection, so concurrent handlers do not wait on each
+// other. The manager is cheap to clone and replaces the connection whenever it breaks.
+pub async fn get_redis_connection_manager(
+    redis_hostname: String,
+) -> RedisResult<ConnectionManager> {
+    ConnectionManager::new_with_backoff_and_timeouts(
+        get_redis_client(redis_hostname)?,
+        REDIS_RECONNECT_BACKOFF_BASE,
+        REDIS_RECONNECT_BACKOFF_FACTOR_MS,
+        REDIS_RECONNECT_RETRIES,
+        REDIS_RESPONSE_TIMEOUT,
+        REDIS_CONNECTION_TIMEOUT,
+    )
+    .await
 }
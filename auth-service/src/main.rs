@@ -1,15 +1,33 @@
 use std::sync::Arc;
 
 use auth_service::{
-    app_state::AppState,
-    get_postgres_pool, get_redis_client,
-    services::{PostgresUserStore, RedisBannedTokenStore, RedisTwoFaCodeStore},
+    app_state::{AppState, AuthPolicy},
+    domain::{BreachedPasswords, ClientSecret, MachineClient, MachineClientStore, PasswordPolicy},
+    get_postgres_pool, get_redis_connection_manager,
+    services::{
+        PostgresJwtKeyStore, PostgresMachineClientStore, PostgresOAuthClientStore,
+        PostgresPasskeyCredentialStore, PostgresRecoveryCodeStore, PostgresRefreshTokenStore,
+        PostgresSessionStore, PostgresUserStore, RedisAuthorizationCodeStore,
+        RedisBannedTokenStore, RedisEmailTokenStore, RedisLoginAttemptStore,
+        RedisPasswordResetTokenStore, RedisRateLimitStore, RedisTwoFaCodeStore,
+        RedisWebAuthnChallengeStore,
+    },
     utils::{
-        constants::{self, DATABASE_URL},
+        auth::JWT_KEY_OVERLAP_SECONDS,
+        constants::{
+            self, APP_SERVICE_CLIENT_ID, APP_SERVICE_CLIENT_SECRET, BREACHED_PASSWORDS_PATH,
+            DATABASE_URL, JWT_KEY_ENCRYPTION_KEY, JWT_KEY_ROTATION_INTERVAL, JWT_PRIVATE_KEY_PATH,
+            JWT_RETIRED_KEY_PATHS, JWT_SECRET, PASSWORD_HASH_PARAMS, PASSWORD_POLICY, RATE_LIMITS,
+            REQUIRE_EMAIL_VERIFICATION, TOTP_ENCRYPTION_KEY,
+        },
+        jwt_key::JwtKey,
+        jwt_keyring::{spawn_key_rotation, JwtKeyRotation, JwtKeyring},
+        secret_cipher::SecretCipher,
         tracing::init_tracing,
     },
     Application,
 };
+use redis::aio::ConnectionManager;
 use sqlx::PgPool;
 use tokio::sync::RwLock;
 
@@ -18,26 +36,95 @@ async fn main() {
     init_tracing();
     let pg_pool = configure_postgres().await;
 
-    let user_store = Arc::new(RwLock::new(PostgresUserStore::new(pg_pool)));
+    let secret_cipher = SecretCipher::from_base64(&TOTP_ENCRYPTION_KEY)
+        .expect("TOTP_ENCRYPTION_KEY must be 32 bytes encoded in base64");
+
+    let user_store = Arc::new(PostgresUserStore::new(
+        pg_pool.clone(),
+        secret_cipher,
+        *PASSWORD_HASH_PARAMS,
+    ));
+    let refresh_token_store = Arc::new(PostgresRefreshTokenStore::new(pg_pool.clone()));
+    let session_store = Arc::new(PostgresSessionStore::new(pg_pool.clone()));
+    let recovery_code_store = Arc::new(PostgresRecoveryCodeStore::new(pg_pool.clone()));
+    let passkey_credential_store = Arc::new(PostgresPasskeyCredentialStore::new(pg_pool.clone()));
+    let oauth_client_store = Arc::new(PostgresOAuthClientStore::new(pg_pool.clone()));
+    let machine_client_store = Arc::new(PostgresMachineClientStore::new(pg_pool.clone()));
+    register_app_service(&*machine_client_store).await;
+
+    let shared_redis_conn = configure_redis().await;
+
+    let banned_token_store = Arc::new(RedisBannedTokenStore::new(shared_redis_conn.clone()));
+
+    let two_fa_code_store = Arc::new(RedisTwoFaCodeStore::new(shared_redis_conn.clone()));
+
+    let webauthn_challenge_store =
+        Arc::new(RedisWebAuthnChallengeStore::new(shared_redis_conn.clone()));
+
+    let password_reset_token_store =
+        Arc::new(RedisPasswordResetTokenStore::new(shared_redis_conn.clone()));
 
-    let shared_redis_conn = configure_redis();
-    let shared_redis_conn = Arc::new(RwLock::new(shared_redis_conn));
+    let email_token_store = Arc::new(RedisEmailTokenStore::new(shared_redis_conn.clone()));
 
-    let banned_token_store = Arc::new(RwLock::new(RedisBannedTokenStore::new(
-        shared_redis_conn.clone(),
-    )));
+    // Shared through Redis, so the limits hold across every instance of the service
+    let login_attempt_store = Arc::new(RedisLoginAttemptStore::new(shared_redis_conn.clone()));
 
-    let two_fa_code_store = Arc::new(RwLock::new(RedisTwoFaCodeStore::new(
-        shared_redis_conn.clone(),
-    )));
+    let rate_limit_store = Arc::new(RedisRateLimitStore::new(shared_redis_conn.clone()));
+
+    let authorization_code_store =
+        Arc::new(RedisAuthorizationCodeStore::new(shared_redis_conn.clone()));
 
     let email_client = Arc::new(auth_service::services::MockEmailClient {});
 
+    let jwt_keyring = match *JWT_KEY_ROTATION_INTERVAL {
+        Some(interval) => {
+            if JWT_PRIVATE_KEY_PATH.is_some() {
+                tracing::warn!("JWT keys are rotated, ignoring JWT_PRIVATE_KEY_PATH");
+            }
+
+            // A key of its own, so the signing keys are not exposed along with the TOTP secrets
+            let jwt_key_cipher = JWT_KEY_ENCRYPTION_KEY
+                .as_deref()
+                .map(SecretCipher::from_base64)
+                .expect("JWT_KEY_ENCRYPTION_KEY must be set when JWT keys are rotated")
+                .expect("JWT_KEY_ENCRYPTION_KEY must be 32 bytes encoded in base64");
+            let jwt_key_store = Arc::new(PostgresJwtKeyStore::new(pg_pool, jwt_key_cipher));
+            let rotation = JwtKeyRotation::new(jwt_key_store, interval, jwt_key_overlap());
+            let jwt_keyring = rotation
+                .sync()
+                .await
+                .expect("Failed to load JWT signing keys");
+            let jwt_keyring = Arc::new(RwLock::new(jwt_keyring));
+            spawn_key_rotation(jwt_keyring.clone(), rotation);
+            jwt_keyring
+        }
+        None => Arc::new(RwLock::new(configure_jwt_keyring())),
+    };
+
     let app_state = AppState::new(
         user_store,
         banned_token_store,
         two_fa_code_store,
+        refresh_token_store,
+        session_store,
+        recovery_code_store,
+        passkey_credential_store,
+        webauthn_challenge_store,
+        password_reset_token_store,
+        email_token_store,
+        login_attempt_store,
+        rate_limit_store,
+        oauth_client_store,
+        authorization_code_store,
+        machine_client_store,
         email_client,
+        jwt_keyring,
+        AuthPolicy {
+            require_verified_email: *REQUIRE_EMAIL_VERIFICATION,
+            rate_limits: RATE_LIMITS.clone(),
+            password: configure_password_policy(),
+            ..Default::default()
+        },
     );
 
     let app = Application::build(app_state, constants::prod::APP_ADDRESS)
@@ -60,9 +147,71 @@ async fn configure_postgres() -> PgPool {
     pg_pool
 }
 
-fn configure_redis() -> redis::Connection {
-    get_redis_client(constants::REDIS_HOST_NAME.to_owned())
-        .expect("Failed to get Redis client")
-        .get_connection()
-        .expect("Failed to get Redis connection")
+async fn configure_redis() -> ConnectionManager {
+    get_redis_connection_manager(constants::REDIS_HOST_NAME.to_owned())
+        .await
+        .expect("Failed to connect to Redis")
+}
+
+// Registers the app service as a machine client without scopes, so it can introspect tokens.
+// Saving it again on every start replaces the secret once it is rotated.
+async fn register_app_service(machine_client_store: &dyn MachineClientStore) {
+    let Some(secret) = APP_SERVICE_CLIENT_SECRET.as_deref() else {
+        tracing::warn!(
+            "APP_SERVICE_CLIENT_SECRET is not set, the app service client is not registered"
+        );
+        return;
+    };
+
+    let secret = ClientSecret::parse(secret.to_owned())
+        .unwrap_or_else(|e| panic!("APP_SERVICE_CLIENT_SECRET is invalid: {}", e));
+    let client = MachineClient {
+        client_id: APP_SERVICE_CLIENT_ID.to_owned(),
+        name: "App service".to_owned(),
+        allowed_scopes: Vec::new(),
+    };
+
+    machine_client_store
+        .save_client(client, &secret)
+        .await
+        .expect("Failed to register the app service client");
+}
+
+fn configure_password_policy() -> PasswordPolicy {
+    let breached_passwords = BREACHED_PASSWORDS_PATH.as_deref().map(|path| {
+        let file = std::fs::File::open(path).expect("Failed to open breached passwords file");
+        let breached_passwords = BreachedPasswords::from_reader(file)
+            .unwrap_or_else(|e| panic!("Failed to load breached passwords: {}", e));
+        Arc::new(breached_passwords)
+    });
+
+    PasswordPolicy {
+        breached_passwords,
+        ..PASSWORD_POLICY.clone()
+    }
+}
+
+fn configure_jwt_keyring() -> JwtKeyring {
+    let active = match JWT_PRIVATE_KEY_PATH.as_deref() {
+        Some(path) => load_jwt_key(path),
+        None => {
+            tracing::warn!("JWT_PRIVATE_KEY_PATH is not set, signing tokens with JWT_SECRET");
+            JwtKey::from_secret(JWT_SECRET.as_bytes())
+        }
+    };
+
+    JWT_RETIRED_KEY_PATHS.iter().fold(
+        JwtKeyring::new(active, jwt_key_overlap()),
+        |keyring, path| keyring.with_retired(load_jwt_key(path)),
+    )
+}
+
+// Retired keys must outlive every token they signed
+fn jwt_key_overlap() -> chrono::Duration {
+    chrono::Duration::seconds(JWT_KEY_OVERLAP_SECONDS as i64)
+}
+
+fn load_jwt_key(path: &str) -> JwtKey {
+    let pem = std::fs::read_to_string(path).expect("Failed to read JWT private key");
+    JwtKey::from_pem(&pem).expect("Failed to parse JWT private key")
 }
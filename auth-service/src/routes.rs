@@ -1,11 +1,40 @@
+pub mod account;
+pub mod authenticated_user;
+pub mod client_info;
+pub mod introspection;
+mod json_request;
+pub mod jwks;
 pub mod login;
+mod login_throttle;
 pub mod logout;
+pub mod oidc;
+pub mod passkeys;
+pub mod password_reset;
+pub mod recovery_codes;
+pub mod refresh;
+pub mod sessions;
 pub mod signup;
+pub mod totp;
 pub mod verify_2fa;
+pub mod verify_email;
 pub mod verify_token;
 
+pub use account::*;
+pub use authenticated_user::*;
+pub use client_info::*;
+pub use introspection::*;
+pub use json_request::*;
+pub use jwks::*;
 pub use login::*;
 pub use logout::*;
+pub use oidc::*;
+pub use passkeys::*;
+pub use password_reset::*;
+pub use recovery_codes::*;
+pub use refresh::*;
+pub use sessions::*;
 pub use signup::*;
+pub use totp::*;
 pub use verify_2fa::*;
+pub use verify_email::*;
 pub use verify_token::*;
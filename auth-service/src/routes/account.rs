@@ -0,0 +1,308 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use axum_extra::extract::CookieJar;
+
+use crate::{
+    app_state::AppState,
+    domain::{
+        error::{AuthAPIError, FieldErrors},
+        Email, EmailConfirmation, EmailToken, EmailTokenStoreError, LoginAttemptKey, Password,
+        UserStoreError,
+    },
+    utils::constants::{
+        EMAIL_CHANGE_TOKEN_TTL_SECONDS, EMAIL_CHANGE_URL, JWT_COOKIE_NAME,
+        REFRESH_TOKEN_COOKIE_NAME,
+    },
+};
+
+use super::{
+    sessions::{current_token_version, end_all_sessions, remove_sessions, start_session},
+    AuthenticatedUser, ClientInfo, JsonRequest,
+};
+
+pub async fn change_password(
+    State(state): State<AppState>,
+    user: AuthenticatedUser,
+    client: ClientInfo,
+    jar: CookieJar,
+    JsonRequest(request): JsonRequest<ChangePasswordRequest>,
+) -> Result<(CookieJar, impl IntoResponse), AuthAPIError> {
+    let mut errors = FieldErrors::default();
+    let new_password = errors.check_password(
+        "newPassword",
+        state
+            .policy
+            .password
+            .parse(request.new_password, &user.email),
+    );
+    let Some(new_password) = new_password else {
+        return Err(errors.into());
+    };
+
+    check_current_password(&user.email, request.current_password, &state).await?;
+
+    state
+        .user_store
+        .update_password(&user.email, new_password)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    let jar = revoke_other_sessions(&user, &client, &state, jar).await?;
+
+    Ok((jar, StatusCode::OK))
+}
+
+// Sends a confirmation link to the new address, the email only changes once it is followed
+pub async fn request_email_change(
+    State(state): State<AppState>,
+    user: AuthenticatedUser,
+    JsonRequest(request): JsonRequest<ChangeEmailRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let mut errors = FieldErrors::default();
+    let new_email = errors.check("newEmail", "invalid_email", Email::parse(request.new_email));
+    let Some(new_email) = new_email else {
+        return Err(errors.into());
+    };
+
+    check_current_password(&user.email, request.current_password, &state).await?;
+
+    match state.user_store.get_user(&new_email).await {
+        Ok(_) => return Err(AuthAPIError::UserAlreadyExists),
+        Err(UserStoreError::UserNotFound) => {}
+        Err(_) => return Err(AuthAPIError::UnexpectedError),
+    }
+
+    // Changing the password or logging out everywhere bumps the version, which voids the link
+    let token_version = current_token_version(&user.email, &state).await?;
+
+    let token = EmailToken::default();
+    let confirmation = EmailConfirmation::Change {
+        email: user.email.clone(),
+        new_email: new_email.clone(),
+        token_version,
+    };
+
+    state
+        .email_token_store
+        .add_token(token.clone(), confirmation)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    let link = format!(
+        "{}?email_change_token={}",
+        *EMAIL_CHANGE_URL,
+        token.as_ref()
+    );
+
+    state
+        .email_client
+        .send_email(
+            &new_email,
+            "Confirm your new email address",
+            &format!(
+                "Use this link within {} minutes to confirm {} as the new email address of your account: {}",
+                EMAIL_CHANGE_TOKEN_TTL_SECONDS / 60,
+                new_email.as_ref(),
+                link
+            ),
+        )
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    let response = Json(ChangeEmailResponse {
+        message: "A confirmation link has been sent to the new email address".to_owned(),
+    });
+
+    Ok((StatusCode::ACCEPTED, response))
+}
+
+// Called by the page the link sent to the new address points to. Every session of the account
+// ends here, since the link may be opened on another device than the one that asked for the change.
+pub async fn confirm_email_change(
+    State(state): State<AppState>,
+    JsonRequest(request): JsonRequest<ConfirmEmailChangeRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let token = EmailToken::parse(request.token).map_err(|_| AuthAPIError::InvalidToken)?;
+
+    let confirmation = state.email_token_store.take_token(&token).await;
+    let (email, new_email, token_version) = match confirmation {
+        Ok(EmailConfirmation::Change {
+            email,
+            new_email,
+            token_version,
+        }) => (email, new_email, token_version),
+        Ok(_) | Err(EmailTokenStoreError::TokenNotFound) => return Err(AuthAPIError::InvalidToken),
+        Err(_) => return Err(AuthAPIError::UnexpectedError),
+    };
+
+    if current_token_version(&email, &state).await? != token_version {
+        return Err(AuthAPIError::InvalidToken);
+    }
+
+    state
+        .user_store
+        .update_email(&email, &new_email)
+        .await
+        .map_err(|e| match e {
+            UserStoreError::UserAlreadyExists => AuthAPIError::UserAlreadyExists,
+            // The account was deleted or moved to another address since
+            UserStoreError::UserNotFound => AuthAPIError::InvalidToken,
+            _ => AuthAPIError::UnexpectedError,
+        })?;
+
+    // Only once the account moved, so a failed change leaves everyone logged in. The version is
+    // bumped under the new address, while sessions and refresh tokens stay keyed by the old one.
+    end_all_sessions(&new_email, &state).await?;
+    remove_sessions(&email, &state).await?;
+
+    let response = Json(ChangeEmailResponse {
+        message: "Email address changed".to_owned(),
+    });
+
+    Ok((StatusCode::OK, response))
+}
+
+// Deletes the account along with everything stored for it
+pub async fn delete_account(
+    State(state): State<AppState>,
+    user: AuthenticatedUser,
+    jar: CookieJar,
+    JsonRequest(request): JsonRequest<DeleteAccountRequest>,
+) -> Result<(CookieJar, impl IntoResponse), AuthAPIError> {
+    check_current_password(&user.email, request.password, &state).await?;
+
+    end_all_sessions(&user.email, &state).await?;
+
+    state
+        .user_store
+        .delete_user(&user.email)
+        .await
+        .map_err(|e| match e {
+            // Deleted concurrently by another request of the same user
+            UserStoreError::UserNotFound => AuthAPIError::InvalidToken,
+            _ => AuthAPIError::UnexpectedError,
+        })?;
+
+    state
+        .two_fa_code_store
+        .remove_code(&user.email)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    state
+        .recovery_code_store
+        .replace_codes(&user.email, Vec::new())
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    state
+        .passkey_credential_store
+        .remove_credentials(&user.email)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    // A link or a lockout must not carry over to a new account with the same address
+    state
+        .password_reset_token_store
+        .remove_tokens(&user.email)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    state
+        .email_token_store
+        .remove_tokens(&user.email)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    for key in [
+        LoginAttemptKey::Email(user.email.clone()),
+        LoginAttemptKey::TwoFactor(user.email.clone()),
+    ] {
+        state
+            .login_attempt_store
+            .reset(&key)
+            .await
+            .map_err(|_| AuthAPIError::UnexpectedError)?;
+    }
+
+    state
+        .banned_token_store
+        .ban_token(&user.claims.jti, user.claims.valid_until())
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    let jar = jar
+        .remove(JWT_COOKIE_NAME)
+        .remove(REFRESH_TOKEN_COOKIE_NAME);
+
+    Ok((jar, StatusCode::OK))
+}
+
+pub(super) async fn check_current_password(
+    email: &Email,
+    current_password: String,
+    state: &AppState,
+) -> Result<(), AuthAPIError> {
+    let current_password =
+        Password::parse(current_password).map_err(|_| AuthAPIError::IncorrectCredentials)?;
+
+    state
+        .user_store
+        .validate_user(email, &current_password)
+        .await
+        .map_err(|e| match e {
+            UserStoreError::InvalidCredentials => AuthAPIError::IncorrectCredentials,
+            UserStoreError::UserNotFound => AuthAPIError::InvalidToken,
+            _ => AuthAPIError::UnexpectedError,
+        })?;
+
+    Ok(())
+}
+
+// Ends every session of the user and starts a new one for the caller, so only they stay logged in
+async fn revoke_other_sessions(
+    user: &AuthenticatedUser,
+    client: &ClientInfo,
+    state: &AppState,
+    jar: CookieJar,
+) -> Result<CookieJar, AuthAPIError> {
+    state
+        .banned_token_store
+        .ban_token(&user.claims.jti, user.claims.valid_until())
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    end_all_sessions(&user.email, state).await?;
+
+    start_session(&user.email, client, state, jar).await
+}
+
+#[derive(serde::Deserialize)]
+pub struct ChangePasswordRequest {
+    #[serde(rename = "currentPassword")]
+    pub current_password: String,
+    #[serde(rename = "newPassword")]
+    pub new_password: String,
+}
+
+#[derive(serde::Deserialize)]
+pub struct ChangeEmailRequest {
+    #[serde(rename = "currentPassword")]
+    pub current_password: String,
+    #[serde(rename = "newEmail")]
+    pub new_email: String,
+}
+
+#[derive(serde::Deserialize)]
+pub struct DeleteAccountRequest {
+    pub password: String,
+}
+
+#[derive(serde::Deserialize)]
+pub struct ConfirmEmailChangeRequest {
+    pub token: String,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct ChangeEmailResponse {
+    pub message: String,
+}
@@ -0,0 +1,64 @@
+use axum::{extract::FromRequestParts, http::request::Parts};
+use axum_extra::extract::CookieJar;
+use uuid::Uuid;
+
+use crate::{
+    app_state::AppState,
+    domain::{error::AuthAPIError, Email},
+    utils::{
+        auth::{validate_token, Claims},
+        constants::JWT_COOKIE_NAME,
+    },
+};
+
+// Extractor for routes that act on the account of the logged in user
+pub struct AuthenticatedUser {
+    pub email: Email,
+    // Session the token was issued for
+    pub session_id: Uuid,
+    pub token: String,
+    pub claims: Claims,
+}
+
+#[async_trait::async_trait]
+impl FromRequestParts<AppState> for AuthenticatedUser {
+    type Rejection = AuthAPIError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let jar = CookieJar::from_headers(&parts.headers);
+        let token = jar
+            .get(JWT_COOKIE_NAME)
+            .ok_or(AuthAPIError::MissingToken)?
+            .value()
+            .to_owned();
+
+        let claims = validate_token(
+            &token,
+            &[],
+            &*state.banned_token_store,
+            &*state.session_store,
+            &*state.user_store,
+            &*state.jwt_keyring.read().await,
+        )
+        .await
+        .map_err(|_| AuthAPIError::InvalidToken)?;
+
+        // Tokens issued to OpenID Connect clients only give access to what the user allowed them
+        if claims.client_id.is_some() {
+            return Err(AuthAPIError::InvalidToken);
+        }
+
+        let email = Email::parse(claims.sub.clone()).map_err(|_| AuthAPIError::InvalidToken)?;
+        let session_id = claims.sid.ok_or(AuthAPIError::InvalidToken)?;
+
+        Ok(Self {
+            email,
+            session_id,
+            token,
+            claims,
+        })
+    }
+}
@@ -0,0 +1,123 @@
+use axum::{
+    extract::{rejection::FormRejection, State},
+    http::{header, HeaderMap, StatusCode},
+    response::IntoResponse,
+    Form, Json,
+};
+use uuid::Uuid;
+
+use crate::{
+    app_state::AppState,
+    domain::error::OAuthError,
+    utils::auth::{decode_auth_token, validate_token},
+};
+
+use super::authenticate_machine_client;
+
+// Token introspection (RFC 7662). A backend service that was handed a token asks whether it is
+// still active and who it was issued for. Any token that would not be accepted by the routes of
+// this service, for whatever reason, is reported as inactive without saying why.
+pub async fn introspect(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    form: Result<Form<ClientTokenRequest>, FormRejection>,
+) -> Result<impl IntoResponse, OAuthError> {
+    let Form(request) =
+        form.map_err(|rejection| OAuthError::InvalidRequest(rejection.body_text()))?;
+
+    authenticate_machine_client(&state, &headers, request.client_id, request.client_secret).await?;
+    let token = required_token(request.token)?;
+
+    let claims = validate_token(
+        &token,
+        &[],
+        &*state.banned_token_store,
+        &*state.session_store,
+        &*state.user_store,
+        &*state.jwt_keyring.read().await,
+    )
+    .await;
+
+    let response = match claims {
+        Ok(claims) => IntrospectionResponse {
+            active: true,
+            scope: claims.scope,
+            client_id: claims.client_id,
+            sub: Some(claims.sub),
+            exp: Some(claims.exp),
+            iat: claims.iat,
+            jti: Some(claims.jti),
+        },
+        Err(_) => IntrospectionResponse::default(),
+    };
+
+    Ok(([(header::CACHE_CONTROL, "no-store")], Json(response)))
+}
+
+// Token revocation (RFC 7009). Only access tokens can be revoked, they are banned until they
+// expire. A client may only revoke the tokens issued to itself, login tokens of users belong to no
+// client and are ended by logging out.
+pub async fn revoke(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    form: Result<Form<ClientTokenRequest>, FormRejection>,
+) -> Result<impl IntoResponse, OAuthError> {
+    let Form(request) =
+        form.map_err(|rejection| OAuthError::InvalidRequest(rejection.body_text()))?;
+
+    let client =
+        authenticate_machine_client(&state, &headers, request.client_id, request.client_secret)
+            .await?;
+    let token = required_token(request.token)?;
+
+    // Invalid and expired tokens need no revoking, which is not an error either
+    let Ok(claims) = decode_auth_token(&token, &*state.jwt_keyring.read().await) else {
+        return Ok(StatusCode::OK);
+    };
+
+    if claims.client_id.as_deref() != Some(client.client_id.as_str()) {
+        return Err(OAuthError::UnauthorizedClient);
+    }
+
+    state
+        .banned_token_store
+        .ban_token(&claims.jti, claims.valid_until())
+        .await
+        .map_err(|_| OAuthError::ServerError)?;
+
+    Ok(StatusCode::OK)
+}
+
+fn required_token(token: Option<String>) -> Result<String, OAuthError> {
+    token
+        .filter(|token| !token.is_empty())
+        .ok_or_else(|| OAuthError::InvalidRequest("token is required".to_owned()))
+}
+
+// Form of both endpoints, a `token_type_hint` is ignored since access tokens are the only kind
+// they know about
+#[derive(serde::Deserialize)]
+pub struct ClientTokenRequest {
+    pub token: Option<String>,
+    pub client_id: Option<String>,
+    pub client_secret: Option<String>,
+}
+
+// Only `active` is set for an inactive token (RFC 7662, section 2.2)
+#[derive(Debug, Default, serde::Serialize, serde::Deserialize)]
+pub struct IntrospectionResponse {
+    pub active: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+    // Only set for tokens issued to an OAuth or machine client
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sub: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exp: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub iat: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jti: Option<Uuid>,
+}
@@ -0,0 +1,29 @@
+use axum::{
+    extract::{rejection::JsonRejection, FromRequest, Request},
+    Json,
+};
+
+use crate::domain::error::AuthAPIError;
+
+// Extractor for JSON request bodies. Unlike `Json` it answers bodies it can not read with a
+// problem document, like every other error of the API.
+pub struct JsonRequest<T>(pub T);
+
+#[async_trait::async_trait]
+impl<T, S> FromRequest<S> for JsonRequest<T>
+where
+    Json<T>: FromRequest<S, Rejection = JsonRejection>,
+    S: Send + Sync,
+{
+    type Rejection = AuthAPIError;
+
+    async fn from_request(request: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let Json(value) = Json::<T>::from_request(request, state)
+            .await
+            .map_err(|rejection| {
+                AuthAPIError::MalformedRequest(rejection.status(), rejection.body_text())
+            })?;
+
+        Ok(JsonRequest(value))
+    }
+}
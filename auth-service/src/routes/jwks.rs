@@ -0,0 +1,10 @@
+use axum::{extract::State, response::IntoResponse, Json};
+
+use crate::app_state::AppState;
+
+// Publish the public signing keys so other services can validate tokens without calling us
+pub async fn jwks(State(state): State<AppState>) -> impl IntoResponse {
+    let jwks = state.jwt_keyring.read().await.jwks();
+
+    Json(jwks)
+}
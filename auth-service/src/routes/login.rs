@@ -1,71 +1,108 @@
+use crate::{
+    app_state::AppState,
+    domain::{
+        error::{AuthAPIError, FieldErrors},
+        Email, LoginAttemptId, LoginAttemptKey, Password, TwoFACode, TwoFAMethod, UserStoreError,
+    },
+};
 use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
 use axum_extra::extract::CookieJar;
 
-use crate::{
-    app_state::AppState,
-    domain::{error::AuthAPIError, Email, LoginAttemptId, Password, TwoFACode, UserStoreError},
+use super::{
+    login_throttle::{check_login_allowed, record_login_failure, reset_login_failures},
+    sessions::start_session,
+    ClientInfo, JsonRequest,
 };
 
 pub async fn login(
     State(state): State<AppState>,
+    client: ClientInfo,
     jar: CookieJar,
-    Json(request): Json<LoginRequest>,
+    JsonRequest(request): JsonRequest<LoginRequest>,
 ) -> Result<(CookieJar, impl IntoResponse), AuthAPIError> {
-    let email = Email::parse(request.email).map_err(|_| AuthAPIError::InvalidCredentials)?;
-
-    let password =
-        Password::parse(request.password).map_err(|_| AuthAPIError::InvalidCredentials)?;
-
-    let user_store = state.user_store.read().await;
-
-    user_store
-        .validate_user(&email, &password)
-        .await
-        .map_err(|e| match e {
-            UserStoreError::UserNotFound => AuthAPIError::IncorrectCredentials,
-            UserStoreError::InvalidCredentials => AuthAPIError::IncorrectCredentials,
-            _ => AuthAPIError::UnexpectedError,
-        })?;
+    let mut errors = FieldErrors::default();
+    let email = errors.check("email", "invalid_email", Email::parse(request.email));
+    let password = errors.check(
+        "password",
+        "invalid_password",
+        Password::parse(request.password),
+    );
+    let (Some(email), Some(password)) = (email, password) else {
+        return Err(errors.into());
+    };
+
+    let throttle_keys = [
+        LoginAttemptKey::Email(email.clone()),
+        LoginAttemptKey::Ip(client.ip),
+    ];
+
+    // Checked before the password, so a locked out account can not be probed any further
+    check_login_allowed(&throttle_keys, &state).await?;
+
+    match state.user_store.validate_user(&email, &password).await {
+        Ok(_) => {}
+        // Unknown emails count as failures too, otherwise the limits would reveal which exist
+        Err(UserStoreError::UserNotFound | UserStoreError::InvalidCredentials) => {
+            record_login_failure(&throttle_keys, &state).await?;
+            return Err(AuthAPIError::IncorrectCredentials);
+        }
+        Err(_) => return Err(AuthAPIError::UnexpectedError),
+    }
 
-    let user = user_store
+    let user = state
+        .user_store
         .get_user(&email)
         .await
         .map_err(|_| AuthAPIError::UnexpectedError)?;
 
+    if state.policy.require_verified_email && !user.email_verified {
+        return Err(AuthAPIError::EmailNotVerified);
+    }
+
     match user.requires_2fa {
-        true => handle_2fa(&user.email, &state, jar).await,
-        false => handle_no_2fa(&user.email, jar).await,
+        true => handle_2fa(&user.email, user.two_fa_method, &state, jar).await,
+        false => {
+            // Only the account is forgiven, failures of the address keep counting. With 2FA that
+            // waits for the second factor, so wrong codes are not forgotten on the next login.
+            reset_login_failures(&throttle_keys[0], &state).await?;
+            handle_no_2fa(&user.email, &client, &state, jar).await
+        }
     }
 }
 
 async fn handle_2fa(
     email: &Email,
+    two_fa_method: TwoFAMethod,
     state: &AppState,
     jar: CookieJar,
 ) -> Result<(CookieJar, (StatusCode, Json<LoginResponse>)), AuthAPIError> {
     let login_attempt_id = LoginAttemptId::default();
     let two_fa_code = TwoFACode::default();
 
-    let mut store = state.two_fa_code_store.write().await;
-
-    store
+    // The login attempt is tracked for both methods, with TOTP the stored code is never sent
+    // and the one from the authenticator app is checked instead
+    state
+        .two_fa_code_store
         .add_code(email.clone(), login_attempt_id.clone(), two_fa_code.clone())
         .await
         .map_err(|_| AuthAPIError::UnexpectedError)?;
 
-    state
-        .email_client
-        .send_email(
-            email,
-            "2FA token",
-            &format!("Your 2FA code is: {}", two_fa_code.as_ref().to_string()),
-        )
-        .await
-        .map_err(|_| AuthAPIError::UnexpectedError)?;
+    if two_fa_method == TwoFAMethod::Email {
+        state
+            .email_client
+            .send_email(
+                email,
+                "2FA token",
+                &format!("Your 2FA code is: {}", two_fa_code.as_ref()),
+            )
+            .await
+            .map_err(|_| AuthAPIError::UnexpectedError)?;
+    }
 
     let response = Json(LoginResponse::TwoFactorAuth(TwoFactorAuthResponse {
         message: "2FA required".to_string(),
         login_attempt_id: login_attempt_id.as_ref().to_string(),
+        two_fa_method,
     }));
 
     Ok((jar, (StatusCode::PARTIAL_CONTENT, response)))
@@ -73,12 +110,11 @@ async fn handle_2fa(
 
 async fn handle_no_2fa(
     email: &Email,
+    client: &ClientInfo,
+    state: &AppState,
     jar: CookieJar,
 ) -> Result<(CookieJar, (StatusCode, Json<LoginResponse>)), AuthAPIError> {
-    let auth_cookie = crate::utils::auth::generate_auth_cookie(email)
-        .map_err(|_| AuthAPIError::UnexpectedError)?;
-
-    let jar = jar.add(auth_cookie);
+    let jar = start_session(email, client, state, jar).await?;
     Ok((jar, (StatusCode::OK, LoginResponse::RegularAuth.into())))
 }
 
@@ -100,6 +136,8 @@ pub struct TwoFactorAuthResponse {
     pub message: String,
     #[serde(rename = "loginAttemptId")]
     pub login_attempt_id: String,
+    #[serde(rename = "twoFAMethod")]
+    pub two_fa_method: TwoFAMethod,
 }
 
 impl IntoResponse for LoginResponse {
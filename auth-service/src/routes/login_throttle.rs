@@ -0,0 +1,103 @@
+use std::time::Duration;
+
+use crate::{
+    app_state::AppState,
+    domain::{error::AuthAPIError, Email, LoginAttemptKey},
+};
+
+// Refuses the attempt while any of the keys is locked out
+pub(super) async fn check_login_allowed(
+    keys: &[LoginAttemptKey],
+    state: &AppState,
+) -> Result<(), AuthAPIError> {
+    let store = &state.login_attempt_store;
+
+    let mut locked_for = Duration::ZERO;
+    for key in keys {
+        let remaining = store
+            .locked_for(key)
+            .await
+            .map_err(|_| AuthAPIError::UnexpectedError)?;
+        locked_for = locked_for.max(remaining.unwrap_or_default());
+    }
+
+    if locked_for.is_zero() {
+        return Ok(());
+    }
+
+    // Rounded up, so a client that waits exactly as told is let in
+    let seconds = locked_for.as_secs() + u64::from(locked_for.subsec_nanos() > 0);
+    Err(AuthAPIError::TooManyAttempts(seconds))
+}
+
+// Counts a failed attempt against every key and locks out the ones over their limit
+pub(super) async fn record_login_failure(
+    keys: &[LoginAttemptKey],
+    state: &AppState,
+) -> Result<(), AuthAPIError> {
+    let policy = &state.policy.login_throttle;
+    let store = &state.login_attempt_store;
+
+    for key in keys {
+        let max_failures = match key {
+            LoginAttemptKey::Ip(_) => policy.max_failures_per_ip,
+            LoginAttemptKey::Email(_) | LoginAttemptKey::TwoFactor(_) => {
+                policy.max_failures_per_email
+            }
+        };
+
+        let failures = store
+            .add_failure(key, policy.failure_window)
+            .await
+            .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+        if failures < max_failures {
+            continue;
+        }
+
+        let doublings = (failures - max_failures).min(16);
+        let lockout = policy
+            .base_lockout
+            .saturating_mul(1 << doublings)
+            .min(policy.max_lockout);
+
+        store
+            .lock(key, lockout)
+            .await
+            .map_err(|_| AuthAPIError::UnexpectedError)?;
+    }
+
+    Ok(())
+}
+
+pub(super) async fn reset_login_failures(
+    key: &LoginAttemptKey,
+    state: &AppState,
+) -> Result<(), AuthAPIError> {
+    state
+        .login_attempt_store
+        .reset(key)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)
+}
+
+// Counts a wrong code against the 2FA login attempts of the account. Returns whether they have
+// used up their tries, in which case the caller has to drop the pending one. The tries are only
+// given back by a successful verification, a new login does not start over with a fresh budget.
+pub(super) async fn record_two_fa_failure(
+    email: &Email,
+    state: &AppState,
+) -> Result<bool, AuthAPIError> {
+    let policy = &state.policy.login_throttle;
+
+    let failures = state
+        .login_attempt_store
+        .add_failure(
+            &LoginAttemptKey::TwoFactor(email.clone()),
+            policy.failure_window,
+        )
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    Ok(failures >= policy.max_two_fa_attempts)
+}
@@ -3,10 +3,18 @@ use axum_extra::extract::CookieJar;
 
 use crate::{
     app_state::AppState,
-    domain::{BannedTokenStoreError, error::AuthAPIError},
-    utils::{auth::validate_token, constants::JWT_COOKIE_NAME},
+    domain::{
+        error::AuthAPIError, BannedTokenStoreError, Email, RefreshToken, RefreshTokenStoreError,
+        SessionStoreError,
+    },
+    utils::{
+        auth::validate_token,
+        constants::{JWT_COOKIE_NAME, REFRESH_TOKEN_COOKIE_NAME},
+    },
 };
 
+use super::{sessions::end_all_sessions, AuthenticatedUser};
+
 pub async fn logout(
     State(app_state): State<AppState>,
     jar: CookieJar,
@@ -17,19 +25,80 @@ pub async fn logout(
 
     let token = cookie.value().to_owned();
 
-    let mut banned_token_store = app_state.banned_token_store.write().await;
+    let session_store = &*app_state.session_store;
+    let jwt_keyring = app_state.jwt_keyring.read().await;
 
-    validate_token(&token, &*banned_token_store)
-        .await
-        .map_err(|_| AuthAPIError::InvalidToken)?;
+    let claims = validate_token(
+        &token,
+        &[],
+        &*app_state.banned_token_store,
+        session_store,
+        &*app_state.user_store,
+        &jwt_keyring,
+    )
+    .await
+    .map_err(|_| AuthAPIError::InvalidToken)?;
+
+    let email = Email::parse(claims.sub.clone()).map_err(|_| AuthAPIError::InvalidToken)?;
+    let session_id = claims.sid.ok_or(AuthAPIError::InvalidToken)?;
+
+    match session_store.remove_session(&email, &session_id).await {
+        // Ended by a concurrent request of the same session
+        Ok(()) | Err(SessionStoreError::SessionNotFound) => {}
+        Err(SessionStoreError::UnexpectedError) => return Err(AuthAPIError::UnexpectedError),
+    }
 
     let jar = jar.remove(JWT_COOKIE_NAME);
 
-    banned_token_store.ban_token(&token).await.map_err(|e| match e {
-        BannedTokenStoreError::UnexpectedError => {
-            AuthAPIError::UnexpectedError
+    app_state
+        .banned_token_store
+        .ban_token(&claims.jti, claims.valid_until())
+        .await
+        .map_err(|e| match e {
+            BannedTokenStoreError::UnexpectedError => AuthAPIError::UnexpectedError,
+        })?;
+
+    // The refresh token must not outlive the session it belongs to
+    if let Some(cookie) = jar.get(REFRESH_TOKEN_COOKIE_NAME) {
+        if let Ok(refresh_token) = RefreshToken::parse(cookie.value().to_owned()) {
+            revoke_refresh_token(&app_state, &refresh_token).await?;
         }
-    })?;
+    }
+
+    let jar = jar.remove(REFRESH_TOKEN_COOKIE_NAME);
+
+    Ok((jar, StatusCode::OK))
+}
+
+// Logs the user out on every device, including the one making the request
+pub async fn logout_all(
+    State(app_state): State<AppState>,
+    user: AuthenticatedUser,
+    jar: CookieJar,
+) -> Result<(CookieJar, impl IntoResponse), AuthAPIError> {
+    end_all_sessions(&user.email, &app_state).await?;
+
+    let jar = jar
+        .remove(JWT_COOKIE_NAME)
+        .remove(REFRESH_TOKEN_COOKIE_NAME);
 
     Ok((jar, StatusCode::OK))
 }
+
+async fn revoke_refresh_token(
+    app_state: &AppState,
+    refresh_token: &RefreshToken,
+) -> Result<(), AuthAPIError> {
+    let refresh_token_store = &app_state.refresh_token_store;
+
+    let record = match refresh_token_store.get_token(refresh_token).await {
+        Ok(record) => record,
+        Err(RefreshTokenStoreError::TokenNotFound) => return Ok(()),
+        Err(_) => return Err(AuthAPIError::UnexpectedError),
+    };
+
+    refresh_token_store
+        .revoke_family(&record.family_id)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)
+}
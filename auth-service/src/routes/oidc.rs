@@ -0,0 +1,653 @@
+use axum::{
+    extract::{rejection::FormRejection, Query, State},
+    http::{header, HeaderMap},
+    response::{IntoResponse, Redirect},
+    Form, Json,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use jsonwebtoken::Algorithm;
+use percent_encoding::percent_decode_str;
+use url::Url;
+
+use crate::{
+    app_state::AppState,
+    domain::{
+        error::{AuthAPIError, FieldErrors, OAuthError},
+        AuthorizationCode, AuthorizationCodeStoreError, AuthorizationGrant, AuthorizationRequest,
+        ClientSecret, CodeChallenge, MachineClient, MachineClientStoreError, OAuthClient,
+        OAuthClientStoreError, Scope, SessionStoreError, UserStoreError, EMAIL_SCOPE, OPENID_SCOPE,
+        SUPPORTED_SCOPES,
+    },
+    utils::{
+        auth::{
+            generate_authorization_request_token, generate_client_access_token, generate_id_token,
+            generate_machine_access_token, validate_authorization_request_token, validate_token,
+            TOKEN_TTL_SECONDS,
+        },
+        constants::OIDC_ISSUER,
+    },
+};
+
+use super::{AuthenticatedUser, JsonRequest};
+
+// Page of the served UI that logs the user in and asks them to allow the request
+const CONSENT_PAGE: &str = "/";
+
+// Start of the authorization code flow (OpenID Connect Core, section 3.1). The browser is sent on
+// to the UI, which logs the user in through the usual routes and asks them to allow the request.
+pub async fn authorize(
+    State(state): State<AppState>,
+    Query(params): Query<AuthorizeParams>,
+) -> Result<Redirect, AuthAPIError> {
+    let client = find_client(&state, params.client_id.as_deref()).await?;
+
+    // Until the redirect URI is known to belong to the client, errors can only be shown to the user
+    let mut errors = FieldErrors::default();
+    let client = errors.check("client_id", "unknown_client", client);
+    let redirect_uri = client.as_ref().and_then(|client| {
+        errors.check(
+            "redirect_uri",
+            "unregistered_redirect_uri",
+            registered_redirect_uri(client, params.redirect_uri.as_deref()),
+        )
+    });
+    let (Some(client), Some(redirect_uri)) = (client, redirect_uri) else {
+        return Err(errors.into());
+    };
+
+    let request = match parse_authorization_request(client, redirect_uri.clone(), &params) {
+        Ok(request) => request,
+        Err(error) => {
+            let url = error_redirect_url(&redirect_uri, &error, params.state.as_deref())?;
+            return Ok(Redirect::to(&url));
+        }
+    };
+
+    let token = generate_authorization_request_token(&request, &*state.jwt_keyring.read().await)
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    Ok(Redirect::to(&format!(
+        "{}?authorizationRequest={}",
+        CONSENT_PAGE, token
+    )))
+}
+
+// What the consent page shows the logged in user before they allow the request
+pub async fn get_consent(
+    State(state): State<AppState>,
+    user: AuthenticatedUser,
+    Query(params): Query<ConsentParams>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let (request, client) = open_authorization_request(&state, &params.request).await?;
+
+    Ok(Json(ConsentDetailsResponse {
+        client_name: client.name,
+        scopes: request.scope.iter().map(str::to_owned).collect(),
+        email: user.email.as_ref().to_owned(),
+    }))
+}
+
+// Answer of the user to the request. Either way the UI sends the browser back to the client with
+// the returned URI, which carries a code the client can redeem if the request was allowed.
+pub async fn submit_consent(
+    State(state): State<AppState>,
+    user: AuthenticatedUser,
+    JsonRequest(consent): JsonRequest<ConsentRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let (request, client) = open_authorization_request(&state, &consent.request).await?;
+
+    if !consent.approved {
+        let redirect_uri = error_redirect_url(
+            &request.redirect_uri,
+            &OAuthError::AccessDenied,
+            request.state.as_deref(),
+        )?;
+        return Ok(Json(ConsentResponse { redirect_uri }));
+    }
+
+    let code = AuthorizationCode::default();
+    let grant = AuthorizationGrant {
+        client_id: client.client_id,
+        redirect_uri: request.redirect_uri.clone(),
+        email: user.email,
+        session_id: user.session_id,
+        scope: request.scope,
+        nonce: request.nonce,
+        code_challenge: request.code_challenge,
+    };
+
+    state
+        .authorization_code_store
+        .add_code(code.clone(), grant)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    let redirect_uri = redirect_url(
+        &request.redirect_uri,
+        &[("code", code.as_ref())],
+        request.state.as_deref(),
+    )?;
+
+    Ok(Json(ConsentResponse { redirect_uri }))
+}
+
+// Token endpoint (RFC 6749, section 3.2), parameters are sent as a form like the standard requires
+pub async fn token(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    form: Result<Form<TokenRequest>, FormRejection>,
+) -> Result<impl IntoResponse, OAuthError> {
+    let Form(request) =
+        form.map_err(|rejection| OAuthError::InvalidRequest(rejection.body_text()))?;
+
+    let response = match request.grant_type.as_str() {
+        "authorization_code" => redeem_authorization_code(&state, request).await?,
+        "client_credentials" => issue_machine_token(&state, &headers, request).await?,
+        _ => return Err(OAuthError::UnsupportedGrantType),
+    };
+
+    // Tokens must not end up in any cache on the way
+    Ok(([(header::CACHE_CONTROL, "no-store")], Json(response)))
+}
+
+async fn redeem_authorization_code(
+    state: &AppState,
+    request: TokenRequest,
+) -> Result<TokenResponse, OAuthError> {
+    let client_id = request
+        .client_id
+        .ok_or_else(|| OAuthError::InvalidRequest("client_id is required".to_owned()))?;
+    let client = match state.oauth_client_store.get_client(&client_id).await {
+        Ok(client) => client,
+        Err(OAuthClientStoreError::ClientNotFound) => return Err(OAuthError::InvalidClient),
+        Err(OAuthClientStoreError::UnexpectedError) => return Err(OAuthError::ServerError),
+    };
+
+    let code = request
+        .code
+        .ok_or_else(|| OAuthError::InvalidRequest("code is required".to_owned()))?;
+    let code = AuthorizationCode::parse(code).map_err(|_| OAuthError::InvalidGrant)?;
+
+    // The code is used up even if the rest of the request is wrong, so it can not be tried again
+    let grant = match state.authorization_code_store.take_code(&code).await {
+        Ok(grant) => grant,
+        Err(AuthorizationCodeStoreError::CodeNotFound) => return Err(OAuthError::InvalidGrant),
+        Err(AuthorizationCodeStoreError::UnexpectedError) => return Err(OAuthError::ServerError),
+    };
+
+    if grant.client_id != client.client_id
+        || request.redirect_uri.as_deref() != Some(grant.redirect_uri.as_str())
+        || !grant
+            .code_challenge
+            .verify(request.code_verifier.as_deref().unwrap_or_default())
+    {
+        return Err(OAuthError::InvalidGrant);
+    }
+
+    let user = match state.user_store.get_user(&grant.email).await {
+        Ok(user) => user,
+        Err(UserStoreError::UserNotFound) => return Err(OAuthError::InvalidGrant),
+        Err(_) => return Err(OAuthError::ServerError),
+    };
+
+    // The tokens are bound to the session the request was allowed from, which may have ended since
+    state
+        .session_store
+        .get_session(&grant.session_id)
+        .await
+        .map_err(|_| OAuthError::InvalidGrant)?;
+
+    let token_version = state
+        .user_store
+        .get_token_version(&grant.email)
+        .await
+        .map_err(|_| OAuthError::ServerError)?;
+
+    let jwt_keyring = state.jwt_keyring.read().await;
+
+    let access_token = generate_client_access_token(
+        &user,
+        grant.session_id,
+        token_version,
+        &client.client_id,
+        &grant.scope,
+        &jwt_keyring,
+    )
+    .map_err(|_| OAuthError::ServerError)?;
+
+    let id_token = generate_id_token(
+        &OIDC_ISSUER,
+        &user,
+        &client.client_id,
+        &grant.scope,
+        grant.nonce.as_deref(),
+        &jwt_keyring,
+    )
+    .map_err(|_| OAuthError::ServerError)?;
+
+    Ok(TokenResponse {
+        access_token,
+        token_type: "Bearer".to_owned(),
+        expires_in: TOKEN_TTL_SECONDS,
+        id_token: Some(id_token),
+        scope: grant.scope.to_string(),
+    })
+}
+
+// Client credentials grant (RFC 6749, section 4.4), a backend service gets a token for itself
+async fn issue_machine_token(
+    state: &AppState,
+    headers: &HeaderMap,
+    request: TokenRequest,
+) -> Result<TokenResponse, OAuthError> {
+    let client =
+        authenticate_machine_client(state, headers, request.client_id, request.client_secret)
+            .await?;
+
+    // Without a requested scope the client gets every scope it is allowed
+    let scope = match request.scope.as_deref() {
+        Some(scope) => Scope::parse(scope).map_err(OAuthError::InvalidScope)?,
+        None => Scope::parse(&client.allowed_scopes.join(" ")).map_err(|_| {
+            OAuthError::InvalidScope("The client is not allowed any scope".to_owned())
+        })?,
+    };
+    if let Some(scope) = scope.iter().find(|scope| !client.allows_scope(scope)) {
+        return Err(OAuthError::InvalidScope(format!(
+            "The client is not allowed the {} scope",
+            scope
+        )));
+    }
+
+    let access_token =
+        generate_machine_access_token(&client.client_id, &scope, &*state.jwt_keyring.read().await)
+            .map_err(|_| OAuthError::ServerError)?;
+
+    Ok(TokenResponse {
+        access_token,
+        token_type: "Bearer".to_owned(),
+        expires_in: TOKEN_TTL_SECONDS,
+        id_token: None,
+        scope: scope.to_string(),
+    })
+}
+
+// Authenticates a machine client with its secret (RFC 6749, section 2.3.1), sent either in a Basic
+// authorization header or as form parameters, but not both
+pub(crate) async fn authenticate_machine_client(
+    state: &AppState,
+    headers: &HeaderMap,
+    client_id: Option<String>,
+    client_secret: Option<String>,
+) -> Result<MachineClient, OAuthError> {
+    let (client_id, client_secret) = match (basic_credentials(headers)?, client_id, client_secret) {
+        (Some(_), _, Some(_)) => {
+            return Err(OAuthError::InvalidRequest(
+                "Client credentials must only be sent once".to_owned(),
+            ))
+        }
+        (Some(credentials), _, None) => credentials,
+        (None, Some(client_id), Some(client_secret)) => (client_id, client_secret),
+        (None, _, _) => return Err(OAuthError::InvalidClient),
+    };
+
+    let client_secret =
+        ClientSecret::parse(client_secret).map_err(|_| OAuthError::InvalidClient)?;
+
+    match state
+        .machine_client_store
+        .authenticate_client(&client_id, &client_secret)
+        .await
+    {
+        Ok(client) => Ok(client),
+        Err(MachineClientStoreError::InvalidCredentials) => Err(OAuthError::InvalidClient),
+        Err(MachineClientStoreError::UnexpectedError) => Err(OAuthError::ServerError),
+    }
+}
+
+// Claims about the user an access token was issued for (OpenID Connect Core, section 5.3)
+pub async fn userinfo(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let token = bearer_token(&headers).ok_or(AuthAPIError::MissingToken)?;
+
+    // Only tokens of OpenID Connect clients carry a scope, the ones of our own login do not
+    let claims = validate_token(
+        token,
+        &[OPENID_SCOPE],
+        &*state.banned_token_store,
+        &*state.session_store,
+        &*state.user_store,
+        &*state.jwt_keyring.read().await,
+    )
+    .await
+    .map_err(|_| AuthAPIError::InvalidToken)?;
+
+    // Tokens of machine clients have no user, whatever scopes they were allowed
+    let session_id = claims.sid.ok_or(AuthAPIError::InvalidToken)?;
+
+    let with_email = claims.has_scope(EMAIL_SCOPE);
+
+    // The token names the user by account id, the session they allowed the client from knows
+    // their email
+    let session = state
+        .session_store
+        .get_session(&session_id)
+        .await
+        .map_err(|e| match e {
+            SessionStoreError::SessionNotFound => AuthAPIError::InvalidToken,
+            SessionStoreError::UnexpectedError => AuthAPIError::UnexpectedError,
+        })?;
+    let user = state
+        .user_store
+        .get_user(&session.email)
+        .await
+        .map_err(|e| match e {
+            UserStoreError::UserNotFound => AuthAPIError::InvalidToken,
+            _ => AuthAPIError::UnexpectedError,
+        })?;
+
+    Ok(Json(UserInfoResponse {
+        sub: claims.sub,
+        email: with_email.then(|| user.email.as_ref().to_owned()),
+        email_verified: with_email.then_some(user.email_verified),
+    }))
+}
+
+// Discovery document (OpenID Connect Discovery, section 3), every endpoint lives under the issuer
+pub async fn openid_configuration(State(state): State<AppState>) -> impl IntoResponse {
+    let issuer = OIDC_ISSUER.as_str();
+    let jwt_keyring = state.jwt_keyring.read().await;
+    let active_key = jwt_keyring.active_key();
+    // ID tokens are never signed with a shared secret
+    let signing_algorithms: Vec<_> = active_key
+        .jwk()
+        .map(|_| active_key.algorithm())
+        .into_iter()
+        .collect();
+
+    Json(OpenIdConfiguration {
+        issuer: issuer.to_owned(),
+        authorization_endpoint: format!("{}/authorize", issuer),
+        token_endpoint: format!("{}/token", issuer),
+        userinfo_endpoint: format!("{}/userinfo", issuer),
+        jwks_uri: format!("{}/.well-known/jwks.json", issuer),
+        introspection_endpoint: format!("{}/introspect", issuer),
+        revocation_endpoint: format!("{}/revoke", issuer),
+        response_types_supported: vec!["code"],
+        grant_types_supported: vec!["authorization_code", "client_credentials"],
+        subject_types_supported: vec!["public"],
+        id_token_signing_alg_values_supported: signing_algorithms,
+        scopes_supported: SUPPORTED_SCOPES.to_vec(),
+        // OpenID Connect clients prove they started the flow with PKCE instead of a secret, only
+        // machine clients have one
+        token_endpoint_auth_methods_supported: vec![
+            "none",
+            "client_secret_basic",
+            "client_secret_post",
+        ],
+        code_challenge_methods_supported: vec![CodeChallenge::METHOD],
+        claims_supported: vec!["sub", "email", "email_verified"],
+    })
+}
+
+async fn find_client(
+    state: &AppState,
+    client_id: Option<&str>,
+) -> Result<Result<OAuthClient, String>, AuthAPIError> {
+    let Some(client_id) = client_id else {
+        return Ok(Err("client_id is required".to_owned()));
+    };
+
+    match state.oauth_client_store.get_client(client_id).await {
+        Ok(client) => Ok(Ok(client)),
+        Err(OAuthClientStoreError::ClientNotFound) => {
+            Ok(Err(format!("{} is not a registered client", client_id)))
+        }
+        Err(OAuthClientStoreError::UnexpectedError) => Err(AuthAPIError::UnexpectedError),
+    }
+}
+
+fn registered_redirect_uri(
+    client: &OAuthClient,
+    redirect_uri: Option<&str>,
+) -> Result<String, String> {
+    match redirect_uri {
+        Some(uri) if client.allows_redirect_uri(uri) => Ok(uri.to_owned()),
+        Some(uri) => Err(format!("{} is not registered for the client", uri)),
+        None => Err("redirect_uri is required".to_owned()),
+    }
+}
+
+fn parse_authorization_request(
+    client: OAuthClient,
+    redirect_uri: String,
+    params: &AuthorizeParams,
+) -> Result<AuthorizationRequest, OAuthError> {
+    if params.response_type.as_deref() != Some("code") {
+        return Err(OAuthError::UnsupportedResponseType);
+    }
+
+    let scope = Scope::parse(params.scope.as_deref().unwrap_or_default())
+        .map_err(OAuthError::InvalidScope)?;
+    if !scope.contains(OPENID_SCOPE) {
+        return Err(OAuthError::InvalidScope(format!(
+            "The {} scope is required",
+            OPENID_SCOPE
+        )));
+    }
+    // Scopes we do not know are left out of the grant rather than failing the request
+    let scope = scope
+        .restrict(|scope| SUPPORTED_SCOPES.contains(&scope))
+        .ok_or(OAuthError::ServerError)?;
+
+    if params.code_challenge_method.as_deref() != Some(CodeChallenge::METHOD) {
+        return Err(OAuthError::InvalidRequest(format!(
+            "code_challenge_method must be {}",
+            CodeChallenge::METHOD
+        )));
+    }
+    let code_challenge = params
+        .code_challenge
+        .clone()
+        .ok_or_else(|| "code_challenge is required".to_owned())
+        .and_then(CodeChallenge::parse)
+        .map_err(OAuthError::InvalidRequest)?;
+
+    Ok(AuthorizationRequest {
+        client_id: client.client_id,
+        redirect_uri,
+        scope,
+        state: params.state.clone(),
+        nonce: params.nonce.clone(),
+        code_challenge,
+    })
+}
+
+// Checks the token passed through the login and consent pages, and that its client still exists
+// with the same redirect URI
+async fn open_authorization_request(
+    state: &AppState,
+    token: &str,
+) -> Result<(AuthorizationRequest, OAuthClient), AuthAPIError> {
+    let request = validate_authorization_request_token(token, &*state.jwt_keyring.read().await)
+        .map_err(|_| AuthAPIError::InvalidAuthorizationRequest)?;
+
+    match state
+        .oauth_client_store
+        .get_client(&request.client_id)
+        .await
+    {
+        Ok(client) if client.allows_redirect_uri(&request.redirect_uri) => Ok((request, client)),
+        Ok(_) | Err(OAuthClientStoreError::ClientNotFound) => {
+            Err(AuthAPIError::InvalidAuthorizationRequest)
+        }
+        Err(OAuthClientStoreError::UnexpectedError) => Err(AuthAPIError::UnexpectedError),
+    }
+}
+
+// The redirect URI with the given parameters added to its query, plus the state of the client
+fn redirect_url(
+    redirect_uri: &str,
+    params: &[(&str, &str)],
+    state: Option<&str>,
+) -> Result<String, AuthAPIError> {
+    let mut url = Url::parse(redirect_uri).map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    url.query_pairs_mut()
+        .extend_pairs(params)
+        .extend_pairs(state.map(|state| ("state", state)));
+
+    Ok(url.into())
+}
+
+fn error_redirect_url(
+    redirect_uri: &str,
+    error: &OAuthError,
+    state: Option<&str>,
+) -> Result<String, AuthAPIError> {
+    let mut params = vec![("error", error.code())];
+    if let Some(description) = error.description() {
+        params.push(("error_description", description));
+    }
+
+    redirect_url(redirect_uri, &params, state)
+}
+
+// Token of an `Authorization: Bearer <token>` header (RFC 6750, section 2.1)
+fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    let (scheme, token) = authorization_header(headers)?;
+
+    let token = token.trim();
+    (scheme.eq_ignore_ascii_case("bearer") && !token.is_empty()).then_some(token)
+}
+
+// Client id and secret of an `Authorization: Basic` header, both are form encoded before they are
+// joined with a colon (RFC 6749, section 2.3.1). None without such a header.
+fn basic_credentials(headers: &HeaderMap) -> Result<Option<(String, String)>, OAuthError> {
+    let Some((scheme, credentials)) = authorization_header(headers) else {
+        return Ok(None);
+    };
+    if !scheme.eq_ignore_ascii_case("basic") {
+        return Ok(None);
+    }
+
+    let credentials = STANDARD
+        .decode(credentials.trim())
+        .ok()
+        .and_then(|credentials| String::from_utf8(credentials).ok())
+        .ok_or(OAuthError::InvalidClient)?;
+    let (client_id, client_secret) = credentials
+        .split_once(':')
+        .ok_or(OAuthError::InvalidClient)?;
+
+    let form_decode = |value: &str| {
+        percent_decode_str(&value.replace('+', " "))
+            .decode_utf8()
+            .map(|value| value.into_owned())
+            .map_err(|_| OAuthError::InvalidClient)
+    };
+
+    Ok(Some((form_decode(client_id)?, form_decode(client_secret)?)))
+}
+
+fn authorization_header(headers: &HeaderMap) -> Option<(&str, &str)> {
+    headers
+        .get(header::AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .split_once(' ')
+}
+
+#[derive(serde::Deserialize)]
+pub struct AuthorizeParams {
+    pub response_type: Option<String>,
+    pub client_id: Option<String>,
+    pub redirect_uri: Option<String>,
+    pub scope: Option<String>,
+    pub state: Option<String>,
+    pub nonce: Option<String>,
+    pub code_challenge: Option<String>,
+    pub code_challenge_method: Option<String>,
+}
+
+#[derive(serde::Deserialize)]
+pub struct ConsentParams {
+    // The token the authorization endpoint sent the browser to the consent page with
+    #[serde(default)]
+    pub request: String,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct ConsentDetailsResponse {
+    #[serde(rename = "clientName")]
+    pub client_name: String,
+    pub scopes: Vec<String>,
+    // Account the request would be allowed for
+    pub email: String,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct ConsentRequest {
+    pub request: String,
+    pub approved: bool,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct ConsentResponse {
+    #[serde(rename = "redirectUri")]
+    pub redirect_uri: String,
+}
+
+#[derive(serde::Deserialize)]
+pub struct TokenRequest {
+    pub grant_type: String,
+    pub code: Option<String>,
+    pub redirect_uri: Option<String>,
+    pub client_id: Option<String>,
+    pub code_verifier: Option<String>,
+    // Only for the client credentials grant
+    pub client_secret: Option<String>,
+    pub scope: Option<String>,
+}
+
+// Successful response of the token endpoint (RFC 6749, section 5.1)
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct TokenResponse {
+    pub access_token: String,
+    pub token_type: String,
+    // Seconds the access token is valid for
+    pub expires_in: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id_token: Option<String>,
+    pub scope: String,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct UserInfoResponse {
+    pub sub: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub email_verified: Option<bool>,
+}
+
+#[derive(serde::Serialize)]
+pub struct OpenIdConfiguration {
+    pub issuer: String,
+    pub authorization_endpoint: String,
+    pub token_endpoint: String,
+    pub userinfo_endpoint: String,
+    pub jwks_uri: String,
+    // Both are only open to machine clients (RFC 8414)
+    pub introspection_endpoint: String,
+    pub revocation_endpoint: String,
+    pub response_types_supported: Vec<&'static str>,
+    pub grant_types_supported: Vec<&'static str>,
+    pub subject_types_supported: Vec<&'static str>,
+    pub id_token_signing_alg_values_supported: Vec<Algorithm>,
+    pub scopes_supported: Vec<&'static str>,
+    pub token_endpoint_auth_methods_supported: Vec<&'static str>,
+    pub code_challenge_methods_supported: Vec<&'static str>,
+    pub claims_supported: Vec<&'static str>,
+}
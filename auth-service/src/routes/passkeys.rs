@@ -0,0 +1,447 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use axum_extra::extract::CookieJar;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use crate::{
+    app_state::AppState,
+    domain::{
+        error::{AuthAPIError, FieldErrors},
+        CredentialId, Email, LoginAttemptKey, PasskeyCredential, PasskeyCredentialStoreError,
+        UserStoreError, WebAuthnCeremony, WebAuthnChallenge,
+    },
+    utils::{
+        constants::{WEBAUTHN_RP_ID, WEBAUTHN_RP_NAME},
+        webauthn::{
+            decode_base64url, encode_base64url, verify_authentication, verify_registration,
+            COSE_ALGORITHM_ES256, WEBAUTHN_CHALLENGE_TTL_SECONDS,
+        },
+    },
+};
+
+use super::{
+    login_throttle::{check_login_allowed, record_login_failure, reset_login_failures},
+    verify_2fa::complete_login,
+    AuthenticatedUser, ClientInfo, JsonRequest,
+};
+
+const PUBLIC_KEY_CREDENTIAL_TYPE: &str = "public-key";
+
+// Options for `navigator.credentials.create()`, letting the logged in user add a passkey
+pub async fn start_passkey_registration(
+    State(state): State<AppState>,
+    user: AuthenticatedUser,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let existing_credentials = state
+        .passkey_credential_store
+        .get_credentials(&user.email)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    let challenge =
+        WebAuthnChallenge::new(WebAuthnCeremony::Registration, Some(user.email.clone()));
+    let ceremony_id = Uuid::new_v4();
+
+    let public_key = PublicKeyCredentialCreationOptions {
+        challenge: encode_base64url(&challenge.challenge),
+        rp: RelyingPartyEntity {
+            id: WEBAUTHN_RP_ID.to_owned(),
+            name: WEBAUTHN_RP_NAME.to_owned(),
+        },
+        user: UserEntity {
+            // Authenticators store this handle, a digest keeps the address itself off the device
+            id: encode_base64url(&Sha256::digest(user.email.as_ref().as_bytes())),
+            name: user.email.as_ref().to_owned(),
+            display_name: user.email.as_ref().to_owned(),
+        },
+        pub_key_cred_params: vec![CredentialParameters {
+            credential_type: PUBLIC_KEY_CREDENTIAL_TYPE.to_owned(),
+            alg: COSE_ALGORITHM_ES256,
+        }],
+        timeout: WEBAUTHN_CHALLENGE_TTL_SECONDS * 1000,
+        attestation: "none".to_owned(),
+        // Stops the same authenticator from being registered twice
+        exclude_credentials: existing_credentials
+            .iter()
+            .map(|credential| CredentialDescriptor::new(&credential.credential_id))
+            .collect(),
+        authenticator_selection: AuthenticatorSelection {
+            resident_key: "preferred".to_owned(),
+            user_verification: "required".to_owned(),
+        },
+    };
+
+    state
+        .webauthn_challenge_store
+        .add_challenge(ceremony_id, challenge)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    let response = Json(StartPasskeyRegistrationResponse {
+        ceremony_id: ceremony_id.to_string(),
+        public_key,
+    });
+
+    Ok((StatusCode::OK, response))
+}
+
+pub async fn finish_passkey_registration(
+    State(state): State<AppState>,
+    user: AuthenticatedUser,
+    JsonRequest(request): JsonRequest<FinishPasskeyRegistrationRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let ceremony_id =
+        Uuid::parse_str(&request.ceremony_id).map_err(|_| AuthAPIError::InvalidPasskey)?;
+
+    let challenge = state
+        .webauthn_challenge_store
+        .take_challenge(&ceremony_id)
+        .await
+        .map_err(|_| AuthAPIError::InvalidPasskey)?;
+
+    if challenge.ceremony != WebAuthnCeremony::Registration
+        || challenge.email.as_ref() != Some(&user.email)
+    {
+        return Err(AuthAPIError::InvalidPasskey);
+    }
+
+    let client_data_json = decode_base64url(&request.credential.response.client_data_json)
+        .map_err(|_| AuthAPIError::InvalidPasskey)?;
+    let attestation_object = decode_base64url(&request.credential.response.attestation_object)
+        .map_err(|_| AuthAPIError::InvalidPasskey)?;
+
+    let registered =
+        verify_registration(&client_data_json, &attestation_object, &challenge.challenge)
+            .map_err(|_| AuthAPIError::InvalidPasskey)?;
+
+    let credential_id =
+        CredentialId::parse(&request.credential.id).map_err(|_| AuthAPIError::InvalidPasskey)?;
+
+    if credential_id.as_ref() != registered.credential_id.as_slice() {
+        return Err(AuthAPIError::InvalidPasskey);
+    }
+
+    let credential = PasskeyCredential {
+        credential_id,
+        email: user.email,
+        public_key: registered.public_key,
+        sign_count: registered.sign_count,
+    };
+
+    state
+        .passkey_credential_store
+        .add_credential(credential)
+        .await
+        .map_err(|e| match e {
+            PasskeyCredentialStoreError::CredentialAlreadyExists => AuthAPIError::InvalidPasskey,
+            _ => AuthAPIError::UnexpectedError,
+        })?;
+
+    Ok(StatusCode::CREATED)
+}
+
+// Options for `navigator.credentials.get()`. Without an email the browser offers every
+// passkey it holds for this site.
+pub async fn start_passkey_login(
+    State(state): State<AppState>,
+    JsonRequest(request): JsonRequest<StartPasskeyLoginRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let mut errors = FieldErrors::default();
+    let email = errors.check(
+        "email",
+        "invalid_email",
+        request.email.map(Email::parse).transpose(),
+    );
+    let Some(email) = email else {
+        return Err(errors.into());
+    };
+
+    // Unknown users get an empty list like users without passkeys, so accounts cannot be probed
+    let allow_credentials = match &email {
+        Some(email) => state
+            .passkey_credential_store
+            .get_credentials(email)
+            .await
+            .map_err(|_| AuthAPIError::UnexpectedError)?
+            .iter()
+            .map(|credential| CredentialDescriptor::new(&credential.credential_id))
+            .collect(),
+        None => Vec::new(),
+    };
+
+    let challenge = WebAuthnChallenge::new(WebAuthnCeremony::Authentication, email);
+    let ceremony_id = Uuid::new_v4();
+
+    let public_key = PublicKeyCredentialRequestOptions {
+        challenge: encode_base64url(&challenge.challenge),
+        rp_id: WEBAUTHN_RP_ID.to_owned(),
+        timeout: WEBAUTHN_CHALLENGE_TTL_SECONDS * 1000,
+        allow_credentials,
+        user_verification: "required".to_owned(),
+    };
+
+    state
+        .webauthn_challenge_store
+        .add_challenge(ceremony_id, challenge)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    let response = Json(StartPasskeyLoginResponse {
+        ceremony_id: ceremony_id.to_string(),
+        public_key,
+    });
+
+    Ok((StatusCode::OK, response))
+}
+
+// A passkey proves possession of a device and that its owner unlocked it, which is enough on its
+// own, no 2FA code is asked for. Failures are throttled like wrong passwords.
+pub async fn finish_passkey_login(
+    State(state): State<AppState>,
+    client: ClientInfo,
+    jar: CookieJar,
+    JsonRequest(request): JsonRequest<FinishPasskeyLoginRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let ceremony_id =
+        Uuid::parse_str(&request.ceremony_id).map_err(|_| AuthAPIError::InvalidPasskey)?;
+    let credential_id =
+        CredentialId::parse(&request.credential.id).map_err(|_| AuthAPIError::InvalidPasskey)?;
+
+    let response = &request.credential.response;
+    let assertion = Assertion {
+        client_data_json: decode_base64url(&response.client_data_json)
+            .map_err(|_| AuthAPIError::InvalidPasskey)?,
+        authenticator_data: decode_base64url(&response.authenticator_data)
+            .map_err(|_| AuthAPIError::InvalidPasskey)?,
+        signature: decode_base64url(&response.signature)
+            .map_err(|_| AuthAPIError::InvalidPasskey)?,
+    };
+
+    let challenge = state
+        .webauthn_challenge_store
+        .take_challenge(&ceremony_id)
+        .await
+        .map_err(|_| AuthAPIError::IncorrectCredentials)?;
+
+    if challenge.ceremony != WebAuthnCeremony::Authentication {
+        return Err(AuthAPIError::IncorrectCredentials);
+    }
+
+    let ip_key = LoginAttemptKey::Ip(client.ip);
+    let credential = match state
+        .passkey_credential_store
+        .get_credential(&credential_id)
+        .await
+    {
+        Ok(credential) => credential,
+        Err(PasskeyCredentialStoreError::CredentialNotFound) => {
+            record_login_failure(&[ip_key], &state).await?;
+            return Err(AuthAPIError::IncorrectCredentials);
+        }
+        Err(_) => return Err(AuthAPIError::UnexpectedError),
+    };
+
+    let throttle_keys = [LoginAttemptKey::Email(credential.email.clone()), ip_key];
+    check_login_allowed(&throttle_keys, &state).await?;
+
+    match verify_assertion(&assertion, &challenge, &credential, &state).await {
+        Ok(()) => {}
+        Err(AuthAPIError::IncorrectCredentials) => {
+            record_login_failure(&throttle_keys, &state).await?;
+            return Err(AuthAPIError::IncorrectCredentials);
+        }
+        Err(e) => return Err(e),
+    }
+
+    let user = state
+        .user_store
+        .get_user(&credential.email)
+        .await
+        .map_err(|e| match e {
+            UserStoreError::UserNotFound => AuthAPIError::IncorrectCredentials,
+            _ => AuthAPIError::UnexpectedError,
+        })?;
+
+    if state.policy.require_verified_email && !user.email_verified {
+        return Err(AuthAPIError::EmailNotVerified);
+    }
+
+    reset_login_failures(&throttle_keys[0], &state).await?;
+    complete_login(&user.email, &client, &state, jar).await
+}
+
+struct Assertion {
+    client_data_json: Vec<u8>,
+    authenticator_data: Vec<u8>,
+    signature: Vec<u8>,
+}
+
+// Checks the signature and moves the sign count on, every way of failing is a wrong credential
+async fn verify_assertion(
+    assertion: &Assertion,
+    challenge: &WebAuthnChallenge,
+    credential: &PasskeyCredential,
+    state: &AppState,
+) -> Result<(), AuthAPIError> {
+    if challenge
+        .email
+        .as_ref()
+        .is_some_and(|email| *email != credential.email)
+    {
+        return Err(AuthAPIError::IncorrectCredentials);
+    }
+
+    let sign_count = verify_authentication(
+        &assertion.client_data_json,
+        &assertion.authenticator_data,
+        &assertion.signature,
+        &challenge.challenge,
+        &credential.public_key,
+        credential.sign_count,
+    )
+    .map_err(|_| AuthAPIError::IncorrectCredentials)?;
+
+    state
+        .passkey_credential_store
+        .update_sign_count(&credential.credential_id, credential.sign_count, sign_count)
+        .await
+        .map_err(|e| match e {
+            PasskeyCredentialStoreError::SignCountChanged => AuthAPIError::IncorrectCredentials,
+            _ => AuthAPIError::UnexpectedError,
+        })
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StartPasskeyRegistrationResponse {
+    #[serde(rename = "ceremonyId")]
+    pub ceremony_id: String,
+    #[serde(rename = "publicKey")]
+    pub public_key: PublicKeyCredentialCreationOptions,
+}
+
+// Binary values are base64url encoded, the browser decodes them before calling the WebAuthn API
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PublicKeyCredentialCreationOptions {
+    pub challenge: String,
+    pub rp: RelyingPartyEntity,
+    pub user: UserEntity,
+    pub pub_key_cred_params: Vec<CredentialParameters>,
+    pub timeout: u64,
+    pub attestation: String,
+    pub exclude_credentials: Vec<CredentialDescriptor>,
+    pub authenticator_selection: AuthenticatorSelection,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RelyingPartyEntity {
+    pub id: String,
+    pub name: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UserEntity {
+    pub id: String,
+    pub name: String,
+    pub display_name: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CredentialParameters {
+    #[serde(rename = "type")]
+    pub credential_type: String,
+    pub alg: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CredentialDescriptor {
+    #[serde(rename = "type")]
+    pub credential_type: String,
+    pub id: String,
+}
+
+impl CredentialDescriptor {
+    fn new(credential_id: &CredentialId) -> Self {
+        Self {
+            credential_type: PUBLIC_KEY_CREDENTIAL_TYPE.to_owned(),
+            id: credential_id.to_base64(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuthenticatorSelection {
+    pub resident_key: String,
+    pub user_verification: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct FinishPasskeyRegistrationRequest {
+    #[serde(rename = "ceremonyId")]
+    pub ceremony_id: String,
+    pub credential: RegistrationCredential,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct RegistrationCredential {
+    pub id: String,
+    pub response: AttestationResponse,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct AttestationResponse {
+    #[serde(rename = "clientDataJSON")]
+    pub client_data_json: String,
+    #[serde(rename = "attestationObject")]
+    pub attestation_object: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct StartPasskeyLoginRequest {
+    pub email: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StartPasskeyLoginResponse {
+    #[serde(rename = "ceremonyId")]
+    pub ceremony_id: String,
+    #[serde(rename = "publicKey")]
+    pub public_key: PublicKeyCredentialRequestOptions,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PublicKeyCredentialRequestOptions {
+    pub challenge: String,
+    pub rp_id: String,
+    pub timeout: u64,
+    pub allow_credentials: Vec<CredentialDescriptor>,
+    pub user_verification: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct FinishPasskeyLoginRequest {
+    #[serde(rename = "ceremonyId")]
+    pub ceremony_id: String,
+    pub credential: AuthenticationCredential,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct AuthenticationCredential {
+    pub id: String,
+    pub response: AssertionResponse,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct AssertionResponse {
+    #[serde(rename = "clientDataJSON")]
+    pub client_data_json: String,
+    #[serde(rename = "authenticatorData")]
+    pub authenticator_data: String,
+    pub signature: String,
+    #[serde(rename = "userHandle")]
+    pub user_handle: Option<String>,
+}
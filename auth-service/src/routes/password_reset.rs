@@ -0,0 +1,149 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+
+use crate::{
+    app_state::AppState,
+    domain::{
+        error::{AuthAPIError, FieldErrors},
+        Email, PasswordResetToken, PasswordResetTokenStoreError, UserStoreError,
+    },
+    utils::constants::{PASSWORD_RESET_TOKEN_TTL_SECONDS, PASSWORD_RESET_URL},
+};
+
+use super::{sessions::end_all_sessions, JsonRequest};
+
+const PASSWORD_RESET_REQUESTED_MESSAGE: &str =
+    "If an account exists for this email, a password reset link has been sent to it";
+
+// Answers the same way whether or not the email belongs to a user. The lookup and the email are
+// handled in the background, so neither errors nor response times give away which accounts exist.
+pub async fn request_password_reset(
+    State(state): State<AppState>,
+    JsonRequest(request): JsonRequest<PasswordResetRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let mut errors = FieldErrors::default();
+    let email = errors.check("email", "invalid_email", Email::parse(request.email));
+    let Some(email) = email else {
+        return Err(errors.into());
+    };
+
+    tokio::spawn(async move {
+        if let Err(e) = send_password_reset_link(&email, &state).await {
+            tracing::error!(error = ?e, "Failed to send password reset link");
+        }
+    });
+
+    let response = Json(PasswordResetRequestResponse {
+        message: PASSWORD_RESET_REQUESTED_MESSAGE.to_owned(),
+    });
+
+    Ok((StatusCode::ACCEPTED, response))
+}
+
+async fn send_password_reset_link(email: &Email, state: &AppState) -> Result<(), AuthAPIError> {
+    match state.user_store.get_user(email).await {
+        Ok(_) => {}
+        Err(UserStoreError::UserNotFound) => return Ok(()),
+        Err(_) => return Err(AuthAPIError::UnexpectedError),
+    }
+
+    let token = PasswordResetToken::default();
+
+    state
+        .password_reset_token_store
+        .add_token(token.clone(), email.clone())
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    let link = format!("{}?token={}", *PASSWORD_RESET_URL, token.as_ref());
+
+    state
+        .email_client
+        .send_email(
+            email,
+            "Password reset",
+            &format!(
+                "Use this link within {} minutes to choose a new password: {}",
+                PASSWORD_RESET_TOKEN_TTL_SECONDS / 60,
+                link
+            ),
+        )
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)
+}
+
+pub async fn confirm_password_reset(
+    State(state): State<AppState>,
+    JsonRequest(request): JsonRequest<PasswordResetConfirmRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let token = PasswordResetToken::parse(request.token)
+        .map_err(|_| AuthAPIError::InvalidPasswordResetToken)?;
+
+    // The password is checked before the token is used up, so a rejected one can be corrected
+    // through the same link
+    let owner = state
+        .password_reset_token_store
+        .get_token(&token)
+        .await
+        .map_err(token_error)?;
+
+    let mut errors = FieldErrors::default();
+    let password = errors.check_password(
+        "newPassword",
+        state.policy.password.parse(request.new_password, &owner),
+    );
+    let Some(password) = password else {
+        return Err(errors.into());
+    };
+
+    let email = state
+        .password_reset_token_store
+        .take_token(&token)
+        .await
+        .map_err(token_error)?;
+
+    state
+        .user_store
+        .update_password(&email, password)
+        .await
+        .map_err(|e| match e {
+            // The account was deleted after the link was sent
+            UserStoreError::UserNotFound => AuthAPIError::InvalidPasswordResetToken,
+            _ => AuthAPIError::UnexpectedError,
+        })?;
+
+    // Other links sent before the reset must not be usable to change the password again
+    state
+        .password_reset_token_store
+        .remove_tokens(&email)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    // Whoever knew the old password is logged out as well
+    end_all_sessions(&email, &state).await?;
+
+    Ok(StatusCode::OK)
+}
+
+fn token_error(e: PasswordResetTokenStoreError) -> AuthAPIError {
+    match e {
+        PasswordResetTokenStoreError::TokenNotFound => AuthAPIError::InvalidPasswordResetToken,
+        PasswordResetTokenStoreError::UnexpectedError => AuthAPIError::UnexpectedError,
+    }
+}
+
+#[derive(serde::Deserialize)]
+pub struct PasswordResetRequest {
+    pub email: String,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct PasswordResetRequestResponse {
+    pub message: String,
+}
+
+#[derive(serde::Deserialize)]
+pub struct PasswordResetConfirmRequest {
+    pub token: String,
+    #[serde(rename = "newPassword")]
+    pub new_password: String,
+}
@@ -0,0 +1,49 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+
+use crate::{
+    app_state::AppState,
+    domain::{error::AuthAPIError, RecoveryCode},
+};
+
+use super::{account::check_current_password, AuthenticatedUser, JsonRequest};
+
+// Number of codes in a batch, a user who burns through them all can generate a new batch
+pub const RECOVERY_CODE_COUNT: usize = 10;
+
+// Generate a new batch of recovery codes, replacing any earlier batch. Only the hashes are
+// stored, so this response is the only time the codes are shown. The codes get past 2FA, so a
+// session cookie alone is not enough to get hold of them.
+pub async fn regenerate_recovery_codes(
+    State(state): State<AppState>,
+    user: AuthenticatedUser,
+    JsonRequest(request): JsonRequest<RegenerateRecoveryCodesRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    check_current_password(&user.email, request.password, &state).await?;
+
+    let codes: Vec<RecoveryCode> = (0..RECOVERY_CODE_COUNT)
+        .map(|_| RecoveryCode::default())
+        .collect();
+
+    state
+        .recovery_code_store
+        .replace_codes(&user.email, codes.clone())
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    let response = Json(RecoveryCodesResponse {
+        recovery_codes: codes.iter().map(|code| code.as_ref().to_owned()).collect(),
+    });
+
+    Ok((StatusCode::OK, response))
+}
+
+#[derive(serde::Deserialize)]
+pub struct RegenerateRecoveryCodesRequest {
+    pub password: String,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct RecoveryCodesResponse {
+    #[serde(rename = "recoveryCodes")]
+    pub recovery_codes: Vec<String>,
+}
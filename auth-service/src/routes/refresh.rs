@@ -0,0 +1,106 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse};
+use axum_extra::extract::CookieJar;
+use uuid::Uuid;
+
+use crate::{
+    app_state::AppState,
+    domain::{error::AuthAPIError, RefreshToken, RefreshTokenStoreError, SessionStoreError},
+    utils::{
+        auth::{create_refresh_cookie, generate_auth_cookie},
+        constants::REFRESH_TOKEN_COOKIE_NAME,
+    },
+};
+
+use super::{sessions::current_token_version, ClientInfo};
+
+#[tracing::instrument(name = "Refresh", skip_all, err(Debug))]
+pub async fn refresh(
+    State(state): State<AppState>,
+    client: ClientInfo,
+    jar: CookieJar,
+) -> Result<(CookieJar, impl IntoResponse), AuthAPIError> {
+    let cookie = jar
+        .get(REFRESH_TOKEN_COOKIE_NAME)
+        .ok_or(AuthAPIError::MissingToken)?;
+
+    let refresh_token =
+        RefreshToken::parse(cookie.value().to_owned()).map_err(|_| AuthAPIError::InvalidToken)?;
+
+    let new_token = RefreshToken::default();
+
+    let record = match state
+        .refresh_token_store
+        .replace_token(&refresh_token, new_token.clone())
+        .await
+    {
+        Ok(record) => record,
+        Err(RefreshTokenStoreError::TokenReused(family_id)) => {
+            // A rotated token was presented again, so it has leaked. Kill every token in its family
+            // along with the session it belongs to.
+            tracing::warn!(%family_id, "Refresh token reuse detected, revoking token family");
+            state
+                .refresh_token_store
+                .revoke_family(&family_id)
+                .await
+                .map_err(|_| AuthAPIError::UnexpectedError)?;
+            end_session(&family_id, &state).await?;
+            return Err(AuthAPIError::InvalidToken);
+        }
+        Err(RefreshTokenStoreError::TokenNotFound) => return Err(AuthAPIError::InvalidToken),
+        Err(RefreshTokenStoreError::UnexpectedError) => return Err(AuthAPIError::UnexpectedError),
+    };
+
+    // The family id of the refresh tokens is the id of their session
+    let touched = state
+        .session_store
+        .touch_session(&record.family_id, Some(client.ip))
+        .await;
+
+    match touched {
+        Ok(()) => {}
+        Err(SessionStoreError::SessionNotFound) => {
+            // The session has ended, so the token just stored must not outlive it
+            state
+                .refresh_token_store
+                .revoke_family(&record.family_id)
+                .await
+                .map_err(|_| AuthAPIError::UnexpectedError)?;
+            return Err(AuthAPIError::InvalidToken);
+        }
+        Err(SessionStoreError::UnexpectedError) => return Err(AuthAPIError::UnexpectedError),
+    }
+
+    let token_version = current_token_version(&record.email, &state).await?;
+
+    let auth_cookie = generate_auth_cookie(
+        &record.email,
+        record.family_id,
+        token_version,
+        &*state.jwt_keyring.read().await,
+    )
+    .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    let jar = jar
+        .add(auth_cookie)
+        .add(create_refresh_cookie(new_token, record.expires_at));
+
+    Ok((jar, StatusCode::OK))
+}
+
+async fn end_session(session_id: &Uuid, state: &AppState) -> Result<(), AuthAPIError> {
+    let session_store = &state.session_store;
+
+    let session = match session_store.get_session(session_id).await {
+        Ok(session) => session,
+        Err(SessionStoreError::SessionNotFound) => return Ok(()),
+        Err(SessionStoreError::UnexpectedError) => return Err(AuthAPIError::UnexpectedError),
+    };
+
+    match session_store
+        .remove_session(&session.email, session_id)
+        .await
+    {
+        Ok(()) | Err(SessionStoreError::SessionNotFound) => Ok(()),
+        Err(SessionStoreError::UnexpectedError) => Err(AuthAPIError::UnexpectedError),
+    }
+}
@@ -0,0 +1,184 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use axum_extra::extract::CookieJar;
+use chrono::SecondsFormat;
+use uuid::Uuid;
+
+use crate::{
+    app_state::AppState,
+    domain::{error::AuthAPIError, Email, Session, SessionStoreError, UserStoreError},
+    utils::{
+        auth::{generate_auth_cookie, generate_refresh_cookie},
+        constants::{JWT_COOKIE_NAME, REFRESH_TOKEN_COOKIE_NAME},
+    },
+};
+
+use super::{AuthenticatedUser, ClientInfo};
+
+pub async fn list_sessions(
+    State(state): State<AppState>,
+    user: AuthenticatedUser,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let sessions = state
+        .session_store
+        .get_sessions(&user.email)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    let sessions = sessions
+        .into_iter()
+        .map(|session| SessionResponse::new(session, user.session_id))
+        .collect::<Vec<_>>();
+
+    Ok((StatusCode::OK, Json(sessions)))
+}
+
+// Logs out one session of the user, which may be the current one
+pub async fn revoke_session(
+    State(state): State<AppState>,
+    user: AuthenticatedUser,
+    jar: CookieJar,
+    Path(id): Path<Uuid>,
+) -> Result<(CookieJar, impl IntoResponse), AuthAPIError> {
+    state
+        .session_store
+        .remove_session(&user.email, &id)
+        .await
+        .map_err(|e| match e {
+            SessionStoreError::SessionNotFound => AuthAPIError::SessionNotFound,
+            SessionStoreError::UnexpectedError => AuthAPIError::UnexpectedError,
+        })?;
+
+    // Refresh tokens are issued in one family per session
+    state
+        .refresh_token_store
+        .revoke_family(&id)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    let jar = match id == user.session_id {
+        true => jar
+            .remove(JWT_COOKIE_NAME)
+            .remove(REFRESH_TOKEN_COOKIE_NAME),
+        false => jar,
+    };
+
+    Ok((jar, StatusCode::NO_CONTENT))
+}
+
+// Records a new session for a user who just logged in and adds its auth and refresh cookies
+pub(super) async fn start_session(
+    email: &Email,
+    client: &ClientInfo,
+    state: &AppState,
+    jar: CookieJar,
+) -> Result<CookieJar, AuthAPIError> {
+    let session = Session::new(email.clone(), Some(client.ip), client.user_agent.clone());
+    let session_id = session.id;
+
+    state
+        .session_store
+        .add_session(session)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    let token_version = current_token_version(email, state).await?;
+
+    let auth_cookie = generate_auth_cookie(
+        email,
+        session_id,
+        token_version,
+        &*state.jwt_keyring.read().await,
+    )
+    .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    let refresh_cookie = generate_refresh_cookie(email, session_id, &*state.refresh_token_store)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    Ok(jar.add(auth_cookie).add(refresh_cookie))
+}
+
+// Ends every session of the user at once. Outstanding auth tokens are rejected since their token
+// version is outdated, and the refresh tokens of the sessions are revoked.
+pub(super) async fn end_all_sessions(email: &Email, state: &AppState) -> Result<(), AuthAPIError> {
+    state
+        .user_store
+        .bump_token_version(email)
+        .await
+        .map_err(|e| match e {
+            UserStoreError::UserNotFound => AuthAPIError::InvalidToken,
+            _ => AuthAPIError::UnexpectedError,
+        })?;
+
+    remove_sessions(email, state).await
+}
+
+// Removes the sessions stored under the address along with their refresh tokens
+pub(super) async fn remove_sessions(email: &Email, state: &AppState) -> Result<(), AuthAPIError> {
+    state
+        .session_store
+        .remove_sessions(email)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    state
+        .refresh_token_store
+        .revoke_all(email)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)
+}
+
+// Version that auth tokens issued to the user right now have to carry
+pub(super) async fn current_token_version(
+    email: &Email,
+    state: &AppState,
+) -> Result<u32, AuthAPIError> {
+    state
+        .user_store
+        .get_token_version(email)
+        .await
+        .map_err(|e| match e {
+            // The account was deleted in the meantime
+            UserStoreError::UserNotFound => AuthAPIError::InvalidToken,
+            _ => AuthAPIError::UnexpectedError,
+        })
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct SessionResponse {
+    pub id: Uuid,
+    pub device: String,
+    #[serde(rename = "ipAddress")]
+    pub ip_address: Option<String>,
+    #[serde(rename = "userAgent")]
+    pub user_agent: Option<String>,
+    #[serde(rename = "createdAt")]
+    pub created_at: String,
+    #[serde(rename = "lastSeenAt")]
+    pub last_seen_at: String,
+    // Whether this is the session the request was made with
+    pub current: bool,
+}
+
+impl SessionResponse {
+    fn new(session: Session, current_session_id: Uuid) -> Self {
+        Self {
+            id: session.id,
+            device: session.device,
+            ip_address: session.ip_address.map(|ip| ip.to_string()),
+            user_agent: session.user_agent,
+            created_at: session
+                .created_at
+                .to_rfc3339_opts(SecondsFormat::Secs, true),
+            last_seen_at: session
+                .last_seen_at
+                .to_rfc3339_opts(SecondsFormat::Secs, true),
+            current: session.id == current_session_id,
+        }
+    }
+}
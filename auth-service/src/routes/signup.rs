@@ -2,26 +2,47 @@ use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
 
 use crate::{
     app_state::AppState,
-    domain::{error::AuthAPIError, Email, Password, User, UserStoreError},
+    domain::{
+        error::{AuthAPIError, FieldErrors},
+        Email, User, UserStoreError,
+    },
 };
 
+use super::{send_verification_email, JsonRequest};
+
 #[tracing::instrument(name = "Signup", skip_all, err(Debug))]
 pub async fn signup(
     State(state): State<AppState>,
-    Json(request): Json<SignupRequest>,
+    JsonRequest(request): JsonRequest<SignupRequest>,
 ) -> Result<impl IntoResponse, AuthAPIError> {
-    let email = Email::parse(request.email).map_err(|_| AuthAPIError::InvalidCredentials)?;
-    let password =
-        Password::parse(request.password).map_err(|_| AuthAPIError::InvalidCredentials)?;
+    let mut errors = FieldErrors::default();
+    let email = errors.check("email", "invalid_email", Email::parse(request.email));
+    // Rules like `contains_email` need the address, so the password is only checked with a valid one
+    let password = email.as_ref().and_then(|email| {
+        errors.check_password(
+            "password",
+            state.policy.password.parse(request.password, email),
+        )
+    });
+    let (Some(email), Some(password)) = (email, password) else {
+        return Err(errors.into());
+    };
 
-    let user: User = User::new(email, password, request.requires_2fa);
+    let user: User = User::new(email.clone(), request.requires_2fa);
 
-    let mut user_store = state.user_store.write().await;
+    state
+        .user_store
+        .add_user(user, password)
+        .await
+        .map_err(|e| match e {
+            UserStoreError::UserAlreadyExists => AuthAPIError::UserAlreadyExists,
+            _ => AuthAPIError::UnexpectedError,
+        })?;
 
-    user_store.add_user(user).await.map_err(|e| match e {
-        UserStoreError::UserAlreadyExists => AuthAPIError::UserAlreadyExists,
-        _ => AuthAPIError::UnexpectedError,
-    })?;
+    // The account exists at this point, a failed email can be sent again through the resend route
+    if let Err(e) = send_verification_email(&email, &state).await {
+        tracing::error!(error = ?e, "Failed to send verification email");
+    }
 
     let response = Json(SignupResponse {
         message: "User created successfully".to_owned(),
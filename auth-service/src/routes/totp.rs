@@ -0,0 +1,126 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+
+use crate::{
+    app_state::AppState,
+    domain::{
+        error::{AuthAPIError, FieldErrors},
+        TotpSecret, TwoFACode, TwoFAMethod, UserStoreError,
+    },
+    utils::totp::{generate_otpauth_uri, generate_qr_code, verify_totp_code},
+};
+
+use super::{account::check_current_password, AuthenticatedUser, JsonRequest};
+
+// Start an enrollment with a fresh secret. Whatever 2FA is in use, including an authenticator app
+// enrolled earlier, stays in use until the new secret is confirmed.
+pub async fn enroll_totp(
+    State(state): State<AppState>,
+    user: AuthenticatedUser,
+    JsonRequest(request): JsonRequest<EnrollTotpRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    check_current_password(&user.email, request.password, &state).await?;
+
+    let secret = TotpSecret::default();
+
+    let otpauth_uri =
+        generate_otpauth_uri(&secret, &user.email).map_err(|_| AuthAPIError::UnexpectedError)?;
+    let qr_code = generate_qr_code(&otpauth_uri).map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    state
+        .user_store
+        .set_pending_totp_secret(&user.email, secret)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    let response = Json(EnrollTotpResponse {
+        otpauth_uri,
+        qr_code,
+    });
+
+    Ok((StatusCode::OK, response))
+}
+
+// Switch to the authenticator app once it has proven it generates matching codes
+pub async fn confirm_totp(
+    State(state): State<AppState>,
+    user: AuthenticatedUser,
+    JsonRequest(request): JsonRequest<ConfirmTotpRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let mut errors = FieldErrors::default();
+    let two_fa_code = errors.check(
+        "2FACode",
+        "invalid_2fa_code",
+        TwoFACode::parse(&request.two_fa_code),
+    );
+    let Some(two_fa_code) = two_fa_code else {
+        return Err(errors.into());
+    };
+
+    check_current_password(&user.email, request.password, &state).await?;
+
+    let secret = state
+        .user_store
+        .get_pending_totp_secret(&user.email)
+        .await
+        .map_err(|e| match e {
+            UserStoreError::TotpNotEnrolled => AuthAPIError::TotpNotEnrolled,
+            _ => AuthAPIError::UnexpectedError,
+        })?;
+
+    let is_valid = verify_totp_code(&secret, &user.email, &two_fa_code)
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    if !is_valid {
+        return Err(AuthAPIError::IncorrectCredentials);
+    }
+
+    state
+        .user_store
+        .confirm_totp_secret(&user.email, secret)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    Ok(StatusCode::OK)
+}
+
+// Go back to codes sent by email, the enrolled secret is kept but no longer accepted
+pub async fn disable_totp(
+    State(state): State<AppState>,
+    user: AuthenticatedUser,
+    JsonRequest(request): JsonRequest<DisableTotpRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    check_current_password(&user.email, request.password, &state).await?;
+
+    state
+        .user_store
+        .set_two_fa_method(&user.email, TwoFAMethod::Email)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    Ok(StatusCode::OK)
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct EnrollTotpResponse {
+    #[serde(rename = "otpauthUri")]
+    pub otpauth_uri: String,
+    #[serde(rename = "qrCode")]
+    pub qr_code: String,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct EnrollTotpRequest {
+    pub password: String,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct ConfirmTotpRequest {
+    pub password: String,
+    #[serde(rename = "2FACode")]
+    pub two_fa_code: String,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct DisableTotpRequest {
+    pub password: String,
+}
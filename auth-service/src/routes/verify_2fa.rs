@@ -1,34 +1,205 @@
-use axum::{Json, extract::State, http::StatusCode, response::IntoResponse};
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
 use axum_extra::extract::CookieJar;
 
-use crate::{app_state::AppState, domain::{Email, LoginAttemptId, TwoFACode, TwoFACodeStoreError, error::AuthAPIError}};
+use crate::{
+    app_state::AppState,
+    domain::{
+        error::{AuthAPIError, FieldErrors},
+        Email, LoginAttemptId, LoginAttemptKey, RecoveryCode, RecoveryCodeStoreError, TwoFACode,
+        TwoFACodeStoreError, TwoFAMethod, UserStoreError,
+    },
+    utils::totp::verify_totp_code,
+};
+
+use super::{
+    login_throttle::{
+        check_login_allowed, record_login_failure, record_two_fa_failure, reset_login_failures,
+    },
+    sessions::start_session,
+    ClientInfo, JsonRequest,
+};
 
 pub async fn verify_2fa(
     State(state): State<AppState>,
+    client: ClientInfo,
+    jar: CookieJar,
+    JsonRequest(request): JsonRequest<Verify2FARequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let mut errors = FieldErrors::default();
+    let email = errors.check("email", "invalid_email", Email::parse(request.email));
+    let login_attempt_id = errors.check(
+        "loginAttemptId",
+        "invalid_login_attempt_id",
+        LoginAttemptId::parse(request.login_attempt_id),
+    );
+    let two_fa_code = errors.check(
+        "2FACode",
+        "invalid_2fa_code",
+        TwoFACode::parse(&request.two_fa_code),
+    );
+    let (Some(email), Some(login_attempt_id), Some(two_fa_code)) =
+        (email, login_attempt_id, two_fa_code)
+    else {
+        return Err(errors.into());
+    };
+
+    // Wrong codes count against the account too, so they can not be spread over many addresses
+    let throttle_keys = [
+        LoginAttemptKey::Email(email.clone()),
+        LoginAttemptKey::Ip(client.ip),
+    ];
+    check_login_allowed(&throttle_keys, &state).await?;
+
+    let totp_secret = match state.user_store.get_user(&email).await {
+        Ok(user) if user.two_fa_method == TwoFAMethod::Totp => Some(
+            state
+                .user_store
+                .get_totp_secret(&email)
+                .await
+                .map_err(|_| AuthAPIError::UnexpectedError)?,
+        ),
+        Ok(_) | Err(UserStoreError::UserNotFound) => None,
+        Err(_) => return Err(AuthAPIError::UnexpectedError),
+    };
+
+    let store = &state.two_fa_code_store;
+
+    let (stored_login_attempt_id, stored_code) = match store.get_code(&email).await {
+        Ok(stored) => stored,
+        Err(TwoFACodeStoreError::LoginAttemptIdNotFound) => {
+            record_login_failure(&throttle_keys, &state).await?;
+            return Err(AuthAPIError::IncorrectCredentials);
+        }
+        Err(TwoFACodeStoreError::UnexpectedError) => return Err(AuthAPIError::UnexpectedError),
+    };
+
+    if stored_login_attempt_id != login_attempt_id {
+        record_login_failure(&throttle_keys, &state).await?;
+        return Err(AuthAPIError::IncorrectCredentials);
+    }
+
+    let is_valid_code = match &totp_secret {
+        Some(secret) => verify_totp_code(secret, &email, &two_fa_code)
+            .map_err(|_| AuthAPIError::UnexpectedError)?,
+        None => stored_code == two_fa_code,
+    };
+
+    if !is_valid_code {
+        record_login_failure(&throttle_keys, &state).await?;
+
+        // A 6 digit code only survives a few guesses, after that the password is asked again
+        if record_two_fa_failure(&email, &state).await? {
+            store
+                .remove_code(&email)
+                .await
+                .map_err(|_| AuthAPIError::UnexpectedError)?;
+        }
+
+        return Err(AuthAPIError::IncorrectCredentials);
+    }
+
+    store
+        .remove_code(&email)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    reset_login_failures(&LoginAttemptKey::TwoFactor(email.clone()), &state).await?;
+    reset_login_failures(&throttle_keys[0], &state).await?;
+
+    complete_login(&email, &client, &state, jar).await
+}
+
+// Log in with a recovery code instead of the second factor, the code is used up in the process
+pub async fn verify_2fa_recovery(
+    State(state): State<AppState>,
+    client: ClientInfo,
     jar: CookieJar,
-    Json(request): Json<Verify2FARequest>,
+    JsonRequest(request): JsonRequest<Verify2FARecoveryRequest>,
 ) -> Result<impl IntoResponse, AuthAPIError> {
-    let email = Email::parse(request.email).map_err(|_| AuthAPIError::Invalid2FACodeRequest)?;
-    let login_attempt_id = LoginAttemptId::parse(request.login_attempt_id).map_err(|_| AuthAPIError::Invalid2FACodeRequest)?;
-    let two_fa_code = TwoFACode::parse(&request.two_fa_code).map_err(|_| AuthAPIError::Invalid2FACodeRequest)?;
+    let mut errors = FieldErrors::default();
+    let email = errors.check("email", "invalid_email", Email::parse(request.email));
+    let login_attempt_id = errors.check(
+        "loginAttemptId",
+        "invalid_login_attempt_id",
+        LoginAttemptId::parse(request.login_attempt_id),
+    );
+    let recovery_code = errors.check(
+        "recoveryCode",
+        "invalid_recovery_code",
+        RecoveryCode::parse(&request.recovery_code),
+    );
+    let (Some(email), Some(login_attempt_id), Some(recovery_code)) =
+        (email, login_attempt_id, recovery_code)
+    else {
+        return Err(errors.into());
+    };
+
+    // Wrong codes count against the account too, so they can not be spread over many addresses
+    let throttle_keys = [
+        LoginAttemptKey::Email(email.clone()),
+        LoginAttemptKey::Ip(client.ip),
+    ];
+    check_login_allowed(&throttle_keys, &state).await?;
 
-    let mut store = state.two_fa_code_store.write().await;
+    let store = &state.two_fa_code_store;
 
-    let (stored_login_attempt_id, stored_code) = store.get_code(&email).await.map_err(|e| match e {
-        TwoFACodeStoreError::LoginAttemptIdNotFound => AuthAPIError::IncorrectCredentials,
-        TwoFACodeStoreError::UnexpectedError => AuthAPIError::UnexpectedError,
-    })?;
+    let (stored_login_attempt_id, _) = match store.get_code(&email).await {
+        Ok(stored) => stored,
+        Err(TwoFACodeStoreError::LoginAttemptIdNotFound) => {
+            record_login_failure(&throttle_keys, &state).await?;
+            return Err(AuthAPIError::IncorrectCredentials);
+        }
+        Err(TwoFACodeStoreError::UnexpectedError) => return Err(AuthAPIError::UnexpectedError),
+    };
 
-    if stored_login_attempt_id != login_attempt_id || stored_code != two_fa_code {
+    // The password must have been checked already, a recovery code only replaces the second factor
+    if stored_login_attempt_id != login_attempt_id {
+        record_login_failure(&throttle_keys, &state).await?;
         return Err(AuthAPIError::IncorrectCredentials);
     }
 
-    store.remove_code(&email).await.map_err(|_| AuthAPIError::UnexpectedError)?;
+    let result = state
+        .recovery_code_store
+        .use_code(&email, &recovery_code)
+        .await;
 
-    let auth_cookie = crate::utils::auth::generate_auth_cookie(&email)
+    match result {
+        Ok(()) => {}
+        // Wrong recovery codes use up the tries of the login attempt just like wrong 2FA codes
+        Err(RecoveryCodeStoreError::InvalidCode) => {
+            record_login_failure(&throttle_keys, &state).await?;
+
+            if record_two_fa_failure(&email, &state).await? {
+                store
+                    .remove_code(&email)
+                    .await
+                    .map_err(|_| AuthAPIError::UnexpectedError)?;
+            }
+
+            return Err(AuthAPIError::IncorrectCredentials);
+        }
+        Err(RecoveryCodeStoreError::UnexpectedError) => return Err(AuthAPIError::UnexpectedError),
+    }
+
+    store
+        .remove_code(&email)
+        .await
         .map_err(|_| AuthAPIError::UnexpectedError)?;
 
-    let jar = jar.add(auth_cookie);
+    reset_login_failures(&LoginAttemptKey::TwoFactor(email.clone()), &state).await?;
+    reset_login_failures(&throttle_keys[0], &state).await?;
+
+    complete_login(&email, &client, &state, jar).await
+}
+
+// Issue the auth and refresh cookies once every factor has been checked
+pub(super) async fn complete_login(
+    email: &Email,
+    client: &ClientInfo,
+    state: &AppState,
+    jar: CookieJar,
+) -> Result<(StatusCode, (CookieJar, Json<()>)), AuthAPIError> {
+    let jar = start_session(email, client, state, jar).await?;
 
     Ok((StatusCode::OK, (jar, Json(()))))
 }
@@ -41,3 +212,12 @@ pub struct Verify2FARequest {
     #[serde(rename = "2FACode")]
     pub two_fa_code: String,
 }
+
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct Verify2FARecoveryRequest {
+    pub email: String,
+    #[serde(rename = "loginAttemptId")]
+    pub login_attempt_id: String,
+    #[serde(rename = "recoveryCode")]
+    pub recovery_code: String,
+}
@@ -0,0 +1,134 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+
+use crate::{
+    app_state::AppState,
+    domain::{
+        error::{AuthAPIError, FieldErrors},
+        Email, EmailConfirmation, EmailToken, EmailTokenStoreError, UserStoreError,
+    },
+    utils::constants::{EMAIL_VERIFICATION_TOKEN_TTL_SECONDS, EMAIL_VERIFICATION_URL},
+};
+
+use super::JsonRequest;
+
+const VERIFICATION_EMAIL_REQUESTED_MESSAGE: &str =
+    "If an unverified account exists for this email, a new verification link has been sent to it";
+
+// Called by the page the verification email links to, opening the link alone changes nothing
+pub async fn verify_email(
+    State(state): State<AppState>,
+    JsonRequest(request): JsonRequest<VerifyEmailRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let token = EmailToken::parse(request.token).map_err(|_| AuthAPIError::InvalidToken)?;
+
+    let email = match state.email_token_store.take_token(&token).await {
+        Ok(EmailConfirmation::Verification { email }) => email,
+        Ok(_) | Err(EmailTokenStoreError::TokenNotFound) => return Err(AuthAPIError::InvalidToken),
+        Err(_) => return Err(AuthAPIError::UnexpectedError),
+    };
+
+    state
+        .user_store
+        .set_email_verified(&email)
+        .await
+        .map_err(|e| match e {
+            // The account was deleted after the link was sent
+            UserStoreError::UserNotFound => AuthAPIError::InvalidToken,
+            _ => AuthAPIError::UnexpectedError,
+        })?;
+
+    let response = Json(VerifyEmailResponse {
+        message: "Email address verified".to_owned(),
+    });
+
+    Ok((StatusCode::OK, response))
+}
+
+// Sends a fresh link to users who lost or never got the first one. Like the password reset it
+// answers the same way for every email, so it can not be used to find out which accounts exist.
+pub async fn resend_verification_email(
+    State(state): State<AppState>,
+    JsonRequest(request): JsonRequest<ResendVerificationEmailRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let mut errors = FieldErrors::default();
+    let email = errors.check("email", "invalid_email", Email::parse(request.email));
+    let Some(email) = email else {
+        return Err(errors.into());
+    };
+
+    tokio::spawn(async move {
+        let user = match state.user_store.get_user(&email).await {
+            Ok(user) => user,
+            Err(UserStoreError::UserNotFound) => return,
+            Err(e) => {
+                tracing::error!(error = ?e, "Failed to look up user for verification email");
+                return;
+            }
+        };
+
+        if user.email_verified {
+            return;
+        }
+
+        if let Err(e) = send_verification_email(&email, &state).await {
+            tracing::error!(error = ?e, "Failed to send verification email");
+        }
+    });
+
+    let response = Json(VerifyEmailResponse {
+        message: VERIFICATION_EMAIL_REQUESTED_MESSAGE.to_owned(),
+    });
+
+    Ok((StatusCode::ACCEPTED, response))
+}
+
+pub(super) async fn send_verification_email(
+    email: &Email,
+    state: &AppState,
+) -> Result<(), AuthAPIError> {
+    let token = EmailToken::default();
+    let confirmation = EmailConfirmation::Verification {
+        email: email.clone(),
+    };
+
+    state
+        .email_token_store
+        .add_token(token.clone(), confirmation)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    let link = format!(
+        "{}?verify_email_token={}",
+        *EMAIL_VERIFICATION_URL,
+        token.as_ref()
+    );
+
+    state
+        .email_client
+        .send_email(
+            email,
+            "Verify your email address",
+            &format!(
+                "Use this link within {} hours to verify your email address: {}",
+                EMAIL_VERIFICATION_TOKEN_TTL_SECONDS / 3600,
+                link
+            ),
+        )
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)
+}
+
+#[derive(serde::Deserialize)]
+pub struct VerifyEmailRequest {
+    pub token: String,
+}
+
+#[derive(serde::Deserialize)]
+pub struct ResendVerificationEmailRequest {
+    pub email: String,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct VerifyEmailResponse {
+    pub message: String,
+}
@@ -1,16 +1,34 @@
-use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use axum::{extract::State, http::StatusCode, response::IntoResponse};
 
 use crate::{app_state::AppState, domain::error::AuthAPIError, utils::auth::validate_token};
 
+use super::JsonRequest;
+
 pub async fn verify_token(
     State(app_state): State<AppState>,
-    Json(request): Json<VerifyTokenRequest>,
+    JsonRequest(request): JsonRequest<VerifyTokenRequest>,
 ) -> Result<impl IntoResponse, AuthAPIError> {
-    let banned_token_store = app_state.banned_token_store.read().await;
+    let banned_token_store = app_state.banned_token_store;
+    let session_store = app_state.session_store;
+    let user_store = app_state.user_store;
+    let jwt_keyring = app_state.jwt_keyring.read().await;
+
+    let claims = validate_token(
+        &request.token,
+        &[],
+        &*banned_token_store,
+        &*session_store,
+        &*user_store,
+        &jwt_keyring,
+    )
+    .await
+    .map_err(|_| AuthAPIError::InvalidToken)?;
 
-    validate_token(&request.token, &*banned_token_store)
-        .await
-        .map_err(|_| AuthAPIError::InvalidToken)?;
+    // Only login tokens of users verify here, tokens issued to OAuth and machine clients are
+    // checked through introspection, which also tells what they were issued for
+    if claims.client_id.is_some() {
+        return Err(AuthAPIError::InvalidToken);
+    }
 
     Ok(StatusCode::OK.into_response())
 }
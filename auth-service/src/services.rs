@@ -1,10 +1,35 @@
 mod data_stores;
 
-pub use data_stores::hashmap_user_store::*;
-pub use data_stores::hashset_banned_token_store::*;
+pub use data_stores::hashmap_authorization_code_store::*;
+pub use data_stores::hashmap_banned_token_store::*;
+pub use data_stores::hashmap_email_token_store::*;
+pub use data_stores::hashmap_jwt_key_store::*;
+pub use data_stores::hashmap_login_attempt_store::*;
+pub use data_stores::hashmap_machine_client_store::*;
+pub use data_stores::hashmap_oauth_client_store::*;
+pub use data_stores::hashmap_passkey_credential_store::*;
+pub use data_stores::hashmap_password_reset_token_store::*;
+pub use data_stores::hashmap_rate_limit_store::*;
+pub use data_stores::hashmap_recovery_code_store::*;
+pub use data_stores::hashmap_refresh_token_store::*;
+pub use data_stores::hashmap_session_store::*;
 pub use data_stores::hashmap_two_fa_code_store::*;
+pub use data_stores::hashmap_user_store::*;
+pub use data_stores::hashmap_webauthn_challenge_store::*;
 pub use data_stores::mock_email_client::*;
+pub use data_stores::postgres_jwt_key_store::*;
+pub use data_stores::postgres_machine_client_store::*;
+pub use data_stores::postgres_oauth_client_store::*;
+pub use data_stores::postgres_passkey_credential_store::*;
+pub use data_stores::postgres_recovery_code_store::*;
+pub use data_stores::postgres_refresh_token_store::*;
+pub use data_stores::postgres_session_store::*;
 pub use data_stores::postgres_user_store::*;
+pub use data_stores::redis_authorization_code_store::*;
 pub use data_stores::redis_banned_token_store::*;
+pub use data_stores::redis_email_token_store::*;
+pub use data_stores::redis_login_attempt_store::*;
+pub use data_stores::redis_password_reset_token_store::*;
+pub use data_stores::redis_rate_limit_store::*;
 pub use data_stores::redis_two_fa_code_store::*;
-
+pub use data_stores::redis_webauthn_challenge_store::*;
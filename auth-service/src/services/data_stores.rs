@@ -1,7 +1,33 @@
+pub mod hashmap_authorization_code_store;
+pub mod hashmap_banned_token_store;
+pub mod hashmap_email_token_store;
+pub mod hashmap_jwt_key_store;
+pub mod hashmap_login_attempt_store;
+pub mod hashmap_machine_client_store;
+pub mod hashmap_oauth_client_store;
+pub mod hashmap_passkey_credential_store;
+pub mod hashmap_password_reset_token_store;
+pub mod hashmap_rate_limit_store;
+pub mod hashmap_recovery_code_store;
+pub mod hashmap_refresh_token_store;
+pub mod hashmap_session_store;
 pub mod hashmap_two_fa_code_store;
 pub mod hashmap_user_store;
-pub mod hashset_banned_token_store;
+pub mod hashmap_webauthn_challenge_store;
 pub mod mock_email_client;
+pub mod postgres_jwt_key_store;
+pub mod postgres_machine_client_store;
+pub mod postgres_oauth_client_store;
+pub mod postgres_passkey_credential_store;
+pub mod postgres_recovery_code_store;
+pub mod postgres_refresh_token_store;
+pub mod postgres_session_store;
 pub mod postgres_user_store;
+pub mod redis_authorization_code_store;
 pub mod redis_banned_token_store;
+pub mod redis_email_token_store;
+pub mod redis_login_attempt_store;
+pub mod redis_password_reset_token_store;
+pub mod redis_rate_limit_store;
 pub mod redis_two_fa_code_store;
+pub mod redis_webauthn_challenge_store;
@@ -0,0 +1,82 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use tokio::sync::RwLock;
+
+use crate::{
+    domain::{
+        AuthorizationCode, AuthorizationCodeStore, AuthorizationCodeStoreError, AuthorizationGrant,
+    },
+    utils::constants::AUTHORIZATION_CODE_TTL_SECONDS,
+};
+
+#[derive(Default)]
+pub struct HashMapAuthorizationCodeStore {
+    codes: RwLock<HashMap<AuthorizationCode, (AuthorizationGrant, Instant)>>,
+}
+
+#[async_trait::async_trait]
+impl AuthorizationCodeStore for HashMapAuthorizationCodeStore {
+    async fn add_code(
+        &self,
+        code: AuthorizationCode,
+        grant: AuthorizationGrant,
+    ) -> Result<(), AuthorizationCodeStoreError> {
+        let ttl = Duration::from_secs(AUTHORIZATION_CODE_TTL_SECONDS);
+        let mut codes = self.codes.write().await;
+
+        // Codes that were never redeemed are dropped here, there is no TTL to clean them up
+        codes.retain(|_, (_, created_at)| created_at.elapsed() < ttl);
+        codes.insert(code, (grant, Instant::now()));
+        Ok(())
+    }
+
+    async fn take_code(
+        &self,
+        code: &AuthorizationCode,
+    ) -> Result<AuthorizationGrant, AuthorizationCodeStoreError> {
+        let ttl = Duration::from_secs(AUTHORIZATION_CODE_TTL_SECONDS);
+
+        match self.codes.write().await.remove(code) {
+            Some((grant, created_at)) if created_at.elapsed() < ttl => Ok(grant),
+            _ => Err(AuthorizationCodeStoreError::CodeNotFound),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use crate::domain::{CodeChallenge, Email, Scope};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_code_can_only_be_taken_once() {
+        let store = HashMapAuthorizationCodeStore::default();
+        let code = AuthorizationCode::default();
+        let grant = AuthorizationGrant {
+            client_id: "client".to_owned(),
+            redirect_uri: "https://client.example.com/callback".to_owned(),
+            email: Email::parse("test@example.com".to_owned()).unwrap(),
+            session_id: Uuid::new_v4(),
+            scope: Scope::parse("openid").unwrap(),
+            nonce: None,
+            code_challenge: CodeChallenge::parse(
+                "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM".to_owned(),
+            )
+            .unwrap(),
+        };
+
+        store.add_code(code.clone(), grant.clone()).await.unwrap();
+
+        assert_eq!(store.take_code(&code).await, Ok(grant));
+        assert_eq!(
+            store.take_code(&code).await,
+            Err(AuthorizationCodeStoreError::CodeNotFound)
+        );
+    }
+}
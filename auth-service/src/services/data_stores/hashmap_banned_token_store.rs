@@ -0,0 +1,141 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Weak},
+    time::Duration,
+};
+
+use chrono::{DateTime, Utc};
+use tokio::{sync::RwLock, task::JoinHandle};
+use uuid::Uuid;
+
+use crate::domain::{BannedTokenStore, BannedTokenStoreError};
+
+type BannedTokens = RwLock<HashMap<Uuid, DateTime<Utc>>>;
+
+// Banned token ids along with the moment their token expires
+#[derive(Default)]
+pub struct HashMapBannedTokenStore {
+    tokens: Arc<BannedTokens>,
+}
+
+impl HashMapBannedTokenStore {
+    // Drop the entries of expired tokens every `interval`, so the store does not grow forever. The
+    // task ends once the store is dropped.
+    pub fn spawn_sweeper(&self, interval: Duration) -> JoinHandle<()> {
+        let tokens = Arc::downgrade(&self.tokens);
+
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+
+            loop {
+                ticker.tick().await;
+
+                if !sweep(&tokens).await {
+                    break;
+                }
+            }
+        })
+    }
+}
+
+// Returns false once the store is gone
+async fn sweep(tokens: &Weak<BannedTokens>) -> bool {
+    let Some(tokens) = tokens.upgrade() else {
+        return false;
+    };
+
+    let now = Utc::now();
+    tokens
+        .write()
+        .await
+        .retain(|_, expires_at| *expires_at > now);
+    true
+}
+
+#[async_trait::async_trait]
+impl BannedTokenStore for HashMapBannedTokenStore {
+    async fn ban_token(
+        &self,
+        jti: &Uuid,
+        expires_at: DateTime<Utc>,
+    ) -> Result<(), BannedTokenStoreError> {
+        self.tokens.write().await.insert(*jti, expires_at);
+        Ok(())
+    }
+
+    async fn is_token_banned(&self, jti: &Uuid) -> Result<bool, BannedTokenStoreError> {
+        let tokens = self.tokens.read().await;
+        Ok(tokens
+            .get(jti)
+            .is_some_and(|expires_at| *expires_at > Utc::now()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn in_a_minute() -> DateTime<Utc> {
+        Utc::now() + chrono::Duration::minutes(1)
+    }
+
+    #[tokio::test]
+    async fn test_ban_and_check_token() {
+        let store = HashMapBannedTokenStore::default();
+        let jti = Uuid::new_v4();
+
+        store
+            .ban_token(&jti, in_a_minute())
+            .await
+            .expect("Failed to ban token");
+
+        assert!(store
+            .is_token_banned(&jti)
+            .await
+            .expect("Failed to check token"));
+    }
+
+    #[tokio::test]
+    async fn test_check_unbanned_token() {
+        let store = HashMapBannedTokenStore::default();
+
+        assert!(!store
+            .is_token_banned(&Uuid::new_v4())
+            .await
+            .expect("Failed to check token"));
+    }
+
+    #[tokio::test]
+    async fn test_sweep_drops_expired_tokens() {
+        let store = HashMapBannedTokenStore::default();
+        let expired = Uuid::new_v4();
+        let banned = Uuid::new_v4();
+
+        store
+            .ban_token(&expired, Utc::now() - chrono::Duration::seconds(1))
+            .await
+            .unwrap();
+        store.ban_token(&banned, in_a_minute()).await.unwrap();
+
+        assert!(!store.is_token_banned(&expired).await.unwrap());
+
+        assert!(sweep(&Arc::downgrade(&store.tokens)).await);
+
+        let tokens = store.tokens.read().await;
+        assert_eq!(tokens.len(), 1);
+        assert!(tokens.contains_key(&banned));
+    }
+
+    #[tokio::test]
+    async fn test_sweeper_stops_with_store() {
+        let store = HashMapBannedTokenStore::default();
+        let sweeper = store.spawn_sweeper(Duration::from_millis(10));
+
+        drop(store);
+
+        tokio::time::timeout(Duration::from_secs(1), sweeper)
+            .await
+            .expect("Sweeper did not stop")
+            .unwrap();
+    }
+}
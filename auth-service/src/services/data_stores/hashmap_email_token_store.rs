@@ -0,0 +1,133 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use tokio::sync::RwLock;
+
+use crate::{
+    domain::{Email, EmailConfirmation, EmailToken, EmailTokenStore, EmailTokenStoreError},
+    utils::constants::{EMAIL_CHANGE_TOKEN_TTL_SECONDS, EMAIL_VERIFICATION_TOKEN_TTL_SECONDS},
+};
+
+#[derive(Default)]
+pub struct HashMapEmailTokenStore {
+    tokens: RwLock<HashMap<EmailToken, (EmailConfirmation, Instant)>>,
+}
+
+fn is_expired(confirmation: &EmailConfirmation, created_at: &Instant) -> bool {
+    let ttl = match confirmation {
+        EmailConfirmation::Verification { .. } => EMAIL_VERIFICATION_TOKEN_TTL_SECONDS,
+        EmailConfirmation::Change { .. } => EMAIL_CHANGE_TOKEN_TTL_SECONDS,
+    };
+    created_at.elapsed() >= Duration::from_secs(ttl)
+}
+
+#[async_trait::async_trait]
+impl EmailTokenStore for HashMapEmailTokenStore {
+    async fn add_token(
+        &self,
+        token: EmailToken,
+        confirmation: EmailConfirmation,
+    ) -> Result<(), EmailTokenStoreError> {
+        let mut tokens = self.tokens.write().await;
+
+        // Unused links are dropped here, there is no TTL to clean them up
+        tokens.retain(|_, (confirmation, created_at)| !is_expired(confirmation, created_at));
+
+        tokens.insert(token, (confirmation, Instant::now()));
+        Ok(())
+    }
+
+    async fn take_token(
+        &self,
+        token: &EmailToken,
+    ) -> Result<EmailConfirmation, EmailTokenStoreError> {
+        match self.tokens.write().await.remove(token) {
+            Some((confirmation, created_at)) if !is_expired(&confirmation, &created_at) => {
+                Ok(confirmation)
+            }
+            _ => Err(EmailTokenStoreError::TokenNotFound),
+        }
+    }
+
+    async fn remove_tokens(&self, email: &Email) -> Result<(), EmailTokenStoreError> {
+        self.tokens
+            .write()
+            .await
+            .retain(|_, (confirmation, _)| confirmation.email() != email);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_token_can_only_be_taken_once() {
+        let store = HashMapEmailTokenStore::default();
+        let token = EmailToken::default();
+        let confirmation = EmailConfirmation::Verification {
+            email: Email::parse("test@example.com".to_owned()).unwrap(),
+        };
+
+        store
+            .add_token(token.clone(), confirmation.clone())
+            .await
+            .unwrap();
+
+        assert_eq!(store.take_token(&token).await, Ok(confirmation));
+        assert_eq!(
+            store.take_token(&token).await,
+            Err(EmailTokenStoreError::TokenNotFound)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_remove_tokens_of_account() {
+        let store = HashMapEmailTokenStore::default();
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+        let verification_token = EmailToken::default();
+        let change_token = EmailToken::default();
+        let other_token = EmailToken::default();
+        let other_confirmation = EmailConfirmation::Verification {
+            email: Email::parse("other@example.com".to_owned()).unwrap(),
+        };
+
+        store
+            .add_token(
+                verification_token.clone(),
+                EmailConfirmation::Verification {
+                    email: email.clone(),
+                },
+            )
+            .await
+            .unwrap();
+        store
+            .add_token(
+                change_token.clone(),
+                EmailConfirmation::Change {
+                    email: email.clone(),
+                    new_email: Email::parse("new@example.com".to_owned()).unwrap(),
+                    token_version: 0,
+                },
+            )
+            .await
+            .unwrap();
+        store
+            .add_token(other_token.clone(), other_confirmation.clone())
+            .await
+            .unwrap();
+
+        store.remove_tokens(&email).await.unwrap();
+
+        for token in [verification_token, change_token] {
+            assert_eq!(
+                store.take_token(&token).await,
+                Err(EmailTokenStoreError::TokenNotFound)
+            );
+        }
+        assert_eq!(store.take_token(&other_token).await, Ok(other_confirmation));
+    }
+}
@@ -0,0 +1,80 @@
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use tokio::sync::RwLock;
+
+use crate::domain::{JwtKeyStore, JwtKeyStoreError, StoredJwtKey};
+
+#[derive(Default)]
+pub struct HashMapJwtKeyStore {
+    // Oldest first
+    keys: RwLock<Vec<StoredJwtKey>>,
+}
+
+#[async_trait::async_trait]
+impl JwtKeyStore for HashMapJwtKeyStore {
+    async fn add_key(
+        &self,
+        kid: &str,
+        private_key_pem: &str,
+        interval: Duration,
+    ) -> Result<bool, JwtKeyStoreError> {
+        let interval =
+            chrono::Duration::from_std(interval).map_err(|_| JwtKeyStoreError::UnexpectedError)?;
+        let now = Utc::now();
+
+        let mut keys = self.keys.write().await;
+        if keys
+            .last()
+            .is_some_and(|key| key.created_at > now - interval)
+        {
+            return Ok(false);
+        }
+
+        keys.push(StoredJwtKey {
+            kid: kid.to_owned(),
+            private_key_pem: private_key_pem.to_owned(),
+            created_at: now,
+        });
+        Ok(true)
+    }
+
+    async fn get_keys(&self) -> Result<Vec<StoredJwtKey>, JwtKeyStoreError> {
+        Ok(self.keys.read().await.iter().rev().cloned().collect())
+    }
+
+    async fn remove_keys(&self, added_before: DateTime<Utc>) -> Result<(), JwtKeyStoreError> {
+        self.keys
+            .write()
+            .await
+            .retain(|key| key.created_at >= added_before);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_add_key_only_once_per_interval() {
+        let store = HashMapJwtKeyStore::default();
+        let interval = Duration::from_secs(60);
+
+        assert_eq!(store.add_key("first", "pem", interval).await, Ok(true));
+        assert_eq!(store.add_key("second", "pem", interval).await, Ok(false));
+        assert_eq!(
+            store.add_key("third", "pem", Duration::ZERO).await,
+            Ok(true)
+        );
+
+        let kids: Vec<_> = store
+            .get_keys()
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|key| key.kid)
+            .collect();
+        assert_eq!(kids, ["third", "first"]);
+    }
+}
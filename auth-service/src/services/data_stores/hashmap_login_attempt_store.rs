@@ -0,0 +1,126 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use tokio::sync::RwLock;
+
+use crate::domain::{LoginAttemptKey, LoginAttemptStore, LoginAttemptStoreError};
+
+#[derive(Default)]
+pub struct HashMapLoginAttemptStore {
+    // Failure count and the moment it expires
+    failures: RwLock<HashMap<LoginAttemptKey, (u32, Instant)>>,
+    lockouts: RwLock<HashMap<LoginAttemptKey, Instant>>,
+}
+
+#[async_trait::async_trait]
+impl LoginAttemptStore for HashMapLoginAttemptStore {
+    async fn add_failure(
+        &self,
+        key: &LoginAttemptKey,
+        window: Duration,
+    ) -> Result<u32, LoginAttemptStoreError> {
+        // Stale entries are dropped here, there is no TTL to clean them up
+        let now = Instant::now();
+        self.lockouts
+            .write()
+            .await
+            .retain(|_, locked_until| *locked_until > now);
+
+        let mut failures = self.failures.write().await;
+        failures.retain(|_, (_, expires_at)| *expires_at > now);
+
+        let (count, expires_at) = failures.entry(key.clone()).or_insert((0, now));
+        *count += 1;
+        *expires_at = now + window;
+
+        Ok(*count)
+    }
+
+    async fn lock(
+        &self,
+        key: &LoginAttemptKey,
+        duration: Duration,
+    ) -> Result<(), LoginAttemptStoreError> {
+        self.lockouts
+            .write()
+            .await
+            .insert(key.clone(), Instant::now() + duration);
+        Ok(())
+    }
+
+    async fn locked_for(
+        &self,
+        key: &LoginAttemptKey,
+    ) -> Result<Option<Duration>, LoginAttemptStoreError> {
+        Ok(self
+            .lockouts
+            .read()
+            .await
+            .get(key)
+            .map(|locked_until| locked_until.saturating_duration_since(Instant::now()))
+            .filter(|remaining| !remaining.is_zero()))
+    }
+
+    async fn reset(&self, key: &LoginAttemptKey) -> Result<(), LoginAttemptStoreError> {
+        self.failures.write().await.remove(key);
+        self.lockouts.write().await.remove(key);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::Email;
+
+    fn email_key() -> LoginAttemptKey {
+        LoginAttemptKey::Email(Email::parse("test@example.com".to_owned()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_failures_are_counted_per_key() {
+        let store = HashMapLoginAttemptStore::default();
+        let window = Duration::from_secs(60);
+        let ip_key = LoginAttemptKey::Ip("127.0.0.1".parse().unwrap());
+
+        assert_eq!(store.add_failure(&email_key(), window).await, Ok(1));
+        assert_eq!(store.add_failure(&email_key(), window).await, Ok(2));
+        assert_eq!(store.add_failure(&ip_key, window).await, Ok(1));
+
+        store.reset(&email_key()).await.unwrap();
+        assert_eq!(store.add_failure(&email_key(), window).await, Ok(1));
+    }
+
+    #[tokio::test]
+    async fn test_failures_expire_after_window() {
+        let store = HashMapLoginAttemptStore::default();
+
+        store
+            .add_failure(&email_key(), Duration::ZERO)
+            .await
+            .unwrap();
+
+        let count = store
+            .add_failure(&email_key(), Duration::from_secs(60))
+            .await;
+        assert_eq!(count, Ok(1));
+    }
+
+    #[tokio::test]
+    async fn test_lock_until_reset() {
+        let store = HashMapLoginAttemptStore::default();
+        assert_eq!(store.locked_for(&email_key()).await, Ok(None));
+
+        store
+            .lock(&email_key(), Duration::from_secs(60))
+            .await
+            .unwrap();
+        let remaining = store.locked_for(&email_key()).await.unwrap().unwrap();
+        assert!(remaining <= Duration::from_secs(60));
+
+        store.reset(&email_key()).await.unwrap();
+        assert_eq!(store.locked_for(&email_key()).await, Ok(None));
+    }
+}
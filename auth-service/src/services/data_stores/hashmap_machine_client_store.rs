@@ -0,0 +1,69 @@
+use std::collections::HashMap;
+
+use tokio::sync::RwLock;
+
+use crate::domain::{ClientSecret, MachineClient, MachineClientStore, MachineClientStoreError};
+
+#[derive(Default)]
+pub struct HashMapMachineClientStore {
+    clients: RwLock<HashMap<String, (MachineClient, ClientSecret)>>,
+}
+
+#[async_trait::async_trait]
+impl MachineClientStore for HashMapMachineClientStore {
+    async fn save_client(
+        &self,
+        client: MachineClient,
+        secret: &ClientSecret,
+    ) -> Result<(), MachineClientStoreError> {
+        self.clients
+            .write()
+            .await
+            .insert(client.client_id.clone(), (client, secret.clone()));
+        Ok(())
+    }
+
+    async fn authenticate_client(
+        &self,
+        client_id: &str,
+        secret: &ClientSecret,
+    ) -> Result<MachineClient, MachineClientStoreError> {
+        match self.clients.read().await.get(client_id) {
+            Some((client, stored_secret)) if stored_secret == secret => Ok(client.clone()),
+            _ => Err(MachineClientStoreError::InvalidCredentials),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_authenticate_client_checks_secret() {
+        let store = HashMapMachineClientStore::default();
+        let client = MachineClient {
+            client_id: "billing-job".to_owned(),
+            name: "Billing job".to_owned(),
+            allowed_scopes: vec!["invoices:read".to_owned()],
+        };
+        let secret = ClientSecret::default();
+
+        store.save_client(client.clone(), &secret).await.unwrap();
+
+        assert_eq!(
+            store.authenticate_client("billing-job", &secret).await,
+            Ok(client)
+        );
+        assert_eq!(
+            store
+                .authenticate_client("billing-job", &ClientSecret::default())
+                .await,
+            Err(MachineClientStoreError::InvalidCredentials)
+        );
+        assert_eq!(
+            store.authenticate_client("other-job", &secret).await,
+            Err(MachineClientStoreError::InvalidCredentials)
+        );
+    }
+}
@@ -0,0 +1,58 @@
+use std::collections::HashMap;
+
+use tokio::sync::RwLock;
+
+use crate::domain::{OAuthClient, OAuthClientStore, OAuthClientStoreError};
+
+#[derive(Default)]
+pub struct HashMapOAuthClientStore {
+    clients: RwLock<HashMap<String, OAuthClient>>,
+}
+
+#[async_trait::async_trait]
+impl OAuthClientStore for HashMapOAuthClientStore {
+    async fn save_client(&self, client: OAuthClient) -> Result<(), OAuthClientStoreError> {
+        self.clients
+            .write()
+            .await
+            .insert(client.client_id.clone(), client);
+        Ok(())
+    }
+
+    async fn get_client(&self, client_id: &str) -> Result<OAuthClient, OAuthClientStoreError> {
+        self.clients
+            .read()
+            .await
+            .get(client_id)
+            .cloned()
+            .ok_or(OAuthClientStoreError::ClientNotFound)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_save_client_replaces_existing_client() {
+        let store = HashMapOAuthClientStore::default();
+        let client = OAuthClient {
+            client_id: "client".to_owned(),
+            name: "Client".to_owned(),
+            redirect_uris: vec!["https://client.example.com/callback".to_owned()],
+        };
+
+        store.save_client(client.clone()).await.unwrap();
+        let renamed = OAuthClient {
+            name: "Renamed client".to_owned(),
+            ..client
+        };
+        store.save_client(renamed.clone()).await.unwrap();
+
+        assert_eq!(store.get_client("client").await, Ok(renamed));
+        assert_eq!(
+            store.get_client("other").await,
+            Err(OAuthClientStoreError::ClientNotFound)
+        );
+    }
+}
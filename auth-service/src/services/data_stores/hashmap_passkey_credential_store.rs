@@ -0,0 +1,193 @@
+use std::collections::HashMap;
+
+use tokio::sync::RwLock;
+
+use crate::domain::{
+    CredentialId, Email, PasskeyCredential, PasskeyCredentialStore, PasskeyCredentialStoreError,
+};
+
+#[derive(Default)]
+pub struct HashMapPasskeyCredentialStore {
+    credentials: RwLock<HashMap<CredentialId, PasskeyCredential>>,
+}
+
+#[async_trait::async_trait]
+impl PasskeyCredentialStore for HashMapPasskeyCredentialStore {
+    async fn add_credential(
+        &self,
+        credential: PasskeyCredential,
+    ) -> Result<(), PasskeyCredentialStoreError> {
+        let mut credentials = self.credentials.write().await;
+        if credentials.contains_key(&credential.credential_id) {
+            return Err(PasskeyCredentialStoreError::CredentialAlreadyExists);
+        }
+        credentials.insert(credential.credential_id.clone(), credential);
+        Ok(())
+    }
+
+    async fn get_credential(
+        &self,
+        credential_id: &CredentialId,
+    ) -> Result<PasskeyCredential, PasskeyCredentialStoreError> {
+        self.credentials
+            .read()
+            .await
+            .get(credential_id)
+            .cloned()
+            .ok_or(PasskeyCredentialStoreError::CredentialNotFound)
+    }
+
+    async fn get_credentials(
+        &self,
+        email: &Email,
+    ) -> Result<Vec<PasskeyCredential>, PasskeyCredentialStoreError> {
+        Ok(self
+            .credentials
+            .read()
+            .await
+            .values()
+            .filter(|credential| credential.email == *email)
+            .cloned()
+            .collect())
+    }
+
+    async fn update_sign_count(
+        &self,
+        credential_id: &CredentialId,
+        previous_sign_count: u32,
+        sign_count: u32,
+    ) -> Result<(), PasskeyCredentialStoreError> {
+        let mut credentials = self.credentials.write().await;
+        let credential = credentials
+            .get_mut(credential_id)
+            .ok_or(PasskeyCredentialStoreError::CredentialNotFound)?;
+        if credential.sign_count != previous_sign_count {
+            return Err(PasskeyCredentialStoreError::SignCountChanged);
+        }
+        credential.sign_count = sign_count;
+        Ok(())
+    }
+
+    async fn remove_credentials(&self, email: &Email) -> Result<(), PasskeyCredentialStoreError> {
+        self.credentials
+            .write()
+            .await
+            .retain(|_, credential| credential.email != *email);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_credential(id: u8, email: &str) -> PasskeyCredential {
+        PasskeyCredential {
+            credential_id: CredentialId::from(vec![id; 16]),
+            email: Email::parse(email.to_owned()).unwrap(),
+            public_key: vec![4; 65],
+            sign_count: 0,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_add_and_get_credential() {
+        let store = HashMapPasskeyCredentialStore::default();
+        let credential = get_credential(1, "test@example.com");
+
+        store.add_credential(credential.clone()).await.unwrap();
+
+        let result = store.get_credential(&credential.credential_id).await;
+        assert_eq!(result, Ok(credential));
+    }
+
+    #[tokio::test]
+    async fn test_add_duplicate_credential_fails() {
+        let store = HashMapPasskeyCredentialStore::default();
+        let credential = get_credential(1, "test@example.com");
+
+        store.add_credential(credential.clone()).await.unwrap();
+
+        let result = store.add_credential(credential).await;
+        assert_eq!(
+            result,
+            Err(PasskeyCredentialStoreError::CredentialAlreadyExists)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_get_credentials_of_user() {
+        let store = HashMapPasskeyCredentialStore::default();
+        store
+            .add_credential(get_credential(1, "test@example.com"))
+            .await
+            .unwrap();
+        store
+            .add_credential(get_credential(2, "test@example.com"))
+            .await
+            .unwrap();
+        store
+            .add_credential(get_credential(3, "other@example.com"))
+            .await
+            .unwrap();
+
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+        assert_eq!(store.get_credentials(&email).await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_remove_credentials_of_user() {
+        let store = HashMapPasskeyCredentialStore::default();
+        let other_credential = get_credential(2, "other@example.com");
+        store
+            .add_credential(get_credential(1, "test@example.com"))
+            .await
+            .unwrap();
+        store
+            .add_credential(other_credential.clone())
+            .await
+            .unwrap();
+
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+        store.remove_credentials(&email).await.unwrap();
+
+        assert!(store.get_credentials(&email).await.unwrap().is_empty());
+        let result = store.get_credential(&other_credential.credential_id).await;
+        assert_eq!(result, Ok(other_credential));
+    }
+
+    #[tokio::test]
+    async fn test_update_sign_count() {
+        let store = HashMapPasskeyCredentialStore::default();
+        let credential = get_credential(1, "test@example.com");
+        store.add_credential(credential.clone()).await.unwrap();
+
+        store
+            .update_sign_count(&credential.credential_id, 0, 7)
+            .await
+            .unwrap();
+
+        let result = store.get_credential(&credential.credential_id).await;
+        assert_eq!(result.unwrap().sign_count, 7);
+    }
+
+    #[tokio::test]
+    async fn test_update_sign_count_fails_if_count_changed() {
+        let store = HashMapPasskeyCredentialStore::default();
+        let credential = get_credential(1, "test@example.com");
+        store.add_credential(credential.clone()).await.unwrap();
+
+        store
+            .update_sign_count(&credential.credential_id, 0, 7)
+            .await
+            .unwrap();
+
+        let result = store
+            .update_sign_count(&credential.credential_id, 0, 8)
+            .await;
+        assert_eq!(result, Err(PasskeyCredentialStoreError::SignCountChanged));
+
+        let result = store.get_credential(&credential.credential_id).await;
+        assert_eq!(result.unwrap().sign_count, 7);
+    }
+}
@@ -0,0 +1,130 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use tokio::sync::RwLock;
+
+use crate::{
+    domain::{Email, PasswordResetToken, PasswordResetTokenStore, PasswordResetTokenStoreError},
+    utils::constants::PASSWORD_RESET_TOKEN_TTL_SECONDS,
+};
+
+#[derive(Default)]
+pub struct HashMapPasswordResetTokenStore {
+    tokens: RwLock<HashMap<PasswordResetToken, (Email, Instant)>>,
+}
+
+#[async_trait::async_trait]
+impl PasswordResetTokenStore for HashMapPasswordResetTokenStore {
+    async fn add_token(
+        &self,
+        token: PasswordResetToken,
+        email: Email,
+    ) -> Result<(), PasswordResetTokenStoreError> {
+        let mut tokens = self.tokens.write().await;
+
+        // Unused links are dropped here, there is no TTL to clean them up
+        let ttl = Duration::from_secs(PASSWORD_RESET_TOKEN_TTL_SECONDS);
+        tokens.retain(|_, (_, created_at)| created_at.elapsed() < ttl);
+
+        tokens.insert(token, (email, Instant::now()));
+        Ok(())
+    }
+
+    async fn get_token(
+        &self,
+        token: &PasswordResetToken,
+    ) -> Result<Email, PasswordResetTokenStoreError> {
+        let ttl = Duration::from_secs(PASSWORD_RESET_TOKEN_TTL_SECONDS);
+
+        match self.tokens.read().await.get(token) {
+            Some((email, created_at)) if created_at.elapsed() < ttl => Ok(email.clone()),
+            _ => Err(PasswordResetTokenStoreError::TokenNotFound),
+        }
+    }
+
+    async fn take_token(
+        &self,
+        token: &PasswordResetToken,
+    ) -> Result<Email, PasswordResetTokenStoreError> {
+        let ttl = Duration::from_secs(PASSWORD_RESET_TOKEN_TTL_SECONDS);
+
+        match self.tokens.write().await.remove(token) {
+            Some((email, created_at)) if created_at.elapsed() < ttl => Ok(email),
+            _ => Err(PasswordResetTokenStoreError::TokenNotFound),
+        }
+    }
+
+    async fn remove_tokens(&self, email: &Email) -> Result<(), PasswordResetTokenStoreError> {
+        self.tokens
+            .write()
+            .await
+            .retain(|_, (owner, _)| owner != email);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_token_can_only_be_taken_once() {
+        let store = HashMapPasswordResetTokenStore::default();
+        let token = PasswordResetToken::default();
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+
+        store.add_token(token.clone(), email.clone()).await.unwrap();
+
+        assert_eq!(store.take_token(&token).await, Ok(email));
+        assert_eq!(
+            store.take_token(&token).await,
+            Err(PasswordResetTokenStoreError::TokenNotFound)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_get_token_leaves_token_usable() {
+        let store = HashMapPasswordResetTokenStore::default();
+        let token = PasswordResetToken::default();
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+
+        store.add_token(token.clone(), email.clone()).await.unwrap();
+
+        assert_eq!(store.get_token(&token).await, Ok(email.clone()));
+        assert_eq!(store.take_token(&token).await, Ok(email));
+    }
+
+    #[tokio::test]
+    async fn test_remove_tokens_of_owner() {
+        let store = HashMapPasswordResetTokenStore::default();
+        let token = PasswordResetToken::default();
+        let other_token = PasswordResetToken::default();
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+        let other_email = Email::parse("other@example.com".to_owned()).unwrap();
+
+        store.add_token(token.clone(), email.clone()).await.unwrap();
+        store
+            .add_token(other_token.clone(), other_email.clone())
+            .await
+            .unwrap();
+        store.remove_tokens(&email).await.unwrap();
+
+        assert_eq!(
+            store.get_token(&token).await,
+            Err(PasswordResetTokenStoreError::TokenNotFound)
+        );
+        assert_eq!(store.get_token(&other_token).await, Ok(other_email));
+    }
+
+    #[tokio::test]
+    async fn test_take_unknown_token_fails() {
+        let store = HashMapPasswordResetTokenStore::default();
+
+        assert_eq!(
+            store.take_token(&PasswordResetToken::default()).await,
+            Err(PasswordResetTokenStoreError::TokenNotFound)
+        );
+    }
+}
@@ -0,0 +1,162 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Weak},
+    time::{Duration, Instant},
+};
+
+use tokio::{sync::RwLock, task::JoinHandle};
+
+use crate::domain::{RateLimitStore, RateLimitStoreError, TokenBucket};
+
+// Tokens left, when they were counted and when the bucket will be full again
+type Buckets = RwLock<HashMap<String, (f64, Instant, Instant)>>;
+
+#[derive(Default)]
+pub struct HashMapRateLimitStore {
+    buckets: Arc<Buckets>,
+}
+
+impl HashMapRateLimitStore {
+    // Drop full buckets every `interval`, they behave just like missing ones. The task ends once
+    // the store is dropped.
+    pub fn spawn_sweeper(&self, interval: Duration) -> JoinHandle<()> {
+        let buckets = Arc::downgrade(&self.buckets);
+
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+
+            loop {
+                ticker.tick().await;
+
+                if !sweep(&buckets).await {
+                    break;
+                }
+            }
+        })
+    }
+}
+
+// Returns false once the store is gone
+async fn sweep(buckets: &Weak<Buckets>) -> bool {
+    let Some(buckets) = buckets.upgrade() else {
+        return false;
+    };
+
+    let now = Instant::now();
+    buckets
+        .write()
+        .await
+        .retain(|_, (_, _, full_at)| *full_at > now);
+    true
+}
+
+#[async_trait::async_trait]
+impl RateLimitStore for HashMapRateLimitStore {
+    async fn take_token(
+        &self,
+        key: &str,
+        bucket: &TokenBucket,
+    ) -> Result<Option<Duration>, RateLimitStoreError> {
+        let now = Instant::now();
+        let mut buckets = self.buckets.write().await;
+
+        let capacity = f64::from(bucket.capacity);
+        let refill_interval = bucket.refill_interval().as_secs_f64();
+
+        let (tokens, counted_at, full_at) = buckets
+            .entry(key.to_owned())
+            .or_insert((capacity, now, now));
+
+        let refilled = now.duration_since(*counted_at).as_secs_f64() / refill_interval;
+        *tokens = (*tokens + refilled).min(capacity);
+        *counted_at = now;
+
+        let wait = if *tokens >= 1.0 {
+            *tokens -= 1.0;
+            None
+        } else {
+            Some(Duration::from_secs_f64((1.0 - *tokens) * refill_interval))
+        };
+
+        *full_at = now + Duration::from_secs_f64((capacity - *tokens) * refill_interval);
+
+        Ok(wait)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_bucket_empties_after_capacity_requests() {
+        let store = HashMapRateLimitStore::default();
+        let bucket = TokenBucket {
+            capacity: 3,
+            period: Duration::from_secs(60),
+        };
+
+        for _ in 0..3 {
+            assert_eq!(store.take_token("key", &bucket).await, Ok(None));
+        }
+
+        let wait = store.take_token("key", &bucket).await.unwrap().unwrap();
+        assert!(wait > Duration::ZERO && wait <= Duration::from_secs(20));
+
+        // Buckets are kept apart by their key
+        assert_eq!(store.take_token("other", &bucket).await, Ok(None));
+    }
+
+    #[tokio::test]
+    async fn test_bucket_refills_over_time() {
+        let store = HashMapRateLimitStore::default();
+        let bucket = TokenBucket {
+            capacity: 1,
+            period: Duration::from_millis(20),
+        };
+
+        assert_eq!(store.take_token("key", &bucket).await, Ok(None));
+        assert!(store.take_token("key", &bucket).await.unwrap().is_some());
+
+        tokio::time::sleep(Duration::from_millis(30)).await;
+
+        assert_eq!(store.take_token("key", &bucket).await, Ok(None));
+    }
+
+    #[tokio::test]
+    async fn test_sweep_drops_full_buckets() {
+        let store = HashMapRateLimitStore::default();
+        let quick = TokenBucket {
+            capacity: 1,
+            period: Duration::from_millis(10),
+        };
+        let slow = TokenBucket {
+            capacity: 1,
+            period: Duration::from_secs(60),
+        };
+
+        store.take_token("quick", &quick).await.unwrap();
+        store.take_token("slow", &slow).await.unwrap();
+
+        tokio::time::sleep(Duration::from_millis(20)).await;
+
+        assert!(sweep(&Arc::downgrade(&store.buckets)).await);
+
+        let buckets = store.buckets.read().await;
+        assert_eq!(buckets.len(), 1);
+        assert!(buckets.contains_key("slow"));
+    }
+
+    #[tokio::test]
+    async fn test_sweeper_stops_with_store() {
+        let store = HashMapRateLimitStore::default();
+        let sweeper = store.spawn_sweeper(Duration::from_millis(10));
+
+        drop(store);
+
+        tokio::time::timeout(Duration::from_secs(1), sweeper)
+            .await
+            .expect("Sweeper did not stop")
+            .unwrap();
+    }
+}
@@ -0,0 +1,112 @@
+use std::collections::{HashMap, HashSet};
+
+use tokio::sync::RwLock;
+
+use crate::domain::{Email, RecoveryCode, RecoveryCodeStore, RecoveryCodeStoreError};
+
+#[derive(Default)]
+pub struct HashMapRecoveryCodeStore {
+    codes: RwLock<HashMap<Email, HashSet<RecoveryCode>>>,
+}
+
+#[async_trait::async_trait]
+impl RecoveryCodeStore for HashMapRecoveryCodeStore {
+    async fn replace_codes(
+        &self,
+        email: &Email,
+        codes: Vec<RecoveryCode>,
+    ) -> Result<(), RecoveryCodeStoreError> {
+        self.codes
+            .write()
+            .await
+            .insert(email.clone(), codes.into_iter().collect());
+        Ok(())
+    }
+
+    async fn use_code(
+        &self,
+        email: &Email,
+        code: &RecoveryCode,
+    ) -> Result<(), RecoveryCodeStoreError> {
+        let removed = self
+            .codes
+            .write()
+            .await
+            .get_mut(email)
+            .is_some_and(|codes| codes.remove(code));
+
+        match removed {
+            true => Ok(()),
+            false => Err(RecoveryCodeStoreError::InvalidCode),
+        }
+    }
+
+    async fn count_codes(&self, email: &Email) -> Result<usize, RecoveryCodeStoreError> {
+        Ok(self.codes.read().await.get(email).map_or(0, HashSet::len))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_email() -> Email {
+        Email::parse("test@example.com".to_owned()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_code_can_only_be_used_once() {
+        let store = HashMapRecoveryCodeStore::default();
+        let email = get_email();
+        let code = RecoveryCode::default();
+
+        store
+            .replace_codes(&email, vec![code.clone(), RecoveryCode::default()])
+            .await
+            .unwrap();
+
+        assert!(store.use_code(&email, &code).await.is_ok());
+        assert_eq!(
+            store.use_code(&email, &code).await,
+            Err(RecoveryCodeStoreError::InvalidCode)
+        );
+        assert_eq!(store.count_codes(&email).await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_replace_codes_invalidates_previous_batch() {
+        let store = HashMapRecoveryCodeStore::default();
+        let email = get_email();
+        let old_code = RecoveryCode::default();
+
+        store
+            .replace_codes(&email, vec![old_code.clone()])
+            .await
+            .unwrap();
+        store
+            .replace_codes(&email, vec![RecoveryCode::default()])
+            .await
+            .unwrap();
+
+        assert_eq!(
+            store.use_code(&email, &old_code).await,
+            Err(RecoveryCodeStoreError::InvalidCode)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_use_code_for_unknown_user() {
+        let store = HashMapRecoveryCodeStore::default();
+        let result = store.use_code(&get_email(), &RecoveryCode::default()).await;
+        assert_eq!(result, Err(RecoveryCodeStoreError::InvalidCode));
+    }
+
+    #[test]
+    fn test_parse_normalizes_code() {
+        let code = RecoveryCode::default();
+        let typed = code.as_ref().replace('-', "").to_uppercase();
+        assert_eq!(RecoveryCode::parse(&typed).unwrap(), code);
+        assert!(RecoveryCode::parse("abc").is_err());
+        assert!(RecoveryCode::parse("00000-11111").is_err());
+    }
+}
@@ -0,0 +1,283 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Weak},
+    time::Duration,
+};
+
+use chrono::Utc;
+use tokio::{sync::RwLock, task::JoinHandle};
+use uuid::Uuid;
+
+use crate::domain::{
+    Email, RefreshToken, RefreshTokenRecord, RefreshTokenStore, RefreshTokenStoreError,
+};
+
+// Each token along with whether it has been used up
+type RefreshTokens = RwLock<HashMap<RefreshToken, (RefreshTokenRecord, bool)>>;
+
+#[derive(Default)]
+pub struct HashMapRefreshTokenStore {
+    tokens: Arc<RefreshTokens>,
+}
+
+impl HashMapRefreshTokenStore {
+    // Drop expired tokens every `interval`, used or not they are useless. The task ends once the
+    // store is dropped.
+    pub fn spawn_sweeper(&self, interval: Duration) -> JoinHandle<()> {
+        let tokens = Arc::downgrade(&self.tokens);
+
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+
+            loop {
+                ticker.tick().await;
+
+                if !sweep(&tokens).await {
+                    break;
+                }
+            }
+        })
+    }
+}
+
+// Returns false once the store is gone
+async fn sweep(tokens: &Weak<RefreshTokens>) -> bool {
+    let Some(tokens) = tokens.upgrade() else {
+        return false;
+    };
+
+    let now = Utc::now();
+    tokens
+        .write()
+        .await
+        .retain(|_, (record, _)| record.expires_at > now);
+    true
+}
+
+#[async_trait::async_trait]
+impl RefreshTokenStore for HashMapRefreshTokenStore {
+    async fn add_token(
+        &self,
+        token: RefreshToken,
+        record: RefreshTokenRecord,
+    ) -> Result<(), RefreshTokenStoreError> {
+        self.tokens.write().await.insert(token, (record, false));
+        Ok(())
+    }
+
+    async fn get_token(
+        &self,
+        token: &RefreshToken,
+    ) -> Result<RefreshTokenRecord, RefreshTokenStoreError> {
+        // Expired tokens may linger until the next sweep
+        match self.tokens.read().await.get(token) {
+            Some((record, _)) if record.expires_at > Utc::now() => Ok(record.clone()),
+            _ => Err(RefreshTokenStoreError::TokenNotFound),
+        }
+    }
+
+    async fn replace_token(
+        &self,
+        token: &RefreshToken,
+        new_token: RefreshToken,
+    ) -> Result<RefreshTokenRecord, RefreshTokenStoreError> {
+        let mut tokens = self.tokens.write().await;
+
+        let record = match tokens.get_mut(token) {
+            Some((record, _)) if record.expires_at <= Utc::now() => {
+                return Err(RefreshTokenStoreError::TokenNotFound)
+            }
+            Some((record, true)) => {
+                return Err(RefreshTokenStoreError::TokenReused(record.family_id))
+            }
+            Some((record, used)) => {
+                *used = true;
+                record.clone()
+            }
+            None => return Err(RefreshTokenStoreError::TokenNotFound),
+        };
+
+        tokens.insert(new_token, (record.clone(), false));
+        Ok(record)
+    }
+
+    async fn revoke_family(&self, family_id: &Uuid) -> Result<(), RefreshTokenStoreError> {
+        self.tokens
+            .write()
+            .await
+            .retain(|_, (record, _)| record.family_id != *family_id);
+        Ok(())
+    }
+
+    async fn revoke_all(&self, email: &Email) -> Result<(), RefreshTokenStoreError> {
+        self.tokens
+            .write()
+            .await
+            .retain(|_, (record, _)| record.email != *email);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_record(family_id: Uuid) -> RefreshTokenRecord {
+        RefreshTokenRecord {
+            family_id,
+            email: Email::parse("test@example.org".to_owned()).unwrap(),
+            expires_at: Utc::now() + chrono::Duration::hours(1),
+        }
+    }
+
+    #[tokio::test]
+    async fn should_replace_token_only_once() {
+        let store = HashMapRefreshTokenStore::default();
+        let token = RefreshToken::default();
+        let next = RefreshToken::default();
+        let record = get_record(Uuid::new_v4());
+
+        store
+            .add_token(token.clone(), record.clone())
+            .await
+            .unwrap();
+
+        // The successor keeps the expiry of the family
+        assert_eq!(
+            store.replace_token(&token, next.clone()).await,
+            Ok(record.clone())
+        );
+        assert_eq!(store.get_token(&next).await, Ok(record.clone()));
+        assert_eq!(
+            store.replace_token(&token, RefreshToken::default()).await,
+            Err(RefreshTokenStoreError::TokenReused(record.family_id))
+        );
+    }
+
+    #[tokio::test]
+    async fn should_return_not_found_for_unknown_token() {
+        let store = HashMapRefreshTokenStore::default();
+        let token = RefreshToken::default();
+
+        assert_eq!(
+            store.replace_token(&token, RefreshToken::default()).await,
+            Err(RefreshTokenStoreError::TokenNotFound)
+        );
+    }
+
+    #[tokio::test]
+    async fn should_not_find_expired_token_before_sweep() {
+        let store = HashMapRefreshTokenStore::default();
+        let expired = RefreshToken::default();
+        let record = RefreshTokenRecord {
+            expires_at: Utc::now() - chrono::Duration::seconds(1),
+            ..get_record(Uuid::new_v4())
+        };
+
+        store.add_token(expired.clone(), record).await.unwrap();
+
+        assert_eq!(
+            store.get_token(&expired).await,
+            Err(RefreshTokenStoreError::TokenNotFound)
+        );
+        assert_eq!(
+            store.replace_token(&expired, RefreshToken::default()).await,
+            Err(RefreshTokenStoreError::TokenNotFound)
+        );
+    }
+
+    #[tokio::test]
+    async fn should_sweep_expired_tokens() {
+        let store = HashMapRefreshTokenStore::default();
+        let expired = RefreshToken::default();
+        let valid = RefreshToken::default();
+        let record = RefreshTokenRecord {
+            expires_at: Utc::now() - chrono::Duration::seconds(1),
+            ..get_record(Uuid::new_v4())
+        };
+
+        store.add_token(expired.clone(), record).await.unwrap();
+        store
+            .add_token(valid.clone(), get_record(Uuid::new_v4()))
+            .await
+            .unwrap();
+
+        assert!(sweep(&Arc::downgrade(&store.tokens)).await);
+
+        let tokens = store.tokens.read().await;
+        assert_eq!(tokens.len(), 1);
+        assert!(tokens.contains_key(&valid));
+    }
+
+    #[tokio::test]
+    async fn should_stop_sweeper_with_store() {
+        let store = HashMapRefreshTokenStore::default();
+        let sweeper = store.spawn_sweeper(Duration::from_millis(10));
+
+        drop(store);
+
+        tokio::time::timeout(Duration::from_secs(1), sweeper)
+            .await
+            .expect("Sweeper did not stop")
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn should_revoke_whole_family() {
+        let store = HashMapRefreshTokenStore::default();
+        let family_id = Uuid::new_v4();
+        let first = RefreshToken::default();
+        let second = RefreshToken::default();
+        let other = RefreshToken::default();
+
+        store
+            .add_token(first.clone(), get_record(family_id))
+            .await
+            .unwrap();
+        store
+            .add_token(second.clone(), get_record(family_id))
+            .await
+            .unwrap();
+        store
+            .add_token(other.clone(), get_record(Uuid::new_v4()))
+            .await
+            .unwrap();
+
+        store.revoke_family(&family_id).await.unwrap();
+
+        assert!(store.get_token(&first).await.is_err());
+        assert!(store.get_token(&second).await.is_err());
+        assert!(store.get_token(&other).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn should_revoke_all_tokens_of_user() {
+        let store = HashMapRefreshTokenStore::default();
+        let first = RefreshToken::default();
+        let second = RefreshToken::default();
+        let other = RefreshToken::default();
+        let other_record = RefreshTokenRecord {
+            email: Email::parse("other@example.org".to_owned()).unwrap(),
+            ..get_record(Uuid::new_v4())
+        };
+
+        store
+            .add_token(first.clone(), get_record(Uuid::new_v4()))
+            .await
+            .unwrap();
+        store
+            .add_token(second.clone(), get_record(Uuid::new_v4()))
+            .await
+            .unwrap();
+        store.add_token(other.clone(), other_record).await.unwrap();
+
+        store
+            .revoke_all(&Email::parse("test@example.org".to_owned()).unwrap())
+            .await
+            .unwrap();
+
+        assert!(store.get_token(&first).await.is_err());
+        assert!(store.get_token(&second).await.is_err());
+        assert!(store.get_token(&other).await.is_ok());
+    }
+}
@@ -0,0 +1,135 @@
+use std::{collections::HashMap, net::IpAddr};
+
+use chrono::Utc;
+use tokio::sync::RwLock;
+use uuid::Uuid;
+
+use crate::domain::{Email, Session, SessionStore, SessionStoreError};
+
+#[derive(Default)]
+pub struct HashMapSessionStore {
+    sessions: RwLock<HashMap<Uuid, Session>>,
+}
+
+#[async_trait::async_trait]
+impl SessionStore for HashMapSessionStore {
+    async fn add_session(&self, session: Session) -> Result<(), SessionStoreError> {
+        self.sessions.write().await.insert(session.id, session);
+        Ok(())
+    }
+
+    async fn get_session(&self, id: &Uuid) -> Result<Session, SessionStoreError> {
+        self.sessions
+            .read()
+            .await
+            .get(id)
+            .cloned()
+            .ok_or(SessionStoreError::SessionNotFound)
+    }
+
+    async fn get_sessions(&self, email: &Email) -> Result<Vec<Session>, SessionStoreError> {
+        let mut sessions: Vec<Session> = self
+            .sessions
+            .read()
+            .await
+            .values()
+            .filter(|session| session.email == *email)
+            .cloned()
+            .collect();
+        sessions.sort_by_key(|session| std::cmp::Reverse(session.last_seen_at));
+        Ok(sessions)
+    }
+
+    async fn touch_session(
+        &self,
+        id: &Uuid,
+        ip_address: Option<IpAddr>,
+    ) -> Result<(), SessionStoreError> {
+        let mut sessions = self.sessions.write().await;
+        let session = sessions
+            .get_mut(id)
+            .ok_or(SessionStoreError::SessionNotFound)?;
+        session.last_seen_at = Utc::now();
+        session.ip_address = ip_address.or(session.ip_address);
+        Ok(())
+    }
+
+    async fn remove_session(&self, email: &Email, id: &Uuid) -> Result<(), SessionStoreError> {
+        let mut sessions = self.sessions.write().await;
+        match sessions.get(id) {
+            Some(session) if session.email == *email => {
+                sessions.remove(id);
+                Ok(())
+            }
+            _ => Err(SessionStoreError::SessionNotFound),
+        }
+    }
+
+    async fn remove_sessions(&self, email: &Email) -> Result<(), SessionStoreError> {
+        self.sessions
+            .write()
+            .await
+            .retain(|_, session| session.email != *email);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_email(i: u8) -> Email {
+        Email::parse(format!("user{}@example.com", i)).unwrap()
+    }
+
+    #[tokio::test]
+    async fn should_list_sessions_of_user() {
+        let store = HashMapSessionStore::default();
+        let session = Session::new(get_email(1), None, None);
+        store.add_session(session.clone()).await.unwrap();
+        store
+            .add_session(Session::new(get_email(2), None, None))
+            .await
+            .unwrap();
+
+        assert_eq!(store.get_sessions(&get_email(1)).await, Ok(vec![session]));
+    }
+
+    #[tokio::test]
+    async fn should_only_remove_session_of_its_owner() {
+        let store = HashMapSessionStore::default();
+        let session = Session::new(get_email(1), None, None);
+        store.add_session(session.clone()).await.unwrap();
+
+        assert_eq!(
+            store.remove_session(&get_email(2), &session.id).await,
+            Err(SessionStoreError::SessionNotFound)
+        );
+        assert!(store.get_session(&session.id).await.is_ok());
+
+        store
+            .remove_session(&get_email(1), &session.id)
+            .await
+            .unwrap();
+        assert_eq!(
+            store.get_session(&session.id).await,
+            Err(SessionStoreError::SessionNotFound)
+        );
+    }
+
+    #[tokio::test]
+    async fn should_remove_all_sessions_of_user() {
+        let store = HashMapSessionStore::default();
+        for email in [get_email(1), get_email(1), get_email(2)] {
+            store
+                .add_session(Session::new(email, None, None))
+                .await
+                .unwrap();
+        }
+
+        store.remove_sessions(&get_email(1)).await.unwrap();
+
+        assert!(store.get_sessions(&get_email(1)).await.unwrap().is_empty());
+        assert_eq!(store.get_sessions(&get_email(2)).await.unwrap().len(), 1);
+    }
+}
@@ -2,20 +2,57 @@ use std::{collections::HashMap, sync::Arc};
 
 use tokio::sync::RwLock;
 
-use crate::domain::{Email, Password, User, UserStore, UserStoreError};
+use crate::domain::{
+    Email, Password, PasswordHash, PasswordHashError, PasswordHashParams, TotpSecret, TwoFAMethod,
+    User, UserStore, UserStoreError,
+};
 
 #[derive(Default)]
 pub struct HashMapUserStore {
     users: Arc<RwLock<HashMap<Email, User>>>,
+    password_hashes: Arc<RwLock<HashMap<Email, PasswordHash>>>,
+    totp_secrets: Arc<RwLock<HashMap<Email, TotpSecret>>>,
+    pending_totp_secrets: Arc<RwLock<HashMap<Email, TotpSecret>>>,
+    // Users without an entry are still at version 0
+    token_versions: Arc<RwLock<HashMap<Email, u32>>>,
+    hash_params: PasswordHashParams,
+}
+
+impl HashMapUserStore {
+    pub fn new(hash_params: PasswordHashParams) -> Self {
+        Self {
+            hash_params,
+            ..Default::default()
+        }
+    }
 }
 
 #[async_trait::async_trait]
 impl UserStore for HashMapUserStore {
-    async fn add_user(&mut self, user: User) -> Result<(), UserStoreError> {
-        if self.users.read().await.contains_key(&user.email) {
+    async fn add_user(&self, user: User, password: Password) -> Result<(), UserStoreError> {
+        // Hashed like in Postgres, so tests against this store see the same behaviour
+        let password_hash = PasswordHash::compute(password.as_ref(), &self.hash_params)
+            .await
+            .map_err(|_| UserStoreError::UnexpectedError)?;
+
+        self.import_user(user, password_hash).await
+    }
+
+    async fn import_user(
+        &self,
+        user: User,
+        password_hash: PasswordHash,
+    ) -> Result<(), UserStoreError> {
+        // Checked under the write lock, so two signups for the same email can not both succeed
+        let mut users = self.users.write().await;
+        if users.contains_key(&user.email) {
             return Err(UserStoreError::UserAlreadyExists);
         }
-        self.users.write().await.insert(user.email.clone(), user);
+        self.password_hashes
+            .write()
+            .await
+            .insert(user.email.clone(), password_hash);
+        users.insert(user.email.clone(), user);
         Ok(())
     }
 
@@ -31,11 +68,204 @@ impl UserStore for HashMapUserStore {
         email: &Email,
         password: &Password,
     ) -> Result<User, UserStoreError> {
-        match self.get_user(email).await {
-            Ok(user) if user.password == *password => Ok(user),
-            Ok(_) => Err(UserStoreError::InvalidCredentials),
-            Err(e) => Err(e),
+        let password_hash = self
+            .password_hashes
+            .read()
+            .await
+            .get(email)
+            .cloned()
+            .ok_or(UserStoreError::UserNotFound)?;
+
+        password_hash
+            .verify(password.as_ref())
+            .await
+            .map_err(|e| match e {
+                PasswordHashError::Mismatch => UserStoreError::InvalidCredentials,
+                PasswordHashError::UnexpectedError => UserStoreError::UnexpectedError,
+            })?;
+
+        if password_hash.needs_rehash(&self.hash_params) {
+            let new_hash = PasswordHash::compute(password.as_ref(), &self.hash_params)
+                .await
+                .map_err(|_| UserStoreError::UnexpectedError)?;
+
+            // Only replaces the hash that was checked, a password changed in the meantime is kept
+            let mut password_hashes = self.password_hashes.write().await;
+            if password_hashes.get(email) == Some(&password_hash) {
+                password_hashes.insert(email.clone(), new_hash);
+            }
+        }
+
+        self.get_user(email).await
+    }
+
+    async fn set_pending_totp_secret(
+        &self,
+        email: &Email,
+        secret: TotpSecret,
+    ) -> Result<(), UserStoreError> {
+        if !self.users.read().await.contains_key(email) {
+            return Err(UserStoreError::UserNotFound);
+        }
+        self.pending_totp_secrets
+            .write()
+            .await
+            .insert(email.clone(), secret);
+        Ok(())
+    }
+
+    async fn get_pending_totp_secret(&self, email: &Email) -> Result<TotpSecret, UserStoreError> {
+        if !self.users.read().await.contains_key(email) {
+            return Err(UserStoreError::UserNotFound);
+        }
+        self.pending_totp_secrets
+            .read()
+            .await
+            .get(email)
+            .cloned()
+            .ok_or(UserStoreError::TotpNotEnrolled)
+    }
+
+    async fn confirm_totp_secret(
+        &self,
+        email: &Email,
+        secret: TotpSecret,
+    ) -> Result<(), UserStoreError> {
+        let mut users = self.users.write().await;
+        let user = users.get_mut(email).ok_or(UserStoreError::UserNotFound)?;
+        user.two_fa_method = TwoFAMethod::Totp;
+        user.requires_2fa = true;
+
+        self.pending_totp_secrets.write().await.remove(email);
+        self.totp_secrets
+            .write()
+            .await
+            .insert(email.clone(), secret);
+        Ok(())
+    }
+
+    async fn get_totp_secret(&self, email: &Email) -> Result<TotpSecret, UserStoreError> {
+        if !self.users.read().await.contains_key(email) {
+            return Err(UserStoreError::UserNotFound);
         }
+        self.totp_secrets
+            .read()
+            .await
+            .get(email)
+            .cloned()
+            .ok_or(UserStoreError::TotpNotEnrolled)
+    }
+
+    async fn set_two_fa_method(
+        &self,
+        email: &Email,
+        method: TwoFAMethod,
+    ) -> Result<(), UserStoreError> {
+        if method == TwoFAMethod::Totp {
+            self.get_totp_secret(email).await?;
+        }
+
+        let mut users = self.users.write().await;
+        let user = users.get_mut(email).ok_or(UserStoreError::UserNotFound)?;
+        user.two_fa_method = method;
+        // Picking an authenticator app is an explicit opt-in to 2FA
+        user.requires_2fa |= method == TwoFAMethod::Totp;
+        Ok(())
+    }
+
+    async fn update_password(
+        &self,
+        email: &Email,
+        password: Password,
+    ) -> Result<(), UserStoreError> {
+        let password_hash = PasswordHash::compute(password.as_ref(), &self.hash_params)
+            .await
+            .map_err(|_| UserStoreError::UnexpectedError)?;
+
+        if !self.users.read().await.contains_key(email) {
+            return Err(UserStoreError::UserNotFound);
+        }
+        self.password_hashes
+            .write()
+            .await
+            .insert(email.clone(), password_hash);
+        Ok(())
+    }
+
+    async fn set_email_verified(&self, email: &Email) -> Result<(), UserStoreError> {
+        let mut users = self.users.write().await;
+        let user = users.get_mut(email).ok_or(UserStoreError::UserNotFound)?;
+        user.email_verified = true;
+        Ok(())
+    }
+
+    async fn update_email(&self, email: &Email, new_email: &Email) -> Result<(), UserStoreError> {
+        let mut users = self.users.write().await;
+        if users.contains_key(new_email) {
+            return Err(UserStoreError::UserAlreadyExists);
+        }
+
+        let mut user = users.remove(email).ok_or(UserStoreError::UserNotFound)?;
+        user.email = new_email.clone();
+        user.email_verified = true;
+        users.insert(new_email.clone(), user);
+
+        let mut password_hashes = self.password_hashes.write().await;
+        if let Some(password_hash) = password_hashes.remove(email) {
+            password_hashes.insert(new_email.clone(), password_hash);
+        }
+
+        let mut totp_secrets = self.totp_secrets.write().await;
+        if let Some(secret) = totp_secrets.remove(email) {
+            totp_secrets.insert(new_email.clone(), secret);
+        }
+
+        let mut pending_totp_secrets = self.pending_totp_secrets.write().await;
+        if let Some(secret) = pending_totp_secrets.remove(email) {
+            pending_totp_secrets.insert(new_email.clone(), secret);
+        }
+
+        let mut token_versions = self.token_versions.write().await;
+        if let Some(version) = token_versions.remove(email) {
+            token_versions.insert(new_email.clone(), version);
+        }
+        Ok(())
+    }
+
+    async fn delete_user(&self, email: &Email) -> Result<(), UserStoreError> {
+        self.users
+            .write()
+            .await
+            .remove(email)
+            .ok_or(UserStoreError::UserNotFound)?;
+        self.password_hashes.write().await.remove(email);
+        self.totp_secrets.write().await.remove(email);
+        self.pending_totp_secrets.write().await.remove(email);
+        self.token_versions.write().await.remove(email);
+        Ok(())
+    }
+
+    async fn get_token_version(&self, email: &Email) -> Result<u32, UserStoreError> {
+        if !self.users.read().await.contains_key(email) {
+            return Err(UserStoreError::UserNotFound);
+        }
+        Ok(self
+            .token_versions
+            .read()
+            .await
+            .get(email)
+            .copied()
+            .unwrap_or_default())
+    }
+
+    async fn bump_token_version(&self, email: &Email) -> Result<u32, UserStoreError> {
+        if !self.users.read().await.contains_key(email) {
+            return Err(UserStoreError::UserNotFound);
+        }
+        let mut token_versions = self.token_versions.write().await;
+        let version = token_versions.entry(email.clone()).or_default();
+        *version += 1;
+        Ok(*version)
     }
 }
 
@@ -53,25 +283,25 @@ mod tests {
     }
 
     async fn setup() -> HashMapUserStore {
-        let mut hm = HashMapUserStore::default();
-        let user = User::new(get_valid_email(1), get_valid_password(), false);
-        let _ = hm.add_user(user).await;
+        let hm = HashMapUserStore::default();
+        let user = User::new(get_valid_email(1), false);
+        let _ = hm.add_user(user, get_valid_password()).await;
         hm
     }
 
     #[tokio::test]
     async fn test_add_user_succeed() {
-        let mut store = setup().await;
-        let user = User::new(get_valid_email(2), get_valid_password(), false);
-        let result = store.add_user(user).await;
+        let store = setup().await;
+        let user = User::new(get_valid_email(2), false);
+        let result = store.add_user(user, get_valid_password()).await;
         assert!(result.is_ok());
     }
 
     #[tokio::test]
     async fn test_add_user_fail_user_already_exists() {
-        let user = User::new(get_valid_email(1), get_valid_password(), false);
-        let mut store = setup().await;
-        let result = store.add_user(user).await;
+        let user = User::new(get_valid_email(1), false);
+        let store = setup().await;
+        let result = store.add_user(user, get_valid_password()).await;
         assert!(matches!(result, Err(UserStoreError::UserAlreadyExists)));
     }
 
@@ -118,4 +348,224 @@ mod tests {
             .await;
         assert!(matches!(result, Err(UserStoreError::InvalidCredentials)));
     }
+
+    #[tokio::test]
+    async fn test_validate_user_rehashes_outdated_hash() {
+        let mut store = setup().await;
+        let email = get_valid_email(1);
+        store.hash_params = PasswordHashParams {
+            iterations: 3,
+            ..Default::default()
+        };
+
+        let old_hash = store.password_hashes.read().await[&email].clone();
+        assert!(old_hash.needs_rehash(&store.hash_params));
+
+        store
+            .validate_user(&email, &get_valid_password())
+            .await
+            .unwrap();
+
+        let new_hash = store.password_hashes.read().await[&email].clone();
+        assert!(!new_hash.needs_rehash(&store.hash_params));
+        assert!(store
+            .validate_user(&email, &get_valid_password())
+            .await
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_import_user_upgrades_legacy_hash_on_login() {
+        let store = setup().await;
+        let email = get_valid_email(3);
+        let legacy_hash = bcrypt::hash(get_valid_password().as_ref(), 4).unwrap();
+
+        store
+            .import_user(
+                User::new(email.clone(), false),
+                PasswordHash::parse(legacy_hash).unwrap(),
+            )
+            .await
+            .unwrap();
+        assert!(store.password_hashes.read().await[&email].needs_rehash(&store.hash_params));
+
+        store
+            .validate_user(&email, &get_valid_password())
+            .await
+            .unwrap();
+
+        let new_hash = store.password_hashes.read().await[&email].clone();
+        assert!(!new_hash.needs_rehash(&store.hash_params));
+    }
+
+    #[tokio::test]
+    async fn test_get_totp_secret_fail_when_not_enrolled() {
+        let store = setup().await;
+        let result = store.get_totp_secret(&get_valid_email(1)).await;
+        assert!(matches!(result, Err(UserStoreError::TotpNotEnrolled)));
+    }
+
+    #[tokio::test]
+    async fn test_set_two_fa_method_totp_fail_when_not_enrolled() {
+        let store = setup().await;
+        let result = store
+            .set_two_fa_method(&get_valid_email(1), TwoFAMethod::Totp)
+            .await;
+        assert!(matches!(result, Err(UserStoreError::TotpNotEnrolled)));
+    }
+
+    #[tokio::test]
+    async fn test_confirm_totp_secret_enables_2fa() {
+        let store = setup().await;
+        let email = get_valid_email(1);
+        let secret = TotpSecret::default();
+        store
+            .set_pending_totp_secret(&email, secret.clone())
+            .await
+            .unwrap();
+        assert!(store.get_pending_totp_secret(&email).await.unwrap() == secret);
+
+        store
+            .confirm_totp_secret(&email, secret.clone())
+            .await
+            .unwrap();
+
+        assert!(store.get_totp_secret(&email).await.unwrap() == secret);
+        let result = store.get_pending_totp_secret(&email).await;
+        assert!(matches!(result, Err(UserStoreError::TotpNotEnrolled)));
+
+        let user = store.get_user(&email).await.unwrap();
+        assert_eq!(user.two_fa_method, TwoFAMethod::Totp);
+        assert!(user.requires_2fa);
+    }
+
+    #[tokio::test]
+    async fn test_pending_totp_secret_keeps_confirmed_secret() {
+        let store = setup().await;
+        let email = get_valid_email(1);
+        let secret = TotpSecret::default();
+        store
+            .confirm_totp_secret(&email, secret.clone())
+            .await
+            .unwrap();
+
+        store
+            .set_pending_totp_secret(&email, TotpSecret::default())
+            .await
+            .unwrap();
+
+        assert!(store.get_totp_secret(&email).await.unwrap() == secret);
+        let user = store.get_user(&email).await.unwrap();
+        assert_eq!(user.two_fa_method, TwoFAMethod::Totp);
+    }
+
+    #[tokio::test]
+    async fn test_update_password_replaces_old_password() {
+        let store = setup().await;
+        let email = get_valid_email(1);
+        let new_password = Password::parse("newpassword123".to_owned()).unwrap();
+
+        store
+            .update_password(&email, new_password.clone())
+            .await
+            .unwrap();
+
+        assert!(store.validate_user(&email, &new_password).await.is_ok());
+        let result = store.validate_user(&email, &get_valid_password()).await;
+        assert!(matches!(result, Err(UserStoreError::InvalidCredentials)));
+    }
+
+    #[tokio::test]
+    async fn test_update_password_fail_for_inexistent_user() {
+        let store = setup().await;
+        let result = store
+            .update_password(&get_valid_email(3), get_valid_password())
+            .await;
+        assert!(matches!(result, Err(UserStoreError::UserNotFound)));
+    }
+
+    #[tokio::test]
+    async fn test_set_email_verified() {
+        let store = setup().await;
+        let email = get_valid_email(1);
+        assert!(!store.get_user(&email).await.unwrap().email_verified);
+
+        store.set_email_verified(&email).await.unwrap();
+
+        assert!(store.get_user(&email).await.unwrap().email_verified);
+    }
+
+    #[tokio::test]
+    async fn test_update_email_moves_user() {
+        let store = setup().await;
+        let email = get_valid_email(1);
+        let new_email = get_valid_email(2);
+
+        store.update_email(&email, &new_email).await.unwrap();
+
+        assert!(matches!(
+            store.get_user(&email).await,
+            Err(UserStoreError::UserNotFound)
+        ));
+        let user = store.get_user(&new_email).await.unwrap();
+        assert_eq!(user.email, new_email);
+        assert!(user.email_verified);
+        assert!(store
+            .validate_user(&new_email, &get_valid_password())
+            .await
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_update_email_fail_when_new_email_is_taken() {
+        let store = setup().await;
+        let user = User::new(get_valid_email(2), false);
+        store.add_user(user, get_valid_password()).await.unwrap();
+
+        let result = store
+            .update_email(&get_valid_email(1), &get_valid_email(2))
+            .await;
+        assert!(matches!(result, Err(UserStoreError::UserAlreadyExists)));
+    }
+
+    #[tokio::test]
+    async fn test_delete_user() {
+        let store = setup().await;
+        let email = get_valid_email(1);
+
+        store.delete_user(&email).await.unwrap();
+
+        assert!(matches!(
+            store.get_user(&email).await,
+            Err(UserStoreError::UserNotFound)
+        ));
+        assert!(matches!(
+            store.delete_user(&email).await,
+            Err(UserStoreError::UserNotFound)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_bump_token_version() {
+        let store = setup().await;
+        let email = get_valid_email(1);
+
+        assert_eq!(store.get_token_version(&email).await.unwrap(), 0);
+        assert_eq!(store.bump_token_version(&email).await.unwrap(), 1);
+        assert_eq!(store.get_token_version(&email).await.unwrap(), 1);
+
+        // The version moves along with the account
+        store
+            .update_email(&email, &get_valid_email(2))
+            .await
+            .unwrap();
+        assert_eq!(
+            store.get_token_version(&get_valid_email(2)).await.unwrap(),
+            1
+        );
+        assert!(matches!(
+            store.get_token_version(&email).await,
+            Err(UserStoreError::UserNotFound)
+        ));
+    }
 }
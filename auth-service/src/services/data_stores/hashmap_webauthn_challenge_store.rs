@@ -0,0 +1,72 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use tokio::sync::RwLock;
+use uuid::Uuid;
+
+use crate::{
+    domain::{WebAuthnChallenge, WebAuthnChallengeStore, WebAuthnChallengeStoreError},
+    utils::webauthn::WEBAUTHN_CHALLENGE_TTL_SECONDS,
+};
+
+#[derive(Default)]
+pub struct HashMapWebAuthnChallengeStore {
+    challenges: RwLock<HashMap<Uuid, (WebAuthnChallenge, Instant)>>,
+}
+
+#[async_trait::async_trait]
+impl WebAuthnChallengeStore for HashMapWebAuthnChallengeStore {
+    async fn add_challenge(
+        &self,
+        ceremony_id: Uuid,
+        challenge: WebAuthnChallenge,
+    ) -> Result<(), WebAuthnChallengeStoreError> {
+        let mut challenges = self.challenges.write().await;
+
+        // Abandoned ceremonies are dropped here, there is no TTL to clean them up
+        let ttl = Duration::from_secs(WEBAUTHN_CHALLENGE_TTL_SECONDS);
+        challenges.retain(|_, (_, created_at)| created_at.elapsed() < ttl);
+
+        challenges.insert(ceremony_id, (challenge, Instant::now()));
+        Ok(())
+    }
+
+    async fn take_challenge(
+        &self,
+        ceremony_id: &Uuid,
+    ) -> Result<WebAuthnChallenge, WebAuthnChallengeStoreError> {
+        let ttl = Duration::from_secs(WEBAUTHN_CHALLENGE_TTL_SECONDS);
+
+        match self.challenges.write().await.remove(ceremony_id) {
+            Some((challenge, created_at)) if created_at.elapsed() < ttl => Ok(challenge),
+            _ => Err(WebAuthnChallengeStoreError::ChallengeNotFound),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::domain::WebAuthnCeremony;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_challenge_can_only_be_taken_once() {
+        let store = HashMapWebAuthnChallengeStore::default();
+        let ceremony_id = Uuid::new_v4();
+        let challenge = WebAuthnChallenge::new(WebAuthnCeremony::Authentication, None);
+
+        store
+            .add_challenge(ceremony_id, challenge.clone())
+            .await
+            .unwrap();
+
+        assert_eq!(store.take_challenge(&ceremony_id).await, Ok(challenge));
+        assert_eq!(
+            store.take_challenge(&ceremony_id).await,
+            Err(WebAuthnChallengeStoreError::ChallengeNotFound)
+        );
+    }
+}
@@ -10,7 +10,12 @@ impl EmailClient for MockEmailClient {
         subject: &str,
         content: &str,
     ) -> Result<(), String> {
-        println!("Sending email to {} with subject: {} and content: {}", recepient.as_ref(), subject, content);
+        println!(
+            "Sending email to {} with subject: {} and content: {}",
+            recepient.as_ref(),
+            subject,
+            content
+        );
         Ok(())
     }
 }
@@ -0,0 +1,114 @@
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+
+use crate::{
+    domain::{JwtKeyStore, JwtKeyStoreError, StoredJwtKey},
+    utils::secret_cipher::SecretCipher,
+};
+
+// Private keys are stored encrypted with JWT_KEY_ENCRYPTION_KEY
+pub struct PostgresJwtKeyStore {
+    pool: PgPool,
+    secret_cipher: SecretCipher,
+}
+
+impl PostgresJwtKeyStore {
+    pub fn new(pool: PgPool, secret_cipher: SecretCipher) -> Self {
+        Self {
+            pool,
+            secret_cipher,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl JwtKeyStore for PostgresJwtKeyStore {
+    #[tracing::instrument(name = "Adding JWT key to PostgreSQL", skip_all)]
+    async fn add_key(
+        &self,
+        kid: &str,
+        private_key_pem: &str,
+        interval: Duration,
+    ) -> Result<bool, JwtKeyStoreError> {
+        let encrypted_key = self
+            .secret_cipher
+            .encrypt(private_key_pem.as_bytes())
+            .map_err(|_| JwtKeyStoreError::UnexpectedError)?;
+
+        let mut transaction = self
+            .pool
+            .begin()
+            .await
+            .map_err(|_| JwtKeyStoreError::UnexpectedError)?;
+
+        // Instances rotating at the same time wait for each other, so the check below sees the
+        // key added by whichever one came first. Reads are not blocked.
+        sqlx::query!(r#"LOCK TABLE jwt_keys IN EXCLUSIVE MODE;"#)
+            .execute(&mut *transaction)
+            .await
+            .map_err(|_| JwtKeyStoreError::UnexpectedError)?;
+
+        let result = sqlx::query!(
+            r#"INSERT INTO jwt_keys (kid, private_key)
+               SELECT $1, $2
+               WHERE NOT EXISTS (
+                   SELECT 1 FROM jwt_keys WHERE created_at > NOW() - make_interval(secs => $3)
+               );"#,
+            kid,
+            encrypted_key,
+            interval.as_secs_f64(),
+        )
+        .execute(&mut *transaction)
+        .await
+        .map_err(|_| JwtKeyStoreError::UnexpectedError)?;
+
+        transaction
+            .commit()
+            .await
+            .map_err(|_| JwtKeyStoreError::UnexpectedError)?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    #[tracing::instrument(name = "Retrieving JWT keys from PostgreSQL", skip_all)]
+    async fn get_keys(&self) -> Result<Vec<StoredJwtKey>, JwtKeyStoreError> {
+        let records = sqlx::query!(
+            r#"SELECT kid, private_key, created_at FROM jwt_keys ORDER BY created_at DESC;"#
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|_| JwtKeyStoreError::UnexpectedError)?;
+
+        records
+            .into_iter()
+            .map(|record| {
+                let private_key = self
+                    .secret_cipher
+                    .decrypt(&record.private_key)
+                    .map_err(|_| JwtKeyStoreError::UnexpectedError)?;
+
+                Ok(StoredJwtKey {
+                    kid: record.kid,
+                    private_key_pem: String::from_utf8(private_key)
+                        .map_err(|_| JwtKeyStoreError::UnexpectedError)?,
+                    created_at: record.created_at,
+                })
+            })
+            .collect()
+    }
+
+    #[tracing::instrument(name = "Removing JWT keys from PostgreSQL", skip_all)]
+    async fn remove_keys(&self, added_before: DateTime<Utc>) -> Result<(), JwtKeyStoreError> {
+        sqlx::query!(
+            r#"DELETE FROM jwt_keys WHERE created_at < $1;"#,
+            added_before
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|_| JwtKeyStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,81 @@
+use sqlx::PgPool;
+
+use crate::domain::{
+    ClientSecret, MachineClient, MachineClientStore, MachineClientStoreError, PasswordHash,
+    PasswordHashError, PasswordHashParams,
+};
+
+pub struct PostgresMachineClientStore {
+    pool: PgPool,
+}
+
+impl PostgresMachineClientStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl MachineClientStore for PostgresMachineClientStore {
+    #[tracing::instrument(name = "Saving machine client to PostgreSQL", skip_all)]
+    async fn save_client(
+        &self,
+        client: MachineClient,
+        secret: &ClientSecret,
+    ) -> Result<(), MachineClientStoreError> {
+        // Secrets are hashed like recovery codes, random ones do not need the configured cost
+        let secret_hash = PasswordHash::compute(secret.as_ref(), &PasswordHashParams::default())
+            .await
+            .map_err(|_| MachineClientStoreError::UnexpectedError)?;
+
+        sqlx::query!(
+            r#"INSERT INTO machine_clients (client_id, name, secret_hash, allowed_scopes)
+               VALUES ($1, $2, $3, $4)
+               ON CONFLICT (client_id) DO UPDATE
+               SET name = excluded.name, secret_hash = excluded.secret_hash,
+                   allowed_scopes = excluded.allowed_scopes;"#,
+            client.client_id,
+            client.name,
+            secret_hash.as_ref() as &str,
+            &client.allowed_scopes,
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|_| MachineClientStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Authenticating machine client in PostgreSQL", skip_all)]
+    async fn authenticate_client(
+        &self,
+        client_id: &str,
+        secret: &ClientSecret,
+    ) -> Result<MachineClient, MachineClientStoreError> {
+        let record = sqlx::query!(
+            r#"SELECT client_id, name, secret_hash, allowed_scopes FROM machine_clients
+               WHERE client_id = $1;"#,
+            client_id
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|_| MachineClientStoreError::UnexpectedError)?
+        .ok_or(MachineClientStoreError::InvalidCredentials)?;
+
+        let secret_hash = PasswordHash::parse(record.secret_hash)
+            .map_err(|_| MachineClientStoreError::UnexpectedError)?;
+        secret_hash
+            .verify(secret.as_ref())
+            .await
+            .map_err(|e| match e {
+                PasswordHashError::Mismatch => MachineClientStoreError::InvalidCredentials,
+                PasswordHashError::UnexpectedError => MachineClientStoreError::UnexpectedError,
+            })?;
+
+        Ok(MachineClient {
+            client_id: record.client_id,
+            name: record.name,
+            allowed_scopes: record.allowed_scopes,
+        })
+    }
+}
@@ -0,0 +1,52 @@
+use sqlx::PgPool;
+
+use crate::domain::{OAuthClient, OAuthClientStore, OAuthClientStoreError};
+
+pub struct PostgresOAuthClientStore {
+    pool: PgPool,
+}
+
+impl PostgresOAuthClientStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl OAuthClientStore for PostgresOAuthClientStore {
+    #[tracing::instrument(name = "Saving OAuth client to PostgreSQL", skip_all)]
+    async fn save_client(&self, client: OAuthClient) -> Result<(), OAuthClientStoreError> {
+        sqlx::query!(
+            r#"INSERT INTO oauth_clients (client_id, name, redirect_uris)
+               VALUES ($1, $2, $3)
+               ON CONFLICT (client_id) DO UPDATE
+               SET name = excluded.name, redirect_uris = excluded.redirect_uris;"#,
+            client.client_id,
+            client.name,
+            &client.redirect_uris,
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|_| OAuthClientStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Retrieving OAuth client from PostgreSQL", skip_all)]
+    async fn get_client(&self, client_id: &str) -> Result<OAuthClient, OAuthClientStoreError> {
+        let record = sqlx::query!(
+            r#"SELECT client_id, name, redirect_uris FROM oauth_clients WHERE client_id = $1;"#,
+            client_id
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|_| OAuthClientStoreError::UnexpectedError)?
+        .ok_or(OAuthClientStoreError::ClientNotFound)?;
+
+        Ok(OAuthClient {
+            client_id: record.client_id,
+            name: record.name,
+            redirect_uris: record.redirect_uris,
+        })
+    }
+}
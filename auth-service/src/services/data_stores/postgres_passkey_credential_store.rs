@@ -0,0 +1,140 @@
+use sqlx::PgPool;
+
+use crate::domain::{
+    CredentialId, Email, PasskeyCredential, PasskeyCredentialStore, PasskeyCredentialStoreError,
+};
+
+pub struct PostgresPasskeyCredentialStore {
+    pool: PgPool,
+}
+
+impl PostgresPasskeyCredentialStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl PasskeyCredentialStore for PostgresPasskeyCredentialStore {
+    #[tracing::instrument(name = "Adding passkey credential to PostgreSQL", skip_all)]
+    async fn add_credential(
+        &self,
+        credential: PasskeyCredential,
+    ) -> Result<(), PasskeyCredentialStoreError> {
+        sqlx::query!(
+            r#"INSERT INTO passkey_credentials (credential_id, email, public_key, sign_count) VALUES ($1, $2, $3, $4);"#,
+            credential.credential_id.as_ref(),
+            credential.email.as_ref() as &str,
+            credential.public_key,
+            i64::from(credential.sign_count),
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| {
+            e.into_database_error()
+                .map(|db_err| {
+                    if db_err.kind() == sqlx::error::ErrorKind::UniqueViolation {
+                        PasskeyCredentialStoreError::CredentialAlreadyExists
+                    } else {
+                        PasskeyCredentialStoreError::UnexpectedError
+                    }
+                })
+                .unwrap_or(PasskeyCredentialStoreError::UnexpectedError)
+        })?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Retrieving passkey credential from PostgreSQL", skip_all)]
+    async fn get_credential(
+        &self,
+        credential_id: &CredentialId,
+    ) -> Result<PasskeyCredential, PasskeyCredentialStoreError> {
+        let record = sqlx::query!(
+            r#"SELECT credential_id, email, public_key, sign_count FROM passkey_credentials WHERE credential_id = $1;"#,
+            credential_id.as_ref()
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|_| PasskeyCredentialStoreError::UnexpectedError)?
+        .ok_or(PasskeyCredentialStoreError::CredentialNotFound)?;
+
+        Ok(PasskeyCredential {
+            credential_id: CredentialId::from(record.credential_id),
+            email: Email::parse(record.email)
+                .map_err(|_| PasskeyCredentialStoreError::UnexpectedError)?,
+            public_key: record.public_key,
+            sign_count: u32::try_from(record.sign_count)
+                .map_err(|_| PasskeyCredentialStoreError::UnexpectedError)?,
+        })
+    }
+
+    #[tracing::instrument(
+        name = "Retrieving passkey credentials of user from PostgreSQL",
+        skip_all
+    )]
+    async fn get_credentials(
+        &self,
+        email: &Email,
+    ) -> Result<Vec<PasskeyCredential>, PasskeyCredentialStoreError> {
+        let records = sqlx::query!(
+            r#"SELECT credential_id, email, public_key, sign_count FROM passkey_credentials WHERE email = $1 ORDER BY created_at;"#,
+            email.as_ref() as &str
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|_| PasskeyCredentialStoreError::UnexpectedError)?;
+
+        records
+            .into_iter()
+            .map(|record| {
+                Ok(PasskeyCredential {
+                    credential_id: CredentialId::from(record.credential_id),
+                    email: Email::parse(record.email)
+                        .map_err(|_| PasskeyCredentialStoreError::UnexpectedError)?,
+                    public_key: record.public_key,
+                    sign_count: u32::try_from(record.sign_count)
+                        .map_err(|_| PasskeyCredentialStoreError::UnexpectedError)?,
+                })
+            })
+            .collect()
+    }
+
+    #[tracing::instrument(name = "Updating passkey sign count in PostgreSQL", skip_all)]
+    async fn update_sign_count(
+        &self,
+        credential_id: &CredentialId,
+        previous_sign_count: u32,
+        sign_count: u32,
+    ) -> Result<(), PasskeyCredentialStoreError> {
+        let result = sqlx::query!(
+            r#"UPDATE passkey_credentials SET sign_count = $1 WHERE credential_id = $2 AND sign_count = $3;"#,
+            i64::from(sign_count),
+            credential_id.as_ref(),
+            i64::from(previous_sign_count),
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|_| PasskeyCredentialStoreError::UnexpectedError)?;
+
+        // Either the count moved on in between or the credential is gone, both end the login
+        if result.rows_affected() == 0 {
+            return Err(PasskeyCredentialStoreError::SignCountChanged);
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Removing passkey credentials from PostgreSQL", skip_all)]
+    async fn remove_credentials(&self, email: &Email) -> Result<(), PasskeyCredentialStoreError> {
+        sqlx::query!(
+            r#"DELETE FROM passkey_credentials WHERE email = $1;"#,
+            email.as_ref() as &str,
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|_| PasskeyCredentialStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+}
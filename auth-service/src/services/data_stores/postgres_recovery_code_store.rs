@@ -0,0 +1,121 @@
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::domain::{
+    Email, PasswordHash, PasswordHashError, PasswordHashParams, RecoveryCode, RecoveryCodeStore,
+    RecoveryCodeStoreError,
+};
+
+pub struct PostgresRecoveryCodeStore {
+    pool: PgPool,
+}
+
+impl PostgresRecoveryCodeStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl RecoveryCodeStore for PostgresRecoveryCodeStore {
+    #[tracing::instrument(name = "Replacing recovery codes in PostgreSQL", skip_all)]
+    async fn replace_codes(
+        &self,
+        email: &Email,
+        codes: Vec<RecoveryCode>,
+    ) -> Result<(), RecoveryCodeStoreError> {
+        // Codes are hashed like passwords, they grant the same access when combined with one.
+        // Being random they do not need the configured cost, the default one is plenty.
+        let mut code_hashes = Vec::with_capacity(codes.len());
+        for code in codes {
+            let code_hash = PasswordHash::compute(code.as_ref(), &PasswordHashParams::default())
+                .await
+                .map_err(|_| RecoveryCodeStoreError::UnexpectedError)?;
+            code_hashes.push(code_hash);
+        }
+
+        let mut transaction = self
+            .pool
+            .begin()
+            .await
+            .map_err(|_| RecoveryCodeStoreError::UnexpectedError)?;
+
+        sqlx::query!(
+            r#"DELETE FROM recovery_codes WHERE email = $1;"#,
+            email.as_ref() as &str
+        )
+        .execute(&mut *transaction)
+        .await
+        .map_err(|_| RecoveryCodeStoreError::UnexpectedError)?;
+
+        for code_hash in code_hashes {
+            sqlx::query!(
+                r#"INSERT INTO recovery_codes (id, email, code_hash) VALUES ($1, $2, $3);"#,
+                Uuid::new_v4(),
+                email.as_ref() as &str,
+                code_hash.as_ref() as &str,
+            )
+            .execute(&mut *transaction)
+            .await
+            .map_err(|_| RecoveryCodeStoreError::UnexpectedError)?;
+        }
+
+        transaction
+            .commit()
+            .await
+            .map_err(|_| RecoveryCodeStoreError::UnexpectedError)
+    }
+
+    #[tracing::instrument(name = "Using recovery code in PostgreSQL", skip_all)]
+    async fn use_code(
+        &self,
+        email: &Email,
+        code: &RecoveryCode,
+    ) -> Result<(), RecoveryCodeStoreError> {
+        let records = sqlx::query!(
+            r#"SELECT id, code_hash FROM recovery_codes WHERE email = $1;"#,
+            email.as_ref() as &str
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|_| RecoveryCodeStoreError::UnexpectedError)?;
+
+        for record in records {
+            let code_hash = PasswordHash::parse(record.code_hash)
+                .map_err(|_| RecoveryCodeStoreError::UnexpectedError)?;
+            match code_hash.verify(code.as_ref()).await {
+                Ok(()) => {}
+                Err(PasswordHashError::Mismatch) => continue,
+                Err(PasswordHashError::UnexpectedError) => {
+                    return Err(RecoveryCodeStoreError::UnexpectedError)
+                }
+            }
+
+            // Deleting is what consumes the code, only one of two concurrent attempts can succeed
+            let result = sqlx::query!(r#"DELETE FROM recovery_codes WHERE id = $1;"#, record.id)
+                .execute(&self.pool)
+                .await
+                .map_err(|_| RecoveryCodeStoreError::UnexpectedError)?;
+
+            return match result.rows_affected() {
+                0 => Err(RecoveryCodeStoreError::InvalidCode),
+                _ => Ok(()),
+            };
+        }
+
+        Err(RecoveryCodeStoreError::InvalidCode)
+    }
+
+    #[tracing::instrument(name = "Counting recovery codes in PostgreSQL", skip_all)]
+    async fn count_codes(&self, email: &Email) -> Result<usize, RecoveryCodeStoreError> {
+        let count = sqlx::query_scalar!(
+            r#"SELECT COUNT(*) AS "count!" FROM recovery_codes WHERE email = $1;"#,
+            email.as_ref() as &str
+        )
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|_| RecoveryCodeStoreError::UnexpectedError)?;
+
+        Ok(count as usize)
+    }
+}
@@ -0,0 +1,152 @@
+use chrono::Utc;
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::domain::{
+    Email, RefreshToken, RefreshTokenRecord, RefreshTokenStore, RefreshTokenStoreError,
+};
+
+pub struct PostgresRefreshTokenStore {
+    pool: PgPool,
+}
+
+impl PostgresRefreshTokenStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl RefreshTokenStore for PostgresRefreshTokenStore {
+    #[tracing::instrument(name = "Adding refresh token to PostgreSQL", skip_all)]
+    async fn add_token(
+        &self,
+        token: RefreshToken,
+        record: RefreshTokenRecord,
+    ) -> Result<(), RefreshTokenStoreError> {
+        // Expired tokens are useless, used or not, so they are dropped whenever a token is stored
+        sqlx::query!(
+            r#"
+            WITH pruned AS (DELETE FROM refresh_tokens WHERE expires_at <= NOW())
+            INSERT INTO refresh_tokens (token_hash, family_id, email, expires_at)
+            VALUES ($1, $2, $3, $4);
+            "#,
+            hash_token(&token),
+            record.family_id,
+            record.email.as_ref() as &str,
+            record.expires_at,
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|_| RefreshTokenStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Retrieving refresh token from PostgreSQL", skip_all)]
+    async fn get_token(
+        &self,
+        token: &RefreshToken,
+    ) -> Result<RefreshTokenRecord, RefreshTokenStoreError> {
+        let record = sqlx::query!(
+            r#"SELECT family_id, email, expires_at FROM refresh_tokens WHERE token_hash = $1;"#,
+            hash_token(token)
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|_| RefreshTokenStoreError::UnexpectedError)?
+        .ok_or(RefreshTokenStoreError::TokenNotFound)?;
+
+        Ok(RefreshTokenRecord {
+            family_id: record.family_id,
+            email: Email::parse(record.email)
+                .map_err(|_| RefreshTokenStoreError::UnexpectedError)?,
+            expires_at: record.expires_at,
+        })
+    }
+
+    #[tracing::instrument(name = "Replacing refresh token in PostgreSQL", skip_all)]
+    async fn replace_token(
+        &self,
+        token: &RefreshToken,
+        new_token: RefreshToken,
+    ) -> Result<RefreshTokenRecord, RefreshTokenStoreError> {
+        // Flip the flag and insert the successor in one statement so concurrent refreshes cannot
+        // both win. Expired tokens are dropped along the way, as when adding a token.
+        let record = sqlx::query!(
+            r#"
+            WITH used AS (
+                UPDATE refresh_tokens SET used = TRUE
+                WHERE token_hash = $1 AND used = FALSE AND expires_at > NOW()
+                RETURNING family_id, email, expires_at
+            ), pruned AS (
+                DELETE FROM refresh_tokens WHERE expires_at <= NOW()
+            )
+            INSERT INTO refresh_tokens (token_hash, family_id, email, expires_at)
+            SELECT $2, family_id, email, expires_at FROM used
+            RETURNING family_id, email, expires_at;
+            "#,
+            hash_token(token),
+            hash_token(&new_token),
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|_| RefreshTokenStoreError::UnexpectedError)?;
+
+        if let Some(record) = record {
+            return Ok(RefreshTokenRecord {
+                family_id: record.family_id,
+                email: Email::parse(record.email)
+                    .map_err(|_| RefreshTokenStoreError::UnexpectedError)?,
+                expires_at: record.expires_at,
+            });
+        }
+
+        let record = sqlx::query!(
+            r#"SELECT family_id, used, expires_at FROM refresh_tokens WHERE token_hash = $1;"#,
+            hash_token(token)
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|_| RefreshTokenStoreError::UnexpectedError)?;
+
+        match record {
+            Some(record) if record.expires_at > Utc::now() && record.used => {
+                Err(RefreshTokenStoreError::TokenReused(record.family_id))
+            }
+            _ => Err(RefreshTokenStoreError::TokenNotFound),
+        }
+    }
+
+    #[tracing::instrument(name = "Revoking refresh token family in PostgreSQL", skip_all)]
+    async fn revoke_family(&self, family_id: &Uuid) -> Result<(), RefreshTokenStoreError> {
+        sqlx::query!(
+            r#"DELETE FROM refresh_tokens WHERE family_id = $1;"#,
+            family_id
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|_| RefreshTokenStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Revoking all refresh tokens of a user in PostgreSQL", skip_all)]
+    async fn revoke_all(&self, email: &Email) -> Result<(), RefreshTokenStoreError> {
+        sqlx::query!(
+            r#"DELETE FROM refresh_tokens WHERE email = $1;"#,
+            email.as_ref() as &str
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|_| RefreshTokenStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+}
+
+// Only a digest of the token is persisted, so a database leak does not expose usable tokens
+fn hash_token(token: &RefreshToken) -> String {
+    format!("{:x}", Sha256::digest(token.as_ref().as_bytes()))
+}
@@ -0,0 +1,144 @@
+use std::net::IpAddr;
+
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::domain::{Email, Session, SessionStore, SessionStoreError};
+
+pub struct PostgresSessionStore {
+    pool: PgPool,
+}
+
+impl PostgresSessionStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl SessionStore for PostgresSessionStore {
+    #[tracing::instrument(name = "Adding session to PostgreSQL", skip_all)]
+    async fn add_session(&self, session: Session) -> Result<(), SessionStoreError> {
+        sqlx::query!(
+            r#"INSERT INTO sessions (id, email, device, ip_address, user_agent, created_at, last_seen_at)
+               VALUES ($1, $2, $3, $4, $5, $6, $7);"#,
+            session.id,
+            session.email.as_ref() as &str,
+            session.device,
+            session.ip_address.map(|ip| ip.to_string()),
+            session.user_agent,
+            session.created_at,
+            session.last_seen_at,
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|_| SessionStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Retrieving session from PostgreSQL", skip_all)]
+    async fn get_session(&self, id: &Uuid) -> Result<Session, SessionStoreError> {
+        let record = sqlx::query!(
+            r#"SELECT id, email, device, ip_address, user_agent, created_at, last_seen_at
+               FROM sessions WHERE id = $1;"#,
+            id
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|_| SessionStoreError::UnexpectedError)?
+        .ok_or(SessionStoreError::SessionNotFound)?;
+
+        Ok(Session {
+            id: record.id,
+            email: Email::parse(record.email).map_err(|_| SessionStoreError::UnexpectedError)?,
+            device: record.device,
+            ip_address: record.ip_address.and_then(|ip| ip.parse().ok()),
+            user_agent: record.user_agent,
+            created_at: record.created_at,
+            last_seen_at: record.last_seen_at,
+        })
+    }
+
+    #[tracing::instrument(name = "Retrieving sessions of user from PostgreSQL", skip_all)]
+    async fn get_sessions(&self, email: &Email) -> Result<Vec<Session>, SessionStoreError> {
+        let records = sqlx::query!(
+            r#"SELECT id, email, device, ip_address, user_agent, created_at, last_seen_at
+               FROM sessions WHERE email = $1 ORDER BY last_seen_at DESC;"#,
+            email.as_ref() as &str
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|_| SessionStoreError::UnexpectedError)?;
+
+        records
+            .into_iter()
+            .map(|record| {
+                Ok(Session {
+                    id: record.id,
+                    email: Email::parse(record.email)
+                        .map_err(|_| SessionStoreError::UnexpectedError)?,
+                    device: record.device,
+                    ip_address: record.ip_address.and_then(|ip| ip.parse().ok()),
+                    user_agent: record.user_agent,
+                    created_at: record.created_at,
+                    last_seen_at: record.last_seen_at,
+                })
+            })
+            .collect()
+    }
+
+    #[tracing::instrument(name = "Touching session in PostgreSQL", skip_all)]
+    async fn touch_session(
+        &self,
+        id: &Uuid,
+        ip_address: Option<IpAddr>,
+    ) -> Result<(), SessionStoreError> {
+        let result = sqlx::query!(
+            r#"UPDATE sessions SET last_seen_at = now(), ip_address = coalesce($1, ip_address)
+               WHERE id = $2;"#,
+            ip_address.map(|ip| ip.to_string()),
+            id
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|_| SessionStoreError::UnexpectedError)?;
+
+        if result.rows_affected() == 0 {
+            return Err(SessionStoreError::SessionNotFound);
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Removing session from PostgreSQL", skip_all)]
+    async fn remove_session(&self, email: &Email, id: &Uuid) -> Result<(), SessionStoreError> {
+        let result = sqlx::query!(
+            r#"DELETE FROM sessions WHERE id = $1 AND email = $2;"#,
+            id,
+            email.as_ref() as &str
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|_| SessionStoreError::UnexpectedError)?;
+
+        if result.rows_affected() == 0 {
+            return Err(SessionStoreError::SessionNotFound);
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Removing sessions of user from PostgreSQL", skip_all)]
+    async fn remove_sessions(&self, email: &Email) -> Result<(), SessionStoreError> {
+        sqlx::query!(
+            r#"DELETE FROM sessions WHERE email = $1;"#,
+            email.as_ref() as &str
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|_| SessionStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+}
@@ -1,50 +1,102 @@
-use argon2::{
-    password_hash::SaltString, Algorithm, Argon2, Params, PasswordHash, PasswordHasher,
-    PasswordVerifier, Version,
-};
 use sqlx::PgPool;
-use tokio::task::spawn_blocking;
 
-use crate::domain::{Email, Password, User, UserStore, UserStoreError};
+use crate::{
+    domain::{
+        Email, Password, PasswordHash, PasswordHashError, PasswordHashParams, TotpSecret,
+        TwoFAMethod, User, UserStore, UserStoreError,
+    },
+    utils::secret_cipher::SecretCipher,
+};
 
 pub struct PostgresUserStore {
     pool: PgPool,
+    // TOTP secrets have to be recoverable to verify codes, so they are encrypted rather than hashed
+    secret_cipher: SecretCipher,
+    hash_params: PasswordHashParams,
 }
 
 impl PostgresUserStore {
-    pub fn new(pool: PgPool) -> Self {
-        Self { pool }
+    pub fn new(pool: PgPool, secret_cipher: SecretCipher, hash_params: PasswordHashParams) -> Self {
+        Self {
+            pool,
+            secret_cipher,
+            hash_params,
+        }
+    }
+
+    fn decrypt_totp_secret(&self, encrypted_secret: &str) -> Result<TotpSecret, UserStoreError> {
+        let secret = self
+            .secret_cipher
+            .decrypt(encrypted_secret)
+            .map_err(|_| UserStoreError::UnexpectedError)?;
+
+        TotpSecret::parse(secret).map_err(|_| UserStoreError::UnexpectedError)
+    }
+
+    // Upgrades a hash to the configured parameters while the password is at hand. A failure only
+    // delays the upgrade to the next login, so it is logged rather than returned.
+    async fn rehash_password(&self, email: &Email, password: &Password, old_hash: &PasswordHash) {
+        let password_hash = PasswordHash::compute(password.as_ref(), &self.hash_params).await;
+        let password_hash = match password_hash {
+            Ok(password_hash) => password_hash,
+            Err(e) => {
+                tracing::warn!(error = ?e, "Failed to rehash password");
+                return;
+            }
+        };
+
+        // Only replaces the hash that was checked, a password changed in the meantime is kept
+        let result = sqlx::query!(
+            r#"UPDATE users SET password_hash = $1 WHERE email = $2 AND password_hash = $3;"#,
+            password_hash.as_ref() as &str,
+            email.as_ref() as &str,
+            old_hash.as_ref() as &str,
+        )
+        .execute(&self.pool)
+        .await;
+
+        if let Err(e) = result {
+            tracing::warn!(error = ?e, "Failed to store rehashed password");
+        }
     }
 }
 
 #[async_trait::async_trait]
 impl UserStore for PostgresUserStore {
     #[tracing::instrument(name = "Adding user to PostgreSQL", skip_all)]
-    async fn add_user(&mut self, user: User) -> Result<(), UserStoreError> {
-        let password_hash = spawn_blocking(async move || -> Result<String, UserStoreError> {
-            compute_password_hash(user.password.as_ref().to_string())
-                .await
-                .map_err(|_| UserStoreError::UnexpectedError)
-        })
-        .await
-        .map_err(|_| UserStoreError::UnexpectedError)?
-        .await?;
+    async fn add_user(&self, user: User, password: Password) -> Result<(), UserStoreError> {
+        let password_hash = PasswordHash::compute(password.as_ref(), &self.hash_params)
+            .await
+            .map_err(|_| UserStoreError::UnexpectedError)?;
 
+        self.import_user(user, password_hash).await
+    }
+
+    #[tracing::instrument(name = "Importing user into PostgreSQL", skip_all)]
+    async fn import_user(
+        &self,
+        user: User,
+        password_hash: PasswordHash,
+    ) -> Result<(), UserStoreError> {
         sqlx::query!(
-            r#"INSERT INTO users (email, password_hash, requires_2fa) VALUES ($1, $2, $3);"#,
+            r#"INSERT INTO users (id, email, password_hash, requires_2fa, email_verified, created_at)
+               VALUES ($1, $2, $3, $4, $5, $6);"#,
+            user.id,
             user.email.as_ref() as &str,
             password_hash.as_ref() as &str,
             user.requires_2fa,
+            user.email_verified,
+            user.created_at,
         )
         .execute(&self.pool)
         .await
         .map_err(|e| {
             e.into_database_error()
-                .and_then(|db_err| {
+                .map(|db_err| {
                     if db_err.kind() == sqlx::error::ErrorKind::UniqueViolation {
-                        Some(UserStoreError::UserAlreadyExists)
+                        UserStoreError::UserAlreadyExists
                     } else {
-                        Some(UserStoreError::UnexpectedError)
+                        UserStoreError::UnexpectedError
                     }
                 })
                 .unwrap_or(UserStoreError::UnexpectedError)
@@ -56,25 +108,24 @@ impl UserStore for PostgresUserStore {
     #[tracing::instrument(name = "Retrieving user from PostgreSQL", skip_all)]
     async fn get_user(&self, email: &Email) -> Result<User, UserStoreError> {
         let record = sqlx::query!(
-            r#"SELECT email, password_hash, requires_2fa FROM users WHERE email = $1;"#,
+            r#"SELECT id, email, requires_2fa, two_fa_method, email_verified, created_at
+               FROM users WHERE email = $1;"#,
             email.as_ref() as &str
-        );
-
-        let record = record
-            .fetch_one(&self.pool)
-            .await
-            .map_err(|_| UserStoreError::UserNotFound)?;
-
-        // TODO: Storing password hash is meaningless. Need to refactor the code.
-        //       password should not be part of User at all.
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|_| UserStoreError::UnexpectedError)?
+        .ok_or(UserStoreError::UserNotFound)?;
 
-        let user = User {
-            email: Email::parse(record.email).unwrap(),
-            password: Password::parse(record.password_hash).unwrap(),
+        Ok(User {
+            id: record.id,
+            email: Email::parse(record.email).map_err(|_| UserStoreError::UnexpectedError)?,
             requires_2fa: record.requires_2fa,
-        };
-
-        Ok(user)
+            two_fa_method: TwoFAMethod::parse(&record.two_fa_method)
+                .map_err(|_| UserStoreError::UnexpectedError)?,
+            email_verified: record.email_verified,
+            created_at: record.created_at,
+        })
     }
 
     #[tracing::instrument(name = "Validating user credentials in PostgreSQL", skip_all)]
@@ -83,61 +134,267 @@ impl UserStore for PostgresUserStore {
         email: &Email,
         password: &Password,
     ) -> Result<User, UserStoreError> {
-        let user = self.get_user(email).await?;
+        let record = sqlx::query!(
+            r#"SELECT password_hash FROM users WHERE email = $1;"#,
+            email.as_ref() as &str
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|_| UserStoreError::UnexpectedError)?
+        .ok_or(UserStoreError::UserNotFound)?;
 
-        spawn_blocking({
-            let expected_password_hash = user.password.as_ref().to_string();
-            let password_candidate = password.as_ref().to_string();
+        let password_hash = PasswordHash::parse(record.password_hash)
+            .map_err(|_| UserStoreError::UnexpectedError)?;
 
-            async move || -> Result<(), UserStoreError> {
-                verify_password_hash(expected_password_hash, password_candidate)
-                    .await
-                    .map_err(|_| UserStoreError::InvalidCredentials)
-            }
-        })
+        password_hash
+            .verify(password.as_ref())
+            .await
+            .map_err(|e| match e {
+                PasswordHashError::Mismatch => UserStoreError::InvalidCredentials,
+                PasswordHashError::UnexpectedError => UserStoreError::UnexpectedError,
+            })?;
+
+        if password_hash.needs_rehash(&self.hash_params) {
+            self.rehash_password(email, password, &password_hash).await;
+        }
+
+        self.get_user(email).await
+    }
+
+    #[tracing::instrument(name = "Storing pending TOTP secret in PostgreSQL", skip_all)]
+    async fn set_pending_totp_secret(
+        &self,
+        email: &Email,
+        secret: TotpSecret,
+    ) -> Result<(), UserStoreError> {
+        let encrypted_secret = self
+            .secret_cipher
+            .encrypt(secret.as_ref())
+            .map_err(|_| UserStoreError::UnexpectedError)?;
+
+        let result = sqlx::query!(
+            r#"UPDATE users SET pending_totp_secret = $1 WHERE email = $2;"#,
+            encrypted_secret,
+            email.as_ref() as &str,
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|_| UserStoreError::UnexpectedError)?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Retrieving pending TOTP secret from PostgreSQL", skip_all)]
+    async fn get_pending_totp_secret(&self, email: &Email) -> Result<TotpSecret, UserStoreError> {
+        let record = sqlx::query!(
+            r#"SELECT pending_totp_secret FROM users WHERE email = $1;"#,
+            email.as_ref() as &str
+        )
+        .fetch_optional(&self.pool)
         .await
         .map_err(|_| UserStoreError::UnexpectedError)?
-        .await?;
+        .ok_or(UserStoreError::UserNotFound)?;
 
-        Ok(user)
+        let encrypted_secret = record
+            .pending_totp_secret
+            .ok_or(UserStoreError::TotpNotEnrolled)?;
+        self.decrypt_totp_secret(&encrypted_secret)
     }
-}
 
-#[tracing::instrument(name = "Computing password hash", skip_all)]
-async fn compute_password_hash(password: String) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
-    let current_span = tracing::Span::current();
-    tokio::task::spawn_blocking(move || {
-        current_span.in_scope(|| {
-            let salt = SaltString::generate(&mut rand::thread_rng());
-
-            let password_hash = Argon2::new(
-                Algorithm::Argon2id,
-                Version::V0x13,
-                Params::new(15000, 2, 1, None)?,
-            )
-            .hash_password(password.as_bytes(), &salt)?
-            .to_string();
-
-            Ok(password_hash)
-        })
-    })
-    .await?
-}
+    #[tracing::instrument(name = "Confirming TOTP secret in PostgreSQL", skip_all)]
+    async fn confirm_totp_secret(
+        &self,
+        email: &Email,
+        secret: TotpSecret,
+    ) -> Result<(), UserStoreError> {
+        let encrypted_secret = self
+            .secret_cipher
+            .encrypt(secret.as_ref())
+            .map_err(|_| UserStoreError::UnexpectedError)?;
 
-#[tracing::instrument(name = "Verifying password hash", skip_all)]
-async fn verify_password_hash(
-    expected_password_hash: String,
-    password_candidate: String,
-) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    let current_span = tracing::Span::current();
-    tokio::task::spawn_blocking(move || {
-        current_span.in_scope(|| {
-            let expected_hash: PasswordHash<'_> = PasswordHash::new(&expected_password_hash)?;
-
-            Argon2::default()
-                .verify_password(password_candidate.as_bytes(), &expected_hash)
-                .map_err(|e| e.into())
-        })
-    })
-    .await?
+        let result = sqlx::query!(
+            r#"UPDATE users
+               SET totp_secret = $1, pending_totp_secret = NULL, two_fa_method = 'totp', requires_2fa = TRUE
+               WHERE email = $2;"#,
+            encrypted_secret,
+            email.as_ref() as &str,
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|_| UserStoreError::UnexpectedError)?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Retrieving TOTP secret from PostgreSQL", skip_all)]
+    async fn get_totp_secret(&self, email: &Email) -> Result<TotpSecret, UserStoreError> {
+        let record = sqlx::query!(
+            r#"SELECT totp_secret FROM users WHERE email = $1;"#,
+            email.as_ref() as &str
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|_| UserStoreError::UnexpectedError)?
+        .ok_or(UserStoreError::UserNotFound)?;
+
+        let encrypted_secret = record.totp_secret.ok_or(UserStoreError::TotpNotEnrolled)?;
+        self.decrypt_totp_secret(&encrypted_secret)
+    }
+
+    #[tracing::instrument(name = "Updating 2FA method in PostgreSQL", skip_all)]
+    async fn set_two_fa_method(
+        &self,
+        email: &Email,
+        method: TwoFAMethod,
+    ) -> Result<(), UserStoreError> {
+        if method == TwoFAMethod::Totp {
+            self.get_totp_secret(email).await?;
+        }
+
+        // Picking an authenticator app is an explicit opt-in to 2FA
+        let result = sqlx::query!(
+            r#"UPDATE users
+               SET two_fa_method = $1, requires_2fa = requires_2fa OR $2
+               WHERE email = $3;"#,
+            method.as_ref() as &str,
+            method == TwoFAMethod::Totp,
+            email.as_ref() as &str,
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|_| UserStoreError::UnexpectedError)?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Updating password in PostgreSQL", skip_all)]
+    async fn update_password(
+        &self,
+        email: &Email,
+        password: Password,
+    ) -> Result<(), UserStoreError> {
+        let password_hash = PasswordHash::compute(password.as_ref(), &self.hash_params)
+            .await
+            .map_err(|_| UserStoreError::UnexpectedError)?;
+
+        let result = sqlx::query!(
+            r#"UPDATE users SET password_hash = $1 WHERE email = $2;"#,
+            password_hash.as_ref() as &str,
+            email.as_ref() as &str,
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|_| UserStoreError::UnexpectedError)?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Marking email as verified in PostgreSQL", skip_all)]
+    async fn set_email_verified(&self, email: &Email) -> Result<(), UserStoreError> {
+        let result = sqlx::query!(
+            r#"UPDATE users SET email_verified = true WHERE email = $1;"#,
+            email.as_ref() as &str,
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|_| UserStoreError::UnexpectedError)?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Updating email in PostgreSQL", skip_all)]
+    async fn update_email(&self, email: &Email, new_email: &Email) -> Result<(), UserStoreError> {
+        // Recovery codes and passkeys follow through their foreign keys
+        let result = sqlx::query!(
+            r#"UPDATE users SET email = $1, email_verified = true WHERE email = $2;"#,
+            new_email.as_ref() as &str,
+            email.as_ref() as &str,
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| {
+            e.into_database_error()
+                .map(|db_err| {
+                    if db_err.kind() == sqlx::error::ErrorKind::UniqueViolation {
+                        UserStoreError::UserAlreadyExists
+                    } else {
+                        UserStoreError::UnexpectedError
+                    }
+                })
+                .unwrap_or(UserStoreError::UnexpectedError)
+        })?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Deleting user from PostgreSQL", skip_all)]
+    async fn delete_user(&self, email: &Email) -> Result<(), UserStoreError> {
+        // Recovery codes and passkeys go along through their foreign keys
+        let result = sqlx::query!(
+            r#"DELETE FROM users WHERE email = $1;"#,
+            email.as_ref() as &str,
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|_| UserStoreError::UnexpectedError)?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Retrieving token version from PostgreSQL", skip_all)]
+    async fn get_token_version(&self, email: &Email) -> Result<u32, UserStoreError> {
+        let record = sqlx::query!(
+            r#"SELECT token_version FROM users WHERE email = $1;"#,
+            email.as_ref() as &str,
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|_| UserStoreError::UnexpectedError)?
+        .ok_or(UserStoreError::UserNotFound)?;
+
+        u32::try_from(record.token_version).map_err(|_| UserStoreError::UnexpectedError)
+    }
+
+    #[tracing::instrument(name = "Bumping token version in PostgreSQL", skip_all)]
+    async fn bump_token_version(&self, email: &Email) -> Result<u32, UserStoreError> {
+        let record = sqlx::query!(
+            r#"UPDATE users SET token_version = token_version + 1 WHERE email = $1
+               RETURNING token_version;"#,
+            email.as_ref() as &str,
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|_| UserStoreError::UnexpectedError)?
+        .ok_or(UserStoreError::UserNotFound)?;
+
+        u32::try_from(record.token_version).map_err(|_| UserStoreError::UnexpectedError)
+    }
 }
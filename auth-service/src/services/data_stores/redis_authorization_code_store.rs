@@ -0,0 +1,104 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use redis::{aio::ConnectionManager, AsyncCommands};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use crate::{
+    domain::{
+        AuthorizationCode, AuthorizationCodeStore, AuthorizationCodeStoreError, AuthorizationGrant,
+        CodeChallenge, Email, Scope,
+    },
+    utils::constants::AUTHORIZATION_CODE_TTL_SECONDS,
+};
+
+pub struct RedisAuthorizationCodeStore {
+    conn: ConnectionManager,
+}
+
+impl RedisAuthorizationCodeStore {
+    pub fn new(conn: ConnectionManager) -> Self {
+        Self { conn }
+    }
+}
+
+#[async_trait::async_trait]
+impl AuthorizationCodeStore for RedisAuthorizationCodeStore {
+    async fn add_code(
+        &self,
+        code: AuthorizationCode,
+        grant: AuthorizationGrant,
+    ) -> Result<(), AuthorizationCodeStoreError> {
+        let key = get_key(&code);
+        let record = GrantRecord {
+            client_id: grant.client_id,
+            redirect_uri: grant.redirect_uri,
+            email: grant.email,
+            session_id: grant.session_id,
+            scope: grant.scope,
+            nonce: grant.nonce,
+            code_challenge: grant.code_challenge,
+        };
+        let val = serde_json::to_string(&record)
+            .map_err(|_| AuthorizationCodeStoreError::UnexpectedError)?;
+
+        let mut connection = self.conn.clone();
+
+        connection
+            .set_ex::<String, String, ()>(key, val, AUTHORIZATION_CODE_TTL_SECONDS)
+            .await
+            .map_err(|_| AuthorizationCodeStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    async fn take_code(
+        &self,
+        code: &AuthorizationCode,
+    ) -> Result<AuthorizationGrant, AuthorizationCodeStoreError> {
+        let key = get_key(code);
+        let mut connection = self.conn.clone();
+
+        let val: Option<String> = connection
+            .get_del::<String, Option<String>>(key)
+            .await
+            .map_err(|_| AuthorizationCodeStoreError::UnexpectedError)?;
+        let val = val.ok_or(AuthorizationCodeStoreError::CodeNotFound)?;
+
+        let record: GrantRecord =
+            serde_json::from_str(&val).map_err(|_| AuthorizationCodeStoreError::UnexpectedError)?;
+
+        Ok(AuthorizationGrant {
+            client_id: record.client_id,
+            redirect_uri: record.redirect_uri,
+            email: record.email,
+            session_id: record.session_id,
+            scope: record.scope,
+            nonce: record.nonce,
+            code_challenge: record.code_challenge,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct GrantRecord {
+    client_id: String,
+    redirect_uri: String,
+    email: Email,
+    session_id: Uuid,
+    scope: Scope,
+    nonce: Option<String>,
+    code_challenge: CodeChallenge,
+}
+
+const AUTHORIZATION_CODE_PREFIX: &str = "authorization_code:";
+
+// Only a hash of the code ends up in Redis, like with password reset tokens
+fn get_key(code: &AuthorizationCode) -> String {
+    let digest = Sha256::digest(code.as_ref().as_bytes());
+    format!(
+        "{}{}",
+        AUTHORIZATION_CODE_PREFIX,
+        URL_SAFE_NO_PAD.encode(digest)
+    )
+}
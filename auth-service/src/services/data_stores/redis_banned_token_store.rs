@@ -1,43 +1,51 @@
-use std::sync::Arc;
-use tokio::sync::RwLock;
+use chrono::{DateTime, Utc};
+use redis::{aio::ConnectionManager, AsyncCommands};
+use uuid::Uuid;
 
-use redis::Commands;
-
-use crate::{
-    domain::{BannedTokenStore, BannedTokenStoreError},
-    utils::auth::TOKEN_TTL_SECONDS,
-};
+use crate::domain::{BannedTokenStore, BannedTokenStoreError};
 
 pub struct RedisBannedTokenStore {
-    conn: Arc<RwLock<redis::Connection>>,
+    conn: ConnectionManager,
 }
 
 impl RedisBannedTokenStore {
-    pub fn new(conn: Arc<RwLock<redis::Connection>>) -> Self {
+    pub fn new(conn: ConnectionManager) -> Self {
         Self { conn }
     }
 }
 
 #[async_trait::async_trait]
 impl BannedTokenStore for RedisBannedTokenStore {
-    async fn ban_token(&mut self, token: &str) -> Result<(), BannedTokenStoreError> {
-        let key: String = get_key(token);
+    async fn ban_token(
+        &self,
+        jti: &Uuid,
+        expires_at: DateTime<Utc>,
+    ) -> Result<(), BannedTokenStoreError> {
+        // Expired tokens are rejected anyway, there is nothing left to ban
+        let ttl_seconds = (expires_at - Utc::now()).num_seconds();
+        if ttl_seconds <= 0 {
+            return Ok(());
+        }
+
+        let key = get_key(jti);
 
-        let mut connection = self.conn.write().await;
+        let mut connection = self.conn.clone();
 
         connection
-            .set_ex::<String, bool, ()>(key, true, TOKEN_TTL_SECONDS)
+            .set_ex::<String, bool, ()>(key, true, ttl_seconds as u64)
+            .await
             .map_err(|_| BannedTokenStoreError::UnexpectedError)?;
 
         Ok(())
     }
 
-    async fn is_token_banned(&self, token: &str) -> Result<bool, BannedTokenStoreError> {
-        let key = get_key(token);
-        let mut connection = self.conn.write().await;
+    async fn is_token_banned(&self, jti: &Uuid) -> Result<bool, BannedTokenStoreError> {
+        let key = get_key(jti);
+        let mut connection = self.conn.clone();
 
         let has_key = connection
             .exists::<String, bool>(key)
+            .await
             .map_err(|_| BannedTokenStoreError::UnexpectedError)?;
 
         Ok(has_key)
@@ -46,6 +54,6 @@ impl BannedTokenStore for RedisBannedTokenStore {
 
 const BANNED_TOKEN_KEY_PREFIX: &str = "banned_token:";
 
-fn get_key(token: &str) -> String {
-    format!("{}{}", BANNED_TOKEN_KEY_PREFIX, token)
+fn get_key(jti: &Uuid) -> String {
+    format!("{}{}", BANNED_TOKEN_KEY_PREFIX, jti)
 }
@@ -0,0 +1,108 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use redis::{aio::ConnectionManager, AsyncCommands};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    domain::{Email, EmailConfirmation, EmailToken, EmailTokenStore, EmailTokenStoreError},
+    utils::constants::{EMAIL_CHANGE_TOKEN_TTL_SECONDS, EMAIL_VERIFICATION_TOKEN_TTL_SECONDS},
+};
+
+pub struct RedisEmailTokenStore {
+    conn: ConnectionManager,
+}
+
+impl RedisEmailTokenStore {
+    pub fn new(conn: ConnectionManager) -> Self {
+        Self { conn }
+    }
+}
+
+#[async_trait::async_trait]
+impl EmailTokenStore for RedisEmailTokenStore {
+    async fn add_token(
+        &self,
+        token: EmailToken,
+        confirmation: EmailConfirmation,
+    ) -> Result<(), EmailTokenStoreError> {
+        let key = get_key(&token);
+        let owner_key = get_owner_key(confirmation.email());
+        let ttl = match confirmation {
+            EmailConfirmation::Verification { .. } => EMAIL_VERIFICATION_TOKEN_TTL_SECONDS,
+            EmailConfirmation::Change { .. } => EMAIL_CHANGE_TOKEN_TTL_SECONDS,
+        };
+        let val = serde_json::to_string(&confirmation)
+            .map_err(|_| EmailTokenStoreError::UnexpectedError)?;
+
+        let mut connection = self.conn.clone();
+
+        connection
+            .set_ex::<&str, String, ()>(&key, val, ttl)
+            .await
+            .map_err(|_| EmailTokenStoreError::UnexpectedError)?;
+
+        // Kept as long as the longest lived kind of token, a shorter one added later must not
+        // cut the set off before an earlier token expires
+        connection
+            .sadd::<&str, &str, ()>(&owner_key, &key)
+            .await
+            .map_err(|_| EmailTokenStoreError::UnexpectedError)?;
+        connection
+            .expire::<&str, ()>(&owner_key, OWNER_TTL_SECONDS as i64)
+            .await
+            .map_err(|_| EmailTokenStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    async fn take_token(
+        &self,
+        token: &EmailToken,
+    ) -> Result<EmailConfirmation, EmailTokenStoreError> {
+        let key = get_key(token);
+        let mut connection = self.conn.clone();
+
+        let val: Option<String> = connection
+            .get_del::<String, Option<String>>(key)
+            .await
+            .map_err(|_| EmailTokenStoreError::UnexpectedError)?;
+        let val = val.ok_or(EmailTokenStoreError::TokenNotFound)?;
+
+        serde_json::from_str(&val).map_err(|_| EmailTokenStoreError::UnexpectedError)
+    }
+
+    async fn remove_tokens(&self, email: &Email) -> Result<(), EmailTokenStoreError> {
+        let owner_key = get_owner_key(email);
+        let mut connection = self.conn.clone();
+
+        let mut keys: Vec<String> = connection
+            .smembers::<&str, Vec<String>>(&owner_key)
+            .await
+            .map_err(|_| EmailTokenStoreError::UnexpectedError)?;
+        keys.push(owner_key);
+
+        connection
+            .del::<&[String], ()>(&keys)
+            .await
+            .map_err(|_| EmailTokenStoreError::UnexpectedError)
+    }
+}
+
+const EMAIL_TOKEN_PREFIX: &str = "email_token:";
+const EMAIL_TOKEN_OWNER_PREFIX: &str = "email_tokens_of:";
+const OWNER_TTL_SECONDS: u64 =
+    if EMAIL_VERIFICATION_TOKEN_TTL_SECONDS > EMAIL_CHANGE_TOKEN_TTL_SECONDS {
+        EMAIL_VERIFICATION_TOKEN_TTL_SECONDS
+    } else {
+        EMAIL_CHANGE_TOKEN_TTL_SECONDS
+    };
+
+// Only a hash of the token ends up in Redis, like with password reset tokens
+fn get_key(token: &EmailToken) -> String {
+    let digest = Sha256::digest(token.as_ref().as_bytes());
+    format!("{}{}", EMAIL_TOKEN_PREFIX, URL_SAFE_NO_PAD.encode(digest))
+}
+
+// Keys of the tokens sent for the account, so they can be removed along with it
+fn get_owner_key(email: &Email) -> String {
+    format!("{}{}", EMAIL_TOKEN_OWNER_PREFIX, email.as_ref())
+}
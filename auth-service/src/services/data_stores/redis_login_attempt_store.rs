@@ -0,0 +1,96 @@
+use std::time::Duration;
+
+use redis::{aio::ConnectionManager, AsyncCommands};
+
+use crate::domain::{LoginAttemptKey, LoginAttemptStore, LoginAttemptStoreError};
+
+pub struct RedisLoginAttemptStore {
+    conn: ConnectionManager,
+}
+
+impl RedisLoginAttemptStore {
+    pub fn new(conn: ConnectionManager) -> Self {
+        Self { conn }
+    }
+}
+
+#[async_trait::async_trait]
+impl LoginAttemptStore for RedisLoginAttemptStore {
+    async fn add_failure(
+        &self,
+        key: &LoginAttemptKey,
+        window: Duration,
+    ) -> Result<u32, LoginAttemptStoreError> {
+        let key = get_key(LOGIN_FAILURES_PREFIX, key);
+        let mut connection = self.conn.clone();
+
+        let count = connection
+            .incr::<&str, u32, u32>(&key, 1)
+            .await
+            .map_err(|_| LoginAttemptStoreError::UnexpectedError)?;
+        connection
+            .expire::<&str, ()>(&key, window.as_secs().max(1) as i64)
+            .await
+            .map_err(|_| LoginAttemptStoreError::UnexpectedError)?;
+
+        Ok(count)
+    }
+
+    async fn lock(
+        &self,
+        key: &LoginAttemptKey,
+        duration: Duration,
+    ) -> Result<(), LoginAttemptStoreError> {
+        let key = get_key(LOGIN_LOCKOUT_PREFIX, key);
+        let mut connection = self.conn.clone();
+
+        connection
+            .set_ex::<String, u8, ()>(key, 1, duration.as_secs().max(1))
+            .await
+            .map_err(|_| LoginAttemptStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    async fn locked_for(
+        &self,
+        key: &LoginAttemptKey,
+    ) -> Result<Option<Duration>, LoginAttemptStoreError> {
+        let key = get_key(LOGIN_LOCKOUT_PREFIX, key);
+        let mut connection = self.conn.clone();
+
+        // Negative when the key does not exist or has no expiry
+        let ttl = connection
+            .ttl::<String, i64>(key)
+            .await
+            .map_err(|_| LoginAttemptStoreError::UnexpectedError)?;
+
+        Ok((ttl > 0).then(|| Duration::from_secs(ttl as u64)))
+    }
+
+    async fn reset(&self, key: &LoginAttemptKey) -> Result<(), LoginAttemptStoreError> {
+        let keys = [
+            get_key(LOGIN_FAILURES_PREFIX, key),
+            get_key(LOGIN_LOCKOUT_PREFIX, key),
+        ];
+        let mut connection = self.conn.clone();
+
+        connection
+            .del::<&[String], ()>(&keys)
+            .await
+            .map_err(|_| LoginAttemptStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+}
+
+const LOGIN_FAILURES_PREFIX: &str = "login_failures:";
+const LOGIN_LOCKOUT_PREFIX: &str = "login_lockout:";
+
+fn get_key(prefix: &str, key: &LoginAttemptKey) -> String {
+    match key {
+        LoginAttemptKey::Email(email) => format!("{}email:{}", prefix, email.as_ref()),
+        LoginAttemptKey::Ip(ip) => format!("{}ip:{}", prefix, ip),
+        LoginAttemptKey::TwoFactor(email) => format!("{}two_fa:{}", prefix, email.as_ref()),
+    }
+}
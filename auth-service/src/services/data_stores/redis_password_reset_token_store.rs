@@ -0,0 +1,118 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use redis::{aio::ConnectionManager, AsyncCommands};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    domain::{Email, PasswordResetToken, PasswordResetTokenStore, PasswordResetTokenStoreError},
+    utils::constants::PASSWORD_RESET_TOKEN_TTL_SECONDS,
+};
+
+pub struct RedisPasswordResetTokenStore {
+    conn: ConnectionManager,
+}
+
+impl RedisPasswordResetTokenStore {
+    pub fn new(conn: ConnectionManager) -> Self {
+        Self { conn }
+    }
+}
+
+#[async_trait::async_trait]
+impl PasswordResetTokenStore for RedisPasswordResetTokenStore {
+    async fn add_token(
+        &self,
+        token: PasswordResetToken,
+        email: Email,
+    ) -> Result<(), PasswordResetTokenStoreError> {
+        let key = get_key(&token);
+        let owner_key = get_owner_key(&email);
+        let mut connection = self.conn.clone();
+
+        connection
+            .set_ex::<&str, String, ()>(
+                &key,
+                email.as_ref().to_owned(),
+                PASSWORD_RESET_TOKEN_TTL_SECONDS,
+            )
+            .await
+            .map_err(|_| PasswordResetTokenStoreError::UnexpectedError)?;
+
+        // The set of the owner lives as long as its newest token
+        connection
+            .sadd::<&str, &str, ()>(&owner_key, &key)
+            .await
+            .map_err(|_| PasswordResetTokenStoreError::UnexpectedError)?;
+        connection
+            .expire::<&str, ()>(&owner_key, PASSWORD_RESET_TOKEN_TTL_SECONDS as i64)
+            .await
+            .map_err(|_| PasswordResetTokenStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    async fn get_token(
+        &self,
+        token: &PasswordResetToken,
+    ) -> Result<Email, PasswordResetTokenStoreError> {
+        let key = get_key(token);
+        let mut connection = self.conn.clone();
+
+        let email: Option<String> = connection
+            .get::<String, Option<String>>(key)
+            .await
+            .map_err(|_| PasswordResetTokenStoreError::UnexpectedError)?;
+        let email = email.ok_or(PasswordResetTokenStoreError::TokenNotFound)?;
+
+        Email::parse(email).map_err(|_| PasswordResetTokenStoreError::UnexpectedError)
+    }
+
+    async fn take_token(
+        &self,
+        token: &PasswordResetToken,
+    ) -> Result<Email, PasswordResetTokenStoreError> {
+        let key = get_key(token);
+        let mut connection = self.conn.clone();
+
+        let email: Option<String> = connection
+            .get_del::<String, Option<String>>(key)
+            .await
+            .map_err(|_| PasswordResetTokenStoreError::UnexpectedError)?;
+        let email = email.ok_or(PasswordResetTokenStoreError::TokenNotFound)?;
+
+        Email::parse(email).map_err(|_| PasswordResetTokenStoreError::UnexpectedError)
+    }
+
+    async fn remove_tokens(&self, email: &Email) -> Result<(), PasswordResetTokenStoreError> {
+        let owner_key = get_owner_key(email);
+        let mut connection = self.conn.clone();
+
+        let mut keys: Vec<String> = connection
+            .smembers::<&str, Vec<String>>(&owner_key)
+            .await
+            .map_err(|_| PasswordResetTokenStoreError::UnexpectedError)?;
+        keys.push(owner_key);
+
+        connection
+            .del::<&[String], ()>(&keys)
+            .await
+            .map_err(|_| PasswordResetTokenStoreError::UnexpectedError)
+    }
+}
+
+const PASSWORD_RESET_TOKEN_PREFIX: &str = "password_reset_token:";
+const PASSWORD_RESET_OWNER_PREFIX: &str = "password_reset_tokens_of:";
+
+// Only a hash of the token ends up in Redis, so a leaked key can not be used as a link
+fn get_key(token: &PasswordResetToken) -> String {
+    let digest = Sha256::digest(token.as_ref().as_bytes());
+    format!(
+        "{}{}",
+        PASSWORD_RESET_TOKEN_PREFIX,
+        URL_SAFE_NO_PAD.encode(digest)
+    )
+}
+
+// Keys of the tokens sent to the owner, so they can be removed along with the account
+fn get_owner_key(email: &Email) -> String {
+    format!("{}{}", PASSWORD_RESET_OWNER_PREFIX, email.as_ref())
+}
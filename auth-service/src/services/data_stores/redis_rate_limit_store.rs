@@ -0,0 +1,73 @@
+use std::time::Duration;
+
+use lazy_static::lazy_static;
+use redis::{aio::ConnectionManager, Script};
+
+use crate::domain::{RateLimitStore, RateLimitStoreError, TokenBucket};
+
+pub struct RedisRateLimitStore {
+    conn: ConnectionManager,
+}
+
+impl RedisRateLimitStore {
+    pub fn new(conn: ConnectionManager) -> Self {
+        Self { conn }
+    }
+}
+
+#[async_trait::async_trait]
+impl RateLimitStore for RedisRateLimitStore {
+    async fn take_token(
+        &self,
+        key: &str,
+        bucket: &TokenBucket,
+    ) -> Result<Option<Duration>, RateLimitStoreError> {
+        let key = format!("{}{}", RATE_LIMIT_PREFIX, key);
+        let refill_interval_ms = bucket.refill_interval().as_millis().max(1) as u64;
+        let mut connection = self.conn.clone();
+
+        let wait_ms: u64 = TAKE_TOKEN_SCRIPT
+            .key(key)
+            .arg(bucket.capacity)
+            .arg(refill_interval_ms)
+            .invoke_async(&mut connection)
+            .await
+            .map_err(|_| RateLimitStoreError::UnexpectedError)?;
+
+        Ok((wait_ms > 0).then(|| Duration::from_millis(wait_ms)))
+    }
+}
+
+const RATE_LIMIT_PREFIX: &str = "rate_limit:";
+
+lazy_static! {
+    // Refilling and taking a token happen in one script, so replicas sharing a bucket can not
+    // both take its last token. The clock of Redis is used for the same reason. Returns the
+    // milliseconds to wait for the next token, 0 when one was taken.
+    static ref TAKE_TOKEN_SCRIPT: Script = Script::new(
+        r"
+        local capacity = tonumber(ARGV[1])
+        local refill_interval = tonumber(ARGV[2])
+        local time = redis.call('TIME')
+        local now = tonumber(time[1]) * 1000 + math.floor(tonumber(time[2]) / 1000)
+
+        local bucket = redis.call('HMGET', KEYS[1], 'tokens', 'counted_at')
+        local tokens = tonumber(bucket[1]) or capacity
+        local counted_at = tonumber(bucket[2]) or now
+
+        tokens = math.min(capacity, tokens + math.max(0, now - counted_at) / refill_interval)
+
+        local wait = 0
+        if tokens >= 1 then
+            tokens = tokens - 1
+        else
+            wait = math.ceil((1 - tokens) * refill_interval)
+        end
+
+        redis.call('HSET', KEYS[1], 'tokens', tostring(tokens), 'counted_at', now)
+        redis.call('PEXPIRE', KEYS[1], math.ceil((capacity - tokens) * refill_interval) + 1)
+
+        return wait
+        "
+    );
+}
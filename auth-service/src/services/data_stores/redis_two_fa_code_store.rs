@@ -1,17 +1,14 @@
-use std::sync::Arc;
-use tokio::sync::RwLock;
-
-use redis::{Commands, Connection};
+use redis::{aio::ConnectionManager, AsyncCommands};
 use serde::{Deserialize, Serialize};
 
 use crate::domain::{Email, LoginAttemptId, TwoFACode, TwoFACodeStore, TwoFACodeStoreError};
 
 pub struct RedisTwoFaCodeStore {
-    conn: Arc<RwLock<Connection>>,
+    conn: ConnectionManager,
 }
 
 impl RedisTwoFaCodeStore {
-    pub fn new(conn: Arc<RwLock<Connection>>) -> Self {
+    pub fn new(conn: ConnectionManager) -> Self {
         Self { conn }
     }
 }
@@ -19,7 +16,7 @@ impl RedisTwoFaCodeStore {
 #[async_trait::async_trait]
 impl TwoFACodeStore for RedisTwoFaCodeStore {
     async fn add_code(
-        &mut self,
+        &self,
         email: Email,
         login_attempt_id: LoginAttemptId,
         code: TwoFACode,
@@ -32,20 +29,22 @@ impl TwoFACodeStore for RedisTwoFaCodeStore {
         let val =
             serde_json::to_string(&tuple).map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
 
-        let mut connection = self.conn.write().await;
+        let mut connection = self.conn.clone();
 
         connection
             .set_ex::<String, String, ()>(key, val, TEN_MINUTES_IN_SECONDS)
+            .await
             .map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
 
         Ok(())
     }
 
-    async fn remove_code(&mut self, email: &Email) -> Result<(), TwoFACodeStoreError> {
+    async fn remove_code(&self, email: &Email) -> Result<(), TwoFACodeStoreError> {
         let key = get_key(email);
-        let mut connection = self.conn.write().await;
+        let mut connection = self.conn.clone();
         connection
             .del::<String, ()>(key)
+            .await
             .map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
         Ok(())
     }
@@ -55,9 +54,10 @@ impl TwoFACodeStore for RedisTwoFaCodeStore {
         email: &Email,
     ) -> Result<(LoginAttemptId, TwoFACode), TwoFACodeStoreError> {
         let key = get_key(email);
-        let mut connection = self.conn.write().await;
+        let mut connection = self.conn.clone();
         let val: String = connection
             .get::<String, String>(key)
+            .await
             .map_err(|_| TwoFACodeStoreError::LoginAttemptIdNotFound)?;
         let tuple: TwoFaTuple =
             serde_json::from_str(&val).map_err(|_| TwoFACodeStoreError::UnexpectedError)?;
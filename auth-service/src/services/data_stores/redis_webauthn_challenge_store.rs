@@ -0,0 +1,90 @@
+use redis::{aio::ConnectionManager, AsyncCommands};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    domain::{
+        Email, WebAuthnCeremony, WebAuthnChallenge, WebAuthnChallengeStore,
+        WebAuthnChallengeStoreError,
+    },
+    utils::webauthn::WEBAUTHN_CHALLENGE_TTL_SECONDS,
+};
+
+pub struct RedisWebAuthnChallengeStore {
+    conn: ConnectionManager,
+}
+
+impl RedisWebAuthnChallengeStore {
+    pub fn new(conn: ConnectionManager) -> Self {
+        Self { conn }
+    }
+}
+
+#[async_trait::async_trait]
+impl WebAuthnChallengeStore for RedisWebAuthnChallengeStore {
+    async fn add_challenge(
+        &self,
+        ceremony_id: Uuid,
+        challenge: WebAuthnChallenge,
+    ) -> Result<(), WebAuthnChallengeStoreError> {
+        let key = get_key(&ceremony_id);
+        let record = ChallengeRecord {
+            ceremony: challenge.ceremony,
+            challenge: challenge.challenge,
+            email: challenge.email.map(|email| email.as_ref().to_owned()),
+        };
+        let val = serde_json::to_string(&record)
+            .map_err(|_| WebAuthnChallengeStoreError::UnexpectedError)?;
+
+        let mut connection = self.conn.clone();
+
+        connection
+            .set_ex::<String, String, ()>(key, val, WEBAUTHN_CHALLENGE_TTL_SECONDS)
+            .await
+            .map_err(|_| WebAuthnChallengeStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    async fn take_challenge(
+        &self,
+        ceremony_id: &Uuid,
+    ) -> Result<WebAuthnChallenge, WebAuthnChallengeStoreError> {
+        let key = get_key(ceremony_id);
+        let mut connection = self.conn.clone();
+
+        let val: Option<String> = connection
+            .get_del::<String, Option<String>>(key)
+            .await
+            .map_err(|_| WebAuthnChallengeStoreError::UnexpectedError)?;
+        let val = val.ok_or(WebAuthnChallengeStoreError::ChallengeNotFound)?;
+
+        let record: ChallengeRecord =
+            serde_json::from_str(&val).map_err(|_| WebAuthnChallengeStoreError::UnexpectedError)?;
+
+        let email = record
+            .email
+            .map(Email::parse)
+            .transpose()
+            .map_err(|_| WebAuthnChallengeStoreError::UnexpectedError)?;
+
+        Ok(WebAuthnChallenge {
+            ceremony: record.ceremony,
+            challenge: record.challenge,
+            email,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct ChallengeRecord {
+    ceremony: WebAuthnCeremony,
+    challenge: Vec<u8>,
+    email: Option<String>,
+}
+
+const WEBAUTHN_CHALLENGE_PREFIX: &str = "webauthn_challenge:";
+
+fn get_key(ceremony_id: &Uuid) -> String {
+    format!("{}{}", WEBAUTHN_CHALLENGE_PREFIX, ceremony_id)
+}
@@ -1,3 +1,11 @@
 pub mod auth;
 pub mod constants;
+pub mod jwt_key;
+pub mod jwt_keyring;
+pub mod rate_limit;
+pub mod request_id;
+pub mod secret_cipher;
+pub mod totp;
 pub mod tracing;
+pub mod user_import;
+pub mod webauthn;
@@ -1,15 +1,28 @@
 use axum_extra::extract::cookie::{Cookie, SameSite};
-use chrono::Utc;
-use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Validation};
+use chrono::{DateTime, Utc};
+use jsonwebtoken::{decode, decode_header, encode, Header, Validation};
 use serde::{Deserialize, Serialize};
+use uuid::Uuid;
 
-use crate::domain::{BannedTokenStore, Email};
+use crate::domain::{
+    AuthorizationRequest, BannedTokenStore, Email, RefreshToken, RefreshTokenRecord,
+    RefreshTokenStore, Scope, SessionStore, User, UserStore, EMAIL_SCOPE,
+};
 
-use super::constants::{JWT_COOKIE_NAME, JWT_SECRET};
+use super::{
+    constants::{JWT_COOKIE_NAME, REFRESH_TOKEN_COOKIE_NAME},
+    jwt_key::JwtKey,
+    jwt_keyring::JwtKeyring,
+};
 
-// Create cookie with a new JWT auth token
-pub fn generate_auth_cookie(email: &Email) -> Result<Cookie<'static>, GenerateTokenError> {
-    let token = generate_auth_token(email)?;
+// Create cookie with a new JWT auth token for the given session and current token version of the user
+pub fn generate_auth_cookie(
+    email: &Email,
+    session_id: Uuid,
+    token_version: u32,
+    jwt_keyring: &JwtKeyring,
+) -> Result<Cookie<'static>, GenerateTokenError> {
+    let token = generate_auth_token(email, session_id, token_version, jwt_keyring)?;
     Ok(create_auth_cookie(token))
 }
 
@@ -24,6 +37,52 @@ fn create_auth_cookie(token: String) -> Cookie<'static> {
     cookie
 }
 
+// Create cookie with a new refresh token, stored as the first member of the given token family
+pub async fn generate_refresh_cookie(
+    email: &Email,
+    family_id: Uuid,
+    refresh_token_store: &dyn RefreshTokenStore,
+) -> Result<Cookie<'static>, GenerateTokenError> {
+    let token = RefreshToken::default();
+    let expires_at = refresh_token_expiry()?;
+    let record = RefreshTokenRecord {
+        family_id,
+        email: email.clone(),
+        expires_at,
+    };
+
+    refresh_token_store
+        .add_token(token.clone(), record)
+        .await
+        .map_err(|_| GenerateTokenError::UnexpectedError)?;
+
+    Ok(create_refresh_cookie(token, expires_at))
+}
+
+pub fn refresh_token_expiry() -> Result<DateTime<Utc>, GenerateTokenError> {
+    let delta = chrono::Duration::try_seconds(REFRESH_TOKEN_TTL_SECONDS as i64)
+        .ok_or(GenerateTokenError::UnexpectedError)?;
+
+    Utc::now()
+        .checked_add_signed(delta)
+        .ok_or(GenerateTokenError::UnexpectedError)
+}
+
+// Create a persistent cookie holding the refresh token, unlike the JWT cookie it outlives the browser session.
+// It is kept as long as the token is valid.
+pub fn create_refresh_cookie(token: RefreshToken, expires_at: DateTime<Utc>) -> Cookie<'static> {
+    let max_age = (expires_at - Utc::now()).num_seconds().max(0);
+
+    let cookie = Cookie::build((REFRESH_TOKEN_COOKIE_NAME, token.as_ref().to_owned()))
+        .path("/")
+        .http_only(true)
+        .same_site(SameSite::Lax)
+        .max_age(time::Duration::seconds(max_age))
+        .build();
+
+    cookie
+}
+
 #[derive(Debug)]
 pub enum GenerateTokenError {
     TokenError(jsonwebtoken::errors::Error),
@@ -33,72 +92,403 @@ pub enum GenerateTokenError {
 // This value determines how long the JWT auth token is valid for
 pub const TOKEN_TTL_SECONDS: u64 = 600; // 10 minutes
 
+// Auth tokens are still accepted for this long after their `exp`, to allow for clock skew
+pub const TOKEN_EXPIRY_LEEWAY_SECONDS: u64 = 60;
+
+// Retired signing keys keep validating for this long, until the last token they signed expired
+pub const JWT_KEY_OVERLAP_SECONDS: u64 = TOKEN_TTL_SECONDS + TOKEN_EXPIRY_LEEWAY_SECONDS;
+
+// This value determines how long a refresh token can be exchanged for a new JWT auth token
+pub const REFRESH_TOKEN_TTL_SECONDS: u64 = 1_209_600; // 14 days
+
 // Create JWT auth token
-fn generate_auth_token(email: &Email) -> Result<String, GenerateTokenError> {
+fn generate_auth_token(
+    email: &Email,
+    session_id: Uuid,
+    token_version: u32,
+    jwt_keyring: &JwtKeyring,
+) -> Result<String, GenerateTokenError> {
+    generate_access_token(email.as_ref(), session_id, token_version, None, jwt_keyring)
+}
+
+// Create the access token handed to an OAuth client, limited to the scope the user granted it.
+// Like the ID token it names the user by account id, the email is only released with its scope.
+pub fn generate_client_access_token(
+    user: &User,
+    session_id: Uuid,
+    token_version: u32,
+    client_id: &str,
+    scope: &Scope,
+    jwt_keyring: &JwtKeyring,
+) -> Result<String, GenerateTokenError> {
+    generate_access_token(
+        &user.id.to_string(),
+        session_id,
+        token_version,
+        Some((client_id, scope)),
+        jwt_keyring,
+    )
+}
+
+fn generate_access_token(
+    sub: &str,
+    session_id: Uuid,
+    token_version: u32,
+    client: Option<(&str, &Scope)>,
+    jwt_keyring: &JwtKeyring,
+) -> Result<String, GenerateTokenError> {
+    let (iat, exp) = access_token_lifetime()?;
+
+    let claims = Claims {
+        sub: sub.to_owned(),
+        exp,
+        iat: Some(iat),
+        jti: Uuid::new_v4(),
+        sid: Some(session_id),
+        ver: Some(token_version),
+        client_id: client.map(|(client_id, _)| client_id.to_owned()),
+        scope: client.map(|(_, scope)| scope.to_string()),
+    };
+
+    create_token(&claims, jwt_keyring).map_err(GenerateTokenError::TokenError)
+}
+
+// Create the access token of a machine client, issued to the client itself rather than a user
+pub fn generate_machine_access_token(
+    client_id: &str,
+    scope: &Scope,
+    jwt_keyring: &JwtKeyring,
+) -> Result<String, GenerateTokenError> {
+    let (iat, exp) = access_token_lifetime()?;
+
+    let claims = Claims {
+        sub: client_id.to_owned(),
+        exp,
+        iat: Some(iat),
+        jti: Uuid::new_v4(),
+        sid: None,
+        ver: None,
+        client_id: Some(client_id.to_owned()),
+        scope: Some(scope.to_string()),
+    };
+
+    create_token(&claims, jwt_keyring).map_err(GenerateTokenError::TokenError)
+}
+
+// Issue and expiration time of an access token created now
+fn access_token_lifetime() -> Result<(usize, usize), GenerateTokenError> {
     let delta = chrono::Duration::try_seconds(TOKEN_TTL_SECONDS as i64)
         .ok_or(GenerateTokenError::UnexpectedError)?;
 
+    let now = Utc::now();
+
     // Create JWT expiration time
-    let exp = Utc::now()
+    let exp = now
         .checked_add_signed(delta)
         .ok_or(GenerateTokenError::UnexpectedError)?
         .timestamp();
 
-    // Cast exp to a usize, which is what Claims expects
-    let exp: usize = exp
-        .try_into()
-        .map_err(|_| GenerateTokenError::UnexpectedError)?;
+    // Cast both to a usize, which is what Claims expects
+    match (now.timestamp().try_into(), exp.try_into()) {
+        (Ok(iat), Ok(exp)) => Ok((iat, exp)),
+        _ => Err(GenerateTokenError::UnexpectedError),
+    }
+}
 
-    let sub = email.as_ref().to_owned();
+// Check if JWT auth token is valid by decoding it using the key named in its `kid` header. The
+// session it was issued for must not have been revoked either, nor all tokens of its user. With
+// required scopes, only tokens that were granted every one of them are valid.
+pub async fn validate_token(
+    token: &str,
+    required_scopes: &[&str],
+    banned_tokens: &dyn BannedTokenStore,
+    sessions: &dyn SessionStore,
+    users: &dyn UserStore,
+    jwt_keyring: &JwtKeyring,
+) -> Result<Claims, jsonwebtoken::errors::Error> {
+    let invalid_token =
+        || jsonwebtoken::errors::Error::from(jsonwebtoken::errors::ErrorKind::InvalidToken);
+
+    let claims = decode_auth_token(token, jwt_keyring)?;
 
-    let claims = Claims { sub, exp };
+    if !required_scopes.iter().all(|scope| claims.has_scope(scope)) {
+        return Err(invalid_token());
+    }
+
+    if banned_tokens
+        .is_token_banned(&claims.jti)
+        .await
+        .unwrap_or(false)
+    {
+        return Err(invalid_token());
+    }
 
-    create_token(&claims).map_err(GenerateTokenError::TokenError)
+    let (session_id, token_version) = match (claims.sid, claims.ver) {
+        (Some(session_id), Some(token_version)) => (session_id, token_version),
+        // Tokens of machine clients have no session, they are only ended by their expiry or a ban
+        (None, None) if claims.client_id.is_some() => return Ok(claims),
+        _ => return Err(invalid_token()),
+    };
+
+    let session = sessions
+        .get_session(&session_id)
+        .await
+        .map_err(|_| invalid_token())?;
+
+    // Tokens of OAuth clients name the user by account id rather than email
+    let same_user = match claims.client_id {
+        Some(_) => users
+            .get_user(&session.email)
+            .await
+            .is_ok_and(|user| user.id.to_string() == claims.sub),
+        None => session.email.as_ref() == claims.sub,
+    };
+    if !same_user {
+        return Err(invalid_token());
+    }
+
+    match users.get_token_version(&session.email).await {
+        Ok(version) if token_version >= version => Ok(claims),
+        _ => Err(invalid_token()),
+    }
 }
 
-// Check if JWT auth token is valid by decoding it using the JWT secret
-pub async fn validate_token(
+// Decode a JWT auth token and check its signature and expiry, without looking at any store
+pub fn decode_auth_token(
     token: &str,
-    banned_tokens: &dyn BannedTokenStore,
+    jwt_keyring: &JwtKeyring,
 ) -> Result<Claims, jsonwebtoken::errors::Error> {
-    if banned_tokens.is_token_banned(token).await.unwrap_or(false) {
-        return Err(jsonwebtoken::errors::Error::from(
-            jsonwebtoken::errors::ErrorKind::InvalidToken,
-        ));
-    }
-    decode::<Claims>(
-        token,
-        &DecodingKey::from_secret(JWT_SECRET.as_bytes()),
-        &Validation::default(),
-    )
-    .map(|data| data.claims)
+    let jwt_key = find_key(token, jwt_keyring)?;
+
+    let mut validation = Validation::new(jwt_key.algorithm());
+    validation.leeway = TOKEN_EXPIRY_LEEWAY_SECONDS;
+
+    decode::<Claims>(token, jwt_key.decoding_key(), &validation).map(|data| data.claims)
 }
 
-// Create JWT auth token by encoding claims using the JWT secret
-fn create_token(claims: &Claims) -> Result<String, jsonwebtoken::errors::Error> {
-    encode(
-        &jsonwebtoken::Header::default(),
-        &claims,
-        &EncodingKey::from_secret(JWT_SECRET.as_bytes()),
-    )
+// Look up the key named in the `kid` header of a token
+fn find_key<'a>(
+    token: &str,
+    jwt_keyring: &'a JwtKeyring,
+) -> Result<&'a JwtKey, jsonwebtoken::errors::Error> {
+    // Tokens issued before keys carried a kid can only have been signed by the active key
+    match decode_header(token)?.kid {
+        Some(kid) => jwt_keyring.find(&kid).ok_or_else(|| {
+            jsonwebtoken::errors::Error::from(jsonwebtoken::errors::ErrorKind::InvalidToken)
+        }),
+        None => Ok(jwt_keyring.active_key()),
+    }
+}
+
+// Create JWT by encoding claims using the active key of the keyring
+fn create_token<T: Serialize>(
+    claims: &T,
+    jwt_keyring: &JwtKeyring,
+) -> Result<String, jsonwebtoken::errors::Error> {
+    let jwt_key = jwt_keyring.active_key();
+
+    let header = Header {
+        kid: Some(jwt_key.kid().to_owned()),
+        ..Header::new(jwt_key.algorithm())
+    };
+
+    encode(&header, &claims, jwt_key.encoding_key())
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Claims {
     pub sub: String,
     pub exp: usize,
+    // Missing from tokens issued before it was added
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub iat: Option<usize>,
+    // Unique id of the token
+    pub jti: Uuid,
+    // Session the token was issued for, none for tokens of machine clients
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sid: Option<Uuid>,
+    // Token version of the user at the time the token was issued, none for machine clients too
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ver: Option<u32>,
+    // Only set for tokens issued to an OAuth client, which are limited to the granted scope
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+}
+
+impl Claims {
+    pub fn has_scope(&self, scope: &str) -> bool {
+        self.scope
+            .as_deref()
+            .is_some_and(|granted| granted.split(' ').any(|granted| granted == scope))
+    }
+
+    // Last moment the token is accepted, a ban on it does not need to outlast this
+    pub fn valid_until(&self) -> DateTime<Utc> {
+        let valid_until = self.exp as i64 + TOKEN_EXPIRY_LEEWAY_SECONDS as i64;
+        DateTime::from_timestamp(valid_until, 0).unwrap_or(DateTime::<Utc>::MAX_UTC)
+    }
+}
+
+// This value determines how long a user has to log in and allow the request of an OAuth client
+pub const AUTHORIZATION_REQUEST_TOKEN_TTL_SECONDS: u64 = 600; // 10 minutes
+
+// Auth tokens carry no audience, which makes `validate_token` reject these tokens and their
+// validator reject auth tokens
+const AUTHORIZATION_REQUEST_AUDIENCE: &str = "authorization-request";
+
+// Create the signed token that carries a validated authorization request through the login and
+// consent pages, so nothing has to be stored until the user allowed it
+pub fn generate_authorization_request_token(
+    request: &AuthorizationRequest,
+    jwt_keyring: &JwtKeyring,
+) -> Result<String, GenerateTokenError> {
+    let delta = chrono::Duration::try_seconds(AUTHORIZATION_REQUEST_TOKEN_TTL_SECONDS as i64)
+        .ok_or(GenerateTokenError::UnexpectedError)?;
+
+    let exp: usize = Utc::now()
+        .checked_add_signed(delta)
+        .ok_or(GenerateTokenError::UnexpectedError)?
+        .timestamp()
+        .try_into()
+        .map_err(|_| GenerateTokenError::UnexpectedError)?;
+
+    let claims = AuthorizationRequestClaims {
+        aud: AUTHORIZATION_REQUEST_AUDIENCE.to_owned(),
+        exp,
+        request: request.clone(),
+    };
+
+    create_token(&claims, jwt_keyring).map_err(GenerateTokenError::TokenError)
+}
+
+pub fn validate_authorization_request_token(
+    token: &str,
+    jwt_keyring: &JwtKeyring,
+) -> Result<AuthorizationRequest, jsonwebtoken::errors::Error> {
+    let jwt_key = find_key(token, jwt_keyring)?;
+
+    let mut validation = Validation::new(jwt_key.algorithm());
+    validation.set_audience(&[AUTHORIZATION_REQUEST_AUDIENCE]);
+
+    decode::<AuthorizationRequestClaims>(token, jwt_key.decoding_key(), &validation)
+        .map(|data| data.claims.request)
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AuthorizationRequestClaims {
+    pub aud: String,
+    pub exp: usize,
+    #[serde(flatten)]
+    pub request: AuthorizationRequest,
+}
+
+// This value determines how long an ID token is valid for
+pub const ID_TOKEN_TTL_SECONDS: u64 = 600; // 10 minutes
+
+// Create the OpenID Connect ID token telling a client who signed in. The subject is the id of the
+// account, which unlike the email never changes.
+pub fn generate_id_token(
+    issuer: &str,
+    user: &User,
+    client_id: &str,
+    scope: &Scope,
+    nonce: Option<&str>,
+    jwt_keyring: &JwtKeyring,
+) -> Result<String, GenerateTokenError> {
+    // Clients could not validate a token signed with a shared secret
+    if jwt_keyring.active_key().jwk().is_none() {
+        return Err(GenerateTokenError::UnexpectedError);
+    }
+
+    let delta = chrono::Duration::try_seconds(ID_TOKEN_TTL_SECONDS as i64)
+        .ok_or(GenerateTokenError::UnexpectedError)?;
+
+    let now = Utc::now();
+    let iat: usize = now
+        .timestamp()
+        .try_into()
+        .map_err(|_| GenerateTokenError::UnexpectedError)?;
+    let exp: usize = now
+        .checked_add_signed(delta)
+        .ok_or(GenerateTokenError::UnexpectedError)?
+        .timestamp()
+        .try_into()
+        .map_err(|_| GenerateTokenError::UnexpectedError)?;
+
+    let with_email = scope.contains(EMAIL_SCOPE);
+    let claims = IdTokenClaims {
+        iss: issuer.to_owned(),
+        sub: user.id.to_string(),
+        aud: client_id.to_owned(),
+        exp,
+        iat,
+        nonce: nonce.map(str::to_owned),
+        email: with_email.then(|| user.email.as_ref().to_owned()),
+        email_verified: with_email.then_some(user.email_verified),
+    };
+
+    create_token(&claims, jwt_keyring).map_err(GenerateTokenError::TokenError)
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct IdTokenClaims {
+    pub iss: String,
+    pub sub: String,
+    pub aud: String,
+    pub exp: usize,
+    pub iat: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nonce: Option<String>,
+    // Only with the email scope
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub email_verified: Option<bool>,
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::services::HashsetBannedTokenStore;
+    use crate::{
+        domain::{Password, Session, User},
+        services::{
+            HashMapBannedTokenStore, HashMapRefreshTokenStore, HashMapSessionStore,
+            HashMapUserStore,
+        },
+    };
 
     use super::*;
 
+    fn get_jwt_keyring() -> JwtKeyring {
+        JwtKeyring::new(
+            JwtKey::from_secret(b"secret"),
+            chrono::Duration::minutes(10),
+        )
+    }
+
+    async fn get_session_store(email: &Email) -> (HashMapSessionStore, Uuid) {
+        let sessions = HashMapSessionStore::default();
+        let session = Session::new(email.clone(), None, None);
+        let session_id = session.id;
+        sessions.add_session(session).await.unwrap();
+        (sessions, session_id)
+    }
+
+    async fn get_user_store(email: &Email) -> HashMapUserStore {
+        let users = HashMapUserStore::default();
+        let password = Password::parse("password123".to_owned()).unwrap();
+        users
+            .add_user(User::new(email.clone(), false), password)
+            .await
+            .unwrap();
+        users
+    }
+
     #[tokio::test]
     async fn test_generate_auth_cookie() {
         let email = Email::parse("test@example.com".to_owned()).unwrap();
-        let cookie = generate_auth_cookie(&email).unwrap();
+        let cookie = generate_auth_cookie(&email, Uuid::new_v4(), 0, &get_jwt_keyring()).unwrap();
         assert_eq!(cookie.name(), JWT_COOKIE_NAME);
         assert_eq!(cookie.value().split('.').count(), 3);
         assert_eq!(cookie.path(), Some("/"));
@@ -117,21 +507,55 @@ mod tests {
         assert_eq!(cookie.same_site(), Some(SameSite::Lax));
     }
 
+    #[tokio::test]
+    async fn test_generate_refresh_cookie() {
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+        let family_id = Uuid::new_v4();
+        let store = HashMapRefreshTokenStore::default();
+
+        let cookie = generate_refresh_cookie(&email, family_id, &store)
+            .await
+            .unwrap();
+        assert_eq!(cookie.name(), REFRESH_TOKEN_COOKIE_NAME);
+        assert_eq!(cookie.path(), Some("/"));
+        assert_eq!(cookie.http_only(), Some(true));
+        assert!(cookie.max_age().is_some());
+
+        let token = RefreshToken::parse(cookie.value().to_owned()).unwrap();
+        let record = store.get_token(&token).await.unwrap();
+        assert_eq!(record.family_id, family_id);
+        assert_eq!(record.email, email);
+        assert!(record.expires_at > Utc::now());
+    }
+
     #[tokio::test]
     async fn test_generate_auth_token() {
         let email = Email::parse("test@example.com".to_owned()).unwrap();
-        let result = generate_auth_token(&email).unwrap();
+        let session_id = Uuid::new_v4();
+        let result = generate_auth_token(&email, session_id, 0, &get_jwt_keyring()).unwrap();
         assert_eq!(result.split('.').count(), 3);
     }
 
     #[tokio::test]
     async fn test_validate_token_with_valid_token() {
         let email = Email::parse("test@example.com".to_owned()).unwrap();
-        let token = generate_auth_token(&email).unwrap();
+        let (sessions, session_id) = get_session_store(&email).await;
+        let users = get_user_store(&email).await;
+        let jwt_keyring = get_jwt_keyring();
+        let token = generate_auth_token(&email, session_id, 0, &jwt_keyring).unwrap();
 
-        let banned_token_source = HashsetBannedTokenStore::default();
+        let banned_token_source = HashMapBannedTokenStore::default();
 
-        let result = validate_token(&token, &banned_token_source).await.unwrap();
+        let result = validate_token(
+            &token,
+            &[],
+            &banned_token_source,
+            &sessions,
+            &users,
+            &jwt_keyring,
+        )
+        .await
+        .unwrap();
 
         assert_eq!(result.sub, "test@example.com");
 
@@ -145,19 +569,262 @@ mod tests {
 
     #[tokio::test]
     async fn test_validate_token_with_invalid_token() {
+        let jwt_keyring = get_jwt_keyring();
         let token = "invalid_token".to_owned();
-        let banned_token_source = HashsetBannedTokenStore::default();
-        let result = validate_token(&token, &banned_token_source).await;
+        let banned_token_source = HashMapBannedTokenStore::default();
+        let sessions = HashMapSessionStore::default();
+        let users = HashMapUserStore::default();
+        let result = validate_token(
+            &token,
+            &[],
+            &banned_token_source,
+            &sessions,
+            &users,
+            &jwt_keyring,
+        )
+        .await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_validate_token_with_other_key() {
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+        let (sessions, session_id) = get_session_store(&email).await;
+        let users = get_user_store(&email).await;
+        let token = generate_auth_token(&email, session_id, 0, &get_jwt_keyring()).unwrap();
+        let jwt_keyring = JwtKeyring::new(
+            JwtKey::from_secret(b"another secret"),
+            chrono::Duration::minutes(10),
+        );
+        let banned_token_source = HashMapBannedTokenStore::default();
+        let result = validate_token(
+            &token,
+            &[],
+            &banned_token_source,
+            &sessions,
+            &users,
+            &jwt_keyring,
+        )
+        .await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_generate_auth_token_sets_kid() {
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+        let session_id = Uuid::new_v4();
+        let jwt_keyring = get_jwt_keyring();
+        let token = generate_auth_token(&email, session_id, 0, &jwt_keyring).unwrap();
+        let header = decode_header(&token).unwrap();
+        assert_eq!(header.kid.as_deref(), Some(jwt_keyring.active_key().kid()));
+    }
+
+    #[tokio::test]
+    async fn test_validate_token_signed_with_retired_key_within_overlap() {
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+        let (sessions, session_id) = get_session_store(&email).await;
+        let users = get_user_store(&email).await;
+        let mut jwt_keyring = get_jwt_keyring();
+        let token = generate_auth_token(&email, session_id, 0, &jwt_keyring).unwrap();
+
+        jwt_keyring.promote(JwtKey::from_secret(b"another secret"));
+
+        let banned_token_source = HashMapBannedTokenStore::default();
+        let result = validate_token(
+            &token,
+            &[],
+            &banned_token_source,
+            &sessions,
+            &users,
+            &jwt_keyring,
+        )
+        .await
+        .unwrap();
+        assert_eq!(result.sub, "test@example.com");
+    }
+
+    #[tokio::test]
+    async fn test_validate_token_signed_with_retired_key_after_overlap() {
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+        let (sessions, session_id) = get_session_store(&email).await;
+        let users = get_user_store(&email).await;
+        let mut jwt_keyring =
+            JwtKeyring::new(JwtKey::from_secret(b"secret"), chrono::Duration::zero());
+        let token = generate_auth_token(&email, session_id, 0, &jwt_keyring).unwrap();
+
+        jwt_keyring.promote(JwtKey::from_secret(b"another secret"));
+
+        let banned_token_source = HashMapBannedTokenStore::default();
+        let result = validate_token(
+            &token,
+            &[],
+            &banned_token_source,
+            &sessions,
+            &users,
+            &jwt_keyring,
+        )
+        .await;
         assert!(result.is_err());
     }
 
     #[tokio::test]
     async fn test_validate_token_with_banned_token() {
         let email = Email::parse("test@example.com".to_owned()).unwrap();
-        let token = generate_auth_token(&email).unwrap();
-        let mut banned_token_source = HashsetBannedTokenStore::default();
-        banned_token_source.ban_token(&token).await.expect("Failed to ban token");
-        let result = validate_token(&token, &banned_token_source).await;
+        let (sessions, session_id) = get_session_store(&email).await;
+        let users = get_user_store(&email).await;
+        let jwt_keyring = get_jwt_keyring();
+        let token = generate_auth_token(&email, session_id, 0, &jwt_keyring).unwrap();
+        let banned_token_source = HashMapBannedTokenStore::default();
+        let claims = decode_auth_token(&token, &jwt_keyring).unwrap();
+        banned_token_source
+            .ban_token(&claims.jti, claims.valid_until())
+            .await
+            .expect("Failed to ban token");
+        let result = validate_token(
+            &token,
+            &[],
+            &banned_token_source,
+            &sessions,
+            &users,
+            &jwt_keyring,
+        )
+        .await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_validate_token_with_revoked_session() {
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+        let jwt_keyring = get_jwt_keyring();
+        let (sessions, session_id) = get_session_store(&email).await;
+        let users = get_user_store(&email).await;
+        let token = generate_auth_token(&email, session_id, 0, &jwt_keyring).unwrap();
+        let banned_token_source = HashMapBannedTokenStore::default();
+
+        sessions.remove_session(&email, &session_id).await.unwrap();
+
+        let result = validate_token(
+            &token,
+            &[],
+            &banned_token_source,
+            &sessions,
+            &users,
+            &jwt_keyring,
+        )
+        .await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_validate_token_with_outdated_token_version() {
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+        let jwt_keyring = get_jwt_keyring();
+        let (sessions, session_id) = get_session_store(&email).await;
+        let users = get_user_store(&email).await;
+        let token = generate_auth_token(&email, session_id, 0, &jwt_keyring).unwrap();
+        let banned_token_source = HashMapBannedTokenStore::default();
+
+        let version = users.bump_token_version(&email).await.unwrap();
+
+        let result = validate_token(
+            &token,
+            &[],
+            &banned_token_source,
+            &sessions,
+            &users,
+            &jwt_keyring,
+        )
+        .await;
+        assert!(result.is_err());
+
+        let token = generate_auth_token(&email, session_id, version, &jwt_keyring).unwrap();
+        let result = validate_token(
+            &token,
+            &[],
+            &banned_token_source,
+            &sessions,
+            &users,
+            &jwt_keyring,
+        )
+        .await;
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_validate_token_with_session_of_other_user() {
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+        let other_email = Email::parse("other@example.com".to_owned()).unwrap();
+        let jwt_keyring = get_jwt_keyring();
+        let (sessions, session_id) = get_session_store(&other_email).await;
+        let users = get_user_store(&email).await;
+        let token = generate_auth_token(&email, session_id, 0, &jwt_keyring).unwrap();
+        let banned_token_source = HashMapBannedTokenStore::default();
+
+        let result = validate_token(
+            &token,
+            &[],
+            &banned_token_source,
+            &sessions,
+            &users,
+            &jwt_keyring,
+        )
+        .await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_validate_machine_token_with_required_scopes() {
+        let jwt_keyring = get_jwt_keyring();
+        let sessions = HashMapSessionStore::default();
+        let users = HashMapUserStore::default();
+        let banned_token_source = HashMapBannedTokenStore::default();
+        let scope = Scope::parse("reports:read reports:write").unwrap();
+        let token = generate_machine_access_token("billing-job", &scope, &jwt_keyring).unwrap();
+
+        let claims = validate_token(
+            &token,
+            &["reports:read"],
+            &banned_token_source,
+            &sessions,
+            &users,
+            &jwt_keyring,
+        )
+        .await
+        .unwrap();
+        assert_eq!(claims.sub, "billing-job");
+        assert_eq!(claims.sid, None);
+
+        let result = validate_token(
+            &token,
+            &["reports:read", "invoices:read"],
+            &banned_token_source,
+            &sessions,
+            &users,
+            &jwt_keyring,
+        )
+        .await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_validate_login_token_with_required_scopes() {
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+        let jwt_keyring = get_jwt_keyring();
+        let (sessions, session_id) = get_session_store(&email).await;
+        let users = get_user_store(&email).await;
+        let token = generate_auth_token(&email, session_id, 0, &jwt_keyring).unwrap();
+        let banned_token_source = HashMapBannedTokenStore::default();
+
+        // Tokens of our own login carry no scope, so they never satisfy one
+        let result = validate_token(
+            &token,
+            &["reports:read"],
+            &banned_token_source,
+            &sessions,
+            &users,
+            &jwt_keyring,
+        )
+        .await;
         assert!(result.is_err());
     }
 }
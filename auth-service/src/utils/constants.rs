@@ -1,12 +1,37 @@
+use std::time::Duration;
+
 use dotenvy::dotenv;
 use lazy_static::lazy_static;
 
-use crate::utils::constants::env::DATABASE_URL_ENV_VAR;
+use crate::{
+    domain::{CharacterClass, PasswordHashParams, PasswordPolicy, RateLimitRule},
+    utils::constants::env::DATABASE_URL_ENV_VAR,
+};
 
 lazy_static! {
     pub static ref JWT_SECRET: String = set_token();
     pub static ref DATABASE_URL: String = set_db_url();
     pub static ref REDIS_HOST_NAME: String = set_redis_host_name();
+    pub static ref JWT_PRIVATE_KEY_PATH: Option<String> = set_jwt_private_key_path();
+    pub static ref JWT_RETIRED_KEY_PATHS: Vec<String> = set_jwt_retired_key_paths();
+    pub static ref JWT_KEY_ROTATION_INTERVAL: Option<Duration> = set_jwt_key_rotation_interval();
+    // Only needed when keys are rotated, they are kept apart from the TOTP secrets
+    pub static ref JWT_KEY_ENCRYPTION_KEY: Option<String> = set_jwt_key_encryption_key();
+    pub static ref TOTP_ENCRYPTION_KEY: String = set_totp_encryption_key();
+    pub static ref TOTP_ALLOWED_SKEW_STEPS: u8 = set_totp_allowed_skew_steps();
+    pub static ref WEBAUTHN_RP_ID: String = set_webauthn_rp_id();
+    pub static ref WEBAUTHN_RP_ORIGIN: String = set_webauthn_rp_origin();
+    pub static ref PASSWORD_RESET_URL: String = set_password_reset_url();
+    pub static ref EMAIL_VERIFICATION_URL: String = set_email_verification_url();
+    pub static ref EMAIL_CHANGE_URL: String = set_email_change_url();
+    pub static ref REQUIRE_EMAIL_VERIFICATION: bool = set_require_email_verification();
+    pub static ref RATE_LIMITS: Vec<RateLimitRule> = set_rate_limits();
+    pub static ref PASSWORD_HASH_PARAMS: PasswordHashParams = set_password_hash_params();
+    // Without the breached password corpus, which is loaded from BREACHED_PASSWORDS_PATH at startup
+    pub static ref PASSWORD_POLICY: PasswordPolicy = set_password_policy();
+    pub static ref BREACHED_PASSWORDS_PATH: Option<String> = set_breached_passwords_path();
+    pub static ref OIDC_ISSUER: String = set_oidc_issuer();
+    pub static ref APP_SERVICE_CLIENT_SECRET: Option<String> = set_app_service_client_secret();
 }
 
 fn set_token() -> String {
@@ -24,8 +49,8 @@ fn set_token() -> String {
 fn set_db_url() -> String {
     dotenv().ok();
 
-    let db_url =
-        std::env::var(DATABASE_URL_ENV_VAR).expect("DATABASE_URL must be set in environment variables");
+    let db_url = std::env::var(DATABASE_URL_ENV_VAR)
+        .expect("DATABASE_URL must be set in environment variables");
 
     if db_url.is_empty() {
         panic!("DATABASE_URL must not be empty");
@@ -36,18 +61,284 @@ fn set_db_url() -> String {
 
 fn set_redis_host_name() -> String {
     dotenv().ok();
-    std::env::var(env::REDIS_HOST_NAME_ENV_VAR)
-      .unwrap_or(DEFAULT_REDIS_HOST_NAME.to_owned())
+    std::env::var(env::REDIS_HOST_NAME_ENV_VAR).unwrap_or(DEFAULT_REDIS_HOST_NAME.to_owned())
+}
+
+fn set_jwt_private_key_path() -> Option<String> {
+    dotenv().ok();
+    std::env::var(env::JWT_PRIVATE_KEY_PATH_ENV_VAR)
+        .ok()
+        .filter(|path| !path.is_empty())
+}
+
+fn set_jwt_key_encryption_key() -> Option<String> {
+    dotenv().ok();
+    std::env::var(env::JWT_KEY_ENCRYPTION_KEY_ENV_VAR)
+        .ok()
+        .filter(|key| !key.is_empty())
+}
+
+fn set_jwt_retired_key_paths() -> Vec<String> {
+    dotenv().ok();
+    std::env::var(env::JWT_RETIRED_KEY_PATHS_ENV_VAR)
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|path| !path.is_empty())
+        .map(str::to_owned)
+        .collect()
+}
+
+fn set_jwt_key_rotation_interval() -> Option<Duration> {
+    dotenv().ok();
+    std::env::var(env::JWT_KEY_ROTATION_INTERVAL_SECONDS_ENV_VAR)
+        .ok()
+        .map(|seconds| {
+            seconds
+                .parse()
+                .expect("JWT_KEY_ROTATION_INTERVAL_SECONDS must be a number of seconds")
+        })
+        .filter(|seconds| *seconds > 0)
+        .map(Duration::from_secs)
+}
+
+fn set_totp_encryption_key() -> String {
+    dotenv().ok();
+
+    let key = std::env::var(env::TOTP_ENCRYPTION_KEY_ENV_VAR)
+        .expect("TOTP_ENCRYPTION_KEY must be set in environment variables");
+
+    if key.is_empty() {
+        panic!("TOTP_ENCRYPTION_KEY must not be empty");
+    }
+    key
+}
+
+fn set_totp_allowed_skew_steps() -> u8 {
+    dotenv().ok();
+    std::env::var(env::TOTP_ALLOWED_SKEW_STEPS_ENV_VAR)
+        .ok()
+        .map(|steps| {
+            steps
+                .parse()
+                .expect("TOTP_ALLOWED_SKEW_STEPS must be a small non-negative number")
+        })
+        .unwrap_or(DEFAULT_TOTP_ALLOWED_SKEW_STEPS)
+}
+
+fn set_webauthn_rp_id() -> String {
+    dotenv().ok();
+    std::env::var(env::WEBAUTHN_RP_ID_ENV_VAR)
+        .ok()
+        .filter(|rp_id| !rp_id.is_empty())
+        .unwrap_or(DEFAULT_WEBAUTHN_RP_ID.to_owned())
+}
+
+fn set_webauthn_rp_origin() -> String {
+    dotenv().ok();
+    std::env::var(env::WEBAUTHN_RP_ORIGIN_ENV_VAR)
+        .ok()
+        .filter(|origin| !origin.is_empty())
+        .unwrap_or(DEFAULT_WEBAUTHN_RP_ORIGIN.to_owned())
+}
+
+fn set_password_reset_url() -> String {
+    dotenv().ok();
+    std::env::var(env::PASSWORD_RESET_URL_ENV_VAR)
+        .ok()
+        .filter(|url| !url.is_empty())
+        .unwrap_or(DEFAULT_PASSWORD_RESET_URL.to_owned())
+}
+
+fn set_email_verification_url() -> String {
+    dotenv().ok();
+    std::env::var(env::EMAIL_VERIFICATION_URL_ENV_VAR)
+        .ok()
+        .filter(|url| !url.is_empty())
+        .unwrap_or(DEFAULT_EMAIL_VERIFICATION_URL.to_owned())
+}
+
+fn set_email_change_url() -> String {
+    dotenv().ok();
+    std::env::var(env::EMAIL_CHANGE_URL_ENV_VAR)
+        .ok()
+        .filter(|url| !url.is_empty())
+        .unwrap_or(DEFAULT_EMAIL_CHANGE_URL.to_owned())
+}
+
+fn set_require_email_verification() -> bool {
+    dotenv().ok();
+    std::env::var(env::REQUIRE_EMAIL_VERIFICATION_ENV_VAR)
+        .ok()
+        .map(|value| {
+            value
+                .parse()
+                .expect("REQUIRE_EMAIL_VERIFICATION must be either true or false")
+        })
+        .unwrap_or(false)
+}
+
+fn set_rate_limits() -> Vec<RateLimitRule> {
+    dotenv().ok();
+    let rules = std::env::var(env::RATE_LIMITS_ENV_VAR).unwrap_or(DEFAULT_RATE_LIMITS.to_owned());
+
+    RateLimitRule::parse_list(&rules).unwrap_or_else(|e| panic!("RATE_LIMITS is invalid: {}", e))
+}
+
+// Each parameter falls back to its default on its own, so one can be raised without the others
+fn set_password_hash_params() -> PasswordHashParams {
+    dotenv().ok();
+    let defaults = PasswordHashParams::default();
+    let var = |name: &str, default: u32| {
+        std::env::var(name)
+            .ok()
+            .map(|value| {
+                value
+                    .parse()
+                    .unwrap_or_else(|_| panic!("{} must be a positive number", name))
+            })
+            .unwrap_or(default)
+    };
+
+    PasswordHashParams::new(
+        var(env::PASSWORD_HASH_MEMORY_KIB_ENV_VAR, defaults.memory_kib),
+        var(env::PASSWORD_HASH_ITERATIONS_ENV_VAR, defaults.iterations),
+        var(env::PASSWORD_HASH_PARALLELISM_ENV_VAR, defaults.parallelism),
+    )
+    .unwrap_or_else(|e| panic!("Password hash parameters are invalid: {}", e))
+}
+
+// Like the hash parameters, every rule falls back to its default on its own
+fn set_password_policy() -> PasswordPolicy {
+    dotenv().ok();
+    let defaults = PasswordPolicy::default();
+    let var = |name: &str| std::env::var(name).ok().filter(|value| !value.is_empty());
+
+    PasswordPolicy {
+        min_length: var(env::PASSWORD_MIN_LENGTH_ENV_VAR)
+            .map(|value| value.parse().expect("PASSWORD_MIN_LENGTH must be a number"))
+            .unwrap_or(defaults.min_length),
+        max_length: var(env::PASSWORD_MAX_LENGTH_ENV_VAR)
+            .map(|value| value.parse().expect("PASSWORD_MAX_LENGTH must be a number"))
+            .unwrap_or(defaults.max_length),
+        required_classes: var(env::PASSWORD_REQUIRED_CHARACTER_CLASSES_ENV_VAR)
+            .map(|value| {
+                CharacterClass::parse_list(&value).unwrap_or_else(|e| {
+                    panic!("PASSWORD_REQUIRED_CHARACTER_CLASSES is invalid: {}", e)
+                })
+            })
+            .unwrap_or(defaults.required_classes),
+        reject_email: var(env::PASSWORD_REJECT_EMAIL_ENV_VAR)
+            .map(|value| {
+                value
+                    .parse()
+                    .expect("PASSWORD_REJECT_EMAIL must be either true or false")
+            })
+            .unwrap_or(defaults.reject_email),
+        min_strength: var(env::PASSWORD_MIN_STRENGTH_ENV_VAR)
+            .map(|value| {
+                value
+                    .parse()
+                    .ok()
+                    .filter(|strength| *strength <= 4)
+                    .expect("PASSWORD_MIN_STRENGTH must be a number from 0 to 4")
+            })
+            .unwrap_or(defaults.min_strength),
+        breached_passwords: None,
+    }
+}
+
+fn set_breached_passwords_path() -> Option<String> {
+    dotenv().ok();
+    std::env::var(env::BREACHED_PASSWORDS_PATH_ENV_VAR)
+        .ok()
+        .filter(|path| !path.is_empty())
+}
+
+// The issuer is the public URL of the service, the OpenID Connect endpoints are announced under it
+fn set_oidc_issuer() -> String {
+    dotenv().ok();
+    std::env::var(env::OIDC_ISSUER_ENV_VAR)
+        .ok()
+        .filter(|issuer| !issuer.is_empty())
+        .unwrap_or(DEFAULT_OIDC_ISSUER.to_owned())
+        .trim_end_matches('/')
+        .to_owned()
+}
+
+fn set_app_service_client_secret() -> Option<String> {
+    dotenv().ok();
+    std::env::var(env::APP_SERVICE_CLIENT_SECRET_ENV_VAR)
+        .ok()
+        .filter(|secret| !secret.is_empty())
 }
 
 pub mod env {
     pub const DATABASE_URL_ENV_VAR: &str = "DATABASE_URL";
     pub const JWT_SECRET_ENV_VAR: &str = "JWT_SECRET";
     pub const REDIS_HOST_NAME_ENV_VAR: &str = "REDIS_HOST_NAME";
+    pub const JWT_PRIVATE_KEY_PATH_ENV_VAR: &str = "JWT_PRIVATE_KEY_PATH";
+    pub const JWT_RETIRED_KEY_PATHS_ENV_VAR: &str = "JWT_RETIRED_KEY_PATHS";
+    pub const JWT_KEY_ROTATION_INTERVAL_SECONDS_ENV_VAR: &str = "JWT_KEY_ROTATION_INTERVAL_SECONDS";
+    pub const JWT_KEY_ENCRYPTION_KEY_ENV_VAR: &str = "JWT_KEY_ENCRYPTION_KEY";
+    pub const TOTP_ENCRYPTION_KEY_ENV_VAR: &str = "TOTP_ENCRYPTION_KEY";
+    pub const TOTP_ALLOWED_SKEW_STEPS_ENV_VAR: &str = "TOTP_ALLOWED_SKEW_STEPS";
+    pub const WEBAUTHN_RP_ID_ENV_VAR: &str = "WEBAUTHN_RP_ID";
+    pub const WEBAUTHN_RP_ORIGIN_ENV_VAR: &str = "WEBAUTHN_RP_ORIGIN";
+    pub const PASSWORD_RESET_URL_ENV_VAR: &str = "PASSWORD_RESET_URL";
+    pub const EMAIL_VERIFICATION_URL_ENV_VAR: &str = "EMAIL_VERIFICATION_URL";
+    pub const EMAIL_CHANGE_URL_ENV_VAR: &str = "EMAIL_CHANGE_URL";
+    pub const REQUIRE_EMAIL_VERIFICATION_ENV_VAR: &str = "REQUIRE_EMAIL_VERIFICATION";
+    pub const RATE_LIMITS_ENV_VAR: &str = "RATE_LIMITS";
+    pub const PASSWORD_HASH_MEMORY_KIB_ENV_VAR: &str = "PASSWORD_HASH_MEMORY_KIB";
+    pub const PASSWORD_HASH_ITERATIONS_ENV_VAR: &str = "PASSWORD_HASH_ITERATIONS";
+    pub const PASSWORD_HASH_PARALLELISM_ENV_VAR: &str = "PASSWORD_HASH_PARALLELISM";
+    pub const PASSWORD_MIN_LENGTH_ENV_VAR: &str = "PASSWORD_MIN_LENGTH";
+    pub const PASSWORD_MAX_LENGTH_ENV_VAR: &str = "PASSWORD_MAX_LENGTH";
+    pub const PASSWORD_REQUIRED_CHARACTER_CLASSES_ENV_VAR: &str =
+        "PASSWORD_REQUIRED_CHARACTER_CLASSES";
+    pub const PASSWORD_REJECT_EMAIL_ENV_VAR: &str = "PASSWORD_REJECT_EMAIL";
+    pub const PASSWORD_MIN_STRENGTH_ENV_VAR: &str = "PASSWORD_MIN_STRENGTH";
+    pub const BREACHED_PASSWORDS_PATH_ENV_VAR: &str = "BREACHED_PASSWORDS_PATH";
+    pub const OIDC_ISSUER_ENV_VAR: &str = "OIDC_ISSUER";
+    pub const APP_SERVICE_CLIENT_SECRET_ENV_VAR: &str = "APP_SERVICE_CLIENT_SECRET";
 }
 
 pub const JWT_COOKIE_NAME: &str = "jwt";
+pub const REFRESH_TOKEN_COOKIE_NAME: &str = "refresh_token";
 pub const DEFAULT_REDIS_HOST_NAME: &str = "127.0.0.1";
+pub const TOTP_ISSUER: &str = "Auth Service";
+// Accept codes from one 30 second step either side of the current one to absorb clock drift
+pub const DEFAULT_TOTP_ALLOWED_SKEW_STEPS: u8 = 1;
+// Passkeys are bound to the domain of the relying party and only usable from its origin
+pub const WEBAUTHN_RP_NAME: &str = "Auth Service";
+pub const DEFAULT_WEBAUTHN_RP_ID: &str = "localhost";
+pub const DEFAULT_WEBAUTHN_RP_ORIGIN: &str = "http://localhost:3000";
+// Page the emailed link points to, the token is appended as the `token` query parameter
+pub const DEFAULT_PASSWORD_RESET_URL: &str = "http://localhost:3000/";
+pub const PASSWORD_RESET_TOKEN_TTL_SECONDS: u64 = 15 * 60;
+// How long the link in a verification email and the one confirming a new address can be used
+pub const EMAIL_VERIFICATION_TOKEN_TTL_SECONDS: u64 = 24 * 60 * 60;
+pub const EMAIL_CHANGE_TOKEN_TTL_SECONDS: u64 = 60 * 60;
+pub const DEFAULT_OIDC_ISSUER: &str = "http://localhost:3000";
+// The app service introspects the login tokens of its users as this machine client
+pub const APP_SERVICE_CLIENT_ID: &str = "app-service";
+// Clients redeem their code right after the redirect, it does not need to live any longer
+pub const AUTHORIZATION_CODE_TTL_SECONDS: u64 = 60;
+// Pages the verification and email change links point to, the token is appended as the
+// `verify_email_token` or `email_change_token` query parameter. The page only posts it once the
+// user confirms, since mail scanners open links too.
+pub const DEFAULT_EMAIL_VERIFICATION_URL: &str = "http://localhost:3000/";
+pub const DEFAULT_EMAIL_CHANGE_URL: &str = "http://localhost:3000/";
+// Comma separated `<path> <ip|email|subject> <requests>/<seconds>` rules, `*` matches every path.
+// Routes that send emails are limited per recipient so they can not be used to flood an inbox.
+pub const DEFAULT_RATE_LIMITS: &str = "* ip 600/60, \
+    /signup ip 20/60, \
+    /login ip 60/60, \
+    /verify-2fa ip 60/60, \
+    /password-reset/request email 5/900, \
+    /verify-email/resend email 5/900, \
+    /account/email subject 5/900";
 
 pub mod test {
     pub const APP_ADDRESS: &str = "127.0.0.1:0";
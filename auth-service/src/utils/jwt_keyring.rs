@@ -0,0 +1,366 @@
+use std::{sync::Arc, time::Duration};
+
+use chrono::{DateTime, Utc};
+use jsonwebtoken::jwk::JwkSet;
+use tokio::{sync::RwLock, task::JoinHandle};
+
+use crate::domain::{JwtKeyStore, JwtKeyStoreError};
+
+use super::jwt_key::{JwtKey, JwtKeyError};
+
+// How often every instance reloads the stored keys, adding a new one when it is due
+pub const JWT_KEY_RELOAD_INTERVAL: Duration = Duration::from_secs(60);
+
+// A stored key only starts signing once every instance has had the chance to load it, so none of
+// them is handed a token signed with a key it does not know yet
+const JWT_KEY_ACTIVATION_DELAY_SECONDS: i64 = 2 * JWT_KEY_RELOAD_INTERVAL.as_secs() as i64;
+
+// Set of keys behind JWT auth tokens. New tokens are always signed with the active key,
+// while retired keys keep validating tokens they signed until their overlap window ends.
+pub struct JwtKeyring {
+    active: JwtKey,
+    // Stored keys that validate tokens but do not sign any until their activation delay passed
+    pending: Vec<JwtKey>,
+    retired: Vec<RetiredKey>,
+    overlap: chrono::Duration,
+}
+
+struct RetiredKey {
+    key: JwtKey,
+    valid_until: DateTime<Utc>,
+}
+
+impl JwtKeyring {
+    pub fn new(active: JwtKey, overlap: chrono::Duration) -> Self {
+        Self {
+            active,
+            pending: Vec::new(),
+            retired: Vec::new(),
+            overlap,
+        }
+    }
+
+    // Keyring of stored keys given newest first. The newest key past its activation delay signs,
+    // or the oldest one while none is. Older keys keep validating for one overlap window after
+    // the key following them started signing.
+    fn from_stored(keys: Vec<(JwtKey, DateTime<Utc>)>, overlap: chrono::Duration) -> Option<Self> {
+        let now = Utc::now();
+        let activation_delay = chrono::Duration::seconds(JWT_KEY_ACTIVATION_DELAY_SECONDS);
+        let active_index = match keys
+            .iter()
+            .position(|(_, created_at)| *created_at <= now - activation_delay)
+        {
+            Some(index) => index,
+            None => keys.len().checked_sub(1)?,
+        };
+
+        let mut keys = keys.into_iter();
+        let pending = keys
+            .by_ref()
+            .take(active_index)
+            .map(|(key, _)| key)
+            .collect();
+        let (active, mut next_created_at) = keys.next()?;
+
+        let mut retired = Vec::new();
+        for (key, created_at) in keys {
+            let valid_until = next_created_at + activation_delay + overlap;
+            if valid_until > now {
+                retired.push(RetiredKey { key, valid_until });
+            }
+            next_created_at = created_at;
+        }
+
+        Some(Self {
+            active,
+            pending,
+            retired,
+            overlap,
+        })
+    }
+
+    // Keep validating tokens signed with `key` for one overlap window from now
+    pub fn with_retired(mut self, key: JwtKey) -> Self {
+        self.retire(key);
+        self
+    }
+
+    pub fn active_key(&self) -> &JwtKey {
+        &self.active
+    }
+
+    // Look up a key that may still validate tokens, either the active or a pending one, or a
+    // retired one within its overlap
+    pub fn find(&self, kid: &str) -> Option<&JwtKey> {
+        let now = Utc::now();
+        std::iter::once(&self.active)
+            .chain(&self.pending)
+            .chain(
+                self.retired
+                    .iter()
+                    .filter(|retired| retired.valid_until > now)
+                    .map(|retired| &retired.key),
+            )
+            .find(|key| key.kid() == kid)
+    }
+
+    // Make `key` the signing key and retire the previous one
+    pub fn promote(&mut self, key: JwtKey) {
+        let previous = std::mem::replace(&mut self.active, key);
+        self.retire(previous);
+    }
+
+    // Pending keys are published ahead of time, so clients caching the set know them before
+    // they sign anything
+    pub fn jwks(&self) -> JwkSet {
+        let now = Utc::now();
+        let keys = std::iter::once(&self.active)
+            .chain(&self.pending)
+            .chain(
+                self.retired
+                    .iter()
+                    .filter(|retired| retired.valid_until > now)
+                    .map(|retired| &retired.key),
+            )
+            .filter_map(|key| key.jwk().cloned())
+            .collect();
+
+        JwkSet { keys }
+    }
+
+    fn retire(&mut self, key: JwtKey) {
+        let now = Utc::now();
+        self.retired.retain(|retired| retired.valid_until > now);
+        self.retired.push(RetiredKey {
+            key,
+            valid_until: now + self.overlap,
+        });
+    }
+}
+
+// Rotates keys kept in a store shared by every instance. Each instance adds a fresh key once the
+// newest one is `interval` old, only one of them gets to, and loads the stored keys.
+pub struct JwtKeyRotation {
+    store: Arc<dyn JwtKeyStore>,
+    interval: Duration,
+    overlap: chrono::Duration,
+}
+
+#[derive(Debug)]
+pub enum JwtKeyRotationError {
+    StoreError(JwtKeyStoreError),
+    InvalidKey(JwtKeyError),
+    NoKeys,
+}
+
+impl JwtKeyRotation {
+    pub fn new(store: Arc<dyn JwtKeyStore>, interval: Duration, overlap: chrono::Duration) -> Self {
+        Self {
+            store,
+            interval,
+            overlap,
+        }
+    }
+
+    // Adds a key when one is due and loads every key that may still validate tokens, removing
+    // the ones that no longer do
+    pub async fn sync(&self) -> Result<JwtKeyring, JwtKeyRotationError> {
+        let mut stored = self
+            .store
+            .get_keys()
+            .await
+            .map_err(JwtKeyRotationError::StoreError)?;
+
+        let due = stored.first().is_none_or(|newest| {
+            (Utc::now() - newest.created_at)
+                .to_std()
+                .is_ok_and(|age| age >= self.interval)
+        });
+
+        if due {
+            let pem = JwtKey::generate_pem().map_err(JwtKeyRotationError::InvalidKey)?;
+            let key = JwtKey::from_pem(&pem).map_err(JwtKeyRotationError::InvalidKey)?;
+            let added = self
+                .store
+                .add_key(key.kid(), &pem, self.interval)
+                .await
+                .map_err(JwtKeyRotationError::StoreError)?;
+
+            if added {
+                tracing::info!(kid = key.kid(), "Added new JWT signing key");
+            }
+
+            stored = self
+                .store
+                .get_keys()
+                .await
+                .map_err(JwtKeyRotationError::StoreError)?;
+        }
+
+        let keys = stored
+            .iter()
+            .map(|key| Ok((JwtKey::from_pem(&key.private_key_pem)?, key.created_at)))
+            .collect::<Result<Vec<_>, JwtKeyError>>()
+            .map_err(JwtKeyRotationError::InvalidKey)?;
+        let keyring =
+            JwtKeyring::from_stored(keys, self.overlap).ok_or(JwtKeyRotationError::NoKeys)?;
+
+        if let Some(oldest) = stored
+            .iter()
+            .rev()
+            .find(|key| keyring.find(&key.kid).is_some())
+        {
+            self.store
+                .remove_keys(oldest.created_at)
+                .await
+                .map_err(JwtKeyRotationError::StoreError)?;
+        }
+
+        Ok(keyring)
+    }
+}
+
+// Syncs the keyring with the stored keys every reload interval. The keyring is expected to have
+// been loaded from the store already.
+pub fn spawn_key_rotation(
+    keyring: Arc<RwLock<JwtKeyring>>,
+    rotation: JwtKeyRotation,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(JWT_KEY_RELOAD_INTERVAL);
+        // The first tick completes immediately
+        ticker.tick().await;
+
+        loop {
+            ticker.tick().await;
+
+            match rotation.sync().await {
+                Ok(synced) => *keyring.write().await = synced,
+                Err(e) => tracing::error!(error = ?e, "Failed to sync JWT signing keys"),
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::HashMapJwtKeyStore;
+
+    fn get_keyring(overlap: chrono::Duration) -> JwtKeyring {
+        JwtKeyring::new(JwtKey::generate().unwrap(), overlap)
+    }
+
+    #[test]
+    fn test_find_active_key() {
+        let keyring = get_keyring(chrono::Duration::minutes(10));
+        let kid = keyring.active_key().kid().to_owned();
+        assert!(keyring.find(&kid).is_some());
+        assert!(keyring.find("unknown").is_none());
+    }
+
+    fn get_stored_key(age: chrono::Duration) -> (JwtKey, DateTime<Utc>) {
+        (JwtKey::generate().unwrap(), Utc::now() - age)
+    }
+
+    #[test]
+    fn test_stored_key_signs_once_past_activation_delay() {
+        let (new_key, new_created_at) = get_stored_key(chrono::Duration::zero());
+        let new_kid = new_key.kid().to_owned();
+        let (old_key, old_created_at) = get_stored_key(chrono::Duration::minutes(30));
+        let old_kid = old_key.kid().to_owned();
+
+        let keyring = JwtKeyring::from_stored(
+            vec![(new_key, new_created_at), (old_key, old_created_at)],
+            chrono::Duration::minutes(10),
+        )
+        .unwrap();
+
+        assert_eq!(keyring.active_key().kid(), old_kid);
+        assert!(keyring.find(&new_kid).is_some());
+        assert_eq!(keyring.jwks().keys.len(), 2);
+    }
+
+    #[test]
+    fn test_oldest_stored_key_signs_while_none_is_active() {
+        let (key, created_at) = get_stored_key(chrono::Duration::zero());
+        let kid = key.kid().to_owned();
+
+        let keyring =
+            JwtKeyring::from_stored(vec![(key, created_at)], chrono::Duration::minutes(10))
+                .unwrap();
+
+        assert_eq!(keyring.active_key().kid(), kid);
+        assert!(JwtKeyring::from_stored(Vec::new(), chrono::Duration::minutes(10)).is_none());
+    }
+
+    #[test]
+    fn test_stored_key_is_retired_until_overlap_after_next_one_signs() {
+        let active = get_stored_key(chrono::Duration::minutes(5));
+        let (retired_key, retired_created_at) = get_stored_key(chrono::Duration::hours(1));
+        let retired_kid = retired_key.kid().to_owned();
+        let (expired_key, expired_created_at) = get_stored_key(chrono::Duration::hours(2));
+        let expired_kid = expired_key.kid().to_owned();
+
+        let keyring = JwtKeyring::from_stored(
+            vec![
+                active,
+                (retired_key, retired_created_at),
+                (expired_key, expired_created_at),
+            ],
+            chrono::Duration::minutes(10),
+        )
+        .unwrap();
+
+        assert!(keyring.find(&retired_kid).is_some());
+        assert!(keyring.find(&expired_kid).is_none());
+        assert_eq!(keyring.jwks().keys.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_sync_adds_pending_key_when_due() {
+        let store: Arc<dyn JwtKeyStore> = Arc::new(HashMapJwtKeyStore::default());
+        let overlap = chrono::Duration::minutes(10);
+
+        let first = JwtKeyRotation::new(store.clone(), Duration::from_secs(3600), overlap)
+            .sync()
+            .await
+            .unwrap();
+        let kid = first.active_key().kid().to_owned();
+
+        // Another instance loads the same key instead of adding its own
+        let second = JwtKeyRotation::new(store.clone(), Duration::from_secs(3600), overlap)
+            .sync()
+            .await
+            .unwrap();
+        assert_eq!(second.active_key().kid(), kid);
+        assert_eq!(store.get_keys().await.unwrap().len(), 1);
+
+        let rotated = JwtKeyRotation::new(store.clone(), Duration::ZERO, overlap)
+            .sync()
+            .await
+            .unwrap();
+        assert_eq!(rotated.active_key().kid(), kid);
+        assert_eq!(rotated.jwks().keys.len(), 2);
+    }
+
+    #[test]
+    fn test_with_retired_key() {
+        let retired = JwtKey::generate().unwrap();
+        let retired_kid = retired.kid().to_owned();
+
+        let keyring = get_keyring(chrono::Duration::minutes(10)).with_retired(retired);
+
+        assert_ne!(keyring.active_key().kid(), retired_kid);
+        assert!(keyring.find(&retired_kid).is_some());
+    }
+
+    #[test]
+    fn test_jwks_skips_shared_secrets() {
+        let keyring = JwtKeyring::new(
+            JwtKey::from_secret(b"secret"),
+            chrono::Duration::minutes(10),
+        );
+        assert!(keyring.jwks().keys.is_empty());
+    }
+}
@@ -0,0 +1,124 @@
+use std::{net::SocketAddr, time::Duration};
+
+use axum::{
+    body::{to_bytes, Body, Bytes},
+    extract::{ConnectInfo, Request, State},
+    http::{request::Parts, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use axum_extra::extract::CookieJar;
+
+use crate::{
+    app_state::AppState,
+    domain::{error::AuthAPIError, Email, RateLimitKey, RateLimitRule},
+    utils::{auth::decode_auth_token, constants::JWT_COOKIE_NAME},
+};
+
+// Bodies are only buffered for rules keyed by email, which are meant for small JSON requests
+const MAX_BUFFERED_BODY_BYTES: usize = 64 * 1024;
+
+// Middleware applying the rate limit rules of the policy. Every matching rule takes a token from
+// the bucket of its route and client key, the request is refused if any of them is empty.
+pub async fn rate_limit(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let path = request.uri().path().to_owned();
+    let rules: Vec<&RateLimitRule> = state
+        .policy
+        .rate_limits
+        .iter()
+        .filter(|rule| rule.matches(&path))
+        .collect();
+
+    if rules.is_empty() {
+        return next.run(request).await;
+    }
+
+    let (parts, body) = request.into_parts();
+
+    // Rules keyed by email look into the body, it is handed on to the route afterwards
+    let (body, buffered) = if rules.iter().any(|rule| rule.key == RateLimitKey::Email) {
+        match to_bytes(body, MAX_BUFFERED_BODY_BYTES).await {
+            Ok(bytes) => (Body::from(bytes.clone()), bytes),
+            Err(_) => return StatusCode::PAYLOAD_TOO_LARGE.into_response(),
+        }
+    } else {
+        (body, Bytes::new())
+    };
+
+    let wait = take_tokens(&rules, &parts, &buffered, &state).await;
+
+    if !wait.is_zero() {
+        // Rounded up, so a client that waits exactly as told gets a fresh token
+        let seconds = wait.as_secs() + u64::from(wait.subsec_nanos() > 0);
+        return AuthAPIError::RateLimited(seconds).into_response();
+    }
+
+    next.run(Request::from_parts(parts, body)).await
+}
+
+// Returns the longest wait for a token among the rules, zero if every one of them had a token
+async fn take_tokens(
+    rules: &[&RateLimitRule],
+    parts: &Parts,
+    body: &[u8],
+    state: &AppState,
+) -> Duration {
+    let mut wait = Duration::ZERO;
+
+    for rule in rules {
+        let Some(client) = client_key(rule.key, parts, body, state).await else {
+            continue;
+        };
+        let key = format!("{}:{}:{}", rule.route, rule.key.name(), client);
+
+        let result = state.rate_limit_store.take_token(&key, &rule.bucket).await;
+
+        match result {
+            Ok(rule_wait) => wait = wait.max(rule_wait.unwrap_or_default()),
+            // An outage of the store should not take every route down with it
+            Err(e) => tracing::error!(error = ?e, "Failed to apply rate limit"),
+        }
+    }
+
+    wait
+}
+
+// Requests without the value a rule is keyed by are not counted against it
+async fn client_key(
+    key: RateLimitKey,
+    parts: &Parts,
+    body: &[u8],
+    state: &AppState,
+) -> Option<String> {
+    match key {
+        RateLimitKey::Ip => parts
+            .extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(client)| client.ip().to_string()),
+        RateLimitKey::Email => {
+            let request: EmailField = serde_json::from_slice(body).ok()?;
+            let email = Email::parse(request.email).ok()?;
+            Some(email.as_ref().to_owned())
+        }
+        RateLimitKey::Subject => {
+            let jar = CookieJar::from_headers(&parts.headers);
+            let token = jar.get(JWT_COOKIE_NAME)?.value().to_owned();
+
+            // Only the signature is checked, the key just has to be one an attacker can not forge.
+            // Whether the session is still valid is up to the route.
+            let claims = decode_auth_token(&token, &*state.jwt_keyring.read().await).ok()?;
+
+            // Tokens issued to clients are not accepted by the routes keyed by the user
+            if claims.client_id.is_some() {
+                return None;
+            }
+
+            Some(claims.sub)
+        }
+    }
+}
+
+#[derive(serde::Deserialize)]
+struct EmailField {
+    email: String,
+}
@@ -0,0 +1,48 @@
+use axum::{
+    extract::Request,
+    http::{HeaderName, HeaderValue},
+    middleware::Next,
+    response::Response,
+};
+use uuid::Uuid;
+
+pub const REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
+
+tokio::task_local! {
+    static CURRENT_REQUEST_ID: RequestId;
+}
+
+// Identifies a request in the logs, in the `x-request-id` response header and in error responses
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RequestId(pub Uuid);
+
+impl RequestId {
+    // Id of the request the current task is handling, if any
+    pub fn current() -> Option<Self> {
+        CURRENT_REQUEST_ID.try_with(|request_id| *request_id).ok()
+    }
+}
+
+// Middleware giving every request an id, kept from the `x-request-id` header when a proxy or the
+// app service already assigned one. It is added to the request extensions for the trace span,
+// and kept for the handler so errors can refer to it without having the request at hand.
+pub async fn request_id(mut request: Request, next: Next) -> Response {
+    let request_id = request
+        .headers()
+        .get(&REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| Uuid::parse_str(value).ok())
+        .map(RequestId)
+        .unwrap_or_else(|| RequestId(Uuid::new_v4()));
+
+    request.extensions_mut().insert(request_id);
+    let mut response = CURRENT_REQUEST_ID
+        .scope(request_id, next.run(request))
+        .await;
+
+    if let Ok(value) = HeaderValue::from_str(&request_id.0.to_string()) {
+        response.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+
+    response
+}
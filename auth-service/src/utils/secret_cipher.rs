@@ -0,0 +1,96 @@
+use aes_gcm::{
+    aead::{Aead, AeadCore, KeyInit, OsRng},
+    Aes256Gcm, Key, Nonce,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+
+// Length of the random nonce that is prepended to every ciphertext
+const NONCE_LENGTH: usize = 12;
+
+// Encrypts secrets that must be stored in a recoverable form, like TOTP seeds
+#[derive(Clone)]
+pub struct SecretCipher {
+    cipher: Aes256Gcm,
+}
+
+#[derive(Debug)]
+pub enum SecretCipherError {
+    InvalidKey,
+    InvalidCiphertext,
+}
+
+impl SecretCipher {
+    pub fn new(key: &[u8; 32]) -> Self {
+        Self {
+            cipher: Aes256Gcm::new(&Key::<Aes256Gcm>::from(*key)),
+        }
+    }
+
+    // Key given as 32 bytes encoded in standard base64
+    pub fn from_base64(key: &str) -> Result<Self, SecretCipherError> {
+        let key: [u8; 32] = STANDARD
+            .decode(key)
+            .map_err(|_| SecretCipherError::InvalidKey)?
+            .try_into()
+            .map_err(|_| SecretCipherError::InvalidKey)?;
+
+        Ok(Self::new(&key))
+    }
+
+    pub fn encrypt(&self, plaintext: &[u8]) -> Result<String, SecretCipherError> {
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .cipher
+            .encrypt(&nonce, plaintext)
+            .map_err(|_| SecretCipherError::InvalidCiphertext)?;
+
+        let mut bytes = nonce.to_vec();
+        bytes.extend(ciphertext);
+        Ok(STANDARD.encode(bytes))
+    }
+
+    pub fn decrypt(&self, encoded: &str) -> Result<Vec<u8>, SecretCipherError> {
+        let bytes = STANDARD
+            .decode(encoded)
+            .map_err(|_| SecretCipherError::InvalidCiphertext)?;
+
+        if bytes.len() < NONCE_LENGTH {
+            return Err(SecretCipherError::InvalidCiphertext);
+        }
+
+        let (nonce, ciphertext) = bytes.split_at(NONCE_LENGTH);
+        let nonce: [u8; NONCE_LENGTH] = nonce
+            .try_into()
+            .map_err(|_| SecretCipherError::InvalidCiphertext)?;
+
+        self.cipher
+            .decrypt(&Nonce::from(nonce), ciphertext)
+            .map_err(|_| SecretCipherError::InvalidCiphertext)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encrypt_and_decrypt() {
+        let cipher = SecretCipher::new(&[7u8; 32]);
+        let encrypted = cipher.encrypt(b"secret").unwrap();
+        assert_ne!(encrypted.as_bytes(), b"secret");
+        assert_eq!(cipher.decrypt(&encrypted).unwrap(), b"secret");
+    }
+
+    #[test]
+    fn test_decrypt_with_other_key_fails() {
+        let encrypted = SecretCipher::new(&[7u8; 32]).encrypt(b"secret").unwrap();
+        let result = SecretCipher::new(&[8u8; 32]).decrypt(&encrypted);
+        assert!(matches!(result, Err(SecretCipherError::InvalidCiphertext)));
+    }
+
+    #[test]
+    fn test_from_base64_rejects_short_key() {
+        let result = SecretCipher::from_base64(&STANDARD.encode([1u8; 16]));
+        assert!(matches!(result, Err(SecretCipherError::InvalidKey)));
+    }
+}
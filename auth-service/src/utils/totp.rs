@@ -0,0 +1,124 @@
+use base64::{engine::general_purpose::STANDARD, Engine};
+use qrcode::{render::svg, QrCode};
+use totp_rs::{Algorithm, TOTP};
+
+use crate::domain::{Email, TotpSecret, TwoFACode};
+
+use super::constants::{TOTP_ALLOWED_SKEW_STEPS, TOTP_ISSUER};
+
+// Authenticator apps only reliably support the RFC 6238 defaults
+const TOTP_DIGITS: usize = 6;
+const TOTP_STEP_SECONDS: u64 = 30;
+
+#[derive(Debug)]
+pub enum TotpError {
+    InvalidParameters,
+    UnexpectedError,
+}
+
+// Create the otpauth:// URI that authenticator apps import, usually by scanning it as a QR code
+pub fn generate_otpauth_uri(secret: &TotpSecret, email: &Email) -> Result<String, TotpError> {
+    Ok(create_totp(secret, email, 0)?.get_url())
+}
+
+// Render the URI as an SVG QR code, embedded in a data URI so it can be used as an image source
+pub fn generate_qr_code(otpauth_uri: &str) -> Result<String, TotpError> {
+    let svg = QrCode::new(otpauth_uri.as_bytes())
+        .map_err(|_| TotpError::UnexpectedError)?
+        .render::<svg::Color<'_>>()
+        .min_dimensions(200, 200)
+        .build();
+
+    Ok(format!(
+        "data:image/svg+xml;base64,{}",
+        STANDARD.encode(svg)
+    ))
+}
+
+// Check a code against the current time step and the configured number of neighbouring steps
+pub fn verify_totp_code(
+    secret: &TotpSecret,
+    email: &Email,
+    code: &TwoFACode,
+) -> Result<bool, TotpError> {
+    create_totp(secret, email, *TOTP_ALLOWED_SKEW_STEPS)?
+        .check_current(code.as_ref())
+        .map_err(|_| TotpError::UnexpectedError)
+}
+
+fn create_totp(secret: &TotpSecret, email: &Email, skew: u8) -> Result<TOTP, TotpError> {
+    TOTP::new(
+        Algorithm::SHA1,
+        TOTP_DIGITS,
+        skew,
+        TOTP_STEP_SECONDS,
+        secret.as_ref().to_vec(),
+        Some(TOTP_ISSUER.to_owned()),
+        email.as_ref().to_owned(),
+    )
+    .map_err(|_| TotpError::InvalidParameters)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_email() -> Email {
+        Email::parse("test@example.com".to_owned()).unwrap()
+    }
+
+    fn current_code(secret: &TotpSecret, email: &Email) -> TwoFACode {
+        let code = create_totp(secret, email, 0)
+            .unwrap()
+            .generate_current()
+            .unwrap();
+        TwoFACode::parse(&code).unwrap()
+    }
+
+    #[test]
+    fn test_generate_otpauth_uri() {
+        let uri = generate_otpauth_uri(&TotpSecret::default(), &get_email()).unwrap();
+        assert!(uri.starts_with("otpauth://totp/"));
+        assert!(uri.contains("secret="));
+        assert!(uri.contains("issuer=Auth%20Service"));
+    }
+
+    #[test]
+    fn test_generate_qr_code() {
+        let uri = generate_otpauth_uri(&TotpSecret::default(), &get_email()).unwrap();
+        let qr_code = generate_qr_code(&uri).unwrap();
+        assert!(qr_code.starts_with("data:image/svg+xml;base64,"));
+    }
+
+    #[test]
+    fn test_verify_current_code() {
+        let secret = TotpSecret::default();
+        let email = get_email();
+        let code = current_code(&secret, &email);
+        assert!(verify_totp_code(&secret, &email, &code).unwrap());
+    }
+
+    #[test]
+    fn test_verify_code_within_skew() {
+        let secret = TotpSecret::default();
+        let email = get_email();
+        let totp = create_totp(&secret, &email, 0).unwrap();
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+
+        let previous = TwoFACode::parse(&totp.generate(now - TOTP_STEP_SECONDS)).unwrap();
+        assert!(verify_totp_code(&secret, &email, &previous).unwrap());
+
+        let stale = TwoFACode::parse(&totp.generate(now - 10 * TOTP_STEP_SECONDS)).unwrap();
+        assert!(!verify_totp_code(&secret, &email, &stale).unwrap());
+    }
+
+    #[test]
+    fn test_verify_code_for_other_secret() {
+        let email = get_email();
+        let code = current_code(&TotpSecret::default(), &email);
+        assert!(!verify_totp_code(&TotpSecret::default(), &email, &code).unwrap());
+    }
+}
@@ -3,6 +3,8 @@ use std::time::Duration;
 use axum::{body::Body, extract::Request, response::Response};
 use tracing::{Level, Span};
 
+use crate::utils::request_id::RequestId;
+
 pub fn init_tracing() {
     tracing_subscriber::fmt()
         .compact()
@@ -10,31 +12,38 @@ pub fn init_tracing() {
         .init();
 }
 
-pub fn make_span_with_request_id(
-  request: &Request<Body>
-) -> Span {
-  let request_id = uuid::Uuid::new_v4();
-  tracing::span!(
-    Level::INFO,
-    "[REQUEST]",
-    method = tracing::field::display(request.method()),
-    uri = tracing::field::display(request.uri()),
-    version = tracing::field::debug(request.version()),
-    request_id = tracing::field::display(request_id),
-  )
+pub fn make_span_with_request_id(request: &Request<Body>) -> Span {
+    // Set by the `request_id` middleware, which runs before the trace layer
+    let request_id = request
+        .extensions()
+        .get::<RequestId>()
+        .map(|request_id| request_id.0)
+        .unwrap_or_default();
+    tracing::span!(
+        Level::INFO,
+        "[REQUEST]",
+        method = tracing::field::display(request.method()),
+        uri = tracing::field::display(request.uri()),
+        version = tracing::field::debug(request.version()),
+        request_id = tracing::field::display(request_id),
+    )
 }
 
 pub fn on_request(_request: &Request<Body>, _span: &Span) {
-  tracing::event!(Level::INFO, "[REQUEST START]");
+    tracing::event!(Level::INFO, "[REQUEST START]");
 }
 
 pub fn on_response(response: &Response, latency: Duration, _span: &Span) {
-  let status = response.status();
-  let status_code = status.as_u16();
-  let status_code_class = status_code / 100;
+    let status = response.status();
+    let status_code = status.as_u16();
+    let status_code_class = status_code / 100;
 
-  match status_code_class {
-    4..=5 => tracing::event!(Level::ERROR, latency = ?latency, status = status_code, "[REQUEST END]"),
-    _ => tracing::event!(Level::INFO, latency = ?latency, status = status_code, "[REQUEST END]"),
-  }
+    match status_code_class {
+        4..=5 => {
+            tracing::event!(Level::ERROR, latency = ?latency, status = status_code, "[REQUEST END]")
+        }
+        _ => {
+            tracing::event!(Level::INFO, latency = ?latency, status = status_code, "[REQUEST END]")
+        }
+    }
 }
@@ -0,0 +1,151 @@
+use std::{
+    io::{BufRead, BufReader, Read},
+    path::Path,
+};
+
+use crate::domain::{Email, PasswordHash, User};
+
+// Layout of the file users are imported from, told apart by its extension
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ImportFormat {
+    Csv,
+    JsonLines,
+}
+
+impl ImportFormat {
+    pub fn from_path(path: &Path) -> Result<Self, String> {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("csv") => Ok(ImportFormat::Csv),
+            Some("jsonl") | Some("ndjson") => Ok(ImportFormat::JsonLines),
+            _ => Err(format!(
+                "{} is neither a .csv nor a .jsonl file",
+                path.display()
+            )),
+        }
+    }
+}
+
+// One account as exported by the old system. CSV files name the fields in their header, the
+// flags may be left out and default to false.
+#[derive(Debug, serde::Deserialize)]
+struct UserRecord {
+    email: String,
+    password_hash: String,
+    #[serde(default)]
+    requires_2fa: bool,
+    #[serde(default)]
+    email_verified: bool,
+}
+
+impl UserRecord {
+    fn into_user(self) -> Result<(User, PasswordHash), String> {
+        let email = Email::parse(self.email)?;
+        let password_hash = PasswordHash::parse(self.password_hash)?;
+
+        let mut user = User::new(email, self.requires_2fa);
+        user.email_verified = self.email_verified;
+
+        Ok((user, password_hash))
+    }
+}
+
+// Reads the users one by one, so large exports are never held in memory. A broken record only
+// fails its own item, the error names the line it is on.
+pub fn read_users<'a>(
+    reader: impl Read + 'a,
+    format: ImportFormat,
+) -> Box<dyn Iterator<Item = Result<(User, PasswordHash), String>> + 'a> {
+    match format {
+        ImportFormat::Csv => {
+            let mut reader = csv::Reader::from_reader(reader);
+            let headers = reader.headers().cloned();
+
+            Box::new(reader.into_records().map(move |record| {
+                let record = record.map_err(|e| e.to_string())?;
+                let headers = headers.as_ref().map_err(|e| e.to_string())?;
+
+                record
+                    .deserialize::<UserRecord>(Some(headers))
+                    .map_err(|e| e.to_string())
+                    .and_then(UserRecord::into_user)
+                    .map_err(|e| {
+                        let line = record.position().map_or(0, |position| position.line());
+                        format!("line {}: {}", line, e)
+                    })
+            }))
+        }
+        ImportFormat::JsonLines => Box::new(
+            BufReader::new(reader)
+                .lines()
+                .enumerate()
+                .filter(|(_, line)| !line.as_ref().is_ok_and(|line| line.trim().is_empty()))
+                .map(|(index, line)| {
+                    line.map_err(|e| e.to_string())
+                        .and_then(|line| {
+                            serde_json::from_str::<UserRecord>(&line).map_err(|e| e.to_string())
+                        })
+                        .and_then(UserRecord::into_user)
+                        .map_err(|e| format!("line {}: {}", index + 1, e))
+                }),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BCRYPT_HASH: &str = "$2b$04$EGdrhbKUv8Oc9vGiXX0HQOxSg445d458Muh7DAHskb6QbtCvdxcie";
+
+    #[test]
+    fn should_pick_the_format_from_the_extension() {
+        assert_eq!(
+            ImportFormat::from_path(Path::new("users.csv")),
+            Ok(ImportFormat::Csv)
+        );
+        assert_eq!(
+            ImportFormat::from_path(Path::new("export/users.jsonl")),
+            Ok(ImportFormat::JsonLines)
+        );
+        assert!(ImportFormat::from_path(Path::new("users.json")).is_err());
+    }
+
+    #[test]
+    fn should_read_users_from_csv() {
+        let csv = format!(
+            "email,password_hash,requires_2fa,email_verified\n\
+             first@example.com,{BCRYPT_HASH},true,true\n\
+             not-an-email,{BCRYPT_HASH},false,false\n\
+             second@example.com,plaintext,false,false\n"
+        );
+
+        let users: Vec<_> = read_users(csv.as_bytes(), ImportFormat::Csv).collect();
+
+        assert_eq!(users.len(), 3);
+        let (user, password_hash) = users[0].as_ref().unwrap();
+        assert_eq!(user.email.as_ref(), "first@example.com");
+        assert!(user.requires_2fa);
+        assert!(user.email_verified);
+        assert_eq!(password_hash.as_ref(), BCRYPT_HASH);
+        assert!(users[1].as_ref().is_err_and(|e| e.starts_with("line 3:")));
+        assert!(users[2].as_ref().is_err_and(|e| e.starts_with("line 4:")));
+    }
+
+    #[test]
+    fn should_read_users_from_json_lines() {
+        let jsonl = format!(
+            "{{\"email\": \"first@example.com\", \"password_hash\": \"{BCRYPT_HASH}\"}}\n\
+             \n\
+             {{\"email\": \"second@example.com\"}}\n"
+        );
+
+        let users: Vec<_> = read_users(jsonl.as_bytes(), ImportFormat::JsonLines).collect();
+
+        assert_eq!(users.len(), 2);
+        let (user, _) = users[0].as_ref().unwrap();
+        assert_eq!(user.email.as_ref(), "first@example.com");
+        assert!(!user.requires_2fa);
+        assert!(!user.email_verified);
+        assert!(users[1].as_ref().is_err_and(|e| e.starts_with("line 3:")));
+    }
+}
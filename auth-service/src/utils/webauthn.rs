@@ -0,0 +1,394 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use ciborium::Value;
+use p256::{
+    ecdsa::{signature::Verifier, Signature, VerifyingKey},
+    EncodedPoint,
+};
+use sha2::{Digest, Sha256};
+
+use super::constants::{WEBAUTHN_RP_ID, WEBAUTHN_RP_ORIGIN};
+
+// How long a started registration or login ceremony can be finished
+pub const WEBAUTHN_CHALLENGE_TTL_SECONDS: u64 = 300; // 5 minutes
+
+// COSE algorithm identifier of ECDSA with P-256 and SHA-256, the only one we accept
+pub const COSE_ALGORITHM_ES256: i64 = -7;
+
+const COSE_KEY_TYPE_EC2: i64 = 2;
+const COSE_CURVE_P256: i64 = 1;
+
+const FLAG_USER_PRESENT: u8 = 0x01;
+const FLAG_USER_VERIFIED: u8 = 0x04;
+const FLAG_ATTESTED_CREDENTIAL_DATA: u8 = 0x40;
+
+// rpIdHash, flags and signCount
+const AUTHENTICATOR_DATA_MIN_LENGTH: usize = 37;
+const AAGUID_LENGTH: usize = 16;
+
+#[derive(Debug, PartialEq)]
+pub enum WebAuthnError {
+    InvalidEncoding,
+    InvalidClientData,
+    ChallengeMismatch,
+    OriginMismatch,
+    InvalidAuthenticatorData,
+    RelyingPartyMismatch,
+    UserNotPresent,
+    UserNotVerified,
+    UnsupportedKey,
+    InvalidSignature,
+    SignCountRegressed,
+}
+
+// Credential taken from a verified registration ceremony
+#[derive(Debug)]
+pub struct RegisteredCredential {
+    pub credential_id: Vec<u8>,
+    pub public_key: Vec<u8>,
+    pub sign_count: u32,
+}
+
+#[derive(serde::Deserialize)]
+struct ClientData {
+    #[serde(rename = "type")]
+    ceremony_type: String,
+    challenge: String,
+    origin: String,
+}
+
+struct AuthenticatorData {
+    rp_id_hash: [u8; 32],
+    flags: u8,
+    sign_count: u32,
+    // Only present in registration ceremonies
+    attested_credential: Option<(Vec<u8>, Vec<u8>)>,
+}
+
+pub fn decode_base64url(value: &str) -> Result<Vec<u8>, WebAuthnError> {
+    URL_SAFE_NO_PAD
+        .decode(value)
+        .map_err(|_| WebAuthnError::InvalidEncoding)
+}
+
+pub fn encode_base64url(bytes: &[u8]) -> String {
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+// Check a `navigator.credentials.create()` response and return the new credential.
+// Attestation statements are not verified, we request `none` and do not restrict authenticator models.
+pub fn verify_registration(
+    client_data_json: &[u8],
+    attestation_object: &[u8],
+    challenge: &[u8],
+) -> Result<RegisteredCredential, WebAuthnError> {
+    verify_client_data(client_data_json, "webauthn.create", challenge)?;
+
+    let attestation: Value = ciborium::de::from_reader(attestation_object)
+        .map_err(|_| WebAuthnError::InvalidEncoding)?;
+
+    let auth_data = attestation
+        .as_map()
+        .and_then(|entries| {
+            entries
+                .iter()
+                .find(|(key, _)| key.as_text() == Some("authData"))
+        })
+        .and_then(|(_, value)| value.as_bytes())
+        .ok_or(WebAuthnError::InvalidAuthenticatorData)?;
+
+    let auth_data = parse_authenticator_data(auth_data)?;
+    verify_relying_party(&auth_data)?;
+
+    let (credential_id, public_key) = auth_data
+        .attested_credential
+        .ok_or(WebAuthnError::InvalidAuthenticatorData)?;
+
+    Ok(RegisteredCredential {
+        credential_id,
+        public_key,
+        sign_count: auth_data.sign_count,
+    })
+}
+
+// Check a `navigator.credentials.get()` response against a stored credential and return the new sign count
+pub fn verify_authentication(
+    client_data_json: &[u8],
+    authenticator_data: &[u8],
+    signature: &[u8],
+    challenge: &[u8],
+    public_key: &[u8],
+    stored_sign_count: u32,
+) -> Result<u32, WebAuthnError> {
+    verify_client_data(client_data_json, "webauthn.get", challenge)?;
+
+    let auth_data = parse_authenticator_data(authenticator_data)?;
+    verify_relying_party(&auth_data)?;
+
+    let verifying_key =
+        VerifyingKey::from_sec1_bytes(public_key).map_err(|_| WebAuthnError::UnsupportedKey)?;
+    let signature = Signature::from_der(signature).map_err(|_| WebAuthnError::InvalidSignature)?;
+
+    // The authenticator signs its data followed by the hash of what the browser sent it
+    let mut signed_data = authenticator_data.to_vec();
+    signed_data.extend(Sha256::digest(client_data_json));
+
+    verifying_key
+        .verify(&signed_data, &signature)
+        .map_err(|_| WebAuthnError::InvalidSignature)?;
+
+    // Authenticators without a counter always report 0, otherwise it has to keep increasing
+    if (auth_data.sign_count != 0 || stored_sign_count != 0)
+        && auth_data.sign_count <= stored_sign_count
+    {
+        return Err(WebAuthnError::SignCountRegressed);
+    }
+
+    Ok(auth_data.sign_count)
+}
+
+fn verify_client_data(
+    client_data_json: &[u8],
+    expected_type: &str,
+    challenge: &[u8],
+) -> Result<(), WebAuthnError> {
+    let client_data: ClientData =
+        serde_json::from_slice(client_data_json).map_err(|_| WebAuthnError::InvalidClientData)?;
+
+    if client_data.ceremony_type != expected_type {
+        return Err(WebAuthnError::InvalidClientData);
+    }
+
+    if decode_base64url(&client_data.challenge)? != challenge {
+        return Err(WebAuthnError::ChallengeMismatch);
+    }
+
+    if client_data.origin != *WEBAUTHN_RP_ORIGIN {
+        return Err(WebAuthnError::OriginMismatch);
+    }
+
+    Ok(())
+}
+
+fn verify_relying_party(auth_data: &AuthenticatorData) -> Result<(), WebAuthnError> {
+    if auth_data.rp_id_hash[..] != Sha256::digest(WEBAUTHN_RP_ID.as_bytes())[..] {
+        return Err(WebAuthnError::RelyingPartyMismatch);
+    }
+
+    if auth_data.flags & FLAG_USER_PRESENT == 0 {
+        return Err(WebAuthnError::UserNotPresent);
+    }
+
+    // A passkey replaces both the password and the second factor, so touching the authenticator is
+    // not enough, it also has to have checked a PIN or biometric
+    if auth_data.flags & FLAG_USER_VERIFIED == 0 {
+        return Err(WebAuthnError::UserNotVerified);
+    }
+
+    Ok(())
+}
+
+fn parse_authenticator_data(bytes: &[u8]) -> Result<AuthenticatorData, WebAuthnError> {
+    if bytes.len() < AUTHENTICATOR_DATA_MIN_LENGTH {
+        return Err(WebAuthnError::InvalidAuthenticatorData);
+    }
+
+    let rp_id_hash: [u8; 32] = bytes[..32]
+        .try_into()
+        .map_err(|_| WebAuthnError::InvalidAuthenticatorData)?;
+    let flags = bytes[32];
+    let sign_count = u32::from_be_bytes(
+        bytes[33..37]
+            .try_into()
+            .map_err(|_| WebAuthnError::InvalidAuthenticatorData)?,
+    );
+
+    let attested_credential = if flags & FLAG_ATTESTED_CREDENTIAL_DATA != 0 {
+        Some(parse_attested_credential(
+            &bytes[AUTHENTICATOR_DATA_MIN_LENGTH..],
+        )?)
+    } else {
+        None
+    };
+
+    Ok(AuthenticatorData {
+        rp_id_hash,
+        flags,
+        sign_count,
+        attested_credential,
+    })
+}
+
+// aaguid (16) | credentialIdLength (2) | credentialId | COSE public key
+fn parse_attested_credential(bytes: &[u8]) -> Result<(Vec<u8>, Vec<u8>), WebAuthnError> {
+    let rest = bytes
+        .get(AAGUID_LENGTH..)
+        .ok_or(WebAuthnError::InvalidAuthenticatorData)?;
+
+    let length_bytes = rest
+        .get(..2)
+        .ok_or(WebAuthnError::InvalidAuthenticatorData)?;
+    let length = u16::from_be_bytes([length_bytes[0], length_bytes[1]]) as usize;
+
+    let credential_id = rest
+        .get(2..2 + length)
+        .ok_or(WebAuthnError::InvalidAuthenticatorData)?
+        .to_vec();
+
+    // Extensions may follow the key, reading a single CBOR item leaves them alone
+    let cose_key: Value = ciborium::de::from_reader(&rest[2 + length..])
+        .map_err(|_| WebAuthnError::InvalidAuthenticatorData)?;
+
+    Ok((credential_id, parse_cose_key(&cose_key)?))
+}
+
+// Turn an EC2 COSE key into an uncompressed SEC1 point
+fn parse_cose_key(cose_key: &Value) -> Result<Vec<u8>, WebAuthnError> {
+    let entries = cose_key.as_map().ok_or(WebAuthnError::UnsupportedKey)?;
+
+    let get = |label: i64| {
+        entries
+            .iter()
+            .find(|(key, _)| {
+                key.as_integer().and_then(|key| i64::try_from(key).ok()) == Some(label)
+            })
+            .map(|(_, value)| value)
+    };
+    let get_integer = |label: i64| {
+        get(label)
+            .and_then(Value::as_integer)
+            .and_then(|value| i64::try_from(value).ok())
+    };
+    let get_coordinate = |label: i64| {
+        get(label)
+            .and_then(Value::as_bytes)
+            .filter(|bytes| bytes.len() == 32)
+            .ok_or(WebAuthnError::UnsupportedKey)
+    };
+
+    if get_integer(1) != Some(COSE_KEY_TYPE_EC2)
+        || get_integer(3) != Some(COSE_ALGORITHM_ES256)
+        || get_integer(-1) != Some(COSE_CURVE_P256)
+    {
+        return Err(WebAuthnError::UnsupportedKey);
+    }
+
+    let point = EncodedPoint::from_affine_coordinates(
+        get_coordinate(-2)?.as_slice().into(),
+        get_coordinate(-3)?.as_slice().into(),
+        false,
+    );
+
+    // Make sure the point is actually on the curve before storing it
+    VerifyingKey::from_encoded_point(&point).map_err(|_| WebAuthnError::UnsupportedKey)?;
+
+    Ok(point.as_bytes().to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn client_data(ceremony_type: &str, challenge: &[u8], origin: &str) -> Vec<u8> {
+        serde_json::json!({
+            "type": ceremony_type,
+            "challenge": encode_base64url(challenge),
+            "origin": origin,
+        })
+        .to_string()
+        .into_bytes()
+    }
+
+    #[test]
+    fn test_client_data_with_wrong_type_is_rejected() {
+        let challenge = [1u8; 32];
+        let result = verify_client_data(
+            &client_data("webauthn.get", &challenge, &WEBAUTHN_RP_ORIGIN),
+            "webauthn.create",
+            &challenge,
+        );
+        assert_eq!(result, Err(WebAuthnError::InvalidClientData));
+    }
+
+    #[test]
+    fn test_client_data_with_wrong_challenge_is_rejected() {
+        let result = verify_client_data(
+            &client_data("webauthn.get", &[1u8; 32], &WEBAUTHN_RP_ORIGIN),
+            "webauthn.get",
+            &[2u8; 32],
+        );
+        assert_eq!(result, Err(WebAuthnError::ChallengeMismatch));
+    }
+
+    #[test]
+    fn test_client_data_with_wrong_origin_is_rejected() {
+        let challenge = [1u8; 32];
+        let result = verify_client_data(
+            &client_data("webauthn.get", &challenge, "https://evil.example"),
+            "webauthn.get",
+            &challenge,
+        );
+        assert_eq!(result, Err(WebAuthnError::OriginMismatch));
+    }
+
+    #[test]
+    fn test_short_authenticator_data_is_rejected() {
+        assert!(matches!(
+            parse_authenticator_data(&[0u8; 36]),
+            Err(WebAuthnError::InvalidAuthenticatorData)
+        ));
+    }
+
+    #[test]
+    fn test_authenticator_data_for_other_relying_party_is_rejected() {
+        let mut bytes = Sha256::digest(b"evil.example").to_vec();
+        bytes.push(FLAG_USER_PRESENT | FLAG_USER_VERIFIED);
+        bytes.extend(1u32.to_be_bytes());
+
+        let auth_data = parse_authenticator_data(&bytes).unwrap();
+        assert_eq!(
+            verify_relying_party(&auth_data),
+            Err(WebAuthnError::RelyingPartyMismatch)
+        );
+    }
+
+    #[test]
+    fn test_authenticator_data_without_user_presence_is_rejected() {
+        let mut bytes = Sha256::digest(WEBAUTHN_RP_ID.as_bytes()).to_vec();
+        bytes.push(0);
+        bytes.extend(1u32.to_be_bytes());
+
+        let auth_data = parse_authenticator_data(&bytes).unwrap();
+        assert_eq!(auth_data.sign_count, 1);
+        assert_eq!(
+            verify_relying_party(&auth_data),
+            Err(WebAuthnError::UserNotPresent)
+        );
+    }
+
+    #[test]
+    fn test_authenticator_data_without_user_verification_is_rejected() {
+        let mut bytes = Sha256::digest(WEBAUTHN_RP_ID.as_bytes()).to_vec();
+        bytes.push(FLAG_USER_PRESENT);
+        bytes.extend(1u32.to_be_bytes());
+
+        let auth_data = parse_authenticator_data(&bytes).unwrap();
+        assert_eq!(
+            verify_relying_party(&auth_data),
+            Err(WebAuthnError::UserNotVerified)
+        );
+    }
+
+    #[test]
+    fn test_cose_key_with_other_algorithm_is_rejected() {
+        let cose_key = Value::Map(vec![
+            (Value::from(1), Value::from(1)),
+            (Value::from(3), Value::from(-8)),
+            (Value::from(-1), Value::from(6)),
+            (Value::from(-2), Value::Bytes(vec![0; 32])),
+        ]);
+        assert_eq!(
+            parse_cose_key(&cose_key),
+            Err(WebAuthnError::UnsupportedKey)
+        );
+    }
+}
@@ -0,0 +1,544 @@
+use auth_service::{
+    domain::{
+        CredentialId, Email, EmailConfirmation, EmailToken, LoginAttemptId, PasskeyCredential,
+        PasswordResetToken, RecoveryCode, RefreshToken, TwoFACode,
+    },
+    utils::constants::{JWT_COOKIE_NAME, REFRESH_TOKEN_COOKIE_NAME},
+    ErrorResponse,
+};
+
+use crate::helpers::{get_random_email, TestApp};
+
+struct Session {
+    auth_token: String,
+    refresh_token: String,
+}
+
+async fn signup(app: &TestApp) -> String {
+    let email = get_random_email();
+    let signup_body = serde_json::json!({
+        "email": email,
+        "password": "password123",
+        "requires2FA": false
+    });
+    assert_eq!(app.post_signup(&signup_body).await.status().as_u16(), 201);
+
+    email
+}
+
+async fn login(app: &TestApp, email: &str, password: &str) -> Session {
+    let login_body = serde_json::json!({ "email": email, "password": password });
+    let response = app.post_login(&login_body).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let cookie = |name: &str| {
+        response
+            .cookies()
+            .find(|cookie| cookie.name() == name)
+            .expect("Cookie not found after login")
+            .value()
+            .to_owned()
+    };
+
+    Session {
+        auth_token: cookie(JWT_COOKIE_NAME),
+        refresh_token: cookie(REFRESH_TOKEN_COOKIE_NAME),
+    }
+}
+
+async fn is_refresh_token_valid(app: &TestApp, token: &str) -> bool {
+    app.refresh_token_store
+        .get_token(&RefreshToken::parse(token.to_owned()).unwrap())
+        .await
+        .is_ok()
+}
+
+async fn email_change_token(app: &TestApp, email: &str, new_email: &str) -> String {
+    let email = Email::parse(email.to_owned()).unwrap();
+    let token_version = app.user_store.get_token_version(&email).await.unwrap();
+
+    let token = EmailToken::default();
+    let confirmation = EmailConfirmation::Change {
+        email,
+        new_email: Email::parse(new_email.to_owned()).unwrap(),
+        token_version,
+    };
+    app.email_token_store
+        .add_token(token.clone(), confirmation)
+        .await
+        .expect("Failed to add email change token");
+
+    token.as_ref().to_owned()
+}
+
+#[tokio::test]
+async fn should_return_400_if_changing_password_while_not_logged_in() {
+    let app = TestApp::new().await;
+
+    let body = serde_json::json!({
+        "currentPassword": "password123",
+        "newPassword": "newpassword123",
+    });
+    let response = app.post_change_password(&body).await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_current_password_is_wrong() {
+    let app = TestApp::new().await;
+    let email = signup(&app).await;
+    login(&app, &email, "password123").await;
+
+    let body = serde_json::json!({
+        "currentPassword": "wrongpassword",
+        "newPassword": "newpassword123",
+    });
+    assert_eq!(app.post_change_password(&body).await.status().as_u16(), 401);
+
+    let body = serde_json::json!({
+        "currentPassword": "wrongpassword",
+        "newEmail": get_random_email(),
+    });
+    assert_eq!(app.post_change_email(&body).await.status().as_u16(), 401);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_return_400_if_new_password_is_invalid() {
+    let app = TestApp::new().await;
+    let email = signup(&app).await;
+    login(&app, &email, "password123").await;
+
+    let body = serde_json::json!({
+        "currentPassword": "password123",
+        "newPassword": "short",
+    });
+    let response = app.post_change_password(&body).await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_change_password_and_revoke_other_sessions() {
+    let app = TestApp::new().await;
+    let email = signup(&app).await;
+    let other_session = login(&app, &email, "password123").await;
+    let current_session = login(&app, &email, "password123").await;
+
+    let body = serde_json::json!({
+        "currentPassword": "password123",
+        "newPassword": "newpassword123",
+    });
+    let response = app.post_change_password(&body).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let new_auth_token = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found")
+        .value()
+        .to_owned();
+
+    assert!(!is_refresh_token_valid(&app, &other_session.refresh_token).await);
+    assert!(!is_refresh_token_valid(&app, &current_session.refresh_token).await);
+
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": current_session.auth_token }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": new_auth_token }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let old_login = serde_json::json!({ "email": email, "password": "password123" });
+    assert_eq!(app.post_login(&old_login).await.status().as_u16(), 401);
+    login(&app, &email, "newpassword123").await;
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_return_400_with_field_error_if_new_email_is_malformed() {
+    let app = TestApp::new().await;
+    let email = signup(&app).await;
+    login(&app, &email, "password123").await;
+
+    let body = serde_json::json!({
+        "currentPassword": "password123",
+        "newEmail": "not-an-email",
+    });
+    let response = app.post_change_email(&body).await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    let body = response
+        .json::<ErrorResponse>()
+        .await
+        .expect("Could not deserialize response body to ErrorResponse");
+    assert_eq!(body.code, "invalid_input");
+    let fields: Vec<_> = body
+        .errors
+        .iter()
+        .map(|error| (error.field.as_str(), error.code.as_str()))
+        .collect();
+    assert_eq!(fields, vec![("newEmail", "invalid_email")]);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_return_409_if_new_email_is_taken() {
+    let app = TestApp::new().await;
+    let taken_email = signup(&app).await;
+    let email = signup(&app).await;
+    login(&app, &email, "password123").await;
+
+    let body = serde_json::json!({
+        "currentPassword": "password123",
+        "newEmail": taken_email,
+    });
+    let response = app.post_change_email(&body).await;
+    assert_eq!(response.status().as_u16(), 409);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_not_change_email_before_confirmation() {
+    let app = TestApp::new().await;
+    let email = signup(&app).await;
+    login(&app, &email, "password123").await;
+
+    let body = serde_json::json!({
+        "currentPassword": "password123",
+        "newEmail": get_random_email(),
+    });
+    let response = app.post_change_email(&body).await;
+    assert_eq!(response.status().as_u16(), 202);
+
+    login(&app, &email, "password123").await;
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_change_email_after_confirmation() {
+    let app = TestApp::new().await;
+    let email = signup(&app).await;
+    let session = login(&app, &email, "password123").await;
+
+    let recovery_code = RecoveryCode::default();
+    app.recovery_code_store
+        .replace_codes(&Email::parse(email.clone()).unwrap(), vec![recovery_code])
+        .await
+        .unwrap();
+
+    let new_email = get_random_email();
+    let token = email_change_token(&app, &email, &new_email).await;
+
+    let response = app
+        .post_confirm_email_change(&serde_json::json!({ "token": token }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let old_login = serde_json::json!({ "email": email, "password": "password123" });
+    assert_eq!(app.post_login(&old_login).await.status().as_u16(), 401);
+    login(&app, &new_email, "password123").await;
+
+    let new_email = Email::parse(new_email).unwrap();
+    let user = app.user_store.get_user(&new_email).await.unwrap();
+    assert!(user.email_verified);
+
+    // Data keyed by the email moves along with the account
+    let count = app
+        .recovery_code_store
+        .count_codes(&new_email)
+        .await
+        .unwrap();
+    assert_eq!(count, 1);
+
+    assert!(!is_refresh_token_valid(&app, &session.refresh_token).await);
+
+    // The link only works once
+    let response = app
+        .post_confirm_email_change(&serde_json::json!({ "token": token }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_not_use_up_token_when_link_is_only_opened() {
+    let app = TestApp::new().await;
+    let email = signup(&app).await;
+    let new_email = get_random_email();
+    let token = email_change_token(&app, &email, &new_email).await;
+
+    let response = app
+        .http_client
+        .get(format!("{}/account/email/confirm", &app.address))
+        .query(&[("token", &token)])
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 405);
+
+    let user = app.user_store.get_user(&Email::parse(email).unwrap()).await;
+    assert!(user.is_ok());
+
+    let response = app
+        .post_confirm_email_change(&serde_json::json!({ "token": token }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_return_409_if_new_email_was_taken_before_confirmation() {
+    let app = TestApp::new().await;
+    let email = signup(&app).await;
+    let taken_email = signup(&app).await;
+    let session = login(&app, &email, "password123").await;
+    let token = email_change_token(&app, &email, &taken_email).await;
+
+    let response = app
+        .post_confirm_email_change(&serde_json::json!({ "token": token }))
+        .await;
+    assert_eq!(response.status().as_u16(), 409);
+
+    // Nothing changed, so nobody is logged out
+    assert!(is_refresh_token_valid(&app, &session.refresh_token).await);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_password_changed_before_confirmation() {
+    let app = TestApp::new().await;
+    let email = signup(&app).await;
+    login(&app, &email, "password123").await;
+    let new_email = get_random_email();
+    let token = email_change_token(&app, &email, &new_email).await;
+
+    let body = serde_json::json!({
+        "currentPassword": "password123",
+        "newPassword": "newpassword123",
+    });
+    assert_eq!(app.post_change_password(&body).await.status().as_u16(), 200);
+
+    let response = app
+        .post_confirm_email_change(&serde_json::json!({ "token": token }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let login_body = serde_json::json!({ "email": email, "password": "newpassword123" });
+    assert_eq!(app.post_login(&login_body).await.status().as_u16(), 200);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_email_change_token_is_invalid() {
+    let app = TestApp::new().await;
+    let email = signup(&app).await;
+    let session = login(&app, &email, "password123").await;
+
+    // A verification link of the same account does not confirm a change either
+    let verification_token = EmailToken::default();
+    let confirmation = EmailConfirmation::Verification {
+        email: Email::parse(email).unwrap(),
+    };
+    app.email_token_store
+        .add_token(verification_token.clone(), confirmation)
+        .await
+        .unwrap();
+
+    let test_cases = [
+        "invalid_token".to_owned(),
+        session.auth_token,
+        verification_token.as_ref().to_owned(),
+    ];
+
+    for token in test_cases {
+        let response = app
+            .post_confirm_email_change(&serde_json::json!({ "token": token }))
+            .await;
+        assert_eq!(
+            response.status().as_u16(),
+            401,
+            "Failed for token: {}",
+            token
+        );
+    }
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_return_400_if_deleting_account_while_not_logged_in() {
+    let app = TestApp::new().await;
+
+    let response = app
+        .delete_account(&serde_json::json!({ "password": "password123" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_keep_account_if_password_is_wrong() {
+    let app = TestApp::new().await;
+    let email = signup(&app).await;
+    login(&app, &email, "password123").await;
+
+    let response = app
+        .delete_account(&serde_json::json!({ "password": "wrongpassword" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    login(&app, &email, "password123").await;
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_delete_account_and_purge_its_data() {
+    let app = TestApp::new().await;
+    let email = signup(&app).await;
+    let session = login(&app, &email, "password123").await;
+    let parsed_email = Email::parse(email.clone()).unwrap();
+
+    app.recovery_code_store
+        .replace_codes(&parsed_email, vec![RecoveryCode::default()])
+        .await
+        .unwrap();
+    let credential_id = CredentialId::from(vec![1; 16]);
+    app.passkey_credential_store
+        .add_credential(PasskeyCredential {
+            credential_id: credential_id.clone(),
+            email: parsed_email.clone(),
+            public_key: vec![4; 65],
+            sign_count: 0,
+        })
+        .await
+        .unwrap();
+    app.two_fa_code_store
+        .add_code(
+            parsed_email.clone(),
+            LoginAttemptId::default(),
+            TwoFACode::default(),
+        )
+        .await
+        .unwrap();
+
+    let response = app
+        .delete_account(&serde_json::json!({ "password": "password123" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let login_body = serde_json::json!({ "email": email, "password": "password123" });
+    assert_eq!(app.post_login(&login_body).await.status().as_u16(), 401);
+
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": session.auth_token }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+    assert!(!is_refresh_token_valid(&app, &session.refresh_token).await);
+
+    assert!(app.two_fa_code_store.get_code(&parsed_email).await.is_err());
+    let count = app
+        .recovery_code_store
+        .count_codes(&parsed_email)
+        .await
+        .unwrap();
+    assert_eq!(count, 0);
+    assert!(app
+        .passkey_credential_store
+        .get_credential(&credential_id)
+        .await
+        .is_err());
+
+    // The email can be used for a new account right away
+    let signup_body = serde_json::json!({
+        "email": email,
+        "password": "password123",
+        "requires2FA": false
+    });
+    assert_eq!(app.post_signup(&signup_body).await.status().as_u16(), 201);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_not_carry_links_or_lockouts_over_to_new_account() {
+    let app = TestApp::new().await;
+    let email = signup(&app).await;
+    login(&app, &email, "password123").await;
+    let parsed_email = Email::parse(email.clone()).unwrap();
+
+    let reset_token = PasswordResetToken::default();
+    app.password_reset_token_store
+        .add_token(reset_token.clone(), parsed_email.clone())
+        .await
+        .unwrap();
+
+    let verification_token = EmailToken::default();
+    app.email_token_store
+        .add_token(
+            verification_token.clone(),
+            EmailConfirmation::Verification {
+                email: parsed_email.clone(),
+            },
+        )
+        .await
+        .unwrap();
+    let change_token = email_change_token(&app, &email, &get_random_email()).await;
+
+    let wrong_login = serde_json::json!({ "email": email, "password": "wrongpassword" });
+    for _ in 0..5 {
+        assert_eq!(app.post_login(&wrong_login).await.status().as_u16(), 401);
+    }
+    let login_body = serde_json::json!({ "email": email, "password": "password123" });
+    assert_eq!(app.post_login(&login_body).await.status().as_u16(), 429);
+
+    let response = app
+        .delete_account(&serde_json::json!({ "password": "password123" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    assert!(app
+        .password_reset_token_store
+        .get_token(&reset_token)
+        .await
+        .is_err());
+    assert!(app
+        .email_token_store
+        .take_token(&verification_token)
+        .await
+        .is_err());
+
+    let signup_body = serde_json::json!({
+        "email": email,
+        "password": "password123",
+        "requires2FA": false
+    });
+    assert_eq!(app.post_signup(&signup_body).await.status().as_u16(), 201);
+    assert_eq!(app.post_login(&login_body).await.status().as_u16(), 200);
+
+    // The new account starts over at token version 0, an old change link must not move it
+    let response = app
+        .post_confirm_email_change(&serde_json::json!({ "token": change_token }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+    let user = app.user_store.get_user(&parsed_email).await;
+    assert!(user.is_ok());
+
+    app.cleanup().await;
+}
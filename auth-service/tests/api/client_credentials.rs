@@ -0,0 +1,190 @@
+use auth_service::{
+    domain::{ClientSecret, MachineClient},
+    routes::TokenResponse,
+    utils::auth::validate_token,
+    OAuthErrorResponse,
+};
+
+use crate::helpers::TestApp;
+
+const CLIENT_ID: &str = "billing-job";
+
+async fn register_client(app: &TestApp) -> ClientSecret {
+    let secret = ClientSecret::default();
+    app.machine_client_store
+        .save_client(
+            MachineClient {
+                client_id: CLIENT_ID.to_owned(),
+                name: "Billing job".to_owned(),
+                allowed_scopes: vec!["invoices:read".to_owned(), "invoices:write".to_owned()],
+            },
+            &secret,
+        )
+        .await
+        .expect("Failed to register client");
+
+    secret
+}
+
+#[tokio::test]
+async fn should_issue_token_with_requested_scope() {
+    let app = TestApp::new().await;
+    let secret = register_client(&app).await;
+
+    let form = [
+        ("grant_type", "client_credentials"),
+        ("scope", "invoices:read"),
+    ];
+    let response = app
+        .post_token_as_client(CLIENT_ID, secret.as_ref(), &form)
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let tokens = response
+        .json::<TokenResponse>()
+        .await
+        .expect("Could not deserialize response body to TokenResponse");
+    assert_eq!(tokens.scope, "invoices:read");
+    assert_eq!(tokens.id_token, None);
+
+    let claims = validate_token(
+        &tokens.access_token,
+        &["invoices:read"],
+        &*app.banned_token_store,
+        &*app.session_store,
+        &*app.user_store,
+        &*app.jwt_keyring.read().await,
+    )
+    .await
+    .expect("Token should carry the requested scope");
+    assert_eq!(claims.sub, CLIENT_ID);
+    assert_eq!(claims.client_id.as_deref(), Some(CLIENT_ID));
+
+    let result = validate_token(
+        &tokens.access_token,
+        &["invoices:write"],
+        &*app.banned_token_store,
+        &*app.session_store,
+        &*app.user_store,
+        &*app.jwt_keyring.read().await,
+    )
+    .await;
+    assert!(result.is_err());
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_grant_all_allowed_scopes_without_scope() {
+    let app = TestApp::new().await;
+    let secret = register_client(&app).await;
+
+    // Credentials may also be sent in the form instead of the authorization header
+    let form = [
+        ("grant_type", "client_credentials"),
+        ("client_id", CLIENT_ID),
+        ("client_secret", secret.as_ref()),
+    ];
+    let response = app.post_token(&form).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let tokens = response.json::<TokenResponse>().await.unwrap();
+    assert_eq!(tokens.scope, "invoices:read invoices:write");
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_reject_scope_the_client_is_not_allowed() {
+    let app = TestApp::new().await;
+    let secret = register_client(&app).await;
+
+    let form = [
+        ("grant_type", "client_credentials"),
+        ("scope", "invoices:read users:delete"),
+    ];
+    let response = app
+        .post_token_as_client(CLIENT_ID, secret.as_ref(), &form)
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    let error = response.json::<OAuthErrorResponse>().await.unwrap();
+    assert_eq!(error.error, "invalid_scope");
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_reject_wrong_secret() {
+    let app = TestApp::new().await;
+    register_client(&app).await;
+
+    let form = [("grant_type", "client_credentials")];
+    let wrong_secret = ClientSecret::default();
+    let response = app
+        .post_token_as_client(CLIENT_ID, wrong_secret.as_ref(), &form)
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+    assert_eq!(response.headers().get("www-authenticate").unwrap(), "Basic");
+
+    let error = response.json::<OAuthErrorResponse>().await.unwrap();
+    assert_eq!(error.error, "invalid_client");
+
+    let response = app
+        .post_token_as_client("unknown-job", wrong_secret.as_ref(), &form)
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn machine_token_should_not_get_userinfo() {
+    let app = TestApp::new().await;
+    let secret = ClientSecret::default();
+    app.machine_client_store
+        .save_client(
+            MachineClient {
+                client_id: "someone@example.com".to_owned(),
+                name: "Looks like a user".to_owned(),
+                allowed_scopes: vec!["openid".to_owned()],
+            },
+            &secret,
+        )
+        .await
+        .unwrap();
+
+    let form = [("grant_type", "client_credentials")];
+    let tokens = app
+        .post_token_as_client("someone@example.com", secret.as_ref(), &form)
+        .await
+        .json::<TokenResponse>()
+        .await
+        .unwrap();
+
+    let response = app.get_userinfo(&tokens.access_token).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn machine_token_should_not_verify() {
+    let app = TestApp::new().await;
+    let secret = register_client(&app).await;
+
+    let form = [("grant_type", "client_credentials")];
+    let tokens = app
+        .post_token_as_client(CLIENT_ID, secret.as_ref(), &form)
+        .await
+        .json::<TokenResponse>()
+        .await
+        .unwrap();
+
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": tokens.access_token }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.cleanup().await;
+}
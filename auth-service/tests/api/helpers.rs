@@ -1,11 +1,22 @@
 use std::sync::Arc;
 
 use auth_service::{
-    domain::BannedTokenStore,
-    get_postgres_pool, get_redis_client,
-    utils::constants::{self, DATABASE_URL},
+    app_state::AuthPolicy,
+    domain::{
+        BannedTokenStore, Email, MachineClientStore, OAuthClientStore, PasswordHashParams, Session,
+        UserStore,
+    },
+    get_postgres_pool, get_redis_connection_manager,
+    utils::{
+        auth::TOKEN_TTL_SECONDS,
+        constants::{self, DATABASE_URL},
+        jwt_key::JwtKey,
+        jwt_keyring::JwtKeyring,
+        secret_cipher::SecretCipher,
+    },
     Application,
 };
+use redis::aio::ConnectionManager;
 use reqwest::cookie::Jar;
 use sqlx::{postgres::PgPoolOptions, Executor};
 use tokio::sync::RwLock;
@@ -15,40 +26,145 @@ pub struct TestApp {
     pub address: String,
     pub cookie_jar: Arc<Jar>,
     pub http_client: reqwest::Client,
-    pub banned_token_store: Arc<RwLock<dyn BannedTokenStore>>,
-    pub two_fa_code_store: Arc<RwLock<dyn auth_service::domain::TwoFACodeStore>>,
-    pub user_store: Arc<RwLock<dyn auth_service::domain::UserStore>>,
+    pub banned_token_store: Arc<dyn BannedTokenStore>,
+    pub two_fa_code_store: Arc<dyn auth_service::domain::TwoFACodeStore>,
+    pub refresh_token_store: Arc<dyn auth_service::domain::RefreshTokenStore>,
+    pub session_store: Arc<dyn auth_service::domain::SessionStore>,
+    pub recovery_code_store: Arc<dyn auth_service::domain::RecoveryCodeStore>,
+    pub passkey_credential_store: Arc<dyn auth_service::domain::PasskeyCredentialStore>,
+    pub password_reset_token_store: Arc<dyn auth_service::domain::PasswordResetTokenStore>,
+    pub email_token_store: Arc<dyn auth_service::domain::EmailTokenStore>,
+    pub user_store: Arc<dyn UserStore>,
+    pub oauth_client_store: Arc<dyn OAuthClientStore>,
+    pub machine_client_store: Arc<dyn MachineClientStore>,
+    pub jwt_keyring: Arc<RwLock<JwtKeyring>>,
     db_name: String,
     cleaned_up: bool,
 }
 
 impl TestApp {
     pub async fn new() -> Self {
+        Self::with_policy(AuthPolicy::default()).await
+    }
+
+    pub async fn with_policy(policy: AuthPolicy) -> Self {
+        Self::build(policy, |user_store| user_store, None).await
+    }
+
+    // Lets a test put its own store in front of the Postgres one, e.g. to observe or delay calls
+    pub async fn with_user_store(
+        wrap: impl FnOnce(Arc<dyn UserStore>) -> Arc<dyn UserStore>,
+    ) -> Self {
+        Self::build(AuthPolicy::default(), wrap, None).await
+    }
+
+    // Signs tokens with the given key instead of a freshly generated Ed25519 one
+    pub async fn with_jwt_key(jwt_key: JwtKey) -> Self {
+        Self::build(
+            AuthPolicy::default(),
+            |user_store| user_store,
+            Some(jwt_key),
+        )
+        .await
+    }
+
+    async fn build(
+        policy: AuthPolicy,
+        wrap_user_store: impl FnOnce(Arc<dyn UserStore>) -> Arc<dyn UserStore>,
+        jwt_key: Option<JwtKey>,
+    ) -> Self {
         let db_name = Uuid::new_v4().to_string();
         let pg_pool = configure_postgres(&db_name).await;
 
-        let user_store = Arc::new(RwLock::new(auth_service::services::PostgresUserStore::new(
-            pg_pool,
+        let user_store = wrap_user_store(Arc::new(auth_service::services::PostgresUserStore::new(
+            pg_pool.clone(),
+            SecretCipher::new(&rand::random()),
+            PasswordHashParams::default(),
         )));
 
-        let shared_redis_conn = configure_redis();
-        let shared_redis_conn = Arc::new(RwLock::new(shared_redis_conn));
+        let refresh_token_store = Arc::new(auth_service::services::PostgresRefreshTokenStore::new(
+            pg_pool.clone(),
+        ));
 
-        let banned_token_store = Arc::new(RwLock::new(
-            auth_service::services::RedisBannedTokenStore::new(shared_redis_conn.clone()),
+        let session_store = Arc::new(auth_service::services::PostgresSessionStore::new(
+            pg_pool.clone(),
         ));
 
-        let two_fa_code_store = Arc::new(RwLock::new(
-            auth_service::services::RedisTwoFaCodeStore::new(shared_redis_conn.clone()),
+        let recovery_code_store = Arc::new(auth_service::services::PostgresRecoveryCodeStore::new(
+            pg_pool.clone(),
+        ));
+
+        let passkey_credential_store = Arc::new(
+            auth_service::services::PostgresPasskeyCredentialStore::new(pg_pool.clone()),
+        );
+
+        let oauth_client_store = Arc::new(auth_service::services::PostgresOAuthClientStore::new(
+            pg_pool.clone(),
+        ));
+
+        let machine_client_store = Arc::new(
+            auth_service::services::PostgresMachineClientStore::new(pg_pool),
+        );
+
+        let shared_redis_conn = configure_redis().await;
+
+        let banned_token_store = Arc::new(auth_service::services::RedisBannedTokenStore::new(
+            shared_redis_conn.clone(),
         ));
 
+        let two_fa_code_store = Arc::new(auth_service::services::RedisTwoFaCodeStore::new(
+            shared_redis_conn.clone(),
+        ));
+
+        let webauthn_challenge_store = Arc::new(
+            auth_service::services::RedisWebAuthnChallengeStore::new(shared_redis_conn.clone()),
+        );
+
+        let password_reset_token_store = Arc::new(
+            auth_service::services::RedisPasswordResetTokenStore::new(shared_redis_conn.clone()),
+        );
+
+        let email_token_store = Arc::new(auth_service::services::RedisEmailTokenStore::new(
+            shared_redis_conn.clone(),
+        ));
+
+        // Kept in memory, since every test hits the service from the same address
+        let login_attempt_store =
+            Arc::new(auth_service::services::HashMapLoginAttemptStore::default());
+
+        let rate_limit_store = Arc::new(auth_service::services::HashMapRateLimitStore::default());
+        rate_limit_store.spawn_sweeper(std::time::Duration::from_secs(60));
+
+        let authorization_code_store = Arc::new(
+            auth_service::services::RedisAuthorizationCodeStore::new(shared_redis_conn.clone()),
+        );
+
         let email_client = Arc::new(auth_service::services::MockEmailClient {});
 
+        let jwt_keyring = Arc::new(RwLock::new(JwtKeyring::new(
+            jwt_key.unwrap_or_else(|| JwtKey::generate().expect("Failed to generate JWT key")),
+            chrono::Duration::seconds(TOKEN_TTL_SECONDS as i64),
+        )));
+
         let app_state = auth_service::app_state::AppState::new(
             user_store.clone(),
             banned_token_store.clone(),
             two_fa_code_store.clone(),
+            refresh_token_store.clone(),
+            session_store.clone(),
+            recovery_code_store.clone(),
+            passkey_credential_store.clone(),
+            webauthn_challenge_store,
+            password_reset_token_store.clone(),
+            email_token_store.clone(),
+            login_attempt_store,
+            rate_limit_store,
+            oauth_client_store.clone(),
+            authorization_code_store,
+            machine_client_store.clone(),
             email_client,
+            jwt_keyring.clone(),
+            policy,
         );
 
         let app = Application::build(app_state, constants::test::APP_ADDRESS)
@@ -61,8 +177,10 @@ impl TestApp {
         let _ = tokio::spawn(app.run());
 
         let cookie_jar = Arc::new(Jar::default());
+        // Redirects are asserted on instead of followed, they only lead to other services
         let http_client = reqwest::Client::builder()
             .cookie_provider(cookie_jar.clone())
+            .redirect(reqwest::redirect::Policy::none())
             .build()
             .unwrap();
 
@@ -72,7 +190,16 @@ impl TestApp {
             http_client,
             banned_token_store,
             two_fa_code_store,
+            refresh_token_store,
+            session_store,
+            recovery_code_store,
+            passkey_credential_store,
+            password_reset_token_store,
+            email_token_store,
             user_store,
+            oauth_client_store,
+            machine_client_store,
+            jwt_keyring,
             db_name,
             cleaned_up: false,
         }
@@ -113,6 +240,167 @@ impl TestApp {
             .expect("Failed to execute request.")
     }
 
+    pub async fn post_verify_2fa_recovery<Body: serde::Serialize>(
+        &self,
+        body: &Body,
+    ) -> reqwest::Response {
+        self.http_client
+            .post(format!("{}/verify-2fa/recovery", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_recovery_codes<Body: serde::Serialize>(
+        &self,
+        body: &Body,
+    ) -> reqwest::Response {
+        self.http_client
+            .post(format!("{}/recovery-codes", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_passkey_register_start(&self) -> reqwest::Response {
+        self.http_client
+            .post(format!("{}/passkeys/register/start", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_passkey_register_finish<Body: serde::Serialize>(
+        &self,
+        body: &Body,
+    ) -> reqwest::Response {
+        self.http_client
+            .post(format!("{}/passkeys/register/finish", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_passkey_login_start<Body: serde::Serialize>(
+        &self,
+        body: &Body,
+    ) -> reqwest::Response {
+        self.http_client
+            .post(format!("{}/passkeys/login/start", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_passkey_login_finish<Body: serde::Serialize>(
+        &self,
+        body: &Body,
+    ) -> reqwest::Response {
+        self.http_client
+            .post(format!("{}/passkeys/login/finish", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_password_reset_request<Body: serde::Serialize>(
+        &self,
+        body: &Body,
+    ) -> reqwest::Response {
+        self.http_client
+            .post(format!("{}/password-reset/request", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_password_reset_confirm<Body: serde::Serialize>(
+        &self,
+        body: &Body,
+    ) -> reqwest::Response {
+        self.http_client
+            .post(format!("{}/password-reset/confirm", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_verify_email<Body: serde::Serialize>(
+        &self,
+        body: &Body,
+    ) -> reqwest::Response {
+        self.http_client
+            .post(format!("{}/verify-email", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_verify_email_resend<Body: serde::Serialize>(
+        &self,
+        body: &Body,
+    ) -> reqwest::Response {
+        self.http_client
+            .post(format!("{}/verify-email/resend", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_change_password<Body: serde::Serialize>(
+        &self,
+        body: &Body,
+    ) -> reqwest::Response {
+        self.http_client
+            .post(format!("{}/account/password", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn delete_account<Body: serde::Serialize>(&self, body: &Body) -> reqwest::Response {
+        self.http_client
+            .delete(format!("{}/account", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_change_email<Body: serde::Serialize>(
+        &self,
+        body: &Body,
+    ) -> reqwest::Response {
+        self.http_client
+            .post(format!("{}/account/email", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_confirm_email_change<Body: serde::Serialize>(
+        &self,
+        body: &Body,
+    ) -> reqwest::Response {
+        self.http_client
+            .post(format!("{}/account/email/confirm", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
     pub async fn post_logout(&self) -> reqwest::Response {
         self.http_client
             .post(format!("{}/logout", &self.address))
@@ -121,6 +409,79 @@ impl TestApp {
             .expect("Failed to execute request.")
     }
 
+    pub async fn post_logout_all(&self) -> reqwest::Response {
+        self.http_client
+            .post(format!("{}/logout-all", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_refresh(&self) -> reqwest::Response {
+        self.http_client
+            .post(format!("{}/refresh", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_sessions(&self) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/sessions", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn delete_session(&self, id: &str) -> reqwest::Response {
+        self.http_client
+            .delete(format!("{}/sessions/{}", &self.address, id))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_jwks(&self) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/.well-known/jwks.json", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_totp_enroll<Body: serde::Serialize>(&self, body: &Body) -> reqwest::Response {
+        self.http_client
+            .post(format!("{}/totp/enroll", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_totp_confirm<Body: serde::Serialize>(
+        &self,
+        body: &Body,
+    ) -> reqwest::Response {
+        self.http_client
+            .post(format!("{}/totp/confirm", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_totp_disable<Body: serde::Serialize>(
+        &self,
+        body: &Body,
+    ) -> reqwest::Response {
+        self.http_client
+            .post(format!("{}/totp/disable", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
     pub async fn post_verify_token<Body: serde::Serialize>(
         &self,
         body: &Body,
@@ -133,6 +494,127 @@ impl TestApp {
             .expect("Failed to execute request.")
     }
 
+    pub async fn get_authorize<Query: serde::Serialize + ?Sized>(
+        &self,
+        query: &Query,
+    ) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/authorize", &self.address))
+            .query(query)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_consent(&self, request: &str) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/authorize/consent", &self.address))
+            .query(&[("request", request)])
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_consent<Body: serde::Serialize>(&self, body: &Body) -> reqwest::Response {
+        self.http_client
+            .post(format!("{}/authorize/consent", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_token<Form: serde::Serialize + ?Sized>(
+        &self,
+        form: &Form,
+    ) -> reqwest::Response {
+        self.http_client
+            .post(format!("{}/token", &self.address))
+            .form(form)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_token_as_client<Form: serde::Serialize + ?Sized>(
+        &self,
+        client_id: &str,
+        client_secret: &str,
+        form: &Form,
+    ) -> reqwest::Response {
+        self.post_form_as_client("/token", client_id, client_secret, form)
+            .await
+    }
+
+    pub async fn post_introspect<Form: serde::Serialize + ?Sized>(
+        &self,
+        client_id: &str,
+        client_secret: &str,
+        form: &Form,
+    ) -> reqwest::Response {
+        self.post_form_as_client("/introspect", client_id, client_secret, form)
+            .await
+    }
+
+    pub async fn post_revoke<Form: serde::Serialize + ?Sized>(
+        &self,
+        client_id: &str,
+        client_secret: &str,
+        form: &Form,
+    ) -> reqwest::Response {
+        self.post_form_as_client("/revoke", client_id, client_secret, form)
+            .await
+    }
+
+    async fn post_form_as_client<Form: serde::Serialize + ?Sized>(
+        &self,
+        path: &str,
+        client_id: &str,
+        client_secret: &str,
+        form: &Form,
+    ) -> reqwest::Response {
+        self.http_client
+            .post(format!("{}{}", &self.address, path))
+            .basic_auth(client_id, Some(client_secret))
+            .form(form)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_userinfo(&self, access_token: &str) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/userinfo", &self.address))
+            .bearer_auth(access_token)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_openid_configuration(&self) -> reqwest::Response {
+        self.http_client
+            .get(format!(
+                "{}/.well-known/openid-configuration",
+                &self.address
+            ))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    // Records a session for tokens that are issued directly instead of through a login
+    pub async fn add_session(&self, email: &Email) -> Uuid {
+        let session = Session::new(email.clone(), None, None);
+        let session_id = session.id;
+
+        self.session_store
+            .add_session(session)
+            .await
+            .expect("Failed to add session");
+
+        session_id
+    }
+
     pub async fn cleanup(mut self) {
         let database_url = DATABASE_URL.to_owned();
         delete_database(&database_url, &self.db_name).await;
@@ -210,9 +692,8 @@ async fn delete_database(database_url: &str, db_name: &str) {
         .expect("Failed to drop database");
 }
 
-fn configure_redis() -> redis::Connection {
-    get_redis_client(constants::REDIS_HOST_NAME.to_owned())
-        .expect("Failed to get Redis client")
-        .get_connection()
-        .expect("Failed to get Redis connection")
+async fn configure_redis() -> ConnectionManager {
+    get_redis_connection_manager(constants::REDIS_HOST_NAME.to_owned())
+        .await
+        .expect("Failed to connect to Redis")
 }
@@ -0,0 +1,222 @@
+use auth_service::{
+    domain::{ClientSecret, Email, MachineClient, Password, User},
+    routes::{IntrospectionResponse, TokenResponse},
+    utils::auth::{decode_auth_token, generate_auth_cookie},
+    OAuthErrorResponse,
+};
+
+use crate::helpers::{get_random_email, TestApp};
+
+async fn register_client(app: &TestApp, client_id: &str) -> ClientSecret {
+    let secret = ClientSecret::default();
+    app.machine_client_store
+        .save_client(
+            MachineClient {
+                client_id: client_id.to_owned(),
+                name: client_id.to_owned(),
+                allowed_scopes: vec!["reports:read".to_owned()],
+            },
+            &secret,
+        )
+        .await
+        .expect("Failed to register client");
+
+    secret
+}
+
+async fn issue_login_token(app: &TestApp, email: &Email) -> String {
+    let password = Password::parse("password123".to_owned()).unwrap();
+    app.user_store
+        .add_user(User::new(email.clone(), false), password)
+        .await
+        .expect("Failed to add user");
+    let session_id = app.add_session(email).await;
+
+    generate_auth_cookie(email, session_id, 0, &*app.jwt_keyring.read().await)
+        .unwrap()
+        .value()
+        .to_owned()
+}
+
+async fn issue_machine_token(app: &TestApp, client_id: &str, secret: &ClientSecret) -> String {
+    let form = [("grant_type", "client_credentials")];
+    app.post_token_as_client(client_id, secret.as_ref(), &form)
+        .await
+        .json::<TokenResponse>()
+        .await
+        .expect("Could not deserialize response body to TokenResponse")
+        .access_token
+}
+
+#[tokio::test]
+async fn should_introspect_login_token() {
+    let app = TestApp::new().await;
+    let secret = register_client(&app, "app-service").await;
+    let email = Email::parse(get_random_email()).unwrap();
+    let token = issue_login_token(&app, &email).await;
+
+    let response = app
+        .post_introspect("app-service", secret.as_ref(), &[("token", &token)])
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(response.headers().get("cache-control").unwrap(), "no-store");
+
+    let introspection = response
+        .json::<IntrospectionResponse>()
+        .await
+        .expect("Could not deserialize response body to IntrospectionResponse");
+    let claims = decode_auth_token(&token, &*app.jwt_keyring.read().await).unwrap();
+    assert!(introspection.active);
+    assert_eq!(introspection.sub.as_deref(), Some(email.as_ref()));
+    assert_eq!(introspection.jti, Some(claims.jti));
+    assert_eq!(introspection.exp, Some(claims.exp));
+    assert!(introspection.iat.is_some_and(|iat| iat < claims.exp));
+    assert_eq!(introspection.client_id, None);
+    assert_eq!(introspection.scope, None);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_introspect_machine_token() {
+    let app = TestApp::new().await;
+    let secret = register_client(&app, "app-service").await;
+    let reports_secret = register_client(&app, "reports-job").await;
+    let token = issue_machine_token(&app, "reports-job", &reports_secret).await;
+
+    let introspection = app
+        .post_introspect("app-service", secret.as_ref(), &[("token", &token)])
+        .await
+        .json::<IntrospectionResponse>()
+        .await
+        .unwrap();
+    assert!(introspection.active);
+    assert_eq!(introspection.sub.as_deref(), Some("reports-job"));
+    assert_eq!(introspection.client_id.as_deref(), Some("reports-job"));
+    assert_eq!(introspection.scope.as_deref(), Some("reports:read"));
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_only_report_inactive_tokens_as_inactive() {
+    let app = TestApp::new().await;
+    let secret = register_client(&app, "app-service").await;
+
+    let response = app
+        .post_introspect("app-service", secret.as_ref(), &[("token", "invalid")])
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let body = response.json::<serde_json::Value>().await.unwrap();
+    assert_eq!(body, serde_json::json!({ "active": false }));
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_require_client_credentials() {
+    let app = TestApp::new().await;
+    let secret = register_client(&app, "app-service").await;
+    let email = Email::parse(get_random_email()).unwrap();
+    let token = issue_login_token(&app, &email).await;
+
+    let wrong_secret = ClientSecret::default();
+    let form = [("token", &token)];
+    let responses = [
+        app.post_introspect("app-service", wrong_secret.as_ref(), &form)
+            .await,
+        app.post_revoke("app-service", wrong_secret.as_ref(), &form)
+            .await,
+    ];
+    for response in responses {
+        assert_eq!(response.status().as_u16(), 401);
+        let error = response.json::<OAuthErrorResponse>().await.unwrap();
+        assert_eq!(error.error, "invalid_client");
+    }
+
+    let response = app
+        .post_introspect("app-service", secret.as_ref(), &[("token", "")])
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+    let error = response.json::<OAuthErrorResponse>().await.unwrap();
+    assert_eq!(error.error, "invalid_request");
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_not_revoke_login_token() {
+    let app = TestApp::new().await;
+    let secret = register_client(&app, "app-service").await;
+    let email = Email::parse(get_random_email()).unwrap();
+    let token = issue_login_token(&app, &email).await;
+
+    let form = [
+        ("token", token.as_str()),
+        ("token_type_hint", "access_token"),
+    ];
+    let response = app.post_revoke("app-service", secret.as_ref(), &form).await;
+    assert_eq!(response.status().as_u16(), 400);
+    let error = response.json::<OAuthErrorResponse>().await.unwrap();
+    assert_eq!(error.error, "unauthorized_client");
+
+    let introspection = app
+        .post_introspect("app-service", secret.as_ref(), &[("token", &token)])
+        .await
+        .json::<IntrospectionResponse>()
+        .await
+        .unwrap();
+    assert!(introspection.active);
+
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": token }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_accept_revoking_invalid_token() {
+    let app = TestApp::new().await;
+    let secret = register_client(&app, "app-service").await;
+
+    let response = app
+        .post_revoke("app-service", secret.as_ref(), &[("token", "invalid")])
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_not_revoke_token_of_another_client() {
+    let app = TestApp::new().await;
+    let secret = register_client(&app, "app-service").await;
+    let reports_secret = register_client(&app, "reports-job").await;
+    let token = issue_machine_token(&app, "reports-job", &reports_secret).await;
+
+    let response = app
+        .post_revoke("app-service", secret.as_ref(), &[("token", &token)])
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+    let error = response.json::<OAuthErrorResponse>().await.unwrap();
+    assert_eq!(error.error, "unauthorized_client");
+
+    // The client the token was issued to can revoke it
+    let response = app
+        .post_revoke("reports-job", reports_secret.as_ref(), &[("token", &token)])
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let introspection = app
+        .post_introspect("app-service", secret.as_ref(), &[("token", &token)])
+        .await
+        .json::<IntrospectionResponse>()
+        .await
+        .unwrap();
+    assert!(!introspection.active);
+
+    app.cleanup().await;
+}
@@ -0,0 +1,67 @@
+use auth_service::{
+    domain::Email,
+    utils::{auth::generate_auth_cookie, jwt_key::JwtKey},
+};
+use jsonwebtoken::{decode, decode_header, jwk::JwkSet, DecodingKey, Validation};
+use uuid::Uuid;
+
+use crate::helpers::{get_random_email, TestApp};
+
+#[tokio::test]
+async fn should_return_public_signing_keys() {
+    let app = TestApp::new().await;
+
+    let response = app.get_jwks().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let jwks = response
+        .json::<JwkSet>()
+        .await
+        .expect("Failed to deserialize response body to JwkSet");
+    assert_eq!(jwks.keys.len(), 1);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn published_key_should_validate_issued_token() {
+    let app = TestApp::new().await;
+
+    let email = Email::parse(get_random_email()).unwrap();
+    let token = generate_auth_cookie(&email, Uuid::new_v4(), 0, &*app.jwt_keyring.read().await)
+        .unwrap()
+        .value()
+        .to_owned();
+
+    let header = decode_header(&token).unwrap();
+    let jwks = app.get_jwks().await.json::<JwkSet>().await.unwrap();
+    let jwk = jwks
+        .find(header.kid.as_deref().expect("Token should carry a kid"))
+        .expect("Signing key should be published");
+    let decoding_key = DecodingKey::from_jwk(jwk).unwrap();
+
+    let claims = decode::<serde_json::Value>(&token, &decoding_key, &Validation::new(header.alg))
+        .expect("Token should validate against the published key")
+        .claims;
+
+    assert_eq!(claims["sub"], email.as_ref());
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_publish_retired_keys_during_overlap() {
+    let app = TestApp::new().await;
+
+    let previous_kid = app.jwt_keyring.read().await.active_key().kid().to_owned();
+    app.jwt_keyring
+        .write()
+        .await
+        .promote(JwtKey::generate().expect("Failed to generate JWT key"));
+
+    let jwks = app.get_jwks().await.json::<JwkSet>().await.unwrap();
+    assert_eq!(jwks.keys.len(), 2);
+    assert!(jwks.find(&previous_kid).is_some());
+
+    app.cleanup().await;
+}
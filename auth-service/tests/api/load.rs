@@ -0,0 +1,178 @@
+use std::{
+    sync::{Arc, OnceLock},
+    time::Duration,
+};
+
+use auth_service::domain::{
+    Email, Password, PasswordHash, TotpSecret, TwoFAMethod, User, UserStore, UserStoreError,
+};
+use futures::future::join_all;
+use tokio::sync::Barrier;
+
+use crate::helpers::{get_random_email, TestApp};
+
+const CONCURRENT_LOGINS: usize = 20;
+
+// Holds every login and signup at a barrier until all of them have reached the store at once.
+// Requests that were serialized behind each other would never get there together and time out.
+struct RendezvousUserStore {
+    inner: Arc<dyn UserStore>,
+    barrier: Arc<OnceLock<Barrier>>,
+}
+
+impl RendezvousUserStore {
+    async fn rendezvous(&self) {
+        if let Some(barrier) = self.barrier.get() {
+            barrier.wait().await;
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl UserStore for RendezvousUserStore {
+    async fn add_user(&self, user: User, password: Password) -> Result<(), UserStoreError> {
+        self.rendezvous().await;
+        self.inner.add_user(user, password).await
+    }
+
+    async fn import_user(
+        &self,
+        user: User,
+        password_hash: PasswordHash,
+    ) -> Result<(), UserStoreError> {
+        self.inner.import_user(user, password_hash).await
+    }
+
+    async fn get_user(&self, email: &Email) -> Result<User, UserStoreError> {
+        self.inner.get_user(email).await
+    }
+
+    async fn validate_user(
+        &self,
+        email: &Email,
+        password: &Password,
+    ) -> Result<User, UserStoreError> {
+        self.rendezvous().await;
+        self.inner.validate_user(email, password).await
+    }
+
+    async fn set_pending_totp_secret(
+        &self,
+        email: &Email,
+        secret: TotpSecret,
+    ) -> Result<(), UserStoreError> {
+        self.inner.set_pending_totp_secret(email, secret).await
+    }
+
+    async fn get_pending_totp_secret(&self, email: &Email) -> Result<TotpSecret, UserStoreError> {
+        self.inner.get_pending_totp_secret(email).await
+    }
+
+    async fn confirm_totp_secret(
+        &self,
+        email: &Email,
+        secret: TotpSecret,
+    ) -> Result<(), UserStoreError> {
+        self.inner.confirm_totp_secret(email, secret).await
+    }
+
+    async fn get_totp_secret(&self, email: &Email) -> Result<TotpSecret, UserStoreError> {
+        self.inner.get_totp_secret(email).await
+    }
+
+    async fn set_two_fa_method(
+        &self,
+        email: &Email,
+        method: TwoFAMethod,
+    ) -> Result<(), UserStoreError> {
+        self.inner.set_two_fa_method(email, method).await
+    }
+
+    async fn update_password(
+        &self,
+        email: &Email,
+        password: Password,
+    ) -> Result<(), UserStoreError> {
+        self.inner.update_password(email, password).await
+    }
+
+    async fn set_email_verified(&self, email: &Email) -> Result<(), UserStoreError> {
+        self.inner.set_email_verified(email).await
+    }
+
+    async fn update_email(&self, email: &Email, new_email: &Email) -> Result<(), UserStoreError> {
+        self.inner.update_email(email, new_email).await
+    }
+
+    async fn delete_user(&self, email: &Email) -> Result<(), UserStoreError> {
+        self.inner.delete_user(email).await
+    }
+
+    async fn get_token_version(&self, email: &Email) -> Result<u32, UserStoreError> {
+        self.inner.get_token_version(email).await
+    }
+
+    async fn bump_token_version(&self, email: &Email) -> Result<u32, UserStoreError> {
+        self.inner.bump_token_version(email).await
+    }
+}
+
+fn signup_body(email: &str) -> serde_json::Value {
+    serde_json::json!({
+        "email": email,
+        "password": "password123",
+        "requires2FA": false
+    })
+}
+
+#[tokio::test]
+async fn should_not_serialize_concurrent_logins_behind_a_signup() {
+    let barrier = Arc::new(OnceLock::new());
+    let app = TestApp::with_user_store(|inner| {
+        Arc::new(RendezvousUserStore {
+            inner,
+            barrier: barrier.clone(),
+        })
+    })
+    .await;
+
+    let mut emails = Vec::with_capacity(CONCURRENT_LOGINS);
+    for _ in 0..CONCURRENT_LOGINS {
+        let email = get_random_email();
+        assert_eq!(
+            app.post_signup(&signup_body(&email))
+                .await
+                .status()
+                .as_u16(),
+            201
+        );
+        emails.push(email);
+    }
+
+    // From here on every login and the signup below wait for each other inside the store
+    barrier
+        .set(Barrier::new(CONCURRENT_LOGINS + 1))
+        .unwrap_or_else(|_| unreachable!());
+
+    let login_bodies: Vec<_> = emails
+        .iter()
+        .map(|email| serde_json::json!({ "email": email, "password": "password123" }))
+        .collect();
+    let logins = join_all(login_bodies.iter().map(|body| app.post_login(body)));
+    let new_email = get_random_email();
+    let signup_body = signup_body(&new_email);
+    let signup = app.post_signup(&signup_body);
+
+    let (logins, signup) = tokio::time::timeout(Duration::from_secs(30), async {
+        tokio::join!(logins, signup)
+    })
+    .await
+    .expect("Concurrent logins and signup were serialized");
+
+    for response in logins {
+        assert_eq!(response.status().as_u16(), 200);
+    }
+    assert_eq!(signup.status().as_u16(), 201);
+
+    app.cleanup().await;
+}
@@ -1,8 +1,11 @@
 use auth_service::{
-    domain::{Email, LoginAttemptId},
+    app_state::{AuthPolicy, LoginThrottlePolicy},
+    domain::{Email, LoginAttemptId, PasswordHash, User},
     routes::TwoFactorAuthResponse,
     utils::constants::JWT_COOKIE_NAME,
+    ErrorResponse, PROBLEM_JSON_CONTENT_TYPE,
 };
+use uuid::Uuid;
 
 use crate::helpers::{get_random_email, TestApp};
 
@@ -42,6 +45,74 @@ async fn should_return_400_if_invalid_input() {
     app.cleanup().await;
 }
 
+#[tokio::test]
+async fn should_report_every_invalid_field_with_the_request_id() {
+    let app = TestApp::new().await;
+
+    let login_body = serde_json::json!({ "email": "invalid-email", "password": "short" });
+    let response = app.post_login(&login_body).await;
+    assert_eq!(response.status().as_u16(), 400);
+    assert_eq!(
+        response.headers()["content-type"],
+        PROBLEM_JSON_CONTENT_TYPE
+    );
+
+    let request_id = response.headers()["x-request-id"]
+        .to_str()
+        .unwrap()
+        .parse::<Uuid>()
+        .unwrap();
+    let body = response
+        .json::<ErrorResponse>()
+        .await
+        .expect("Could not deserialize response body to ErrorResponse");
+
+    assert_eq!(body.problem_type, "urn:auth-service:problem:invalid_input");
+    assert_eq!(body.status, 400);
+    assert_eq!(body.code, "invalid_input");
+    assert_eq!(body.request_id, Some(request_id));
+    let fields: Vec<_> = body
+        .errors
+        .iter()
+        .map(|error| (error.field.as_str(), error.code.as_str()))
+        .collect();
+    assert_eq!(
+        fields,
+        vec![("email", "invalid_email"), ("password", "invalid_password")]
+    );
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_keep_the_request_id_given_by_the_client() {
+    let app = TestApp::new().await;
+    let request_id = Uuid::new_v4();
+
+    let response = app
+        .http_client
+        .post(format!("{}/login", &app.address))
+        .header("x-request-id", request_id.to_string())
+        .json(&serde_json::json!({ "email": get_random_email(), "password": "password123" }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status().as_u16(), 401);
+    assert_eq!(
+        response.headers()["x-request-id"],
+        request_id.to_string().as_str()
+    );
+
+    let body = response
+        .json::<ErrorResponse>()
+        .await
+        .expect("Could not deserialize response body to ErrorResponse");
+    assert_eq!(body.code, "incorrect_credentials");
+    assert_eq!(body.request_id, Some(request_id));
+
+    app.cleanup().await;
+}
+
 #[tokio::test]
 async fn should_return_401_if_credentials_are_incorrect() {
     let app = TestApp::new().await;
@@ -92,6 +163,45 @@ async fn should_return_200_if_valid_credentials_and_2fa_disabled() {
     app.cleanup().await;
 }
 
+#[tokio::test]
+async fn should_return_200_for_imported_user_with_legacy_hash() {
+    let app = TestApp::new().await;
+
+    let random_email = get_random_email();
+    let email = Email::parse(random_email.clone()).unwrap();
+    let legacy_hash = bcrypt::hash("password123", 4).unwrap();
+
+    app.user_store
+        .import_user(
+            User::new(email, false),
+            PasswordHash::parse(legacy_hash).unwrap(),
+        )
+        .await
+        .unwrap();
+
+    let wrong_login_body = serde_json::json!({
+        "email": random_email,
+        "password": "wrongpassword",
+    });
+
+    assert_eq!(
+        app.post_login(&wrong_login_body).await.status().as_u16(),
+        401
+    );
+
+    let login_body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+    });
+
+    // The second login checks the Argon2id hash the first one replaced the bcrypt hash with
+    for _ in 0..2 {
+        assert_eq!(app.post_login(&login_body).await.status().as_u16(), 200);
+    }
+
+    app.cleanup().await;
+}
+
 #[tokio::test]
 async fn should_return_206_if_valid_credentials_and_2fa_enabled() {
     let app = TestApp::new().await;
@@ -130,8 +240,6 @@ async fn should_return_206_if_valid_credentials_and_2fa_enabled() {
 
     let (stored_attempt_id, _) = app
         .two_fa_code_store
-        .read()
-        .await
         .get_code(&email)
         .await
         .expect("2FA code not found");
@@ -140,3 +248,100 @@ async fn should_return_206_if_valid_credentials_and_2fa_enabled() {
 
     app.cleanup().await;
 }
+
+fn throttle_policy(max_failures_per_email: u32, max_failures_per_ip: u32) -> AuthPolicy {
+    AuthPolicy {
+        login_throttle: LoginThrottlePolicy {
+            max_failures_per_email,
+            max_failures_per_ip,
+            ..Default::default()
+        },
+        ..Default::default()
+    }
+}
+
+async fn signup(app: &TestApp) -> String {
+    let email = get_random_email();
+    let signup_body = serde_json::json!({
+        "email": email,
+        "password": "password123",
+        "requires2FA": false
+    });
+    assert_eq!(app.post_signup(&signup_body).await.status().as_u16(), 201);
+
+    email
+}
+
+#[tokio::test]
+async fn should_return_429_after_too_many_failures_for_account() {
+    let app = TestApp::with_policy(throttle_policy(3, 50)).await;
+    let email = signup(&app).await;
+
+    let wrong_login = serde_json::json!({ "email": email, "password": "wrongpassword" });
+    for _ in 0..3 {
+        assert_eq!(app.post_login(&wrong_login).await.status().as_u16(), 401);
+    }
+
+    // Even the right password is refused during the lockout
+    let login_body = serde_json::json!({ "email": email, "password": "password123" });
+    let response = app.post_login(&login_body).await;
+    assert_eq!(response.status().as_u16(), 429);
+
+    let retry_after: u64 = response
+        .headers()
+        .get("retry-after")
+        .expect("No Retry-After header found")
+        .to_str()
+        .unwrap()
+        .parse()
+        .unwrap();
+    assert!(retry_after > 0);
+
+    // Other accounts are not affected
+    let other_email = signup(&app).await;
+    let login_body = serde_json::json!({ "email": other_email, "password": "password123" });
+    assert_eq!(app.post_login(&login_body).await.status().as_u16(), 200);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_forget_account_failures_after_successful_login() {
+    let app = TestApp::with_policy(throttle_policy(3, 50)).await;
+    let email = signup(&app).await;
+
+    let wrong_login = serde_json::json!({ "email": email, "password": "wrongpassword" });
+    let login_body = serde_json::json!({ "email": email, "password": "password123" });
+
+    for _ in 0..2 {
+        assert_eq!(app.post_login(&wrong_login).await.status().as_u16(), 401);
+    }
+    assert_eq!(app.post_login(&login_body).await.status().as_u16(), 200);
+
+    for _ in 0..2 {
+        assert_eq!(app.post_login(&wrong_login).await.status().as_u16(), 401);
+    }
+    assert_eq!(app.post_login(&login_body).await.status().as_u16(), 200);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_return_429_after_too_many_failures_from_address() {
+    let app = TestApp::with_policy(throttle_policy(50, 3)).await;
+
+    // Unknown accounts count as well, spreading guesses over many emails does not help
+    for _ in 0..3 {
+        let wrong_login =
+            serde_json::json!({ "email": get_random_email(), "password": "wrongpassword" });
+        assert_eq!(app.post_login(&wrong_login).await.status().as_u16(), 401);
+    }
+
+    let email = signup(&app).await;
+    let login_body = serde_json::json!({ "email": email, "password": "password123" });
+    let response = app.post_login(&login_body).await;
+    assert_eq!(response.status().as_u16(), 429);
+    assert!(response.headers().contains_key("retry-after"));
+
+    app.cleanup().await;
+}
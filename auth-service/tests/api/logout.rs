@@ -1,4 +1,7 @@
-use auth_service::utils::constants::JWT_COOKIE_NAME;
+use auth_service::utils::{
+    auth::decode_auth_token,
+    constants::{JWT_COOKIE_NAME, REFRESH_TOKEN_COOKIE_NAME},
+};
 use reqwest::Url;
 
 use crate::helpers::{get_random_email, TestApp};
@@ -62,10 +65,12 @@ async fn should_return_200_if_valid_jwt_cookie() {
     assert_eq!(response.status().as_u16(), 200);
 
     // Verify that the token is banned
-    let is_banned = {
-        let store = app.banned_token_store.read().await;
-        store.is_token_banned(&token).await.expect("Failed to check if token is banned")
-    };
+    let claims = decode_auth_token(&token, &*app.jwt_keyring.read().await).unwrap();
+    let is_banned = app
+        .banned_token_store
+        .is_token_banned(&claims.jti)
+        .await
+        .expect("Failed to check if token is banned");
 
     assert!(is_banned, "Token should be banned after logout");
 
@@ -97,3 +102,72 @@ async fn should_return_400_if_logout_called_twice_in_a_row() {
 
     app.cleanup().await;
 }
+
+#[tokio::test]
+async fn should_return_400_if_logging_out_everywhere_while_not_logged_in() {
+    let app = TestApp::new().await;
+
+    let response = app.post_logout_all().await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_invalidate_every_token_of_the_user_when_logging_out_everywhere() {
+    let app = TestApp::new().await;
+    let random_email = get_random_email();
+
+    let body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "requires2FA": false
+    });
+    let _ = app.post_signup(&body).await;
+
+    let body = serde_json::json!({
+        "email": random_email,
+        "password": "password123"
+    });
+
+    let mut auth_tokens = vec![];
+    let mut refresh_token = String::new();
+    for _ in 0..2 {
+        let response = app.post_login(&body).await;
+        assert_eq!(response.status().as_u16(), 200);
+
+        for cookie in response.cookies() {
+            match cookie.name() {
+                JWT_COOKIE_NAME => auth_tokens.push(cookie.value().to_owned()),
+                REFRESH_TOKEN_COOKIE_NAME => refresh_token = cookie.value().to_owned(),
+                _ => {}
+            }
+        }
+    }
+
+    let response = app.post_logout_all().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    for token in auth_tokens {
+        let response = app
+            .post_verify_token(&serde_json::json!({ "token": token }))
+            .await;
+        assert_eq!(response.status().as_u16(), 401);
+    }
+
+    app.cookie_jar.add_cookie_str(
+        &format!(
+            "{}={}; HttpOnly; SameSite=Lax; Path=/",
+            REFRESH_TOKEN_COOKIE_NAME, refresh_token
+        ),
+        &Url::parse(&app.address).expect("Failed to parse URL"),
+    );
+    let response = app.post_refresh().await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    // Logging in again works as before
+    let response = app.post_login(&body).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.cleanup().await;
+}
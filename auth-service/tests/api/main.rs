@@ -1,7 +1,21 @@
+mod account;
+mod client_credentials;
 mod helpers;
+mod introspection;
+mod jwks;
+mod load;
 mod login;
 mod logout;
+mod oidc;
+mod passkeys;
+mod password_reset;
+mod rate_limit;
+mod recovery_codes;
+mod refresh;
 mod root;
+mod sessions;
 mod signup;
+mod totp;
 mod verify_2fa;
+mod verify_email;
 mod verify_token;
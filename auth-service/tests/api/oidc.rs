@@ -0,0 +1,470 @@
+use auth_service::{
+    domain::{Email, OAuthClient},
+    routes::{ConsentDetailsResponse, ConsentResponse, TokenResponse, UserInfoResponse},
+    utils::{
+        auth::{decode_auth_token, generate_auth_cookie, IdTokenClaims},
+        constants::JWT_COOKIE_NAME,
+        jwt_key::JwtKey,
+    },
+    ErrorResponse, OAuthErrorResponse,
+};
+use jsonwebtoken::{decode, decode_header, Validation};
+use reqwest::Url;
+
+use crate::helpers::{get_random_email, TestApp};
+
+const CLIENT_ID: &str = "test-client";
+const REDIRECT_URI: &str = "https://client.example.com/callback";
+// Example of RFC 7636, appendix B
+const CODE_VERIFIER: &str = "dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk";
+const CODE_CHALLENGE: &str = "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM";
+
+async fn register_client(app: &TestApp) {
+    app.oauth_client_store
+        .save_client(OAuthClient {
+            client_id: CLIENT_ID.to_owned(),
+            name: "Test Client".to_owned(),
+            redirect_uris: vec![REDIRECT_URI.to_owned()],
+        })
+        .await
+        .expect("Failed to register client");
+}
+
+async fn signup_and_login(app: &TestApp) -> String {
+    let random_email = get_random_email();
+
+    let body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "requires2FA": false
+    });
+    let _ = app.post_signup(&body).await;
+
+    let body = serde_json::json!({
+        "email": random_email,
+        "password": "password123"
+    });
+    let response = app.post_login(&body).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    random_email
+}
+
+fn authorize_params(scope: &str) -> Vec<(&'static str, String)> {
+    vec![
+        ("response_type", "code".to_owned()),
+        ("client_id", CLIENT_ID.to_owned()),
+        ("redirect_uri", REDIRECT_URI.to_owned()),
+        ("scope", scope.to_owned()),
+        ("state", "af0ifjsldkj".to_owned()),
+        ("nonce", "n-0S6_WzA2Mj".to_owned()),
+        ("code_challenge", CODE_CHALLENGE.to_owned()),
+        ("code_challenge_method", "S256".to_owned()),
+    ]
+}
+
+fn redirect_location(response: &reqwest::Response) -> Url {
+    assert_eq!(response.status().as_u16(), 303);
+    let location = response
+        .headers()
+        .get("location")
+        .expect("Redirect should have a location")
+        .to_str()
+        .unwrap();
+
+    Url::parse("http://auth.test")
+        .unwrap()
+        .join(location)
+        .unwrap()
+}
+
+fn query_param(url: &Url, name: &str) -> Option<String> {
+    url.query_pairs()
+        .find(|(key, _)| key == name)
+        .map(|(_, value)| value.into_owned())
+}
+
+// Goes through the authorization endpoint and the consent page like the browser would
+async fn authorize(app: &TestApp, scope: &str) -> String {
+    let response = app.get_authorize(&authorize_params(scope)).await;
+    let request = query_param(&redirect_location(&response), "authorizationRequest")
+        .expect("Should redirect to the consent page");
+
+    let body = serde_json::json!({ "request": request, "approved": true });
+    let response = app.post_consent(&body).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let redirect_uri = response
+        .json::<ConsentResponse>()
+        .await
+        .expect("Could not deserialize response body to ConsentResponse")
+        .redirect_uri;
+    let redirect_uri = Url::parse(&redirect_uri).unwrap();
+    assert!(redirect_uri.as_str().starts_with(REDIRECT_URI));
+    assert_eq!(
+        query_param(&redirect_uri, "state").as_deref(),
+        Some("af0ifjsldkj")
+    );
+
+    query_param(&redirect_uri, "code").expect("Redirect should carry a code")
+}
+
+fn token_form<'a>(code: &'a str, code_verifier: &'a str) -> Vec<(&'static str, &'a str)> {
+    vec![
+        ("grant_type", "authorization_code"),
+        ("code", code),
+        ("redirect_uri", REDIRECT_URI),
+        ("client_id", CLIENT_ID),
+        ("code_verifier", code_verifier),
+    ]
+}
+
+#[tokio::test]
+async fn should_issue_tokens_for_allowed_request() {
+    let app = TestApp::new().await;
+    register_client(&app).await;
+    let email = signup_and_login(&app).await;
+
+    let response = app.get_authorize(&authorize_params("openid email")).await;
+    let request = query_param(&redirect_location(&response), "authorizationRequest").unwrap();
+
+    let response = app.get_consent(&request).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let consent = response
+        .json::<ConsentDetailsResponse>()
+        .await
+        .expect("Could not deserialize response body to ConsentDetailsResponse");
+    assert_eq!(consent.client_name, "Test Client");
+    assert_eq!(consent.scopes, vec!["openid", "email"]);
+    assert_eq!(consent.email, email);
+
+    let code = authorize(&app, "openid email").await;
+
+    let response = app.post_token(&token_form(&code, CODE_VERIFIER)).await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(response.headers().get("cache-control").unwrap(), "no-store");
+    let tokens = response
+        .json::<TokenResponse>()
+        .await
+        .expect("Could not deserialize response body to TokenResponse");
+    assert_eq!(tokens.token_type, "Bearer");
+    assert_eq!(tokens.scope, "openid email");
+
+    let id_token = tokens.id_token.expect("Response should carry an ID token");
+    let header = decode_header(&id_token).unwrap();
+    let keyring = app.jwt_keyring.read().await;
+    let mut validation = Validation::new(header.alg);
+    validation.set_audience(&[CLIENT_ID]);
+    let claims = decode::<IdTokenClaims>(
+        &id_token,
+        keyring
+            .find(header.kid.as_deref().unwrap())
+            .unwrap()
+            .decoding_key(),
+        &validation,
+    )
+    .expect("ID token should validate")
+    .claims;
+    drop(keyring);
+    assert_eq!(claims.nonce.as_deref(), Some("n-0S6_WzA2Mj"));
+    assert_eq!(claims.email.as_deref(), Some(email.as_str()));
+
+    let response = app.get_userinfo(&tokens.access_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let userinfo = response
+        .json::<UserInfoResponse>()
+        .await
+        .expect("Could not deserialize response body to UserInfoResponse");
+    assert_eq!(userinfo.sub, claims.sub);
+    assert_eq!(userinfo.email, Some(email));
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_leave_out_email_without_email_scope() {
+    let app = TestApp::new().await;
+    register_client(&app).await;
+    let email = signup_and_login(&app).await;
+
+    let code = authorize(&app, "openid profile").await;
+    let tokens = app
+        .post_token(&token_form(&code, CODE_VERIFIER))
+        .await
+        .json::<TokenResponse>()
+        .await
+        .unwrap();
+    assert_eq!(tokens.scope, "openid");
+
+    let userinfo = app
+        .get_userinfo(&tokens.access_token)
+        .await
+        .json::<UserInfoResponse>()
+        .await
+        .unwrap();
+    assert_eq!(userinfo.email, None);
+    assert_eq!(userinfo.email_verified, None);
+
+    // The access token does not give the email away either
+    let claims = decode_auth_token(&tokens.access_token, &*app.jwt_keyring.read().await).unwrap();
+    assert_eq!(claims.sub, userinfo.sub);
+    assert_ne!(claims.sub, email);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_reject_reused_code() {
+    let app = TestApp::new().await;
+    register_client(&app).await;
+    signup_and_login(&app).await;
+
+    let code = authorize(&app, "openid").await;
+
+    let response = app.post_token(&token_form(&code, CODE_VERIFIER)).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.post_token(&token_form(&code, CODE_VERIFIER)).await;
+    assert_eq!(response.status().as_u16(), 400);
+    let error = response.json::<OAuthErrorResponse>().await.unwrap();
+    assert_eq!(error.error, "invalid_grant");
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_reject_wrong_code_verifier() {
+    let app = TestApp::new().await;
+    register_client(&app).await;
+    signup_and_login(&app).await;
+
+    let code = authorize(&app, "openid").await;
+
+    let wrong_verifier = "a".repeat(43);
+    let response = app.post_token(&token_form(&code, &wrong_verifier)).await;
+    assert_eq!(response.status().as_u16(), 400);
+    let error = response.json::<OAuthErrorResponse>().await.unwrap();
+    assert_eq!(error.error, "invalid_grant");
+
+    // The code is used up by the failed attempt
+    let response = app.post_token(&token_form(&code, CODE_VERIFIER)).await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_reject_unknown_client_and_grant_type() {
+    let app = TestApp::new().await;
+    register_client(&app).await;
+
+    let form = [
+        ("grant_type", "authorization_code"),
+        ("code", "code"),
+        ("redirect_uri", REDIRECT_URI),
+        ("client_id", "other-client"),
+        ("code_verifier", CODE_VERIFIER),
+    ];
+    let response = app.post_token(&form).await;
+    assert_eq!(response.status().as_u16(), 401);
+    let error = response.json::<OAuthErrorResponse>().await.unwrap();
+    assert_eq!(error.error, "invalid_client");
+
+    let response = app.post_token(&[("grant_type", "password")]).await;
+    assert_eq!(response.status().as_u16(), 400);
+    let error = response.json::<OAuthErrorResponse>().await.unwrap();
+    assert_eq!(error.error, "unsupported_grant_type");
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_not_redirect_to_unregistered_uri() {
+    let app = TestApp::new().await;
+    register_client(&app).await;
+
+    let mut params = authorize_params("openid");
+    params[2].1 = "https://attacker.example.com/callback".to_owned();
+
+    let response = app.get_authorize(&params).await;
+    assert_eq!(response.status().as_u16(), 400);
+    let problem = response.json::<ErrorResponse>().await.unwrap();
+    assert_eq!(problem.code, "invalid_input");
+    assert_eq!(problem.errors[0].field, "redirect_uri");
+
+    let mut params = authorize_params("openid");
+    params[1].1 = "unknown-client".to_owned();
+
+    let response = app.get_authorize(&params).await;
+    assert_eq!(response.status().as_u16(), 400);
+    let problem = response.json::<ErrorResponse>().await.unwrap();
+    assert_eq!(problem.errors[0].field, "client_id");
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_redirect_errors_to_client() {
+    let app = TestApp::new().await;
+    register_client(&app).await;
+
+    let response = app.get_authorize(&authorize_params("email")).await;
+    let location = redirect_location(&response);
+    assert!(location.as_str().starts_with(REDIRECT_URI));
+    assert_eq!(
+        query_param(&location, "error").as_deref(),
+        Some("invalid_scope")
+    );
+    assert_eq!(
+        query_param(&location, "state").as_deref(),
+        Some("af0ifjsldkj")
+    );
+
+    let mut params = authorize_params("openid");
+    params.pop();
+    let response = app.get_authorize(&params).await;
+    let location = redirect_location(&response);
+    assert_eq!(
+        query_param(&location, "error").as_deref(),
+        Some("invalid_request")
+    );
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_redirect_access_denied() {
+    let app = TestApp::new().await;
+    register_client(&app).await;
+    signup_and_login(&app).await;
+
+    let response = app.get_authorize(&authorize_params("openid")).await;
+    let request = query_param(&redirect_location(&response), "authorizationRequest").unwrap();
+
+    let body = serde_json::json!({ "request": request, "approved": false });
+    let response = app.post_consent(&body).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let redirect_uri = response
+        .json::<ConsentResponse>()
+        .await
+        .unwrap()
+        .redirect_uri;
+    let redirect_uri = Url::parse(&redirect_uri).unwrap();
+    assert_eq!(
+        query_param(&redirect_uri, "error").as_deref(),
+        Some("access_denied")
+    );
+    assert_eq!(query_param(&redirect_uri, "code"), None);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_require_login_for_consent() {
+    let app = TestApp::new().await;
+    register_client(&app).await;
+
+    let response = app.get_authorize(&authorize_params("openid")).await;
+    let request = query_param(&redirect_location(&response), "authorizationRequest").unwrap();
+
+    let response = app.get_consent(&request).await;
+    assert_eq!(response.status().as_u16(), 400);
+    let problem = response.json::<ErrorResponse>().await.unwrap();
+    assert_eq!(problem.code, "missing_token");
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_reject_tampered_authorization_request() {
+    let app = TestApp::new().await;
+    register_client(&app).await;
+    signup_and_login(&app).await;
+
+    let response = app.get_consent("not-a-token").await;
+    assert_eq!(response.status().as_u16(), 400);
+    let problem = response.json::<ErrorResponse>().await.unwrap();
+    assert_eq!(problem.code, "invalid_authorization_request");
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn client_access_token_should_not_manage_account() {
+    let app = TestApp::new().await;
+    register_client(&app).await;
+    signup_and_login(&app).await;
+
+    let code = authorize(&app, "openid").await;
+    let tokens = app
+        .post_token(&token_form(&code, CODE_VERIFIER))
+        .await
+        .json::<TokenResponse>()
+        .await
+        .unwrap();
+
+    // Swap the session cookie for the token of the client
+    let url = Url::parse(&app.address).unwrap();
+    app.cookie_jar.add_cookie_str(
+        &format!(
+            "{}={}; HttpOnly; SameSite=Lax; Path=/",
+            JWT_COOKIE_NAME, tokens.access_token
+        ),
+        &url,
+    );
+
+    let response = app.get_sessions().await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn userinfo_should_reject_login_token() {
+    let app = TestApp::new().await;
+    let email = Email::parse(signup_and_login(&app).await).unwrap();
+
+    let session_id = app.add_session(&email).await;
+    let token = generate_auth_cookie(&email, session_id, 0, &*app.jwt_keyring.read().await)
+        .unwrap()
+        .value()
+        .to_owned();
+
+    let response = app.get_userinfo(&token).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_publish_openid_configuration() {
+    let app = TestApp::new().await;
+
+    let response = app.get_openid_configuration().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let configuration = response.json::<serde_json::Value>().await.unwrap();
+    let issuer = configuration["issuer"].as_str().unwrap();
+    assert_eq!(configuration["token_endpoint"], format!("{}/token", issuer));
+    assert_eq!(
+        configuration["code_challenge_methods_supported"],
+        serde_json::json!(["S256"])
+    );
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_not_serve_openid_connect_with_shared_secret() {
+    let app = TestApp::with_jwt_key(JwtKey::from_secret(b"secret")).await;
+    register_client(&app).await;
+
+    let response = app.get_openid_configuration().await;
+    assert_eq!(response.status().as_u16(), 404);
+
+    let response = app.get_authorize(&authorize_params("openid")).await;
+    assert_eq!(response.status().as_u16(), 404);
+
+    app.cleanup().await;
+}
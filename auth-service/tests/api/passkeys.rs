@@ -0,0 +1,566 @@
+use auth_service::{
+    app_state::{AuthPolicy, LoginThrottlePolicy},
+    domain::{CredentialId, Email, PasskeyCredential},
+    routes::{StartPasskeyLoginResponse, StartPasskeyRegistrationResponse},
+    utils::{
+        constants::{JWT_COOKIE_NAME, WEBAUTHN_RP_ID, WEBAUTHN_RP_ORIGIN},
+        webauthn::{decode_base64url, encode_base64url},
+    },
+    ErrorResponse,
+};
+use ciborium::Value;
+use p256::ecdsa::{signature::Signer, Signature, SigningKey};
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+
+use crate::helpers::{get_random_email, TestApp};
+
+const FLAG_USER_PRESENT: u8 = 0x01;
+const FLAG_USER_VERIFIED: u8 = 0x04;
+const FLAG_ATTESTED_CREDENTIAL_DATA: u8 = 0x40;
+
+// Stand-in for a hardware key or platform authenticator, producing the same bytes a browser would forward
+struct SoftwareAuthenticator {
+    credential_id: Vec<u8>,
+    signing_key: SigningKey,
+    sign_count: u32,
+    // Whether the owner unlocked the authenticator with a PIN or biometric
+    user_verified: bool,
+}
+
+impl SoftwareAuthenticator {
+    fn new() -> Self {
+        let mut credential_id = vec![0u8; 16];
+        rand::thread_rng().fill_bytes(&mut credential_id);
+
+        Self {
+            credential_id,
+            signing_key: SigningKey::random(&mut rand::rngs::OsRng),
+            sign_count: 0,
+            user_verified: true,
+        }
+    }
+
+    fn credential_id(&self) -> String {
+        encode_base64url(&self.credential_id)
+    }
+
+    fn client_data(ceremony_type: &str, challenge: &str, origin: &str) -> Vec<u8> {
+        serde_json::json!({
+            "type": ceremony_type,
+            "challenge": challenge,
+            "origin": origin,
+            "crossOrigin": false,
+        })
+        .to_string()
+        .into_bytes()
+    }
+
+    fn authenticator_data(&self, rp_id: &str, mut flags: u8) -> Vec<u8> {
+        if self.user_verified {
+            flags |= FLAG_USER_VERIFIED;
+        }
+
+        let mut data = Sha256::digest(rp_id.as_bytes()).to_vec();
+        data.push(flags);
+        data.extend(self.sign_count.to_be_bytes());
+        data
+    }
+
+    fn cose_key(&self) -> Vec<u8> {
+        let point = self.signing_key.verifying_key().to_encoded_point(false);
+        let cose_key = Value::Map(vec![
+            (Value::from(1), Value::from(2)),
+            (Value::from(3), Value::from(-7)),
+            (Value::from(-1), Value::from(1)),
+            (Value::from(-2), Value::Bytes(point.x().unwrap().to_vec())),
+            (Value::from(-3), Value::Bytes(point.y().unwrap().to_vec())),
+        ]);
+
+        let mut bytes = Vec::new();
+        ciborium::ser::into_writer(&cose_key, &mut bytes).unwrap();
+        bytes
+    }
+
+    // Response of `navigator.credentials.create()`
+    fn register(&self, challenge: &str, origin: &str) -> serde_json::Value {
+        let mut auth_data = self.authenticator_data(
+            &WEBAUTHN_RP_ID,
+            FLAG_USER_PRESENT | FLAG_ATTESTED_CREDENTIAL_DATA,
+        );
+        auth_data.extend([0u8; 16]);
+        auth_data.extend((self.credential_id.len() as u16).to_be_bytes());
+        auth_data.extend(&self.credential_id);
+        auth_data.extend(self.cose_key());
+
+        let attestation_object = Value::Map(vec![
+            (Value::from("fmt"), Value::from("none")),
+            (Value::from("attStmt"), Value::Map(vec![])),
+            (Value::from("authData"), Value::Bytes(auth_data)),
+        ]);
+        let mut attestation_bytes = Vec::new();
+        ciborium::ser::into_writer(&attestation_object, &mut attestation_bytes).unwrap();
+
+        serde_json::json!({
+            "id": self.credential_id(),
+            "response": {
+                "clientDataJSON": encode_base64url(&Self::client_data("webauthn.create", challenge, origin)),
+                "attestationObject": encode_base64url(&attestation_bytes),
+            },
+        })
+    }
+
+    // Response of `navigator.credentials.get()`
+    fn authenticate(&mut self, challenge: &str, rp_id: &str) -> serde_json::Value {
+        self.sign_count += 1;
+
+        let client_data = Self::client_data("webauthn.get", challenge, &WEBAUTHN_RP_ORIGIN);
+        let auth_data = self.authenticator_data(rp_id, FLAG_USER_PRESENT);
+
+        let mut signed_data = auth_data.clone();
+        signed_data.extend(Sha256::digest(&client_data));
+        let signature: Signature = self.signing_key.sign(&signed_data);
+
+        serde_json::json!({
+            "id": self.credential_id(),
+            "response": {
+                "clientDataJSON": encode_base64url(&client_data),
+                "authenticatorData": encode_base64url(&auth_data),
+                "signature": encode_base64url(signature.to_der().as_bytes()),
+                "userHandle": null,
+            },
+        })
+    }
+}
+
+async fn signup_and_login(app: &TestApp) -> String {
+    let email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": email,
+        "password": "password123",
+        "requires2FA": true
+    });
+    assert_eq!(app.post_signup(&signup_body).await.status().as_u16(), 201);
+
+    // Skip the 2FA step, only a logged in session is needed to add a passkey
+    let login_attempt = app
+        .post_login(&serde_json::json!({ "email": email, "password": "password123" }))
+        .await;
+    assert_eq!(login_attempt.status().as_u16(), 206);
+    let (login_attempt_id, code) = app
+        .two_fa_code_store
+        .get_code(&auth_service::domain::Email::parse(email.clone()).unwrap())
+        .await
+        .unwrap();
+    let verify_2fa_body = serde_json::json!({
+        "email": email,
+        "loginAttemptId": login_attempt_id.as_ref(),
+        "2FACode": code.as_ref(),
+    });
+    assert_eq!(
+        app.post_verify_2fa(&verify_2fa_body)
+            .await
+            .status()
+            .as_u16(),
+        200
+    );
+
+    email
+}
+
+async fn register_passkey(app: &TestApp, authenticator: &SoftwareAuthenticator) {
+    let options = app
+        .post_passkey_register_start()
+        .await
+        .json::<StartPasskeyRegistrationResponse>()
+        .await
+        .expect("Could not deserialize response body to StartPasskeyRegistrationResponse");
+
+    let body = serde_json::json!({
+        "ceremonyId": options.ceremony_id,
+        "credential": authenticator.register(&options.public_key.challenge, &WEBAUTHN_RP_ORIGIN),
+    });
+
+    let response = app.post_passkey_register_finish(&body).await;
+    assert_eq!(response.status().as_u16(), 201);
+}
+
+// Stores the passkey directly, for accounts that could not log in to register one
+async fn add_passkey(app: &TestApp, email: &str, authenticator: &SoftwareAuthenticator) {
+    let public_key = authenticator
+        .signing_key
+        .verifying_key()
+        .to_encoded_point(false)
+        .as_bytes()
+        .to_vec();
+
+    app.passkey_credential_store
+        .add_credential(PasskeyCredential {
+            credential_id: CredentialId::from(authenticator.credential_id.clone()),
+            email: Email::parse(email.to_owned()).unwrap(),
+            public_key,
+            sign_count: 0,
+        })
+        .await
+        .expect("Failed to add passkey");
+}
+
+async fn start_login(app: &TestApp, email: Option<&str>) -> StartPasskeyLoginResponse {
+    let response = app
+        .post_passkey_login_start(&serde_json::json!({ "email": email }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    response
+        .json::<StartPasskeyLoginResponse>()
+        .await
+        .expect("Could not deserialize response body to StartPasskeyLoginResponse")
+}
+
+#[tokio::test]
+async fn should_return_400_if_registering_while_not_logged_in() {
+    let app = TestApp::new().await;
+
+    let response = app.post_passkey_register_start().await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_return_registration_options() {
+    let app = TestApp::new().await;
+    let email = signup_and_login(&app).await;
+
+    let response = app.post_passkey_register_start().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let options = response
+        .json::<StartPasskeyRegistrationResponse>()
+        .await
+        .unwrap()
+        .public_key;
+
+    assert_eq!(options.rp.id, *WEBAUTHN_RP_ID);
+    assert_eq!(options.user.name, email);
+    assert_eq!(decode_base64url(&options.challenge).unwrap().len(), 32);
+    assert!(options.pub_key_cred_params.iter().any(|p| p.alg == -7));
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_register_passkey_and_exclude_it_afterwards() {
+    let app = TestApp::new().await;
+    let email = signup_and_login(&app).await;
+    let authenticator = SoftwareAuthenticator::new();
+
+    register_passkey(&app, &authenticator).await;
+
+    let credentials = app
+        .passkey_credential_store
+        .get_credentials(&auth_service::domain::Email::parse(email).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(credentials.len(), 1);
+
+    let options = app
+        .post_passkey_register_start()
+        .await
+        .json::<StartPasskeyRegistrationResponse>()
+        .await
+        .unwrap();
+    assert_eq!(options.public_key.exclude_credentials.len(), 1);
+    assert_eq!(
+        options.public_key.exclude_credentials[0].id,
+        authenticator.credential_id()
+    );
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_reject_registration_from_other_origin() {
+    let app = TestApp::new().await;
+    signup_and_login(&app).await;
+    let authenticator = SoftwareAuthenticator::new();
+
+    let options = app
+        .post_passkey_register_start()
+        .await
+        .json::<StartPasskeyRegistrationResponse>()
+        .await
+        .unwrap();
+
+    let body = serde_json::json!({
+        "ceremonyId": options.ceremony_id,
+        "credential": authenticator.register(&options.public_key.challenge, "https://evil.example"),
+    });
+
+    let response = app.post_passkey_register_finish(&body).await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_return_400_if_login_email_is_malformed() {
+    let app = TestApp::new().await;
+
+    let response = app
+        .post_passkey_login_start(&serde_json::json!({ "email": "not-an-email" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    let body = response
+        .json::<ErrorResponse>()
+        .await
+        .expect("Could not deserialize response body to ErrorResponse");
+    assert_eq!(body.code, "invalid_input");
+    let fields: Vec<_> = body
+        .errors
+        .iter()
+        .map(|error| (error.field.as_str(), error.code.as_str()))
+        .collect();
+    assert_eq!(fields, vec![("email", "invalid_email")]);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_login_with_passkey_without_2fa() {
+    let app = TestApp::new().await;
+    let email = signup_and_login(&app).await;
+    let mut authenticator = SoftwareAuthenticator::new();
+    register_passkey(&app, &authenticator).await;
+
+    let options = start_login(&app, Some(&email)).await;
+    assert_eq!(options.public_key.allow_credentials.len(), 1);
+
+    let body = serde_json::json!({
+        "ceremonyId": options.ceremony_id,
+        "credential": authenticator.authenticate(&options.public_key.challenge, &WEBAUTHN_RP_ID),
+    });
+    assert_eq!(options.public_key.user_verification, "required");
+
+    let response = app.post_passkey_login_finish(&body).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let auth_cookie = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found");
+    assert!(!auth_cookie.value().is_empty());
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_login_with_discoverable_passkey() {
+    let app = TestApp::new().await;
+    signup_and_login(&app).await;
+    let mut authenticator = SoftwareAuthenticator::new();
+    register_passkey(&app, &authenticator).await;
+
+    let options = start_login(&app, None).await;
+    assert!(options.public_key.allow_credentials.is_empty());
+
+    let body = serde_json::json!({
+        "ceremonyId": options.ceremony_id,
+        "credential": authenticator.authenticate(&options.public_key.challenge, &WEBAUTHN_RP_ID),
+    });
+
+    let response = app.post_passkey_login_finish(&body).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_ceremony_is_replayed() {
+    let app = TestApp::new().await;
+    signup_and_login(&app).await;
+    let mut authenticator = SoftwareAuthenticator::new();
+    register_passkey(&app, &authenticator).await;
+
+    let options = start_login(&app, None).await;
+    let body = serde_json::json!({
+        "ceremonyId": options.ceremony_id,
+        "credential": authenticator.authenticate(&options.public_key.challenge, &WEBAUTHN_RP_ID),
+    });
+
+    assert_eq!(
+        app.post_passkey_login_finish(&body).await.status().as_u16(),
+        200
+    );
+    assert_eq!(
+        app.post_passkey_login_finish(&body).await.status().as_u16(),
+        401
+    );
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_signed_for_other_relying_party() {
+    let app = TestApp::new().await;
+    signup_and_login(&app).await;
+    let mut authenticator = SoftwareAuthenticator::new();
+    register_passkey(&app, &authenticator).await;
+
+    let options = start_login(&app, None).await;
+    let body = serde_json::json!({
+        "ceremonyId": options.ceremony_id,
+        "credential": authenticator.authenticate(&options.public_key.challenge, "evil.example"),
+    });
+
+    let response = app.post_passkey_login_finish(&body).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_sign_count_goes_backwards() {
+    let app = TestApp::new().await;
+    signup_and_login(&app).await;
+    let mut authenticator = SoftwareAuthenticator::new();
+    register_passkey(&app, &authenticator).await;
+
+    authenticator.sign_count = 10;
+    let options = start_login(&app, None).await;
+    let body = serde_json::json!({
+        "ceremonyId": options.ceremony_id,
+        "credential": authenticator.authenticate(&options.public_key.challenge, &WEBAUTHN_RP_ID),
+    });
+    assert_eq!(
+        app.post_passkey_login_finish(&body).await.status().as_u16(),
+        200
+    );
+
+    // A cloned authenticator would keep counting from an older value
+    authenticator.sign_count = 5;
+    let options = start_login(&app, None).await;
+    let body = serde_json::json!({
+        "ceremonyId": options.ceremony_id,
+        "credential": authenticator.authenticate(&options.public_key.challenge, &WEBAUTHN_RP_ID),
+    });
+    assert_eq!(
+        app.post_passkey_login_finish(&body).await.status().as_u16(),
+        401
+    );
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_return_401_for_unknown_credential() {
+    let app = TestApp::new().await;
+    let mut authenticator = SoftwareAuthenticator::new();
+
+    let options = start_login(&app, None).await;
+    let body = serde_json::json!({
+        "ceremonyId": options.ceremony_id,
+        "credential": authenticator.authenticate(&options.public_key.challenge, &WEBAUTHN_RP_ID),
+    });
+
+    let response = app.post_passkey_login_finish(&body).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_user_was_not_verified() {
+    let app = TestApp::new().await;
+    signup_and_login(&app).await;
+    let mut authenticator = SoftwareAuthenticator::new();
+    register_passkey(&app, &authenticator).await;
+
+    // Only touched, a stolen security key without its PIN must not replace password and 2FA
+    authenticator.user_verified = false;
+    let options = start_login(&app, None).await;
+    let body = serde_json::json!({
+        "ceremonyId": options.ceremony_id,
+        "credential": authenticator.authenticate(&options.public_key.challenge, &WEBAUTHN_RP_ID),
+    });
+
+    let response = app.post_passkey_login_finish(&body).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_return_403_for_unverified_email_when_required() {
+    let app = TestApp::with_policy(AuthPolicy {
+        require_verified_email: true,
+        ..Default::default()
+    })
+    .await;
+    let email = get_random_email();
+    let signup_body = serde_json::json!({
+        "email": email,
+        "password": "password123",
+        "requires2FA": false
+    });
+    assert_eq!(app.post_signup(&signup_body).await.status().as_u16(), 201);
+
+    let mut authenticator = SoftwareAuthenticator::new();
+    add_passkey(&app, &email, &authenticator).await;
+
+    let options = start_login(&app, None).await;
+    let body = serde_json::json!({
+        "ceremonyId": options.ceremony_id,
+        "credential": authenticator.authenticate(&options.public_key.challenge, &WEBAUTHN_RP_ID),
+    });
+
+    let response = app.post_passkey_login_finish(&body).await;
+    assert_eq!(response.status().as_u16(), 403);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_return_429_after_too_many_failed_passkey_logins() {
+    let app = TestApp::with_policy(AuthPolicy {
+        login_throttle: LoginThrottlePolicy {
+            max_failures_per_email: 3,
+            ..Default::default()
+        },
+        ..Default::default()
+    })
+    .await;
+    let email = signup_and_login(&app).await;
+    let mut authenticator = SoftwareAuthenticator::new();
+    register_passkey(&app, &authenticator).await;
+
+    for _ in 0..3 {
+        let options = start_login(&app, Some(&email)).await;
+        let body = serde_json::json!({
+            "ceremonyId": options.ceremony_id,
+            "credential": authenticator.authenticate(&options.public_key.challenge, "evil.example"),
+        });
+        assert_eq!(
+            app.post_passkey_login_finish(&body).await.status().as_u16(),
+            401
+        );
+    }
+
+    // The account is locked out for passwords and passkeys alike
+    let options = start_login(&app, Some(&email)).await;
+    let body = serde_json::json!({
+        "ceremonyId": options.ceremony_id,
+        "credential": authenticator.authenticate(&options.public_key.challenge, &WEBAUTHN_RP_ID),
+    });
+    assert_eq!(
+        app.post_passkey_login_finish(&body).await.status().as_u16(),
+        429
+    );
+
+    let login_body = serde_json::json!({ "email": email, "password": "password123" });
+    assert_eq!(app.post_login(&login_body).await.status().as_u16(), 429);
+
+    app.cleanup().await;
+}
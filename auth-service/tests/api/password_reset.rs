@@ -0,0 +1,286 @@
+use auth_service::{
+    domain::{Email, PasswordPolicyViolation, PasswordResetToken},
+    routes::PasswordResetRequestResponse,
+    utils::constants::JWT_COOKIE_NAME,
+    ErrorResponse,
+};
+
+use crate::helpers::{get_random_email, TestApp};
+
+async fn add_user(app: &TestApp) -> String {
+    let email = get_random_email();
+    let signup_body = serde_json::json!({
+        "email": email,
+        "password": "password123",
+        "requires2FA": false
+    });
+    assert_eq!(app.post_signup(&signup_body).await.status().as_u16(), 201);
+
+    email
+}
+
+async fn add_reset_token(app: &TestApp, email: &str) -> PasswordResetToken {
+    let token = PasswordResetToken::default();
+
+    app.password_reset_token_store
+        .add_token(token.clone(), Email::parse(email.to_owned()).unwrap())
+        .await
+        .expect("Failed to add password reset token");
+
+    token
+}
+
+#[tokio::test]
+async fn should_return_400_if_email_is_malformed() {
+    let app = TestApp::new().await;
+
+    let response = app
+        .post_password_reset_request(&serde_json::json!({ "email": "not-an-email" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    let body = response
+        .json::<ErrorResponse>()
+        .await
+        .expect("Could not deserialize response body to ErrorResponse");
+    assert_eq!(body.code, "invalid_input");
+    let fields: Vec<_> = body
+        .errors
+        .iter()
+        .map(|error| (error.field.as_str(), error.code.as_str()))
+        .collect();
+    assert_eq!(fields, vec![("email", "invalid_email")]);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_respond_identically_for_known_and_unknown_emails() {
+    let app = TestApp::new().await;
+    let email = add_user(&app).await;
+
+    let known = app
+        .post_password_reset_request(&serde_json::json!({ "email": email }))
+        .await;
+    let unknown = app
+        .post_password_reset_request(&serde_json::json!({ "email": get_random_email() }))
+        .await;
+
+    assert_eq!(known.status().as_u16(), 202);
+    assert_eq!(known.status(), unknown.status());
+
+    let known = known
+        .json::<PasswordResetRequestResponse>()
+        .await
+        .expect("Could not deserialize response body to PasswordResetRequestResponse");
+    let unknown = unknown
+        .json::<PasswordResetRequestResponse>()
+        .await
+        .expect("Could not deserialize response body to PasswordResetRequestResponse");
+    assert_eq!(known.message, unknown.message);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_set_new_password_with_valid_token() {
+    let app = TestApp::new().await;
+    let email = add_user(&app).await;
+    let token = add_reset_token(&app, &email).await;
+
+    let old_login = serde_json::json!({ "email": email, "password": "password123" });
+    let response = app.post_login(&old_login).await;
+    let auth_token = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found")
+        .value()
+        .to_owned();
+
+    let body = serde_json::json!({
+        "token": token.as_ref(),
+        "newPassword": "newpassword123",
+    });
+    let response = app.post_password_reset_confirm(&body).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    // Sessions started with the old password are logged out
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": auth_token }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    assert_eq!(app.post_login(&old_login).await.status().as_u16(), 401);
+
+    let new_login = serde_json::json!({ "email": email, "password": "newpassword123" });
+    assert_eq!(app.post_login(&new_login).await.status().as_u16(), 200);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_return_400_if_token_is_reused() {
+    let app = TestApp::new().await;
+    let email = add_user(&app).await;
+    let token = add_reset_token(&app, &email).await;
+
+    let body = serde_json::json!({
+        "token": token.as_ref(),
+        "newPassword": "newpassword123",
+    });
+    assert_eq!(
+        app.post_password_reset_confirm(&body)
+            .await
+            .status()
+            .as_u16(),
+        200
+    );
+    assert_eq!(
+        app.post_password_reset_confirm(&body)
+            .await
+            .status()
+            .as_u16(),
+        400
+    );
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_return_400_for_other_links_after_reset() {
+    let app = TestApp::new().await;
+    let email = add_user(&app).await;
+    let first_token = add_reset_token(&app, &email).await;
+    let second_token = add_reset_token(&app, &email).await;
+
+    let body = serde_json::json!({
+        "token": first_token.as_ref(),
+        "newPassword": "newpassword123",
+    });
+    assert_eq!(
+        app.post_password_reset_confirm(&body)
+            .await
+            .status()
+            .as_u16(),
+        200
+    );
+
+    let body = serde_json::json!({
+        "token": second_token.as_ref(),
+        "newPassword": "otherpassword123",
+    });
+    assert_eq!(
+        app.post_password_reset_confirm(&body)
+            .await
+            .status()
+            .as_u16(),
+        400
+    );
+
+    let new_login = serde_json::json!({ "email": email, "password": "newpassword123" });
+    assert_eq!(app.post_login(&new_login).await.status().as_u16(), 200);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_return_400_for_unknown_or_malformed_token() {
+    let app = TestApp::new().await;
+
+    let test_cases = [
+        PasswordResetToken::default().as_ref().to_owned(),
+        "not a token!".to_owned(),
+        "".to_owned(),
+    ];
+
+    for token in test_cases {
+        let body = serde_json::json!({
+            "token": token,
+            "newPassword": "newpassword123",
+        });
+        let response = app.post_password_reset_confirm(&body).await;
+        assert_eq!(
+            response.status().as_u16(),
+            400,
+            "Failed for token: {}",
+            token
+        );
+    }
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_keep_token_if_new_password_is_invalid() {
+    let app = TestApp::new().await;
+    let email = add_user(&app).await;
+    let token = add_reset_token(&app, &email).await;
+
+    let body = serde_json::json!({
+        "token": token.as_ref(),
+        "newPassword": "short",
+    });
+    assert_eq!(
+        app.post_password_reset_confirm(&body)
+            .await
+            .status()
+            .as_u16(),
+        400
+    );
+
+    let body = serde_json::json!({
+        "token": token.as_ref(),
+        "newPassword": "newpassword123",
+    });
+    assert_eq!(
+        app.post_password_reset_confirm(&body)
+            .await
+            .status()
+            .as_u16(),
+        200
+    );
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_reject_password_containing_the_email_of_the_token_owner() {
+    let app = TestApp::new().await;
+    let email = add_user(&app).await;
+    let token = add_reset_token(&app, &email).await;
+    let local_part = email.split('@').next().unwrap();
+
+    let body = serde_json::json!({
+        "token": token.as_ref(),
+        "newPassword": format!("{}!", local_part),
+    });
+    let response = app.post_password_reset_confirm(&body).await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    let error = response
+        .json::<ErrorResponse>()
+        .await
+        .expect("Could not deserialize response body to ErrorResponse");
+    assert_eq!(error.code, "invalid_input");
+    assert_eq!(error.errors.len(), 1);
+    assert_eq!(error.errors[0].field, "newPassword");
+    assert_eq!(
+        error.errors[0].reasons,
+        vec![PasswordPolicyViolation::ContainsEmail]
+    );
+
+    // The rejected password did not use up the link
+    let body = serde_json::json!({
+        "token": token.as_ref(),
+        "newPassword": "newpassword123",
+    });
+    assert_eq!(
+        app.post_password_reset_confirm(&body)
+            .await
+            .status()
+            .as_u16(),
+        200
+    );
+
+    app.cleanup().await;
+}
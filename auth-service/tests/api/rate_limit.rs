@@ -0,0 +1,108 @@
+use auth_service::{app_state::AuthPolicy, domain::RateLimitRule};
+
+use crate::helpers::{get_random_email, TestApp};
+
+fn rate_limit_policy(rules: &str) -> AuthPolicy {
+    AuthPolicy {
+        rate_limits: RateLimitRule::parse_list(rules).unwrap(),
+        ..Default::default()
+    }
+}
+
+fn signup_body(email: &str) -> serde_json::Value {
+    serde_json::json!({
+        "email": email,
+        "password": "password123",
+        "requires2FA": false
+    })
+}
+
+#[tokio::test]
+async fn should_return_429_once_route_limit_is_reached() {
+    let app = TestApp::with_policy(rate_limit_policy("/signup ip 2/60")).await;
+
+    for _ in 0..2 {
+        let response = app.post_signup(&signup_body(&get_random_email())).await;
+        assert_eq!(response.status().as_u16(), 201);
+    }
+
+    let response = app.post_signup(&signup_body(&get_random_email())).await;
+    assert_eq!(response.status().as_u16(), 429);
+
+    let retry_after: u64 = response
+        .headers()
+        .get("retry-after")
+        .expect("No Retry-After header found")
+        .to_str()
+        .unwrap()
+        .parse()
+        .unwrap();
+    assert!(retry_after > 0 && retry_after <= 30);
+
+    // Other routes have buckets of their own
+    let login_body = serde_json::json!({ "email": get_random_email(), "password": "password123" });
+    assert_eq!(app.post_login(&login_body).await.status().as_u16(), 401);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_limit_each_email_separately() {
+    let app = TestApp::with_policy(rate_limit_policy("/password-reset/request email 1/60")).await;
+    let email = get_random_email();
+
+    let response = app
+        .post_password_reset_request(&serde_json::json!({ "email": email }))
+        .await;
+    assert_eq!(response.status().as_u16(), 202);
+
+    let response = app
+        .post_password_reset_request(&serde_json::json!({ "email": email }))
+        .await;
+    assert_eq!(response.status().as_u16(), 429);
+
+    let response = app
+        .post_password_reset_request(&serde_json::json!({ "email": get_random_email() }))
+        .await;
+    assert_eq!(response.status().as_u16(), 202);
+
+    app.cleanup().await;
+}
+
+async fn signup_and_login(app: &TestApp) {
+    let email = get_random_email();
+    assert_eq!(
+        app.post_signup(&signup_body(&email))
+            .await
+            .status()
+            .as_u16(),
+        201
+    );
+
+    let login_body = serde_json::json!({ "email": email, "password": "password123" });
+    assert_eq!(app.post_login(&login_body).await.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn should_limit_each_logged_in_user_separately() {
+    let app = TestApp::with_policy(rate_limit_policy("/account/password subject 1/60")).await;
+    let change_password_body = serde_json::json!({
+        "currentPassword": "wrongpassword",
+        "newPassword": "newpassword123",
+    });
+
+    signup_and_login(&app).await;
+
+    let response = app.post_change_password(&change_password_body).await;
+    assert_eq!(response.status().as_u16(), 401);
+    let response = app.post_change_password(&change_password_body).await;
+    assert_eq!(response.status().as_u16(), 429);
+
+    // Another user logging in from the same client gets a bucket of their own
+    signup_and_login(&app).await;
+
+    let response = app.post_change_password(&change_password_body).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.cleanup().await;
+}
@@ -0,0 +1,241 @@
+use std::collections::HashSet;
+
+use auth_service::{
+    domain::{Email, Password, RecoveryCode, User},
+    routes::{RecoveryCodesResponse, TwoFactorAuthResponse, RECOVERY_CODE_COUNT},
+    utils::constants::JWT_COOKIE_NAME,
+    ErrorResponse,
+};
+
+use crate::helpers::{get_random_email, TestApp};
+
+async fn add_2fa_user(app: &TestApp) -> String {
+    let email = get_random_email();
+    let user = User::new(Email::parse(email.clone()).unwrap(), true);
+    let password = Password::parse("password123".to_owned()).unwrap();
+
+    app.user_store
+        .add_user(user, password)
+        .await
+        .expect("Failed to create user");
+
+    email
+}
+
+async fn start_login(app: &TestApp, email: &str) -> String {
+    let login_body = serde_json::json!({
+        "email": email,
+        "password": "password123",
+    });
+
+    let response = app.post_login(&login_body).await;
+    assert_eq!(response.status().as_u16(), 206);
+
+    response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFactorAuthResponse")
+        .login_attempt_id
+}
+
+fn password_body() -> serde_json::Value {
+    serde_json::json!({ "password": "password123" })
+}
+
+async fn add_recovery_code(app: &TestApp, email: &str) -> RecoveryCode {
+    let code = RecoveryCode::default();
+
+    app.recovery_code_store
+        .replace_codes(&Email::parse(email.to_owned()).unwrap(), vec![code.clone()])
+        .await
+        .expect("Failed to add recovery code");
+
+    code
+}
+
+#[tokio::test]
+async fn should_return_400_if_regenerating_while_not_logged_in() {
+    let app = TestApp::new().await;
+
+    let response = app.post_recovery_codes(&password_body()).await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_return_a_batch_of_unique_codes() {
+    let app = TestApp::new().await;
+
+    let email = get_random_email();
+    let signup_body = serde_json::json!({
+        "email": email,
+        "password": "password123",
+        "requires2FA": false
+    });
+    assert_eq!(app.post_signup(&signup_body).await.status().as_u16(), 201);
+
+    let login_body = serde_json::json!({
+        "email": email,
+        "password": "password123",
+    });
+    assert_eq!(app.post_login(&login_body).await.status().as_u16(), 200);
+
+    let response = app.post_recovery_codes(&password_body()).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let body = response
+        .json::<RecoveryCodesResponse>()
+        .await
+        .expect("Could not deserialize response body to RecoveryCodesResponse");
+
+    let codes: HashSet<_> = body.recovery_codes.iter().collect();
+    assert_eq!(codes.len(), RECOVERY_CODE_COUNT);
+    assert!(body
+        .recovery_codes
+        .iter()
+        .all(|code| RecoveryCode::parse(code).is_ok()));
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_return_401_without_current_password() {
+    let app = TestApp::new().await;
+
+    let email = get_random_email();
+    let signup_body = serde_json::json!({
+        "email": email,
+        "password": "password123",
+        "requires2FA": false
+    });
+    assert_eq!(app.post_signup(&signup_body).await.status().as_u16(), 201);
+
+    let login_body = serde_json::json!({
+        "email": email,
+        "password": "password123",
+    });
+    assert_eq!(app.post_login(&login_body).await.status().as_u16(), 200);
+
+    add_recovery_code(&app, &email).await;
+
+    // A stolen session cookie alone can not be turned into a way past 2FA
+    let wrong_password = serde_json::json!({ "password": "wrongpassword" });
+    let response = app.post_recovery_codes(&wrong_password).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    // The codes handed out earlier are left alone
+    let email = Email::parse(email).unwrap();
+    assert_eq!(app.recovery_code_store.count_codes(&email).await, Ok(1));
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_return_400_if_invalid_recovery_code() {
+    let app = TestApp::new().await;
+
+    let body = serde_json::json!({
+        "email": get_random_email(),
+        "loginAttemptId": "d498ab94-f157-453f-a6e2-da196ae3e713",
+        "recoveryCode": "not-a-code",
+    });
+
+    let response = app.post_verify_2fa_recovery(&body).await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    let body = response
+        .json::<ErrorResponse>()
+        .await
+        .expect("Could not deserialize response body to ErrorResponse");
+    assert_eq!(body.code, "invalid_input");
+    let fields: Vec<_> = body
+        .errors
+        .iter()
+        .map(|error| (error.field.as_str(), error.code.as_str()))
+        .collect();
+    assert_eq!(fields, vec![("recoveryCode", "invalid_recovery_code")]);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_login_with_recovery_code_only_once() {
+    let app = TestApp::new().await;
+    let email = add_2fa_user(&app).await;
+    let code = add_recovery_code(&app, &email).await;
+
+    let login_attempt_id = start_login(&app, &email).await;
+    let body = serde_json::json!({
+        "email": email,
+        "loginAttemptId": login_attempt_id,
+        "recoveryCode": code.as_ref(),
+    });
+
+    let response = app.post_verify_2fa_recovery(&body).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let auth_cookie = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found");
+    assert!(!auth_cookie.value().is_empty());
+
+    let login_attempt_id = start_login(&app, &email).await;
+    let body = serde_json::json!({
+        "email": email,
+        "loginAttemptId": login_attempt_id,
+        "recoveryCode": code.as_ref(),
+    });
+
+    let response = app.post_verify_2fa_recovery(&body).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_return_401_without_login_attempt() {
+    let app = TestApp::new().await;
+    let email = add_2fa_user(&app).await;
+    let code = add_recovery_code(&app, &email).await;
+
+    let body = serde_json::json!({
+        "email": email,
+        "loginAttemptId": "d498ab94-f157-453f-a6e2-da196ae3e713",
+        "recoveryCode": code.as_ref(),
+    });
+
+    let response = app.post_verify_2fa_recovery(&body).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    // The code was not spent on the rejected attempt
+    let count = app
+        .recovery_code_store
+        .count_codes(&Email::parse(email).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(count, 1);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_reject_codes_from_previous_batch() {
+    let app = TestApp::new().await;
+    let email = add_2fa_user(&app).await;
+    let old_code = add_recovery_code(&app, &email).await;
+    add_recovery_code(&app, &email).await;
+
+    let login_attempt_id = start_login(&app, &email).await;
+    let body = serde_json::json!({
+        "email": email,
+        "loginAttemptId": login_attempt_id,
+        "recoveryCode": old_code.as_ref(),
+    });
+
+    let response = app.post_verify_2fa_recovery(&body).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.cleanup().await;
+}
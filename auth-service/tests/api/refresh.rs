@@ -0,0 +1,177 @@
+use auth_service::{
+    domain::RefreshToken,
+    utils::constants::{JWT_COOKIE_NAME, REFRESH_TOKEN_COOKIE_NAME},
+};
+use reqwest::Url;
+
+use crate::helpers::{get_random_email, TestApp};
+
+async fn signup_and_login(app: &TestApp) -> String {
+    let random_email = get_random_email();
+
+    let body = serde_json::json!({
+        "email": random_email,
+        "password": "password123",
+        "requires2FA": false
+    });
+    let _ = app.post_signup(&body).await;
+
+    let body = serde_json::json!({
+        "email": random_email,
+        "password": "password123"
+    });
+    let response = app.post_login(&body).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let refresh_token = response
+        .cookies()
+        .find(|c| c.name() == REFRESH_TOKEN_COOKIE_NAME)
+        .expect("Refresh token cookie not found during login")
+        .value()
+        .to_owned();
+
+    refresh_token
+}
+
+fn set_refresh_cookie(app: &TestApp, token: &str) {
+    app.cookie_jar.add_cookie_str(
+        &format!(
+            "{}={}; HttpOnly; SameSite=Lax; Path=/",
+            REFRESH_TOKEN_COOKIE_NAME, token
+        ),
+        &Url::parse(&app.address).expect("Failed to parse URL"),
+    );
+}
+
+#[tokio::test]
+async fn should_return_400_if_refresh_cookie_missing() {
+    let app = TestApp::new().await;
+
+    let response = app.post_refresh().await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_refresh_token_unknown() {
+    let app = TestApp::new().await;
+
+    set_refresh_cookie(&app, RefreshToken::default().as_ref());
+
+    let response = app.post_refresh().await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_return_200_and_rotate_refresh_token() {
+    let app = TestApp::new().await;
+
+    let refresh_token = signup_and_login(&app).await;
+
+    let response = app.post_refresh().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let auth_cookie = response
+        .cookies()
+        .find(|c| c.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found");
+    assert!(!auth_cookie.value().is_empty());
+
+    let rotated_token = response
+        .cookies()
+        .find(|c| c.name() == REFRESH_TOKEN_COOKIE_NAME)
+        .expect("No refresh token cookie found")
+        .value()
+        .to_owned();
+    assert_ne!(rotated_token, refresh_token);
+
+    // The rotated token can be used again
+    let response = app.post_refresh().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_keep_expiry_of_login_when_rotating() {
+    let app = TestApp::new().await;
+
+    let refresh_token = RefreshToken::parse(signup_and_login(&app).await).unwrap();
+    let login_record = app
+        .refresh_token_store
+        .get_token(&refresh_token)
+        .await
+        .unwrap();
+
+    let response = app.post_refresh().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let rotated_token = response
+        .cookies()
+        .find(|c| c.name() == REFRESH_TOKEN_COOKIE_NAME)
+        .expect("No refresh token cookie found")
+        .value()
+        .to_owned();
+    let rotated_record = app
+        .refresh_token_store
+        .get_token(&RefreshToken::parse(rotated_token).unwrap())
+        .await
+        .unwrap();
+
+    // Refreshing regularly does not keep a login alive forever
+    assert_eq!(rotated_record.expires_at, login_record.expires_at);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_revoke_token_family_if_old_refresh_token_is_reused() {
+    let app = TestApp::new().await;
+
+    let refresh_token = signup_and_login(&app).await;
+
+    let response = app.post_refresh().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let rotated_token = response
+        .cookies()
+        .find(|c| c.name() == REFRESH_TOKEN_COOKIE_NAME)
+        .expect("No refresh token cookie found")
+        .value()
+        .to_owned();
+
+    // Replay the already rotated token
+    set_refresh_cookie(&app, &refresh_token);
+    let response = app.post_refresh().await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    // The legitimate successor was revoked together with its family
+    set_refresh_cookie(&app, &rotated_token);
+    let response = app.post_refresh().await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let rotated_token = RefreshToken::parse(rotated_token).unwrap();
+    let result = app.refresh_token_store.get_token(&rotated_token).await;
+    assert!(result.is_err());
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_return_401_after_logout() {
+    let app = TestApp::new().await;
+
+    let refresh_token = signup_and_login(&app).await;
+
+    let response = app.post_logout().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    set_refresh_cookie(&app, &refresh_token);
+    let response = app.post_refresh().await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.cleanup().await;
+}
@@ -0,0 +1,160 @@
+use auth_service::{
+    domain::{Email, RefreshToken},
+    routes::SessionResponse,
+    utils::constants::{JWT_COOKIE_NAME, REFRESH_TOKEN_COOKIE_NAME},
+};
+
+use crate::helpers::{get_random_email, TestApp};
+
+struct LoginCookies {
+    auth_token: String,
+    refresh_token: String,
+}
+
+async fn signup(app: &TestApp) -> String {
+    let email = get_random_email();
+    let signup_body = serde_json::json!({
+        "email": email,
+        "password": "password123",
+        "requires2FA": false
+    });
+    assert_eq!(app.post_signup(&signup_body).await.status().as_u16(), 201);
+
+    email
+}
+
+async fn login(app: &TestApp, email: &str) -> LoginCookies {
+    let login_body = serde_json::json!({ "email": email, "password": "password123" });
+    let response = app.post_login(&login_body).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let cookie = |name: &str| {
+        response
+            .cookies()
+            .find(|cookie| cookie.name() == name)
+            .expect("Cookie not found after login")
+            .value()
+            .to_owned()
+    };
+
+    LoginCookies {
+        auth_token: cookie(JWT_COOKIE_NAME),
+        refresh_token: cookie(REFRESH_TOKEN_COOKIE_NAME),
+    }
+}
+
+async fn get_sessions(app: &TestApp) -> Vec<SessionResponse> {
+    let response = app.get_sessions().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    response
+        .json::<Vec<SessionResponse>>()
+        .await
+        .expect("Could not deserialize response body to sessions")
+}
+
+#[tokio::test]
+async fn should_return_400_if_listing_sessions_while_not_logged_in() {
+    let app = TestApp::new().await;
+
+    let response = app.get_sessions().await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_list_sessions_of_the_user() {
+    let app = TestApp::new().await;
+    let other_email = signup(&app).await;
+    login(&app, &other_email).await;
+
+    let email = signup(&app).await;
+    login(&app, &email).await;
+    login(&app, &email).await;
+
+    let sessions = get_sessions(&app).await;
+    assert_eq!(sessions.len(), 2);
+    assert_eq!(sessions.iter().filter(|session| session.current).count(), 1);
+    assert!(sessions
+        .iter()
+        .all(|session| session.ip_address.as_deref() == Some("127.0.0.1")));
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_revoke_another_session() {
+    let app = TestApp::new().await;
+    let email = signup(&app).await;
+    let other_session = login(&app, &email).await;
+    login(&app, &email).await;
+
+    let sessions = get_sessions(&app).await;
+    let other_id = sessions
+        .iter()
+        .find(|session| !session.current)
+        .expect("Other session not listed")
+        .id;
+
+    let response = app.delete_session(&other_id.to_string()).await;
+    assert_eq!(response.status().as_u16(), 204);
+
+    // The auth token of the revoked session is rejected before it expires
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": other_session.auth_token }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let refresh_token = RefreshToken::parse(other_session.refresh_token).unwrap();
+    let result = app.refresh_token_store.get_token(&refresh_token).await;
+    assert!(result.is_err());
+
+    // The current session is untouched
+    let sessions = get_sessions(&app).await;
+    assert_eq!(sessions.len(), 1);
+    assert!(sessions[0].current);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_return_404_for_session_of_another_user() {
+    let app = TestApp::new().await;
+    let other_email = signup(&app).await;
+    login(&app, &other_email).await;
+    let other_id = get_sessions(&app).await[0].id;
+
+    let email = signup(&app).await;
+    login(&app, &email).await;
+
+    let response = app.delete_session(&other_id.to_string()).await;
+    assert_eq!(response.status().as_u16(), 404);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_end_session_on_logout() {
+    let app = TestApp::new().await;
+    let email = signup(&app).await;
+    let session = login(&app, &email).await;
+    login(&app, &email).await;
+
+    assert_eq!(app.post_logout().await.status().as_u16(), 200);
+
+    let sessions = app
+        .session_store
+        .get_sessions(&Email::parse(email).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(sessions.len(), 1);
+
+    // Tokens of the session that did not log out stay valid
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": session.auth_token }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.cleanup().await;
+}
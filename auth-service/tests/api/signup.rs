@@ -1,4 +1,9 @@
-use auth_service::routes::SignupResponse;
+use auth_service::{
+    app_state::AuthPolicy,
+    domain::{CharacterClass, PasswordPolicy, PasswordPolicyViolation},
+    routes::SignupResponse,
+    ErrorResponse, PROBLEM_JSON_CONTENT_TYPE,
+};
 
 use crate::helpers::{get_random_email, TestApp};
 
@@ -121,3 +126,69 @@ async fn should_return_409_if_email_already_exists() {
 
     app.cleanup().await;
 }
+
+#[tokio::test]
+async fn should_return_400_with_every_broken_password_rule() {
+    let app = TestApp::with_policy(AuthPolicy {
+        password: PasswordPolicy {
+            min_length: 12,
+            required_classes: vec![CharacterClass::Digit],
+            ..Default::default()
+        },
+        ..Default::default()
+    })
+    .await;
+
+    let signup_body = serde_json::json!({
+        "email": get_random_email(),
+        "password": "password",
+        "requires2FA": false
+    });
+    let response = app.post_signup(&signup_body).await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    let body = response
+        .json::<ErrorResponse>()
+        .await
+        .expect("Could not deserialize response body to ErrorResponse");
+    assert_eq!(body.errors.len(), 1);
+    assert_eq!(body.errors[0].field, "password");
+    assert_eq!(body.errors[0].code, "weak_password");
+    assert_eq!(
+        body.errors[0].reasons,
+        vec![
+            PasswordPolicyViolation::TooShort { min_length: 12 },
+            PasswordPolicyViolation::MissingCharacterClass {
+                class: CharacterClass::Digit
+            },
+        ]
+    );
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_return_problem_details_for_malformed_body() {
+    let app = TestApp::new().await;
+
+    let response = app
+        .post_signup(&serde_json::json!({ "email": get_random_email() }))
+        .await;
+    assert_eq!(response.status().as_u16(), 422);
+    assert_eq!(
+        response.headers()["content-type"],
+        PROBLEM_JSON_CONTENT_TYPE
+    );
+
+    let body = response
+        .json::<ErrorResponse>()
+        .await
+        .expect("Could not deserialize response body to ErrorResponse");
+    assert_eq!(body.code, "malformed_request");
+    assert_eq!(body.status, 422);
+    assert!(body
+        .detail
+        .is_some_and(|detail| detail.contains("password")));
+
+    app.cleanup().await;
+}
@@ -0,0 +1,377 @@
+use auth_service::{
+    domain::{Email, TotpSecret, TwoFAMethod},
+    routes::{EnrollTotpResponse, TwoFactorAuthResponse},
+    utils::constants::JWT_COOKIE_NAME,
+    ErrorResponse,
+};
+use totp_rs::{Algorithm, TOTP};
+
+use crate::helpers::{get_random_email, TestApp};
+
+async fn signup_and_login(app: &TestApp) -> String {
+    let email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": email,
+        "password": "password123",
+        "requires2FA": false
+    });
+    assert_eq!(app.post_signup(&signup_body).await.status().as_u16(), 201);
+
+    let login_body = serde_json::json!({
+        "email": email,
+        "password": "password123",
+    });
+    assert_eq!(app.post_login(&login_body).await.status().as_u16(), 200);
+
+    email
+}
+
+fn password_body() -> serde_json::Value {
+    serde_json::json!({ "password": "password123" })
+}
+
+// Code an authenticator app would currently show for the secret
+fn totp_code(secret: &TotpSecret, email: &str) -> String {
+    TOTP::new(
+        Algorithm::SHA1,
+        6,
+        0,
+        30,
+        secret.as_ref().to_vec(),
+        None,
+        email.to_owned(),
+    )
+    .unwrap()
+    .generate_current()
+    .unwrap()
+}
+
+async fn current_totp_code(app: &TestApp, email: &str) -> String {
+    let secret = app
+        .user_store
+        .get_totp_secret(&Email::parse(email.to_owned()).unwrap())
+        .await
+        .expect("Failed to get TOTP secret");
+
+    totp_code(&secret, email)
+}
+
+// Code for the secret of an enrollment that has not been confirmed yet
+async fn pending_totp_code(app: &TestApp, email: &str) -> String {
+    let secret = app
+        .user_store
+        .get_pending_totp_secret(&Email::parse(email.to_owned()).unwrap())
+        .await
+        .expect("Failed to get pending TOTP secret");
+
+    totp_code(&secret, email)
+}
+
+async fn enroll_and_confirm(app: &TestApp, email: &str) {
+    assert_eq!(
+        app.post_totp_enroll(&password_body())
+            .await
+            .status()
+            .as_u16(),
+        200
+    );
+
+    let confirm_body = serde_json::json!({
+        "password": "password123",
+        "2FACode": pending_totp_code(app, email).await,
+    });
+    assert_eq!(
+        app.post_totp_confirm(&confirm_body).await.status().as_u16(),
+        200
+    );
+}
+
+#[tokio::test]
+async fn should_return_400_if_not_logged_in() {
+    let app = TestApp::new().await;
+
+    let response = app.post_totp_enroll(&password_body()).await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_return_otpauth_uri_and_qr_code_on_enroll() {
+    let app = TestApp::new().await;
+    signup_and_login(&app).await;
+
+    let response = app.post_totp_enroll(&password_body()).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let body = response
+        .json::<EnrollTotpResponse>()
+        .await
+        .expect("Could not deserialize response body to EnrollTotpResponse");
+
+    assert!(body.otpauth_uri.starts_with("otpauth://totp/"));
+    assert!(body.qr_code.starts_with("data:image/svg+xml;base64,"));
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_return_400_if_confirming_without_enrollment() {
+    let app = TestApp::new().await;
+    signup_and_login(&app).await;
+
+    let response = app
+        .post_totp_confirm(&serde_json::json!({
+            "password": "password123",
+            "2FACode": "123456",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_return_400_if_code_is_malformed() {
+    let app = TestApp::new().await;
+    signup_and_login(&app).await;
+
+    let response = app
+        .post_totp_confirm(&serde_json::json!({
+            "password": "password123",
+            "2FACode": "12ab",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    let body = response
+        .json::<ErrorResponse>()
+        .await
+        .expect("Could not deserialize response body to ErrorResponse");
+    assert_eq!(body.code, "invalid_input");
+    let fields: Vec<_> = body
+        .errors
+        .iter()
+        .map(|error| (error.field.as_str(), error.code.as_str()))
+        .collect();
+    assert_eq!(fields, vec![("2FACode", "invalid_2fa_code")]);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_confirming_with_wrong_code() {
+    let app = TestApp::new().await;
+    let email = signup_and_login(&app).await;
+
+    assert_eq!(
+        app.post_totp_enroll(&password_body())
+            .await
+            .status()
+            .as_u16(),
+        200
+    );
+
+    let code = pending_totp_code(&app, &email).await;
+    let wrong_code = if code == "000000" { "111111" } else { "000000" };
+
+    let response = app
+        .post_totp_confirm(&serde_json::json!({
+            "password": "password123",
+            "2FACode": wrong_code,
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let user = app
+        .user_store
+        .get_user(&Email::parse(email).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(user.two_fa_method, TwoFAMethod::Email);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_login_with_totp_code_after_confirmation() {
+    let app = TestApp::new().await;
+    let email = signup_and_login(&app).await;
+    enroll_and_confirm(&app, &email).await;
+
+    let login_body = serde_json::json!({
+        "email": email,
+        "password": "password123",
+    });
+    let response = app.post_login(&login_body).await;
+    assert_eq!(response.status().as_u16(), 206);
+
+    let login_response = response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFactorAuthResponse");
+    assert_eq!(login_response.two_fa_method, TwoFAMethod::Totp);
+
+    let verify_2fa_body = serde_json::json!({
+        "email": email,
+        "loginAttemptId": login_response.login_attempt_id,
+        "2FACode": current_totp_code(&app, &email).await,
+    });
+    let response = app.post_verify_2fa(&verify_2fa_body).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let auth_cookie = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found");
+    assert!(!auth_cookie.value().is_empty());
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_reject_email_code_when_totp_is_selected() {
+    let app = TestApp::new().await;
+    let email = signup_and_login(&app).await;
+    enroll_and_confirm(&app, &email).await;
+
+    let login_body = serde_json::json!({
+        "email": email,
+        "password": "password123",
+    });
+    let login_response = app
+        .post_login(&login_body)
+        .await
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .unwrap();
+
+    let (_, stored_code) = app
+        .two_fa_code_store
+        .get_code(&Email::parse(email.clone()).unwrap())
+        .await
+        .unwrap();
+
+    let totp_code = current_totp_code(&app, &email).await;
+    if stored_code.as_ref() == totp_code {
+        // One in a million chance the placeholder code matches the authenticator app
+        app.cleanup().await;
+        return;
+    }
+
+    let verify_2fa_body = serde_json::json!({
+        "email": email,
+        "loginAttemptId": login_response.login_attempt_id,
+        "2FACode": stored_code.as_ref(),
+    });
+    let response = app.post_verify_2fa(&verify_2fa_body).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_fall_back_to_email_after_disable() {
+    let app = TestApp::new().await;
+    let email = signup_and_login(&app).await;
+    enroll_and_confirm(&app, &email).await;
+
+    assert_eq!(
+        app.post_totp_disable(&password_body())
+            .await
+            .status()
+            .as_u16(),
+        200
+    );
+
+    let login_body = serde_json::json!({
+        "email": email,
+        "password": "password123",
+    });
+    let login_response = app
+        .post_login(&login_body)
+        .await
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .unwrap();
+    assert_eq!(login_response.two_fa_method, TwoFAMethod::Email);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_return_401_without_current_password() {
+    let app = TestApp::new().await;
+    let email = signup_and_login(&app).await;
+    enroll_and_confirm(&app, &email).await;
+
+    // A stolen session cookie alone can neither replace nor turn off the authenticator app
+    let wrong_password = serde_json::json!({ "password": "wrongpassword" });
+    assert_eq!(
+        app.post_totp_enroll(&wrong_password)
+            .await
+            .status()
+            .as_u16(),
+        401
+    );
+    assert_eq!(
+        app.post_totp_disable(&wrong_password)
+            .await
+            .status()
+            .as_u16(),
+        401
+    );
+
+    let confirm_body = serde_json::json!({
+        "password": "wrongpassword",
+        "2FACode": current_totp_code(&app, &email).await,
+    });
+    assert_eq!(
+        app.post_totp_confirm(&confirm_body).await.status().as_u16(),
+        401
+    );
+
+    let user = app
+        .user_store
+        .get_user(&Email::parse(email).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(user.two_fa_method, TwoFAMethod::Totp);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_keep_confirmed_secret_until_new_enrollment_is_confirmed() {
+    let app = TestApp::new().await;
+    let email = signup_and_login(&app).await;
+    enroll_and_confirm(&app, &email).await;
+
+    let parsed_email = Email::parse(email.clone()).unwrap();
+    let confirmed_secret = app.user_store.get_totp_secret(&parsed_email).await.unwrap();
+
+    assert_eq!(
+        app.post_totp_enroll(&password_body())
+            .await
+            .status()
+            .as_u16(),
+        200
+    );
+
+    let user = app.user_store.get_user(&parsed_email).await.unwrap();
+    assert_eq!(user.two_fa_method, TwoFAMethod::Totp);
+    assert!(app.user_store.get_totp_secret(&parsed_email).await.unwrap() == confirmed_secret);
+
+    let confirm_body = serde_json::json!({
+        "password": "password123",
+        "2FACode": pending_totp_code(&app, &email).await,
+    });
+    assert_eq!(
+        app.post_totp_confirm(&confirm_body).await.status().as_u16(),
+        200
+    );
+    assert!(app.user_store.get_totp_secret(&parsed_email).await.unwrap() != confirmed_secret);
+
+    app.cleanup().await;
+}
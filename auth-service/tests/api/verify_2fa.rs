@@ -1,4 +1,9 @@
-use auth_service::{domain::*, routes::TwoFactorAuthResponse, utils::constants::JWT_COOKIE_NAME};
+use auth_service::{
+    app_state::{AuthPolicy, LoginThrottlePolicy},
+    domain::*,
+    routes::TwoFactorAuthResponse,
+    utils::constants::JWT_COOKIE_NAME,
+};
 use uuid::Uuid;
 
 use crate::helpers::{get_random_email, TestApp};
@@ -66,8 +71,6 @@ async fn should_return_401_if_incorrect_code() {
     let two_fa_code = TwoFACode::parse("123456").unwrap();
 
     app.two_fa_code_store
-        .write()
-        .await
         .add_code(email.clone(), login_attempt_id.clone(), two_fa_code)
         .await
         .expect("Failed to add 2FA code to store");
@@ -93,13 +96,11 @@ async fn should_return_401_if_old_code() {
     let email = Email::parse(random_email.clone()).unwrap();
     let random_password = Uuid::new_v4().to_string();
     let password = Password::parse(random_password.clone()).unwrap();
-    let user = User::new(email.clone(), password.clone(), true);
+    let user = User::new(email.clone(), true);
 
     // create user
     app.user_store
-        .write()
-        .await
-        .add_user(user)
+        .add_user(user, password.clone())
         .await
         .expect("Failed to create user");
 
@@ -118,8 +119,6 @@ async fn should_return_401_if_old_code() {
 
     let (login_attempt_id, two_fa_code) = app
         .two_fa_code_store
-        .read()
-        .await
         .get_code(&email)
         .await
         .expect("Failed to get 2FA code");
@@ -150,12 +149,19 @@ async fn should_return_200_if_correct_code() {
     let app = TestApp::new().await;
 
     let email = get_random_email();
+    let password = Password::parse("password123".to_owned()).unwrap();
+    let user = User::new(Email::parse(email.clone()).unwrap(), true);
+
+    // Tokens are only issued to users that exist
+    app.user_store
+        .add_user(user, password)
+        .await
+        .expect("Failed to create user");
+
     let login_attempt_id = LoginAttemptId::default();
     let two_fa_code = TwoFACode::parse("123456").unwrap();
 
     app.two_fa_code_store
-        .write()
-        .await
         .add_code(
             Email::parse(email.clone()).unwrap(),
             login_attempt_id.clone(),
@@ -189,12 +195,19 @@ async fn should_return_401_for_same_code_twice() {
     let app = TestApp::new().await;
 
     let email = get_random_email();
+    let password = Password::parse("password123".to_owned()).unwrap();
+    let user = User::new(Email::parse(email.clone()).unwrap(), true);
+
+    // Tokens are only issued to users that exist
+    app.user_store
+        .add_user(user, password)
+        .await
+        .expect("Failed to create user");
+
     let login_attempt_id = LoginAttemptId::default();
     let two_fa_code = TwoFACode::parse("123456").unwrap();
 
     app.two_fa_code_store
-        .write()
-        .await
         .add_code(
             Email::parse(email.clone()).unwrap(),
             login_attempt_id.clone(),
@@ -219,3 +232,184 @@ async fn should_return_401_for_same_code_twice() {
 
     app.cleanup().await;
 }
+
+#[tokio::test]
+async fn should_drop_login_attempt_after_too_many_wrong_codes() {
+    let app = TestApp::with_policy(AuthPolicy {
+        login_throttle: LoginThrottlePolicy {
+            max_two_fa_attempts: 3,
+            ..Default::default()
+        },
+        ..Default::default()
+    })
+    .await;
+
+    let email = get_random_email();
+    let login_attempt_id = LoginAttemptId::default();
+    let two_fa_code = TwoFACode::parse("123456").unwrap();
+
+    app.two_fa_code_store
+        .add_code(
+            Email::parse(email.clone()).unwrap(),
+            login_attempt_id.clone(),
+            two_fa_code.clone(),
+        )
+        .await
+        .expect("Failed to add 2FA code to store");
+
+    for wrong_code in ["000000", "000001", "000002"] {
+        let verify_2fa_body = serde_json::json!({
+            "email": email,
+            "loginAttemptId": login_attempt_id.as_ref(),
+            "2FACode": wrong_code
+        });
+        let response = app.post_verify_2fa(&verify_2fa_body).await;
+        assert_eq!(response.status().as_u16(), 401);
+    }
+
+    // The right code comes too late, the attempt is gone
+    let verify_2fa_body = serde_json::json!({
+        "email": email,
+        "loginAttemptId": login_attempt_id.as_ref(),
+        "2FACode": two_fa_code.as_ref()
+    });
+    let response = app.post_verify_2fa(&verify_2fa_body).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_return_429_after_too_many_wrong_codes_from_address() {
+    let app = TestApp::with_policy(AuthPolicy {
+        login_throttle: LoginThrottlePolicy {
+            max_failures_per_ip: 3,
+            ..Default::default()
+        },
+        ..Default::default()
+    })
+    .await;
+
+    for _ in 0..3 {
+        let verify_2fa_body = serde_json::json!({
+            "email": get_random_email(),
+            "loginAttemptId": LoginAttemptId::default().as_ref(),
+            "2FACode": "123456"
+        });
+        let response = app.post_verify_2fa(&verify_2fa_body).await;
+        assert_eq!(response.status().as_u16(), 401);
+    }
+
+    let verify_2fa_body = serde_json::json!({
+        "email": get_random_email(),
+        "loginAttemptId": LoginAttemptId::default().as_ref(),
+        "2FACode": "123456"
+    });
+    let response = app.post_verify_2fa(&verify_2fa_body).await;
+    assert_eq!(response.status().as_u16(), 429);
+
+    app.cleanup().await;
+}
+
+// Signs up a user with email 2FA and logs in with the password, returns the login attempt id
+async fn start_2fa_login(app: &TestApp, email: &str) -> String {
+    let login_body = serde_json::json!({ "email": email, "password": "password123" });
+    let response = app.post_login(&login_body).await;
+    assert_eq!(response.status().as_u16(), 206);
+
+    response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFactorAuthResponse")
+        .login_attempt_id
+}
+
+async fn post_wrong_code(app: &TestApp, email: &str, login_attempt_id: &str) -> reqwest::Response {
+    let (_, code) = app
+        .two_fa_code_store
+        .get_code(&Email::parse(email.to_owned()).unwrap())
+        .await
+        .expect("2FA code not found");
+    let wrong_code = if code.as_ref() == "000000" {
+        "111111"
+    } else {
+        "000000"
+    };
+
+    let verify_2fa_body = serde_json::json!({
+        "email": email,
+        "loginAttemptId": login_attempt_id,
+        "2FACode": wrong_code
+    });
+    app.post_verify_2fa(&verify_2fa_body).await
+}
+
+#[tokio::test]
+async fn should_not_give_new_login_attempts_fresh_tries() {
+    let app = TestApp::with_policy(AuthPolicy {
+        login_throttle: LoginThrottlePolicy {
+            max_two_fa_attempts: 2,
+            ..Default::default()
+        },
+        ..Default::default()
+    })
+    .await;
+
+    let email = get_random_email();
+    let signup_body = serde_json::json!({
+        "email": email,
+        "password": "password123",
+        "requires2FA": true
+    });
+    assert_eq!(app.post_signup(&signup_body).await.status().as_u16(), 201);
+
+    let login_attempt_id = start_2fa_login(&app, &email).await;
+    let response = post_wrong_code(&app, &email, &login_attempt_id).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    // The second wrong code uses up the tries, even though it is for another login attempt
+    let login_attempt_id = start_2fa_login(&app, &email).await;
+    let response = post_wrong_code(&app, &email, &login_attempt_id).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let result = app
+        .two_fa_code_store
+        .get_code(&Email::parse(email.clone()).unwrap())
+        .await;
+    assert_eq!(result, Err(TwoFACodeStoreError::LoginAttemptIdNotFound));
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_return_429_after_too_many_wrong_codes_for_account() {
+    let app = TestApp::with_policy(AuthPolicy {
+        login_throttle: LoginThrottlePolicy {
+            max_failures_per_email: 3,
+            ..Default::default()
+        },
+        ..Default::default()
+    })
+    .await;
+
+    let email = get_random_email();
+    let signup_body = serde_json::json!({
+        "email": email,
+        "password": "password123",
+        "requires2FA": true
+    });
+    assert_eq!(app.post_signup(&signup_body).await.status().as_u16(), 201);
+
+    // Logging in again with the right password does not forgive the wrong codes
+    for _ in 0..3 {
+        let login_attempt_id = start_2fa_login(&app, &email).await;
+        let response = post_wrong_code(&app, &email, &login_attempt_id).await;
+        assert_eq!(response.status().as_u16(), 401);
+    }
+
+    let login_body = serde_json::json!({ "email": email, "password": "password123" });
+    let response = app.post_login(&login_body).await;
+    assert_eq!(response.status().as_u16(), 429);
+
+    app.cleanup().await;
+}
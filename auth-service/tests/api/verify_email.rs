@@ -0,0 +1,229 @@
+use auth_service::{
+    app_state::AuthPolicy,
+    domain::{Email, EmailConfirmation, EmailToken},
+    routes::VerifyEmailResponse,
+    utils::constants::JWT_COOKIE_NAME,
+    ErrorResponse,
+};
+
+use crate::helpers::{get_random_email, TestApp};
+
+async fn signup(app: &TestApp) -> String {
+    let email = get_random_email();
+    let signup_body = serde_json::json!({
+        "email": email,
+        "password": "password123",
+        "requires2FA": false
+    });
+    assert_eq!(app.post_signup(&signup_body).await.status().as_u16(), 201);
+
+    email
+}
+
+async fn verification_token(app: &TestApp, email: &str) -> String {
+    let token = EmailToken::default();
+    let confirmation = EmailConfirmation::Verification {
+        email: Email::parse(email.to_owned()).unwrap(),
+    };
+    app.email_token_store
+        .add_token(token.clone(), confirmation)
+        .await
+        .expect("Failed to add verification token");
+
+    token.as_ref().to_owned()
+}
+
+fn strict_policy() -> AuthPolicy {
+    AuthPolicy {
+        require_verified_email: true,
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn should_create_unverified_users() {
+    let app = TestApp::new().await;
+    let email = signup(&app).await;
+
+    let user = app
+        .user_store
+        .get_user(&Email::parse(email).unwrap())
+        .await
+        .unwrap();
+    assert!(!user.email_verified);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_allow_unverified_login_by_default() {
+    let app = TestApp::new().await;
+    let email = signup(&app).await;
+
+    let login_body = serde_json::json!({ "email": email, "password": "password123" });
+    assert_eq!(app.post_login(&login_body).await.status().as_u16(), 200);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_return_403_for_unverified_login_when_required() {
+    let app = TestApp::with_policy(strict_policy()).await;
+    let email = signup(&app).await;
+
+    let login_body = serde_json::json!({ "email": email, "password": "password123" });
+    assert_eq!(app.post_login(&login_body).await.status().as_u16(), 403);
+
+    // Wrong passwords are still reported as such, the verification state is only revealed
+    // to someone who knows the password
+    let login_body = serde_json::json!({ "email": email, "password": "wrongpassword" });
+    assert_eq!(app.post_login(&login_body).await.status().as_u16(), 401);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_verify_email_and_allow_login() {
+    let app = TestApp::with_policy(strict_policy()).await;
+    let email = signup(&app).await;
+    let token = verification_token(&app, &email).await;
+
+    let response = app
+        .post_verify_email(&serde_json::json!({ "token": token }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let login_body = serde_json::json!({ "email": email, "password": "password123" });
+    assert_eq!(app.post_login(&login_body).await.status().as_u16(), 200);
+
+    // The link only works once
+    let response = app
+        .post_verify_email(&serde_json::json!({ "token": token }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_not_use_up_token_when_link_is_only_opened() {
+    let app = TestApp::with_policy(strict_policy()).await;
+    let email = signup(&app).await;
+    let token = verification_token(&app, &email).await;
+
+    // What a mail scanner following the link would do, the page it lands on has to post the token
+    let response = app
+        .http_client
+        .get(format!("{}/verify-email", &app.address))
+        .query(&[("token", &token)])
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 405);
+
+    let response = app
+        .post_verify_email(&serde_json::json!({ "token": token }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_token_is_invalid() {
+    let app = TestApp::new().await;
+
+    let response = app
+        .post_verify_email(&serde_json::json!({ "token": "invalid_token" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_return_401_for_auth_token() {
+    let app = TestApp::new().await;
+    let email = signup(&app).await;
+
+    let login_body = serde_json::json!({ "email": email, "password": "password123" });
+    let response = app.post_login(&login_body).await;
+    let auth_token = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found")
+        .value()
+        .to_owned();
+
+    let response = app
+        .post_verify_email(&serde_json::json!({ "token": auth_token }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_user_does_not_exist() {
+    let app = TestApp::new().await;
+    let token = verification_token(&app, &get_random_email()).await;
+
+    let response = app
+        .post_verify_email(&serde_json::json!({ "token": token }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_return_400_if_resend_email_is_malformed() {
+    let app = TestApp::new().await;
+
+    let response = app
+        .post_verify_email_resend(&serde_json::json!({ "email": "not-an-email" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    let body = response
+        .json::<ErrorResponse>()
+        .await
+        .expect("Could not deserialize response body to ErrorResponse");
+    assert_eq!(body.code, "invalid_input");
+    let fields: Vec<_> = body
+        .errors
+        .iter()
+        .map(|error| (error.field.as_str(), error.code.as_str()))
+        .collect();
+    assert_eq!(fields, vec![("email", "invalid_email")]);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_respond_identically_when_resending_for_known_and_unknown_emails() {
+    let app = TestApp::new().await;
+    let email = signup(&app).await;
+
+    let known = app
+        .post_verify_email_resend(&serde_json::json!({ "email": email }))
+        .await;
+    let unknown = app
+        .post_verify_email_resend(&serde_json::json!({ "email": get_random_email() }))
+        .await;
+
+    assert_eq!(known.status().as_u16(), 202);
+    assert_eq!(known.status(), unknown.status());
+
+    let known = known
+        .json::<VerifyEmailResponse>()
+        .await
+        .expect("Could not deserialize response body to VerifyEmailResponse");
+    let unknown = unknown
+        .json::<VerifyEmailResponse>()
+        .await
+        .expect("Could not deserialize response body to VerifyEmailResponse");
+    assert_eq!(known.message, unknown.message);
+
+    app.cleanup().await;
+}
@@ -1,7 +1,42 @@
-use auth_service::{domain::Email, utils::auth::generate_auth_cookie};
+use auth_service::{
+    domain::{Email, Password, User},
+    utils::{
+        auth::{decode_auth_token, generate_auth_cookie},
+        jwt_key::JwtKey,
+    },
+};
+use uuid::Uuid;
 
 use crate::helpers::{get_random_email, TestApp};
 
+async fn add_user_with_session(app: &TestApp, email: &Email) -> Uuid {
+    let password = Password::parse("password123".to_owned()).unwrap();
+    app.user_store
+        .add_user(User::new(email.clone(), false), password)
+        .await
+        .expect("Failed to add user");
+
+    app.add_session(email).await
+}
+
+async fn issue_token(app: &TestApp, email: &Email, session_id: Uuid) -> String {
+    let token_version = app
+        .user_store
+        .get_token_version(email)
+        .await
+        .expect("Failed to get token version");
+
+    generate_auth_cookie(
+        email,
+        session_id,
+        token_version,
+        &*app.jwt_keyring.read().await,
+    )
+    .unwrap()
+    .value()
+    .to_owned()
+}
+
 #[tokio::test]
 async fn should_return_422_if_malformed_input() {
     let app = TestApp::new().await;
@@ -25,9 +60,9 @@ async fn should_return_422_if_malformed_input() {
 async fn should_return_200_for_valid_token() {
     let app = TestApp::new().await;
 
-    let email = get_random_email();
-    let email = Email::parse(email).unwrap();
-    let token = generate_auth_cookie(&email).unwrap().value().to_owned();
+    let email = Email::parse(get_random_email()).unwrap();
+    let session_id = add_user_with_session(&app, &email).await;
+    let token = issue_token(&app, &email, session_id).await;
 
     let body = serde_json::json!({
         "token": token
@@ -57,11 +92,15 @@ async fn should_return_401_for_incorrect_token() {
 async fn should_return_401_for_banned_token() {
     let app = TestApp::new().await;
 
-    let email = get_random_email();
-    let email = Email::parse(email).unwrap();
-    let token = generate_auth_cookie(&email).unwrap().value().to_owned();
+    let email = Email::parse(get_random_email()).unwrap();
+    let session_id = add_user_with_session(&app, &email).await;
+    let token = issue_token(&app, &email, session_id).await;
 
-    app.banned_token_store.write().await.ban_token(&token).await.expect("Failed to ban token");
+    let claims = decode_auth_token(&token, &*app.jwt_keyring.read().await).unwrap();
+    app.banned_token_store
+        .ban_token(&claims.jti, claims.valid_until())
+        .await
+        .expect("Failed to ban token");
 
     let body = serde_json::json!({
         "token": token
@@ -72,3 +111,77 @@ async fn should_return_401_for_banned_token() {
 
     app.cleanup().await;
 }
+
+#[tokio::test]
+async fn should_return_200_for_token_signed_with_retired_key() {
+    let app = TestApp::new().await;
+
+    let email = Email::parse(get_random_email()).unwrap();
+    let session_id = add_user_with_session(&app, &email).await;
+    let token = issue_token(&app, &email, session_id).await;
+
+    app.jwt_keyring
+        .write()
+        .await
+        .promote(JwtKey::generate().expect("Failed to generate JWT key"));
+
+    let body = serde_json::json!({
+        "token": token
+    });
+
+    let response = app.post_verify_token(&body).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_return_401_for_revoked_session() {
+    let app = TestApp::new().await;
+
+    let email = Email::parse(get_random_email()).unwrap();
+    let session_id = add_user_with_session(&app, &email).await;
+    let token = issue_token(&app, &email, session_id).await;
+
+    app.session_store
+        .remove_session(&email, &session_id)
+        .await
+        .expect("Failed to remove session");
+
+    let body = serde_json::json!({
+        "token": token
+    });
+
+    let response = app.post_verify_token(&body).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.cleanup().await;
+}
+
+#[tokio::test]
+async fn should_return_401_for_token_of_outdated_version() {
+    let app = TestApp::new().await;
+
+    let email = Email::parse(get_random_email()).unwrap();
+    let session_id = add_user_with_session(&app, &email).await;
+    let token = issue_token(&app, &email, session_id).await;
+
+    app.user_store
+        .bump_token_version(&email)
+        .await
+        .expect("Failed to bump token version");
+
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": token }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    // Tokens issued after the bump are accepted again
+    let token = issue_token(&app, &email, session_id).await;
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": token }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.cleanup().await;
+}
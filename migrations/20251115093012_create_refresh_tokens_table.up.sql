@@ -0,0 +1,9 @@
+create table if not exists refresh_tokens (
+  token_hash varchar(64) primary key,
+  family_id uuid not null,
+  email varchar(255) not null,
+  used boolean not null default false,
+  expires_at timestamp with time zone not null,
+  created_at timestamp with time zone not null default(now() at time zone 'utc')
+);
+create index if not exists refresh_tokens_family_id_idx on refresh_tokens (family_id);
@@ -0,0 +1,2 @@
+alter table users drop column if exists totp_secret;
+alter table users drop column if exists two_fa_method;
@@ -0,0 +1,3 @@
+alter table users add column if not exists two_fa_method varchar(16) not null default 'email';
+-- AES-256-GCM encrypted secret, base64 encoded with the nonce prepended
+alter table users add column if not exists totp_secret text;
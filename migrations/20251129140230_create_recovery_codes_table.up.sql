@@ -0,0 +1,7 @@
+create table if not exists recovery_codes (
+  id uuid primary key,
+  email varchar(255) not null,
+  code_hash varchar(255) not null,
+  created_at timestamp with time zone not null default(now() at time zone 'utc')
+);
+create index if not exists recovery_codes_email_idx on recovery_codes (email);
@@ -0,0 +1,8 @@
+create table if not exists passkey_credentials (
+  credential_id bytea primary key,
+  email varchar(255) not null,
+  public_key bytea not null,
+  sign_count bigint not null default 0,
+  created_at timestamp with time zone not null default(now() at time zone 'utc')
+);
+create index if not exists passkey_credentials_email_idx on passkey_credentials (email);
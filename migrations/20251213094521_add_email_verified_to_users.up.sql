@@ -0,0 +1,3 @@
+-- Accounts created before verification existed are treated as verified, new ones start unverified
+alter table users add column if not exists email_verified boolean not null default true;
+alter table users alter column email_verified set default false;
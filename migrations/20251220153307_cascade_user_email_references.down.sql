@@ -0,0 +1,2 @@
+alter table passkey_credentials drop constraint if exists passkey_credentials_email_fkey;
+alter table recovery_codes drop constraint if exists recovery_codes_email_fkey;
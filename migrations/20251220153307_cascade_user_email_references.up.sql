@@ -0,0 +1,13 @@
+-- Rows left behind by users that no longer exist would block the constraints
+delete from recovery_codes where email not in (select email from users);
+delete from passkey_credentials where email not in (select email from users);
+
+-- Changing or removing the email of a user carries its recovery codes and passkeys along. Refresh
+-- tokens are left out, they are revoked whenever the email changes.
+alter table recovery_codes drop constraint if exists recovery_codes_email_fkey;
+alter table recovery_codes add constraint recovery_codes_email_fkey
+  foreign key (email) references users (email) on update cascade on delete cascade;
+
+alter table passkey_credentials drop constraint if exists passkey_credentials_email_fkey;
+alter table passkey_credentials add constraint passkey_credentials_email_fkey
+  foreign key (email) references users (email) on update cascade on delete cascade;
@@ -0,0 +1,19 @@
+create table if not exists sessions (
+  id uuid primary key,
+  email varchar(255) not null,
+  device varchar(255) not null,
+  ip_address varchar(45),
+  user_agent text,
+  created_at timestamp with time zone not null default(now() at time zone 'utc'),
+  last_seen_at timestamp with time zone not null default(now() at time zone 'utc')
+);
+create index if not exists sessions_email_idx on sessions (email);
+
+-- Every session is the family of refresh tokens it was issued, so logins made before sessions
+-- existed stay valid
+insert into sessions (id, email, device, created_at, last_seen_at)
+  select family_id, min(email), 'Unknown device', min(created_at), max(created_at)
+  from refresh_tokens
+  where not used and expires_at > now()
+  group by family_id
+on conflict (id) do nothing;
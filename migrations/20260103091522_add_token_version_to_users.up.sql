@@ -0,0 +1,2 @@
+-- Auth tokens carry the version of their user when they were issued, raising it invalidates all of them
+alter table users add column if not exists token_version integer not null default 0;
@@ -0,0 +1,3 @@
+-- Stable identifier of an account, unlike the email it never changes
+alter table users add column if not exists id uuid not null default gen_random_uuid();
+create unique index if not exists users_id_key on users (id);
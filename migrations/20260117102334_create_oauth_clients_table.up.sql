@@ -0,0 +1,6 @@
+create table if not exists oauth_clients (
+  client_id varchar(255) primary key,
+  name varchar(255) not null,
+  redirect_uris text[] not null,
+  created_at timestamp with time zone not null default(now() at time zone 'utc')
+);
@@ -0,0 +1,7 @@
+create table if not exists machine_clients (
+  client_id varchar(255) primary key,
+  name varchar(255) not null,
+  secret_hash text not null,
+  allowed_scopes text[] not null,
+  created_at timestamp with time zone not null default(now() at time zone 'utc')
+);
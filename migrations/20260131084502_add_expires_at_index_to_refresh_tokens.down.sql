@@ -0,0 +1 @@
+drop index if exists refresh_tokens_expires_at_idx;
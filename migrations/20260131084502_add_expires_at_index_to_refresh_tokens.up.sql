@@ -0,0 +1,2 @@
+-- Expired refresh tokens are pruned on every write
+create index if not exists refresh_tokens_expires_at_idx on refresh_tokens (expires_at);
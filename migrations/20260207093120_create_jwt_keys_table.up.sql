@@ -0,0 +1,6 @@
+-- Signing keys shared by every instance, private keys are encrypted like TOTP secrets
+create table if not exists jwt_keys (
+  kid varchar(255) primary key,
+  private_key text not null,
+  created_at timestamp with time zone not null default(now() at time zone 'utc')
+);
@@ -0,0 +1,2 @@
+-- Secret of an enrollment that has not been confirmed yet, encrypted like totp_secret
+alter table users add column if not exists pending_totp_secret text;